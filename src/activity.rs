@@ -0,0 +1,449 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::games::Game;
+use crate::settings::Favorite;
+
+/// Samples kept per server, a week of refreshes every five minutes
+pub const MAX_SAMPLES: usize = 7 * 24 * 12;
+
+pub const DAY: u64 = 24 * 60 * 60;
+pub const WEEK: u64 = 7 * DAY;
+
+/// Unix time in seconds and number of players. Stored as a pair to keep the file small.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sample(pub u64, pub u64);
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Data file in the user data directory, stored along with the version of its schema.
+///
+/// Files written before schemas were versioned are version 0, i.e. the bare data without the envelope.
+pub trait DataFile: Default + Serialize + DeserializeOwned {
+    /// File name within the data directory
+    const FILE: &'static str;
+    /// Current schema version
+    const VERSION: u32;
+
+    /// Converts data of schema `version` to the next version.
+    fn migrate(version: u32, data: Value) -> Result<Value, failure::Error> {
+        match version {
+            // Version 1 only introduced the envelope
+            0 => Ok(data),
+            _ => Err(failure::err_msg(format!(
+                "no migration from schema version {}",
+                version
+            ))),
+        }
+    }
+
+    fn path() -> PathBuf {
+        glib::get_user_data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("obozrenie")
+            .join(Self::FILE)
+    }
+
+    /// Loads the file, empty if it does not exist yet.
+    /// Fails if the file is damaged or its schema cannot be migrated, the caller is expected to start anew then.
+    fn load() -> Result<Self, failure::Error> {
+        let path = Self::path();
+
+        match fs::read_to_string(&path) {
+            Ok(data) => {
+                parse(&data).map_err(|e| failure::err_msg(format!("{}: {}", path.display(), e)))
+            }
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    fn save(&self) -> Result<(), failure::Error> {
+        let path = Self::path();
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        fs::write(path, serialize(self)?)?;
+
+        Ok(())
+    }
+
+    /// Copies the file next to itself before it is started anew, so that data of a newer release or a damaged
+    /// file can be restored by hand. Returns the path of the copy.
+    fn back_up() -> Result<PathBuf, failure::Error> {
+        let path = Self::path();
+        let backup = path.with_file_name(format!("{}.bak", Self::FILE));
+
+        fs::copy(&path, &backup)?;
+
+        Ok(backup)
+    }
+}
+
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    version: u32,
+    data: &'a T,
+}
+
+/// Reads a data file of any known schema version, migrating it to the current one.
+pub fn parse<T: DataFile>(text: &str) -> Result<T, failure::Error> {
+    let (mut version, mut data) = match serde_json::from_str::<Value>(text)? {
+        Value::Object(mut envelope) if envelope.contains_key("version") => {
+            let version = envelope
+                .get("version")
+                .and_then(Value::as_u64)
+                .ok_or_else(|| failure::err_msg("invalid schema version"))?;
+            let data = envelope
+                .remove("data")
+                .ok_or_else(|| failure::err_msg("no data"))?;
+
+            (version as u32, data)
+        }
+        data => (0, data),
+    };
+
+    if version > T::VERSION {
+        return Err(failure::err_msg(format!(
+            "written by a newer release (schema version {}, supported up to {})",
+            version,
+            T::VERSION
+        )));
+    }
+
+    while version < T::VERSION {
+        data = T::migrate(version, data)?;
+        version += 1;
+    }
+
+    Ok(serde_json::from_value(data)?)
+}
+
+/// Writes data along with its current schema version.
+pub fn serialize<T: DataFile>(value: &T) -> Result<String, failure::Error> {
+    Ok(serde_json::to_string(&Envelope {
+        version: T::VERSION,
+        data: value,
+    })?)
+}
+
+fn key(favorite: &Favorite) -> String {
+    format!("{}/{}", favorite.game.id(), favorite.addr)
+}
+
+/// Player counts of favorite servers over time.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ActivityLog {
+    servers: HashMap<String, VecDeque<Sample>>,
+}
+
+impl DataFile for ActivityLog {
+    const FILE: &'static str = "activity.json";
+    const VERSION: u32 = 1;
+}
+
+impl ActivityLog {
+    pub fn record(&mut self, favorite: &Favorite, sample: Sample) {
+        let samples = self.servers.entry(key(favorite)).or_default();
+
+        samples.push_back(sample);
+        while samples.len() > MAX_SAMPLES {
+            samples.pop_front();
+        }
+    }
+
+    /// Samples newer than `since`, oldest first.
+    pub fn samples(&self, favorite: &Favorite, since: u64) -> Vec<Sample> {
+        self.servers
+            .get(&key(favorite))
+            .map(|samples| {
+                samples
+                    .iter()
+                    .filter(|Sample(time, _)| *time >= since)
+                    .copied()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Carries the samples over to the server's new address, replacing any kept there.
+    pub fn rename(&mut self, from: &Favorite, to: &Favorite) {
+        if let Some(samples) = self.servers.remove(&key(from)) {
+            self.servers.insert(key(to), samples);
+        }
+    }
+
+    /// Drops servers that are no longer favorite.
+    pub fn retain(&mut self, favorites: &[Favorite]) {
+        let keys = favorites.iter().map(key).collect::<Vec<_>>();
+
+        self.servers.retain(|k, _| keys.contains(k));
+    }
+}
+
+/// Ping samples kept per server for the sparkline
+pub const MAX_PING_SAMPLES: usize = 30;
+
+/// Servers not pinged for this many seconds are dropped from the ping history, see `PingHistory::prune`
+pub const PING_HISTORY_MAX_AGE: u64 = 60 * 60;
+
+#[derive(Clone, Debug, Default)]
+struct PingSamples {
+    /// Unix time of the last sample
+    updated: u64,
+    samples: VecDeque<u64>,
+}
+
+/// Recent pings of servers in milliseconds, one sample per refresh. Kept in memory only.
+#[derive(Clone, Debug, Default)]
+pub struct PingHistory {
+    servers: HashMap<String, PingSamples>,
+}
+
+impl PingHistory {
+    pub fn record(&mut self, server: &Favorite, ping: u64) {
+        let entry = self.servers.entry(key(server)).or_default();
+
+        entry.updated = now();
+        entry.samples.push_back(ping);
+        while entry.samples.len() > MAX_PING_SAMPLES {
+            entry.samples.pop_front();
+        }
+    }
+
+    /// Carries the samples over to the server's new address, replacing any kept there.
+    pub fn rename(&mut self, from: &Favorite, to: &Favorite) {
+        if let Some(samples) = self.servers.remove(&key(from)) {
+            self.servers.insert(key(to), samples);
+        }
+    }
+
+    /// Samples of the server, oldest first.
+    pub fn samples(&self, server: &Favorite) -> Vec<u64> {
+        self.servers
+            .get(&key(server))
+            .map(|entry| entry.samples.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Drops servers last pinged before `since`, a Unix time.
+    pub fn prune(&mut self, since: u64) {
+        self.servers.retain(|_, entry| entry.updated >= since);
+    }
+
+    pub fn len(&self) -> usize {
+        self.servers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.servers.is_empty()
+    }
+}
+
+/// Summary of pings sent to a single server, in milliseconds
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PingStats {
+    pub sent: usize,
+    pub lost: usize,
+    pub min: Option<u64>,
+    pub avg: Option<u64>,
+    pub max: Option<u64>,
+    /// Mean difference between consecutive replies
+    pub jitter: Option<u64>,
+}
+
+impl PingStats {
+    /// Summarizes pings in the order sent, `None` for those without a reply.
+    pub fn new(samples: &[Option<u64>]) -> Self {
+        let replies = samples.iter().filter_map(|v| *v).collect::<Vec<_>>();
+        let jitter = if replies.len() > 1 {
+            Some(
+                replies
+                    .windows(2)
+                    .map(|pair| (pair[1] as i64 - pair[0] as i64).abs() as u64)
+                    .sum::<u64>()
+                    / (replies.len() - 1) as u64,
+            )
+        } else {
+            None
+        };
+
+        Self {
+            sent: samples.len(),
+            lost: samples.len() - replies.len(),
+            min: replies.iter().copied().min(),
+            avg: if replies.is_empty() {
+                None
+            } else {
+                Some(replies.iter().sum::<u64>() / replies.len() as u64)
+            },
+            max: replies.iter().copied().max(),
+            jitter,
+        }
+    }
+
+    /// Share of pings without a reply in percent
+    pub fn loss_percent(&self) -> u64 {
+        if self.sent == 0 {
+            0
+        } else {
+            (self.lost * 100 / self.sent) as u64
+        }
+    }
+}
+
+impl std::fmt::Display for PingStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} sent, {} lost ({}%)",
+            self.sent,
+            self.lost,
+            self.loss_percent()
+        )?;
+
+        let ms = |v: Option<u64>| v.map(|v| format!("{} ms", v)).unwrap_or_else(|| "—".into());
+        if self.avg.is_some() {
+            write!(
+                f,
+                ", min {}, avg {}, max {}, jitter {}",
+                ms(self.min),
+                ms(self.avg),
+                ms(self.max),
+                ms(self.jitter)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Servers kept in the recent list
+pub const MAX_RECENT_SERVERS: usize = 10;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecentServer {
+    pub server: Favorite,
+    /// Unix time of the last connection
+    pub time: u64,
+    /// Whether the server asked for a password then, `None` if unknown
+    pub need_pass: Option<bool>,
+}
+
+/// Servers the user connected to, most recent first.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecentServers {
+    servers: VecDeque<RecentServer>,
+}
+
+impl DataFile for RecentServers {
+    const FILE: &'static str = "recent.json";
+    const VERSION: u32 = 1;
+}
+
+impl RecentServers {
+    /// Moves the server to the top of the list.
+    pub fn record(&mut self, server: Favorite, need_pass: Option<bool>) {
+        self.servers.retain(|recent| recent.server != server);
+        self.servers.push_front(RecentServer {
+            server,
+            time: now(),
+            need_pass,
+        });
+        self.servers.truncate(MAX_RECENT_SERVERS);
+    }
+
+    /// Points the entry of the server at its new address, dropping one already there.
+    pub fn rename(&mut self, from: &Favorite, to: &Favorite) {
+        if !self.servers.iter().any(|recent| recent.server == *from) {
+            return;
+        }
+
+        self.servers.retain(|recent| recent.server != *to);
+        for recent in &mut self.servers {
+            if recent.server == *from {
+                recent.server = to.clone();
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &RecentServer> {
+        self.servers.iter()
+    }
+}
+
+/// Refresh durations kept per game for the rolling average
+pub const MAX_DURATION_SAMPLES: usize = 10;
+
+/// How long refreshes of each game took, in milliseconds.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RefreshStats {
+    games: HashMap<String, VecDeque<u64>>,
+}
+
+impl DataFile for RefreshStats {
+    const FILE: &'static str = "stats.json";
+    const VERSION: u32 = 1;
+}
+
+impl RefreshStats {
+    pub fn record(&mut self, game: Game, duration: Duration) {
+        let samples = self.games.entry(game.id().into()).or_default();
+
+        samples.push_back(duration.as_millis() as u64);
+        while samples.len() > MAX_DURATION_SAMPLES {
+            samples.pop_front();
+        }
+    }
+
+    /// Recent durations of the game, oldest first.
+    pub fn durations(&self, game: Game) -> Vec<Duration> {
+        self.games
+            .get(game.id())
+            .map(|samples| samples.iter().copied().map(Duration::from_millis).collect())
+            .unwrap_or_default()
+    }
+
+    /// Average of the recent durations, `None` until the game has been refreshed.
+    pub fn average(&self, game: Game) -> Option<Duration> {
+        average(&self.durations(game))
+    }
+}
+
+/// Mean of `durations`, `None` if empty.
+pub fn average(durations: &[Duration]) -> Option<Duration> {
+    if durations.is_empty() {
+        None
+    } else {
+        Some(durations.iter().sum::<Duration>() / durations.len() as u32)
+    }
+}
@@ -0,0 +1,67 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use enum_iter::EnumIterator;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use obozrenie::games::Game;
+use obozrenie::settings::Settings;
+
+/// Information attached to bug reports.
+pub struct Diagnostics<'a> {
+    pub settings: &'a Settings,
+    pub last_errors: &'a HashMap<Game, String>,
+    pub server_counts: &'a HashMap<Game, usize>,
+    /// Raw JSON of the selected server
+    pub selected_server: Option<String>,
+}
+
+impl<'a> Diagnostics<'a> {
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+
+        writeln!(out, "Obozrenie {}", env!("CARGO_PKG_VERSION")).unwrap();
+
+        writeln!(out, "\nGames:").unwrap();
+        for game in Game::enum_iter() {
+            writeln!(
+                out,
+                "{}: {} servers, last error: {}",
+                game.id(),
+                self.server_counts.get(&game).copied().unwrap_or(0),
+                self.last_errors
+                    .get(&game)
+                    .map(String::as_str)
+                    .unwrap_or("none")
+            )
+            .unwrap();
+        }
+
+        writeln!(
+            out,
+            "\nSettings:\n{}",
+            serde_json::to_string_pretty(self.settings).unwrap_or_default()
+        )
+        .unwrap();
+
+        if let Some(json) = self.selected_server.as_ref() {
+            writeln!(out, "\nSelected server:\n{}", json).unwrap();
+        }
+
+        out
+    }
+}
@@ -0,0 +1,2028 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use enum_iter::EnumIterator;
+use gtk::prelude::*;
+use log::*;
+use std::collections::{BTreeSet, HashMap};
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::secrets;
+use crate::static_resources::Resources;
+use crate::widgets::*;
+use obozrenie::activity::DataFile;
+use obozrenie::countries;
+use obozrenie::filters;
+use obozrenie::games::{self, selftest, Company, Game, MAX_COMPANIES};
+use obozrenie::import;
+use obozrenie::refresher;
+use obozrenie::session::AlreadyRunning;
+use obozrenie::settings::{
+    Favorite, FavoriteDetails, FollowName, LaunchMethod, LaunchProfile, UnknownPing,
+};
+use obozrenie::share;
+use obozrenie::state;
+
+pub fn show_message(parent: &gtk::ApplicationWindow, kind: gtk::MessageType, text: &str) {
+    let dialog = gtk::MessageDialog::new(
+        Some(parent),
+        gtk::DialogFlags::MODAL,
+        kind,
+        gtk::ButtonsType::Ok,
+        text,
+    );
+    dialog.run();
+    dialog.destroy();
+}
+
+/// Asks the user to confirm an action. Returns whether it was accepted.
+pub fn confirm(resources: &Rc<Resources>, text: &str) -> bool {
+    let window = resources.ui.get_object::<MainWindow, _>().unwrap().0;
+
+    let dialog = gtk::MessageDialog::new(
+        Some(&window),
+        gtk::DialogFlags::MODAL,
+        gtk::MessageType::Warning,
+        gtk::ButtonsType::OkCancel,
+        text,
+    );
+    let accepted = dialog.run() == gtk::ResponseType::Ok;
+    dialog.destroy();
+
+    accepted
+}
+
+/// Asks what to do about an instance of the game launched earlier that is still running. Returns the choice
+/// and whether to keep it for the rest of the session, `None` if the launch is cancelled.
+pub fn ask_already_running(
+    resources: &Rc<Resources>,
+    game: Game,
+) -> Option<(AlreadyRunning, bool)> {
+    let window = resources.ui.get_object::<MainWindow, _>().unwrap().0;
+    let name = resources.settings.lock().unwrap().game_name(game);
+
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some(&format!("{} is already running", name)),
+        Some(&window),
+        gtk::DialogFlags::MODAL,
+        &[
+            ("_Cancel", gtk::ResponseType::Cancel),
+            ("_Launch anyway", gtk::ResponseType::Apply),
+            ("_Bring to front", gtk::ResponseType::Accept),
+        ],
+    );
+    dialog.set_default_response(gtk::ResponseType::Accept);
+
+    let grid = gtk::Grid::new();
+    grid.set_row_spacing(5);
+    grid.set_column_spacing(10);
+    grid.set_property_margin(15);
+
+    let label = gtk::Label::new(Some(&format!(
+        "{} is already running — bring it to front or launch another instance?",
+        name
+    )));
+    label.set_halign(gtk::Align::Start);
+    grid.attach(&label, 0, 0, 1, 1);
+
+    let remember = gtk::CheckButton::new_with_mnemonic("_Remember until Obozrenie quits");
+    grid.attach(&remember, 0, 1, 1, 1);
+
+    dialog.get_content_area().add(&grid);
+    dialog.show_all();
+
+    let decision = match dialog.run() {
+        gtk::ResponseType::Accept => Some(AlreadyRunning::BringToFront),
+        gtk::ResponseType::Apply => Some(AlreadyRunning::LaunchAnyway),
+        _ => None,
+    };
+    let out = decision.map(|decision| (decision, remember.get_active()));
+    dialog.destroy();
+
+    out
+}
+
+/// Shows the outcome of the network self-test.
+pub fn show_network_test(resources: &Rc<Resources>, result: &Result<selftest::SelfTest, String>) {
+    let window = resources.ui.get_object::<MainWindow, _>().unwrap().0;
+
+    let (message_type, text) = match result {
+        Ok(test) if test.verdict() == selftest::Verdict::Works => {
+            (gtk::MessageType::Info, test.advice())
+        }
+        Ok(test) => (gtk::MessageType::Warning, test.advice()),
+        Err(e) => (
+            gtk::MessageType::Error,
+            format!("The network test could not run: {}", e),
+        ),
+    };
+
+    show_message(&window, message_type, &text);
+}
+
+/// Shows how servers and players of the listed servers spread across countries.
+pub fn show_country_breakdown(resources: &Rc<Resources>, stats: &[filters::CountryStats]) {
+    let window = resources.ui.get_object::<MainWindow, _>().unwrap().0;
+
+    if stats.is_empty() {
+        show_message(&window, gtk::MessageType::Info, "No servers listed.");
+        return;
+    }
+
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("Servers by country"),
+        Some(&window),
+        gtk::DialogFlags::MODAL,
+        &[("_Close", gtk::ResponseType::Close)],
+    );
+
+    let grid = gtk::Grid::new();
+    grid.set_row_spacing(5);
+    grid.set_column_spacing(10);
+    grid.set_property_margin(15);
+
+    let max_servers = stats.iter().map(|v| v.servers).max().unwrap_or(1);
+    for (i, entry) in stats.iter().enumerate() {
+        let row = i as i32;
+
+        let country = gtk::Label::new(Some(
+            &entry
+                .country
+                .as_ref()
+                .map(|v| countries::display_name(v))
+                .unwrap_or_else(|| "Unknown".into()),
+        ));
+        country.set_xalign(0.0);
+        grid.attach(&country, 0, row, 1, 1);
+
+        let bar = gtk::LevelBar::new_for_interval(0.0, max_servers as f64);
+        bar.set_value(entry.servers as f64);
+        bar.set_hexpand(true);
+        bar.set_valign(gtk::Align::Center);
+        bar.set_size_request(150, -1);
+        grid.attach(&bar, 1, row, 1, 1);
+
+        let counts = gtk::Label::new(Some(&format!(
+            "{} servers, {} players",
+            entry.servers, entry.players
+        )));
+        counts.set_xalign(0.0);
+        grid.attach(&counts, 2, row, 1, 1);
+    }
+
+    let scrolled = gtk::ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+    scrolled.set_policy(gtk::PolicyType::Never, gtk::PolicyType::Automatic);
+    scrolled.set_propagate_natural_height(true);
+    scrolled.set_max_content_height(400);
+    scrolled.add(&grid);
+
+    dialog.get_content_area().add(&scrolled);
+    dialog.show_all();
+    dialog.run();
+    dialog.destroy();
+}
+
+/// Shows the `first` server side by side with one of `candidates`, named servers of the same game. Details and
+/// rules that differ are in bold.
+pub fn compare_servers(
+    resources: &Rc<Resources>,
+    game: Game,
+    first: (String, rgs::models::Server),
+    candidates: Vec<(String, rgs::models::Server)>,
+) {
+    let window = resources.ui.get_object::<MainWindow, _>().unwrap().0;
+
+    if candidates.is_empty() {
+        show_message(
+            &window,
+            gtk::MessageType::Info,
+            &format!(
+                "No other {} servers listed.",
+                resources.settings.lock().unwrap().game_name(game)
+            ),
+        );
+        return;
+    }
+
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("Compare servers"),
+        Some(&window),
+        gtk::DialogFlags::MODAL,
+        &[("_Close", gtk::ResponseType::Close)],
+    );
+
+    let layout = gtk::Grid::new();
+    layout.set_row_spacing(10);
+    layout.set_column_spacing(10);
+    layout.set_property_margin(15);
+
+    let combo = gtk::ComboBoxText::new();
+    for (name, srv) in &candidates {
+        combo.append_text(&format!("{} ({})", name, games::server_key(srv)));
+    }
+    combo.set_hexpand(true);
+    layout.attach(&gtk::Label::new(Some("Compare with")), 0, 0, 1, 1);
+    layout.attach(&combo, 1, 0, 1, 1);
+
+    let grid = gtk::Grid::new();
+    grid.set_row_spacing(5);
+    grid.set_column_spacing(15);
+
+    let scrolled = gtk::ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+    scrolled.set_policy(gtk::PolicyType::Never, gtk::PolicyType::Automatic);
+    scrolled.set_propagate_natural_height(true);
+    scrolled.set_max_content_height(500);
+    scrolled.add(&grid);
+    layout.attach(&scrolled, 0, 1, 2, 1);
+
+    combo.connect_changed({
+        let resources = resources.clone();
+        let grid = grid.clone();
+        move |combo| {
+            for child in grid.get_children() {
+                grid.remove(&child);
+            }
+
+            if let Some((name, srv)) = combo.get_active().and_then(|i| candidates.get(i as usize)) {
+                fill_comparison(
+                    &resources,
+                    &grid,
+                    [first.0.as_str(), name.as_str()],
+                    (game, &first.1),
+                    (game, srv),
+                );
+            }
+            grid.show_all();
+        }
+    });
+    combo.set_active(Some(0));
+
+    dialog.get_content_area().add(&layout);
+    dialog.show_all();
+    dialog.run();
+    dialog.destroy();
+}
+
+fn fill_comparison(
+    resources: &Resources,
+    grid: &gtk::Grid,
+    names: [&str; 2],
+    a: (Game, &rgs::models::Server),
+    b: (Game, &rgs::models::Server),
+) {
+    let cell = |text: &str, bold: bool| {
+        let label = gtk::Label::new(None);
+        let text = glib::markup_escape_text(text);
+        label.set_markup(&if bold {
+            format!("<b>{}</b>", text)
+        } else {
+            text.to_string()
+        });
+        label.set_xalign(0.0);
+        label.set_yalign(0.0);
+        label.set_selectable(true);
+        label.set_line_wrap(true);
+        label.set_max_width_chars(40);
+        label
+    };
+
+    for (column, name) in names.iter().enumerate() {
+        grid.attach(&cell(name, true), column as i32 + 1, 0, 1, 1);
+    }
+
+    let fields = share::compare_servers(
+        &resources.settings.lock().unwrap(),
+        &*resources.game_list.0[&a.0].name_morpher,
+        a,
+        b,
+    );
+    for (i, field) in fields.into_iter().enumerate() {
+        let row = i as i32 + 1;
+        let differs = field.differs();
+
+        let title = cell(&field.name, differs);
+        title.get_style_context().add_class("dim-label");
+        grid.attach(&title, 0, row, 1, 1);
+        for (column, value) in field.values.iter().enumerate() {
+            grid.attach(
+                &cell(value.as_ref().map(String::as_str).unwrap_or("—"), differs),
+                column as i32 + 1,
+                row,
+                1,
+                1,
+            );
+        }
+    }
+}
+
+/// Shows the log records kept in memory, to be copied into bug reports.
+pub fn show_debug_log(resources: &Rc<Resources>) {
+    let window = resources.ui.get_object::<MainWindow, _>().unwrap().0;
+
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("Debug log"),
+        Some(&window),
+        gtk::DialogFlags::MODAL,
+        &[
+            ("_Copy", gtk::ResponseType::Apply),
+            ("_Close", gtk::ResponseType::Close),
+        ],
+    );
+    dialog.set_default_size(700, 400);
+
+    let text = resources.log_buffer.lock().unwrap().to_text();
+
+    let view = gtk::TextView::new();
+    view.set_editable(false);
+    view.set_monospace(true);
+    view.set_wrap_mode(gtk::WrapMode::WordChar);
+    if let Some(buffer) = view.get_buffer() {
+        buffer.set_text(if text.is_empty() {
+            "No log records yet."
+        } else {
+            &text
+        });
+    }
+
+    let scrolled = gtk::ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+    scrolled.set_policy(gtk::PolicyType::Automatic, gtk::PolicyType::Automatic);
+    scrolled.set_vexpand(true);
+    scrolled.set_property_margin(15);
+    scrolled.add(&view);
+
+    dialog.get_content_area().add(&scrolled);
+    dialog.show_all();
+    while dialog.run() == gtk::ResponseType::Apply {
+        gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(&text);
+    }
+    dialog.destroy();
+}
+
+/// Shows the last refresh outcome of each game with its learned refresh duration, the timeout in use and the
+/// cached server lists of its masters. `filter_rejections` are servers of the last refresh that game filters
+/// rejected, by filter.
+pub fn show_master_servers(
+    resources: &Rc<Resources>,
+    last_errors: &HashMap<Game, String>,
+    filter_rejections: &HashMap<Game, HashMap<String, usize>>,
+) {
+    let window = resources.ui.get_object::<MainWindow, _>().unwrap().0;
+
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("Master servers"),
+        Some(&window),
+        gtk::DialogFlags::MODAL,
+        &[("_Close", gtk::ResponseType::Close)],
+    );
+
+    let grid = gtk::Grid::new();
+    grid.set_row_spacing(5);
+    grid.set_column_spacing(10);
+    grid.set_property_margin(15);
+
+    for (column, title) in [
+        "Game",
+        "Last error",
+        "Average time",
+        "Timeout",
+        "Rejected by filters",
+        "Master cache",
+    ]
+    .iter()
+    .enumerate()
+    {
+        let label = gtk::Label::new(None);
+        label.set_markup(&format!("<b>{}</b>", title));
+        label.set_xalign(0.0);
+        grid.attach(&label, column as i32, 0, 1, 1);
+    }
+
+    let settings = resources.settings.lock().unwrap();
+    let refresh_stats = resources.refresh_stats.lock().unwrap();
+    let mut games = resources.game_list.0.keys().copied().collect::<Vec<_>>();
+    games.sort_by_key(|game| settings.game_name(*game));
+    for (i, game) in games.into_iter().enumerate() {
+        let row = i as i32 + 1;
+
+        let timeout = refresher::query_timeout(game, &settings, &refresh_stats);
+        let cells = [
+            settings.game_name(game),
+            last_errors
+                .get(&game)
+                .cloned()
+                .unwrap_or_else(|| "None".into()),
+            refresh_stats
+                .average(game)
+                .map(|v| format!("{:.1} s", v.as_millis() as f64 / 1000.0))
+                .unwrap_or_else(|| "Not yet known".into()),
+            format!(
+                "{} s, {}",
+                timeout.as_secs(),
+                if settings.query_timeouts.contains_key(&game) {
+                    "set"
+                } else {
+                    "auto"
+                }
+            ),
+            match filter_rejections.get(&game) {
+                Some(rejections) => {
+                    let mut rejections = rejections
+                        .iter()
+                        .map(|(filter, count)| format!("{}: {}", filter, count))
+                        .collect::<Vec<_>>();
+                    rejections.sort();
+                    rejections.join("\n")
+                }
+                None if games::server_filters_bypassed(&settings) => "None".into(),
+                None => "Not counted".into(),
+            },
+            master_cache_text(&resources.game_list.0[&game].querier.master_status()),
+        ];
+
+        for (column, text) in cells.iter().enumerate() {
+            let label = gtk::Label::new(Some(text.as_str()));
+            label.set_xalign(0.0);
+            label.set_selectable(true);
+            grid.attach(&label, column as i32, row, 1, 1);
+        }
+    }
+
+    dialog.get_content_area().add(&grid);
+    dialog.show_all();
+    dialog.run();
+    dialog.destroy();
+}
+
+fn master_cache_text(status: &[games::master_cache::MasterStatus]) -> String {
+    if status.is_empty() {
+        return "Nothing cached".into();
+    }
+
+    status
+        .iter()
+        .map(|status| {
+            let mut text = format!(
+                "{}: {} servers, {} s old{}, reused {} times",
+                status.master,
+                status.servers,
+                status.age.as_secs(),
+                if status.fresh { "" } else { " (expired)" },
+                status.reused
+            );
+            if status.next_request > Duration::from_secs(0) {
+                text.push_str(&format!(
+                    ", next request in {:.1} s",
+                    status.next_request.as_millis() as f64 / 1000.0
+                ));
+            }
+            text
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Reports widgets missing from the UI definition. Has no parent since the main window may be among them.
+pub fn show_missing_widgets(missing: &[MissingWidget]) {
+    for widget in missing {
+        error!("{}", widget);
+    }
+
+    let dialog = gtk::MessageDialog::new(
+        None::<&gtk::Window>,
+        gtk::DialogFlags::MODAL,
+        gtk::MessageType::Error,
+        gtk::ButtonsType::Close,
+        "The user interface definition is broken",
+    );
+    dialog.set_property_secondary_text(Some(
+        &missing
+            .iter()
+            .map(|widget| widget.to_string())
+            .collect::<Vec<_>>()
+            .join("\n"),
+    ));
+    dialog.run();
+    dialog.destroy();
+}
+
+/// Asks the user which game each unknown server type code belongs to.
+fn review_unknown_ids(
+    parent: &gtk::ApplicationWindow,
+    unknown_ids: &BTreeSet<String>,
+) -> Option<HashMap<String, Game>> {
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("Unknown games"),
+        Some(parent),
+        gtk::DialogFlags::MODAL,
+        &[
+            ("_Cancel", gtk::ResponseType::Cancel),
+            ("_Import", gtk::ResponseType::Accept),
+        ],
+    );
+
+    let grid = gtk::Grid::new();
+    grid.set_row_spacing(5);
+    grid.set_column_spacing(5);
+    grid.set_property_margin(15);
+
+    let combos = unknown_ids
+        .iter()
+        .enumerate()
+        .map(|(i, id)| {
+            let combo = gtk::ComboBoxText::new();
+            combo.append(Some(""), "Skip");
+            for game in Game::enum_iter() {
+                combo.append(Some(game.id()), &game.to_string());
+            }
+            combo.set_active_id(Some(""));
+
+            grid.attach(&gtk::Label::new(Some(id.as_str())), 0, i as i32, 1, 1);
+            grid.attach(&combo, 1, i as i32, 1, 1);
+
+            (id.clone(), combo)
+        })
+        .collect::<Vec<_>>();
+
+    dialog.get_content_area().add(&grid);
+    dialog.show_all();
+
+    let accepted = dialog.run() == gtk::ResponseType::Accept;
+
+    let out = combos
+        .into_iter()
+        .filter_map(|(id, combo)| {
+            let game = Game::from_id(&combo.get_active_id()?)?;
+            Some((id, game))
+        })
+        .collect();
+
+    dialog.destroy();
+
+    if accepted {
+        Some(out)
+    } else {
+        None
+    }
+}
+
+/// Imports favorites from XQF or qstat server lists.
+pub fn import_favorites(resources: &Rc<Resources>) {
+    let window = resources.ui.get_object::<MainWindow, _>().unwrap().0;
+
+    let path = match choose_file(&window, "Import servers", gtk::FileChooserAction::Open) {
+        Some(v) => v,
+        None => {
+            return;
+        }
+    };
+
+    let entries = match std::fs::read_to_string(&path) {
+        Ok(data) => import::parse(&data),
+        Err(e) => {
+            show_message(
+                &window,
+                gtk::MessageType::Error,
+                &format!("Failed to read {}: {}", path.display(), e),
+            );
+            return;
+        }
+    };
+
+    let unknown_ids = entries
+        .iter()
+        .filter(|entry| import::map_game_id(&entry.game_id).is_none())
+        .map(|entry| entry.game_id.clone())
+        .collect::<BTreeSet<_>>();
+
+    let overrides = if unknown_ids.is_empty() {
+        HashMap::new()
+    } else {
+        match review_unknown_ids(&window, &unknown_ids) {
+            Some(v) => v,
+            None => {
+                return;
+            }
+        }
+    };
+
+    let summary = {
+        let mut settings = resources.settings.lock().unwrap();
+        let summary = import::merge(&mut settings.favorites, &entries, &overrides);
+        if let Err(e) = settings.save() {
+            warn!("Failed to save settings: {}", e);
+        }
+        summary
+    };
+
+    show_message(&window, gtk::MessageType::Info, &summary.to_string());
+}
+
+fn choose_file(
+    parent: &gtk::ApplicationWindow,
+    title: &str,
+    action: gtk::FileChooserAction,
+) -> Option<std::path::PathBuf> {
+    let file_chooser = gtk::FileChooserDialog::with_buttons(
+        Some(title),
+        Some(parent),
+        action,
+        &[
+            ("_Cancel", gtk::ResponseType::Cancel),
+            ("_OK", gtk::ResponseType::Accept),
+        ],
+    );
+    file_chooser.set_do_overwrite_confirmation(true);
+
+    let path = if file_chooser.run() == gtk::ResponseType::Accept {
+        file_chooser.get_filename()
+    } else {
+        None
+    };
+    file_chooser.destroy();
+
+    path
+}
+
+pub fn export_state(resources: &Rc<Resources>) {
+    let window = resources.ui.get_object::<MainWindow, _>().unwrap().0;
+
+    let path = match choose_file(&window, "Export settings", gtk::FileChooserAction::Save) {
+        Some(v) => v,
+        None => {
+            return;
+        }
+    };
+
+    let bundle = state::StateBundle::new(
+        &resources.settings.lock().unwrap(),
+        &resources.recent_servers.lock().unwrap(),
+        &resources.activity_log.lock().unwrap(),
+    );
+
+    if let Err(e) = bundle
+        .to_json()
+        .and_then(|data| Ok(std::fs::write(&path, data)?))
+    {
+        show_message(
+            &window,
+            gtk::MessageType::Error,
+            &format!("Failed to export settings to {}: {}", path.display(), e),
+        );
+    }
+}
+
+/// Writes favorites of a game into a snippet for the game's own config.
+pub fn export_game_favorites(resources: &Rc<Resources>) {
+    let window = resources.ui.get_object::<MainWindow, _>().unwrap().0;
+
+    let mut favorites = HashMap::<Game, Vec<String>>::new();
+    for favorite in &resources.settings.lock().unwrap().favorites {
+        favorites
+            .entry(favorite.game)
+            .or_default()
+            .push(favorite.addr.clone());
+    }
+    favorites.retain(|game, addrs| {
+        resources
+            .game_list
+            .0
+            .get(game)
+            .map(|entry| entry.launcher.favorites_config(addrs).is_some())
+            .unwrap_or(false)
+    });
+
+    let game = match favorites.len() {
+        0 => {
+            show_message(
+                &window,
+                gtk::MessageType::Info,
+                "There are no favorites of games whose config can be written.",
+            );
+            return;
+        }
+        1 => *favorites.keys().next().unwrap(),
+        _ => {
+            let dialog = gtk::Dialog::new_with_buttons(
+                Some("Export favorites"),
+                Some(&window),
+                gtk::DialogFlags::MODAL,
+                &[
+                    ("_Cancel", gtk::ResponseType::Cancel),
+                    ("_Next", gtk::ResponseType::Accept),
+                ],
+            );
+            let combo = gtk::ComboBoxText::new();
+            let mut games = favorites.iter().collect::<Vec<_>>();
+            games.sort_by_key(|(game, _)| game.id());
+            for (game, addrs) in games {
+                combo.append(
+                    Some(game.id()),
+                    &format!(
+                        "{} ({} servers)",
+                        resources.settings.lock().unwrap().game_name(*game),
+                        addrs.len()
+                    ),
+                );
+            }
+            combo.set_active(Some(0));
+            combo.set_margin_start(12);
+            combo.set_margin_end(12);
+            combo.set_margin_top(12);
+            combo.set_margin_bottom(12);
+            dialog.get_content_area().add(&combo);
+            dialog.show_all();
+
+            let game = if dialog.run() == gtk::ResponseType::Accept {
+                combo.get_active_id().and_then(|id| Game::from_id(&id))
+            } else {
+                None
+            };
+            dialog.destroy();
+
+            match game {
+                Some(v) => v,
+                None => {
+                    return;
+                }
+            }
+        }
+    };
+
+    let path = match choose_file(
+        &window,
+        &format!(
+            "Export {} favorites",
+            resources.settings.lock().unwrap().game_name(game)
+        ),
+        gtk::FileChooserAction::Save,
+    ) {
+        Some(v) => v,
+        None => {
+            return;
+        }
+    };
+
+    let config = resources.game_list.0[&game]
+        .launcher
+        .favorites_config(&favorites[&game])
+        .unwrap_or_default();
+    if let Err(e) = std::fs::write(&path, config) {
+        show_message(
+            &window,
+            gtk::MessageType::Error,
+            &format!("Failed to export favorites to {}: {}", path.display(), e),
+        );
+    }
+}
+
+/// Replaces the application state with a bundle chosen by the user. Returns whether it was imported, for
+/// the caller to reload what depends on it.
+pub fn import_state(resources: &Rc<Resources>) -> bool {
+    let window = resources.ui.get_object::<MainWindow, _>().unwrap().0;
+
+    let path = match choose_file(&window, "Import settings", gtk::FileChooserAction::Open) {
+        Some(v) => v,
+        None => {
+            return false;
+        }
+    };
+
+    let bundle = match std::fs::read_to_string(&path)
+        .map_err(failure::Error::from)
+        .and_then(|data| state::StateBundle::from_json(&data))
+    {
+        Ok(v) => v,
+        Err(e) => {
+            show_message(
+                &window,
+                gtk::MessageType::Error,
+                &format!("Failed to import settings from {}: {}", path.display(), e),
+            );
+            return false;
+        }
+    };
+
+    if bundle.version > state::BUNDLE_VERSION {
+        warn!(
+            "Settings bundle version {} is newer than supported {}, unknown data will be ignored",
+            bundle.version,
+            state::BUNDLE_VERSION
+        );
+    }
+
+    let changed = bundle.overwritten(
+        &resources.settings.lock().unwrap(),
+        &resources.recent_servers.lock().unwrap(),
+        &resources.activity_log.lock().unwrap(),
+    );
+    if changed.is_empty() {
+        show_message(
+            &window,
+            gtk::MessageType::Info,
+            "Settings are already up to date.",
+        );
+        return false;
+    }
+
+    // Preview what is going to be overwritten
+    let dialog = gtk::MessageDialog::new(
+        Some(&window),
+        gtk::DialogFlags::MODAL,
+        gtk::MessageType::Question,
+        gtk::ButtonsType::OkCancel,
+        &format!(
+            "The following settings will be overwritten:\n\n{}{}",
+            changed.join("\n"),
+            if bundle.version > state::BUNDLE_VERSION {
+                "\n\nThe bundle was written by a newer release, what it adds is skipped."
+            } else {
+                ""
+            }
+        ),
+    );
+    let accepted = dialog.run() == gtk::ResponseType::Ok;
+    dialog.destroy();
+
+    if !accepted {
+        return false;
+    }
+
+    let mut settings = resources.settings.lock().unwrap();
+    let mut recent_servers = resources.recent_servers.lock().unwrap();
+    let mut activity_log = resources.activity_log.lock().unwrap();
+    bundle.apply(&mut settings, &mut recent_servers, &mut activity_log);
+    if let Err(e) = settings.save() {
+        warn!("Failed to save settings: {}", e);
+    }
+    if let Err(e) = recent_servers.save() {
+        warn!("Failed to save recent servers: {}", e);
+    }
+    if let Err(e) = activity_log.save() {
+        warn!("Failed to save favorite activity: {}", e);
+    }
+
+    true
+}
+
+/// First-run assistant. Settings are saved whether it is completed or skipped, so it is only shown once.
+pub fn run_welcome_assistant<F>(resources: &Rc<Resources>, on_done: F)
+where
+    F: Fn() + 'static,
+{
+    let window = resources.ui.get_object::<MainWindow, _>().unwrap().0;
+
+    let assistant = gtk::Assistant::new();
+    assistant.set_title("Welcome to Obozrenie");
+    assistant.set_transient_for(Some(&window));
+    assistant.set_modal(true);
+
+    // Games page
+    let games_page = gtk::Box::new(gtk::Orientation::Vertical, 5);
+    games_page.set_property_margin(15);
+    games_page.add(&gtk::Label::new(Some("Which games do you play?")));
+    let game_toggles = Game::enum_iter()
+        .filter(|game| resources.game_list.0.contains_key(game))
+        .map(|game| {
+            let toggle = gtk::CheckButton::new_with_label(
+                &resources.settings.lock().unwrap().game_name(game),
+            );
+            games_page.add(&toggle);
+            (game, toggle)
+        })
+        .collect::<Vec<_>>();
+    // Pre-tick games that are installed
+    resources.with_installed_games({
+        let game_toggles = game_toggles.clone();
+        move |installed_games| {
+            for (game, toggle) in &game_toggles {
+                if installed_games.contains(game) {
+                    toggle.set_active(true);
+                }
+            }
+        }
+    });
+    assistant.append_page(&games_page);
+    assistant.set_page_title(&games_page, "Games");
+    assistant.set_page_complete(&games_page, true);
+
+    // Profile page
+    let profile_page = gtk::Grid::new();
+    profile_page.set_property_margin(15);
+    profile_page.set_row_spacing(5);
+    profile_page.set_column_spacing(5);
+
+    let player_name = gtk::Entry::new();
+    player_name.set_placeholder_text(Some("Optional"));
+    profile_page.attach(&gtk::Label::new(Some("Player name")), 0, 0, 1, 1);
+    profile_page.attach(&player_name, 1, 0, 1, 1);
+
+    let home_country = gtk::Entry::new();
+    home_country.set_max_length(2);
+    home_country.set_placeholder_text(Some("Two-letter code, optional"));
+    profile_page.attach(&gtk::Label::new(Some("Country")), 0, 1, 1, 1);
+    profile_page.attach(&home_country, 1, 1, 1, 1);
+
+    let refresh_on_startup = gtk::CheckButton::new_with_label("Refresh server list on startup");
+    refresh_on_startup.set_active(true);
+    profile_page.attach(&refresh_on_startup, 0, 2, 2, 1);
+
+    assistant.append_page(&profile_page);
+    assistant.set_page_title(&profile_page, "Profile");
+    assistant.set_page_type(&profile_page, gtk::AssistantPageType::Confirm);
+    assistant.set_page_complete(&profile_page, true);
+
+    assistant.connect_apply({
+        let resources = resources.clone();
+        move |_| {
+            let mut settings = resources.settings.lock().unwrap();
+            settings.disabled_games = game_toggles
+                .iter()
+                .filter(|(_, toggle)| !toggle.get_active())
+                .map(|(game, _)| *game)
+                .collect();
+            settings.player_name = player_name
+                .get_text()
+                .map(|s| s.to_string())
+                .unwrap_or_default();
+            settings.home_country = home_country
+                .get_text()
+                .map(|s| s.to_lowercase())
+                .unwrap_or_default();
+            settings.refresh_on_startup = refresh_on_startup.get_active();
+        }
+    });
+
+    let finish = Rc::new({
+        let resources = resources.clone();
+        move |assistant: &gtk::Assistant| {
+            if let Err(e) = resources.settings.lock().unwrap().save() {
+                warn!("Failed to save settings: {}", e);
+            }
+            assistant.destroy();
+            on_done();
+        }
+    });
+
+    assistant.connect_close({
+        let finish = finish.clone();
+        move |assistant| finish(assistant)
+    });
+    // Skipping keeps the defaults
+    assistant.connect_cancel(move |assistant| finish(assistant));
+
+    assistant.show_all();
+}
+
+pub fn show_about(resources: &Rc<Resources>) {
+    let dialog = gtk::AboutDialog::new();
+    dialog.set_transient_for(Some(&resources.ui.get_object::<MainWindow, _>().unwrap().0));
+    dialog.set_modal(true);
+    dialog.set_program_name("Obozrenie");
+    dialog.set_version(Some(env!("CARGO_PKG_VERSION")));
+    dialog.set_comments(Some("Simple and easy to use game server browser."));
+    dialog.set_logo_icon_name(Some("obozrenie"));
+    dialog.set_copyright(Some("Copyright © 2015-2019 Artem Vorotnikov"));
+    dialog.set_license_type(gtk::License::Agpl30);
+    dialog.set_website(Some(env!("CARGO_PKG_REPOSITORY")));
+    dialog.set_website_label(Some("Repository"));
+
+    // Icons are bundled for every supported game
+    let games = Game::enum_iter()
+        .map(|game| game.to_string())
+        .collect::<Vec<_>>();
+    dialog.add_credit_section(
+        "Game icons",
+        &games.iter().map(String::as_str).collect::<Vec<_>>(),
+    );
+
+    dialog.run();
+    dialog.destroy();
+}
+
+pub fn show_preferences(resources: &Rc<Resources>) {
+    let window = resources.ui.get_object::<MainWindow, _>().unwrap().0;
+
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("Preferences"),
+        Some(&window),
+        gtk::DialogFlags::MODAL,
+        &[("_Close", gtk::ResponseType::Close)],
+    );
+
+    let grid = gtk::Grid::new();
+    grid.set_row_spacing(5);
+    grid.set_column_spacing(10);
+    grid.set_property_margin(15);
+
+    let settings = resources.settings.lock().unwrap().clone();
+
+    let player_name = gtk::Entry::new();
+    player_name.set_text(&settings.player_name);
+    player_name.set_placeholder_text(Some("Optional"));
+    grid.attach(&gtk::Label::new(Some("Player name")), 0, 0, 1, 1);
+    grid.attach(&player_name, 1, 0, 1, 1);
+
+    let home_country = gtk::Entry::new();
+    home_country.set_max_length(2);
+    home_country.set_text(&settings.home_country);
+    home_country.set_placeholder_text(Some("Two-letter code, optional"));
+    grid.attach(&gtk::Label::new(Some("Country")), 0, 1, 1, 1);
+    grid.attach(&home_country, 1, 1, 1, 1);
+
+    let joinable_max_ping = gtk::SpinButton::new_with_range(0.0, 999.0, 10.0);
+    joinable_max_ping.set_value(f64::from(settings.joinable_max_ping));
+    grid.attach(
+        &gtk::Label::new(Some("\"Joinable\" ping limit")),
+        0,
+        2,
+        1,
+        1,
+    );
+    grid.attach(&joinable_max_ping, 1, 2, 1, 1);
+
+    let max_concurrent_refreshes = gtk::SpinButton::new_with_range(0.0, 16.0, 1.0);
+    max_concurrent_refreshes.set_value(settings.max_concurrent_refreshes as f64);
+    max_concurrent_refreshes.set_tooltip_text(Some("0 for unlimited"));
+    grid.attach(&gtk::Label::new(Some("Games queried at once")), 0, 3, 1, 1);
+    grid.attach(&max_concurrent_refreshes, 1, 3, 1, 1);
+
+    let refresh_on_startup = gtk::CheckButton::new_with_label("Refresh server list on startup");
+    refresh_on_startup.set_active(settings.refresh_on_startup);
+    grid.attach(&refresh_on_startup, 0, 4, 2, 1);
+
+    // Options below are applied on next start
+    let auto_refresh_interval = gtk::SpinButton::new_with_range(0.0, 3600.0, 30.0);
+    auto_refresh_interval.set_value(f64::from(settings.auto_refresh_interval));
+    auto_refresh_interval.set_tooltip_text(Some("In seconds, 0 to disable. Applied on restart."));
+    grid.attach(&gtk::Label::new(Some("Auto refresh interval")), 0, 5, 1, 1);
+    grid.attach(&auto_refresh_interval, 1, 5, 1, 1);
+
+    let close_to_tray = gtk::CheckButton::new_with_label("Close to system tray");
+    close_to_tray.set_active(settings.close_to_tray);
+    close_to_tray.set_tooltip_text(Some("Applied on restart."));
+    grid.attach(&close_to_tray, 0, 6, 2, 1);
+
+    let notify_favorites =
+        gtk::CheckButton::new_with_label("Notify when favorite servers get players");
+    notify_favorites.set_active(settings.notify_favorites);
+    notify_favorites.set_tooltip_text(Some("Only while the window is hidden to the tray."));
+    grid.attach(&notify_favorites, 0, 7, 2, 1);
+
+    let strip_name_decorations =
+        gtk::CheckButton::new_with_label("Strip decorations and clan tags from server names");
+    strip_name_decorations.set_active(settings.strip_name_decorations);
+    strip_name_decorations.set_tooltip_text(Some("Applied on restart."));
+    grid.attach(&strip_name_decorations, 0, 8, 2, 1);
+
+    let ping_cache_ttl = gtk::SpinButton::new_with_range(0.0, 3600.0, 60.0);
+    ping_cache_ttl.set_value(settings.ping_cache_ttl as f64);
+    ping_cache_ttl.set_tooltip_text(Some(
+        "Servers pinged more recently than this keep their latency, in seconds. 0 to always ping. Applied on restart.",
+    ));
+    grid.attach(&gtk::Label::new(Some("Reuse pings for")), 0, 9, 1, 1);
+    grid.attach(&ping_cache_ttl, 1, 9, 1, 1);
+
+    let show_ping_history = gtk::CheckButton::new_with_label("Show ping history in server details");
+    show_ping_history.set_active(settings.show_ping_history);
+    grid.attach(&show_ping_history, 0, 10, 2, 1);
+
+    let password_retry_window = gtk::SpinButton::new_with_range(0.0, 120.0, 5.0);
+    password_retry_window.set_value(settings.password_retry_window as f64);
+    password_retry_window.set_tooltip_text(Some(
+        "Ask for the password again if a Quake-family game quits this soon after joining a passworded server, in seconds. 0 to disable.",
+    ));
+    grid.attach(&gtk::Label::new(Some("Retry password within")), 0, 11, 1, 1);
+    grid.attach(&password_retry_window, 1, 11, 1, 1);
+
+    let ping_limit = gtk::SpinButton::new_with_range(0.0, 9999.0, 50.0);
+    ping_limit.set_value(settings.ping_limit as f64);
+    ping_limit.set_tooltip_text(Some(
+        "Servers slower than this are not waited for and left out of the list, in milliseconds. 0 to always wait. Applied on restart.",
+    ));
+    grid.attach(&gtk::Label::new(Some("Stop pinging after")), 0, 12, 1, 1);
+    grid.attach(&ping_limit, 1, 12, 1, 1);
+
+    let detach_view_rate = gtk::SpinButton::new_with_range(0.0, 1000.0, 10.0);
+    detach_view_rate.set_value(f64::from(settings.detach_view_rate));
+    detach_view_rate.set_tooltip_text(Some(
+        "While more servers than this arrive per second, the list is hidden until the refresh completes. 0 to always show it.",
+    ));
+    grid.attach(
+        &gtk::Label::new(Some("Hide list above servers/s")),
+        0,
+        13,
+        1,
+        1,
+    );
+    grid.attach(&detach_view_rate, 1, 13, 1, 1);
+
+    let spam_box = gtk::Box::new(gtk::Orientation::Vertical, 2);
+    spam_box.set_tooltip_text(Some(
+        "Hide servers repeating a name already listed on the same host or on several others, e.g. redirect spam.",
+    ));
+    let spam_toggles = Game::enum_iter()
+        .filter(|game| resources.game_list.0.contains_key(game))
+        .map(|game| {
+            let toggle = gtk::CheckButton::new_with_label(&settings.game_name(game));
+            toggle.set_active(settings.collapse_spam_games.contains(&game));
+            spam_box.add(&toggle);
+            (game, toggle)
+        })
+        .collect::<Vec<_>>();
+    let spam_label = gtk::Label::new(Some("Hide name spam in"));
+    spam_label.set_valign(gtk::Align::Start);
+    grid.attach(&spam_label, 0, 14, 1, 1);
+    grid.attach(&spam_box, 1, 14, 1, 1);
+
+    let no_ping_box = gtk::Box::new(gtk::Orientation::Vertical, 2);
+    no_ping_box.set_tooltip_text(Some(
+        "Do not ping servers of games that block ICMP, so that refreshes do not wait for the ping to time out. Latency is then measured from query replies. Applied on restart.",
+    ));
+    let no_ping_toggles = Game::enum_iter()
+        .filter(|game| resources.game_list.0.contains_key(game))
+        .map(|game| {
+            let toggle = gtk::CheckButton::new_with_label(&settings.game_name(game));
+            toggle.set_active(settings.no_ping_games.contains(&game));
+            no_ping_box.add(&toggle);
+            (game, toggle)
+        })
+        .collect::<Vec<_>>();
+    let no_ping_label = gtk::Label::new(Some("Do not ping servers of"));
+    no_ping_label.set_valign(gtk::Align::Start);
+    grid.attach(&no_ping_label, 0, 15, 1, 1);
+    grid.attach(&no_ping_box, 1, 15, 1, 1);
+
+    let unknown_ping = gtk::ComboBoxText::new();
+    unknown_ping.append(Some(UnknownPing::Last.id()), "Last");
+    unknown_ping.append(Some(UnknownPing::First.id()), "First");
+    unknown_ping.set_active_id(Some(settings.unknown_ping.id()));
+    unknown_ping.set_tooltip_text(Some(
+        "Servers that could not be pinged show a dash and stay at this end of the list in either sort order.",
+    ));
+    grid.attach(
+        &gtk::Label::new(Some("List unknown ping servers")),
+        0,
+        16,
+        1,
+        1,
+    );
+    grid.attach(&unknown_ping, 1, 16, 1, 1);
+
+    let incremental_refresh =
+        gtk::CheckButton::new_with_label("Keep listed servers while refreshing");
+    incremental_refresh.set_active(settings.incremental_refresh);
+    incremental_refresh.set_tooltip_text(Some(
+        "Update servers in place as replies arrive and remove the ones that did not reply once the refresh completes, instead of starting from an empty list.",
+    ));
+    grid.attach(&incremental_refresh, 0, 17, 2, 1);
+
+    let bypass_server_filters =
+        gtk::CheckButton::new_with_label("List servers rejected by game filters");
+    bypass_server_filters.set_active(settings.bypass_server_filters);
+    bypass_server_filters.set_tooltip_text(Some(
+        "Show servers that game-specific filters would hide, dimmed, and count them per filter in the master server overview. For telling a master that is down from a filter that hides everything. Applied on restart.",
+    ));
+    grid.attach(&bypass_server_filters, 0, 18, 2, 1);
+
+    let query_retries = gtk::SpinButton::new_with_range(0.0, 10.0, 1.0);
+    query_retries.set_value(f64::from(settings.query_retries));
+    query_retries.set_tooltip_text(Some(
+        "Send a query again this many times if no reply arrives, for lossy networks. Master servers are asked again while none of them has answered, servers they list are not. Applied on restart.",
+    ));
+    grid.attach(&gtk::Label::new(Some("Resend server queries")), 0, 19, 1, 1);
+    grid.attach(&query_retries, 1, 19, 1, 1);
+
+    let query_retry_window = gtk::SpinButton::new_with_range(100.0, 10000.0, 100.0);
+    query_retry_window.set_value(settings.query_retry_window as f64);
+    query_retry_window.set_tooltip_text(Some(
+        "Time to wait for a reply before resending, in milliseconds. Applied on restart.",
+    ));
+    grid.attach(&gtk::Label::new(Some("Resend after")), 0, 20, 1, 1);
+    grid.attach(&query_retry_window, 1, 20, 1, 1);
+
+    let version_box = gtk::Box::new(gtk::Orientation::Vertical, 2);
+    version_box.set_tooltip_text(Some(
+        "Servers running another version are marked with a warning. Leave empty to not check.",
+    ));
+    let version_entries = Game::enum_iter()
+        .filter(|game| resources.game_list.0.contains_key(game) && games::checks_version(*game))
+        .map(|game| {
+            let row = gtk::Box::new(gtk::Orientation::Horizontal, 10);
+            let entry = gtk::Entry::new();
+            entry.set_text(settings.local_version(game).unwrap_or(""));
+            entry.set_placeholder_text(Some("Any"));
+            row.pack_start(
+                &gtk::Label::new(Some(&settings.game_name(game))),
+                false,
+                false,
+                0,
+            );
+            row.pack_end(&entry, false, false, 0);
+            version_box.add(&row);
+            (game, entry)
+        })
+        .collect::<Vec<_>>();
+    let version_label = gtk::Label::new(Some("Installed game versions"));
+    version_label.set_valign(gtk::Align::Start);
+    grid.attach(&version_label, 0, 21, 1, 1);
+    grid.attach(&version_box, 1, 21, 1, 1);
+
+    let refresh_selected_game = gtk::CheckButton::new_with_label("Refresh only the selected game");
+    refresh_selected_game.set_active(settings.refresh_selected_game);
+    refresh_selected_game.set_tooltip_text(Some(
+        "When a single game is selected in the sidebar, the refresh button queries just that game. Shift-click it or use Refresh all games in the menu to query every game.",
+    ));
+    grid.attach(&refresh_selected_game, 0, 22, 2, 1);
+
+    let launch_method = gtk::ComboBoxText::new();
+    for method in LaunchMethod::ALL {
+        launch_method.append(Some(method.id()), method.title());
+    }
+    launch_method.set_active_id(Some(settings.launch_method.id()));
+    launch_method.set_tooltip_text(Some("Applied on restart."));
+    grid.attach(&gtk::Label::new(Some("Launch games with")), 0, 23, 1, 1);
+    grid.attach(&launch_method, 1, 23, 1, 1);
+
+    let launch_box = gtk::Box::new(gtk::Orientation::Vertical, 2);
+    launch_box.set_tooltip_text(Some(
+        "The command is the executable for the installed executable method, or the command line for the custom one, where {addr}, {host}, {port}, {password} and {name} are replaced with the server address, host, port, password and player name. Applied on restart.",
+    ));
+    let launch_rows = Game::enum_iter()
+        .filter(|game| resources.game_list.0.contains_key(game))
+        .map(|game| {
+            let row = gtk::Box::new(gtk::Orientation::Horizontal, 10);
+            let method = gtk::ComboBoxText::new();
+            method.append(Some("default"), "Default");
+            for v in LaunchMethod::ALL {
+                method.append(Some(v.id()), v.title());
+            }
+            method.set_active_id(Some(
+                settings
+                    .game_launch_methods
+                    .get(&game)
+                    .map(|v| v.id())
+                    .unwrap_or("default"),
+            ));
+            let command = gtk::Entry::new();
+            command.set_text(settings.launch_command(game).unwrap_or(""));
+            command.set_placeholder_text(Some("Command"));
+            row.pack_start(
+                &gtk::Label::new(Some(&settings.game_name(game))),
+                false,
+                false,
+                0,
+            );
+            row.pack_end(&command, false, false, 0);
+            row.pack_end(&method, false, false, 0);
+            launch_box.add(&row);
+            (game, method, command)
+        })
+        .collect::<Vec<_>>();
+    let launch_label = gtk::Label::new(Some("Launch per game"));
+    launch_label.set_valign(gtk::Align::Start);
+    grid.attach(&launch_label, 0, 24, 1, 1);
+    grid.attach(&launch_box, 1, 24, 1, 1);
+
+    let address_migration = gtk::CheckButton::new_with_label("Follow servers that change address");
+    address_migration.set_active(settings.address_migration.enabled);
+    address_migration.set_tooltip_text(Some(
+        "When keeping listed servers while refreshing, a server gone from its address is taken to have moved if a single new server has the same name and port. Its favorite, pin and history move along.",
+    ));
+    // Only incremental refreshes keep the rows to compare
+    address_migration.set_sensitive(incremental_refresh.get_active());
+    incremental_refresh.connect_toggled({
+        let address_migration = address_migration.clone();
+        move |w| address_migration.set_sensitive(w.get_active())
+    });
+    grid.attach(&address_migration, 0, 25, 2, 1);
+
+    let anticheat_box = gtk::Box::new(gtk::Orientation::Vertical, 2);
+    anticheat_box.set_tooltip_text(Some(
+        "Name of the anti-cheat that servers of the game report through their secure flag. Leave empty to hide the flag for the game. Applied on restart.",
+    ));
+    let anticheat_entries = Game::enum_iter()
+        .filter(|game| resources.game_list.0.contains_key(game))
+        .map(|game| {
+            let row = gtk::Box::new(gtk::Orientation::Horizontal, 10);
+            let entry = gtk::Entry::new();
+            entry.set_text(&settings.anticheat_name(game).unwrap_or_default());
+            entry.set_placeholder_text(Some("None"));
+            row.pack_start(
+                &gtk::Label::new(Some(&settings.game_name(game))),
+                false,
+                false,
+                0,
+            );
+            row.pack_end(&entry, false, false, 0);
+            anticheat_box.add(&row);
+            (game, entry)
+        })
+        .collect::<Vec<_>>();
+    let anticheat_label = gtk::Label::new(Some("Anti-cheat per game"));
+    anticheat_label.set_valign(gtk::Align::Start);
+    grid.attach(&anticheat_label, 0, 26, 1, 1);
+    grid.attach(&anticheat_box, 1, 26, 1, 1);
+
+    let master_cache_ttl = gtk::SpinButton::new_with_range(0.0, 3600.0, 30.0);
+    master_cache_ttl.set_value(settings.master_cache_ttl as f64);
+    master_cache_ttl.set_tooltip_text(Some(
+        "Refreshes this soon after asking a master server query the servers it listed without asking it again, in seconds. 0 to always ask. Applied on restart.",
+    ));
+    grid.attach(&gtk::Label::new(Some("Reuse master lists")), 0, 27, 1, 1);
+    grid.attach(&master_cache_ttl, 1, 27, 1, 1);
+
+    let master_request_interval = gtk::SpinButton::new_with_range(0.0, 60000.0, 500.0);
+    master_request_interval.set_value(settings.master_request_interval as f64);
+    master_request_interval.set_tooltip_text(Some(
+        "Least time between requests to the same master server, in milliseconds. Games sharing a master wait for each other. Applied on restart.",
+    ));
+    grid.attach(&gtk::Label::new(Some("Master request gap")), 0, 28, 1, 1);
+    grid.attach(&master_request_interval, 1, 28, 1, 1);
+
+    let share_template = gtk::TextView::new();
+    share_template.set_monospace(true);
+    share_template.set_tooltip_text(Some(
+        "Text copied by Share server info. Placeholders: {game}, {name}, {addr}, {map}, {players}, {max_players}, {mod}, {ping}. Empty for the default.",
+    ));
+    if let Some(buffer) = share_template.get_buffer() {
+        buffer.set_text(&settings.share_template);
+    }
+    let share_label = gtk::Label::new(Some("Share template"));
+    share_label.set_valign(gtk::Align::Start);
+    grid.attach(&share_label, 0, 29, 1, 1);
+    grid.attach(&share_template, 1, 29, 1, 1);
+
+    let retries_box = gtk::Box::new(gtk::Orientation::Vertical, 2);
+    retries_box.set_tooltip_text(Some(
+        "Times queries to the game's masters and servers are sent again, instead of the one for all games. Leave empty for the default. Applied on restart.",
+    ));
+    let retries_entries = Game::enum_iter()
+        .filter(|game| resources.game_list.0.contains_key(game))
+        .map(|game| {
+            let row = gtk::Box::new(gtk::Orientation::Horizontal, 10);
+            let entry = gtk::Entry::new();
+            if let Some(retries) = settings.query_retries_per_game.get(&game) {
+                entry.set_text(&retries.to_string());
+            }
+            entry.set_placeholder_text(Some("Default"));
+            row.pack_start(
+                &gtk::Label::new(Some(&settings.game_name(game))),
+                false,
+                false,
+                0,
+            );
+            row.pack_end(&entry, false, false, 0);
+            retries_box.add(&row);
+            (game, entry)
+        })
+        .collect::<Vec<_>>();
+    let retries_label = gtk::Label::new(Some("Resend per game"));
+    retries_label.set_valign(gtk::Align::Start);
+    grid.attach(&retries_label, 0, 30, 1, 1);
+    grid.attach(&retries_box, 1, 30, 1, 1);
+
+    let bind_address = gtk::Entry::new();
+    bind_address.set_text(
+        settings
+            .bind_address
+            .as_ref()
+            .map(|s| s.as_str())
+            .unwrap_or(""),
+    );
+    bind_address.set_placeholder_text(Some("Any"));
+    bind_address.set_tooltip_text(Some(
+        "Local address game server queries go out from, e.g. that of a VPN interface. An IPv4 address leaves IPv6 servers unreachable and the other way around. Empty for any. Applied on restart.",
+    ));
+    grid.attach(&gtk::Label::new(Some("Bind address")), 0, 31, 1, 1);
+    grid.attach(&bind_address, 1, 31, 1, 1);
+
+    dialog.get_content_area().add(&grid);
+    dialog.show_all();
+    dialog.run();
+
+    let mut settings = resources.settings.lock().unwrap();
+    settings.player_name = player_name
+        .get_text()
+        .map(|s| s.to_string())
+        .unwrap_or_default();
+    settings.home_country = home_country
+        .get_text()
+        .map(|s| s.to_lowercase())
+        .unwrap_or_default();
+    settings.joinable_max_ping = joinable_max_ping.get_value_as_int() as u32;
+    settings.max_concurrent_refreshes = max_concurrent_refreshes.get_value_as_int() as usize;
+    settings.refresh_on_startup = refresh_on_startup.get_active();
+    settings.auto_refresh_interval = auto_refresh_interval.get_value_as_int() as u32;
+    settings.close_to_tray = close_to_tray.get_active();
+    settings.notify_favorites = notify_favorites.get_active();
+    settings.strip_name_decorations = strip_name_decorations.get_active();
+    settings.ping_cache_ttl = ping_cache_ttl.get_value_as_int() as u64;
+    settings.master_cache_ttl = master_cache_ttl.get_value_as_int() as u64;
+    settings.master_request_interval = master_request_interval.get_value_as_int() as u64;
+    settings.ping_limit = ping_limit.get_value_as_int() as u64;
+    if let Some(buffer) = share_template.get_buffer() {
+        let (start, end) = buffer.get_bounds();
+        let template = buffer
+            .get_text(&start, &end, false)
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default();
+        settings.share_template = if template.is_empty() {
+            share::DEFAULT_TEMPLATE.into()
+        } else {
+            template
+        };
+    }
+    settings.detach_view_rate = detach_view_rate.get_value_as_int() as u32;
+    settings.collapse_spam_games = spam_toggles
+        .iter()
+        .filter(|(_, toggle)| toggle.get_active())
+        .map(|(game, _)| *game)
+        .collect();
+    settings.no_ping_games = no_ping_toggles
+        .iter()
+        .filter(|(_, toggle)| toggle.get_active())
+        .map(|(game, _)| *game)
+        .collect();
+    if let Some(value) = unknown_ping
+        .get_active_id()
+        .and_then(|id| UnknownPing::from_id(&id))
+    {
+        settings.unknown_ping = value;
+    }
+    settings.show_ping_history = show_ping_history.get_active();
+    settings.incremental_refresh = incremental_refresh.get_active();
+    settings.address_migration.enabled = address_migration.get_active();
+    settings.refresh_selected_game = refresh_selected_game.get_active();
+    if let Some(value) = launch_method
+        .get_active_id()
+        .and_then(|id| LaunchMethod::from_id(&id))
+    {
+        settings.launch_method = value;
+    }
+    for (game, method, command) in launch_rows {
+        match method
+            .get_active_id()
+            .and_then(|id| LaunchMethod::from_id(&id))
+        {
+            Some(value) => {
+                settings.game_launch_methods.insert(game, value);
+            }
+            None => {
+                settings.game_launch_methods.remove(&game);
+            }
+        }
+
+        let command = command
+            .get_text()
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default();
+        if command.is_empty() {
+            settings.launch_commands.remove(&game);
+        } else {
+            settings.launch_commands.insert(game, command);
+        }
+    }
+    settings.bypass_server_filters = bypass_server_filters.get_active();
+    settings.query_retries = query_retries.get_value_as_int() as u32;
+    settings.query_retry_window = query_retry_window.get_value_as_int() as u64;
+    settings.password_retry_window = password_retry_window.get_value_as_int() as u64;
+    let address = bind_address
+        .get_text()
+        .map(|s| s.trim().to_string())
+        .unwrap_or_default();
+    if address.is_empty() {
+        settings.bind_address = None;
+    } else if let Err(e) = address.parse::<std::net::IpAddr>() {
+        warn!(
+            "Invalid bind address {}, keeping the previous one: {}",
+            address, e
+        );
+    } else {
+        settings.bind_address = Some(address);
+    }
+    for (game, entry) in version_entries {
+        let version = entry
+            .get_text()
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default();
+        if version.is_empty() {
+            settings.local_versions.remove(&game);
+        } else {
+            settings.local_versions.insert(game, version);
+        }
+    }
+    for (game, entry) in retries_entries {
+        let retries = entry
+            .get_text()
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default();
+        if retries.is_empty() {
+            settings.query_retries_per_game.remove(&game);
+        } else {
+            match retries.parse() {
+                Ok(v) => {
+                    settings.query_retries_per_game.insert(game, v);
+                }
+                Err(_) => warn!("Ignoring invalid resend count {} for {}", retries, game),
+            }
+        }
+    }
+    for (game, entry) in anticheat_entries {
+        let name = entry
+            .get_text()
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default();
+        // Only departures from the built-in name are kept
+        if name == games::anticheat(game).unwrap_or("") {
+            settings.anticheat_names.remove(&game);
+        } else {
+            settings.anticheat_names.insert(game, name);
+        }
+    }
+    if let Err(e) = settings.save() {
+        warn!("Failed to save settings: {}", e);
+    }
+    drop(settings);
+
+    dialog.destroy();
+
+    // Version warnings and the compatibility filter follow the installed versions
+    resources
+        .ui
+        .get_object::<ServerListFilter, _>()
+        .unwrap()
+        .0
+        .refilter();
+}
+
+pub fn show_shortcuts(resources: &Rc<Resources>) {
+    let window = resources.ui.get_object::<ShortcutsWindow, _>().unwrap().0;
+    window.show_all();
+    window.present();
+}
+
+/// Connection options specific to OpenTTD
+#[derive(Clone, Debug, Default)]
+pub struct OpenTTDJoin {
+    pub password: Option<String>,
+    pub company: Option<Company>,
+    pub company_password: Option<String>,
+}
+
+/// Asks for OpenTTD company and passwords. Returns `None` if cancelled.
+/// Edits the alias, note, stored password and launch profile of a favorite. Returns the details if saved.
+pub fn edit_favorite(resources: &Rc<Resources>, server: Favorite) -> Option<FavoriteDetails> {
+    let window = resources.ui.get_object::<MainWindow, _>().unwrap().0;
+
+    let details = resources
+        .settings
+        .lock()
+        .unwrap()
+        .favorite_details(server.game, &server.addr)
+        .cloned()
+        .unwrap_or_else(|| FavoriteDetails::new(server.clone()));
+
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some(&format!("Favorite {}", server.addr)),
+        Some(&window),
+        gtk::DialogFlags::MODAL,
+        &[
+            ("_Cancel", gtk::ResponseType::Cancel),
+            ("_Save", gtk::ResponseType::Accept),
+        ],
+    );
+    dialog.set_default_response(gtk::ResponseType::Accept);
+
+    let grid = gtk::Grid::new();
+    grid.set_row_spacing(5);
+    grid.set_column_spacing(10);
+    grid.set_property_margin(15);
+
+    let alias = gtk::Entry::new();
+    alias.set_text(details.alias.as_ref().map(String::as_str).unwrap_or(""));
+    alias.set_placeholder_text(Some("Name reported by the server"));
+    alias.set_activates_default(true);
+    grid.attach(&gtk::Label::new(Some("Alias")), 0, 0, 1, 1);
+    grid.attach(&alias, 1, 0, 1, 1);
+
+    let note = gtk::Entry::new();
+    note.set_text(&details.note);
+    note.set_activates_default(true);
+    grid.attach(&gtk::Label::new(Some("Note")), 0, 1, 1, 1);
+    grid.attach(&note, 1, 1, 1, 1);
+
+    let password = gtk::Entry::new();
+    password.set_visibility(false);
+    password.set_activates_default(true);
+    password.set_placeholder_text(Some(if details.password_ref.is_some() {
+        "Stored in the keyring"
+    } else {
+        "None"
+    }));
+    password.set_tooltip_text(Some(
+        "Used when connecting instead of asking. Leave empty to keep the stored one.",
+    ));
+    grid.attach(&gtk::Label::new(Some("Password")), 0, 2, 1, 1);
+    grid.attach(&password, 1, 2, 1, 1);
+
+    let forget_password = gtk::CheckButton::new_with_label("Forget stored password");
+    forget_password.set_sensitive(details.password_ref.is_some());
+    grid.attach(&forget_password, 1, 3, 1, 1);
+
+    let flatpak_ref = gtk::Entry::new();
+    flatpak_ref.set_text(
+        details
+            .profile
+            .flatpak_ref
+            .as_ref()
+            .map(String::as_str)
+            .unwrap_or(""),
+    );
+    flatpak_ref.set_placeholder_text(Some("Same as for the game"));
+    flatpak_ref.set_activates_default(true);
+    grid.attach(&gtk::Label::new(Some("Flatpak ref")), 0, 4, 1, 1);
+    grid.attach(&flatpak_ref, 1, 4, 1, 1);
+
+    let extra_args = gtk::Entry::new();
+    extra_args.set_text(&details.profile.extra_args.join(" "));
+    extra_args.set_placeholder_text(Some("None"));
+    extra_args.set_tooltip_text(Some(
+        "Appended to the game's command line, separated by spaces.",
+    ));
+    extra_args.set_activates_default(true);
+    grid.attach(&gtk::Label::new(Some("Extra arguments")), 0, 5, 1, 1);
+    grid.attach(&extra_args, 1, 5, 1, 1);
+
+    let follow_name = gtk::Entry::new();
+    follow_name.set_text(
+        details
+            .follow_name
+            .as_ref()
+            .map(|v| v.pattern.as_str())
+            .unwrap_or(""),
+    );
+    follow_name.set_placeholder_text(Some("Off"));
+    follow_name.set_tooltip_text(Some(
+        "Server name to find the favorite by after a refresh if its address stops replying, e.g. when it restarts on another port.",
+    ));
+    follow_name.set_activates_default(true);
+    grid.attach(&gtk::Label::new(Some("Follow by name")), 0, 6, 1, 1);
+    grid.attach(&follow_name, 1, 6, 1, 1);
+
+    let follow_substring = gtk::CheckButton::new_with_label("Match part of the name");
+    follow_substring.set_active(details.follow_name.as_ref().map_or(false, |v| v.substring));
+    grid.attach(&follow_substring, 1, 7, 1, 1);
+
+    if !details.past_addrs.is_empty() {
+        let past_addrs = gtk::Label::new(Some(&details.past_addrs.join("\n")));
+        past_addrs.set_selectable(true);
+        past_addrs.set_halign(gtk::Align::Start);
+        let label = gtk::Label::new(Some("Previous addresses"));
+        label.set_valign(gtk::Align::Start);
+        grid.attach(&label, 0, 8, 1, 1);
+        grid.attach(&past_addrs, 1, 8, 1, 1);
+    }
+
+    dialog.get_content_area().add(&grid);
+    dialog.show_all();
+
+    let accepted = dialog.run() == gtk::ResponseType::Accept;
+
+    let text = |entry: &gtk::Entry| {
+        entry
+            .get_text()
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default()
+    };
+    let non_empty = |entry: &gtk::Entry| Some(text(entry)).filter(|s| !s.is_empty());
+
+    let mut out = None;
+    if accepted {
+        let mut details = FavoriteDetails {
+            alias: non_empty(&alias),
+            note: text(&note),
+            profile: LaunchProfile {
+                flatpak_ref: non_empty(&flatpak_ref),
+                extra_args: text(&extra_args)
+                    .split_whitespace()
+                    .map(String::from)
+                    .collect(),
+            },
+            follow_name: non_empty(&follow_name).map(|pattern| FollowName {
+                pattern,
+                substring: follow_substring.get_active(),
+            }),
+            ..details
+        };
+
+        let key = secrets::key(&server);
+        let new_password = password
+            .get_text()
+            .map(|s| s.to_string())
+            .filter(|s| !s.is_empty());
+        if let Some(new_password) = new_password {
+            match secrets::store(&key, &new_password) {
+                Ok(()) => details.password_ref = Some(key),
+                Err(e) => show_message(
+                    &window,
+                    gtk::MessageType::Error,
+                    &format!("Failed to store the password in the keyring: {}", e),
+                ),
+            }
+        } else if forget_password.get_active() {
+            secrets::clear_in_background(key);
+            details.password_ref = None;
+        }
+
+        let mut settings = resources.settings.lock().unwrap();
+        settings.set_favorite_details(details.clone());
+        if let Err(e) = settings.save() {
+            warn!("Failed to save settings: {}", e);
+        }
+
+        out = Some(details);
+    }
+
+    dialog.destroy();
+
+    out
+}
+
+/// Asks which game the server at `addr` is for, among the games that can be launched.
+pub fn choose_game(resources: &Rc<Resources>, addr: &str) -> Option<Game> {
+    let window = resources.ui.get_object::<MainWindow, _>().unwrap().0;
+
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some(&format!("Join {}", addr)),
+        Some(&window),
+        gtk::DialogFlags::MODAL,
+        &[
+            ("_Cancel", gtk::ResponseType::Cancel),
+            ("_Join", gtk::ResponseType::Accept),
+        ],
+    );
+    dialog.set_default_response(gtk::ResponseType::Accept);
+
+    let grid = gtk::Grid::new();
+    grid.set_row_spacing(5);
+    grid.set_column_spacing(10);
+    grid.set_property_margin(15);
+
+    let combo = gtk::ComboBoxText::new();
+    {
+        let settings = resources.settings.lock().unwrap();
+        // All games are offered until the launcher probes finish
+        let installed_games = resources.installed_games.lock().unwrap().clone();
+        let mut games = resources
+            .game_list
+            .0
+            .keys()
+            .filter(|game| {
+                installed_games
+                    .as_ref()
+                    .map(|installed_games| installed_games.contains(game))
+                    .unwrap_or(true)
+            })
+            .copied()
+            .collect::<Vec<_>>();
+        games.sort_by_key(|game| settings.game_name(*game));
+        for game in games {
+            combo.append(Some(game.id()), &settings.game_name(game));
+        }
+    }
+    combo.set_active(Some(0));
+    grid.attach(
+        &gtk::Label::new(Some("The link does not tell the game of the server.")),
+        0,
+        0,
+        2,
+        1,
+    );
+    grid.attach(&gtk::Label::new(Some("Game")), 0, 1, 1, 1);
+    grid.attach(&combo, 1, 1, 1, 1);
+
+    dialog.get_content_area().add(&grid);
+    dialog.show_all();
+
+    let accepted = dialog.run() == gtk::ResponseType::Accept;
+    let game = combo.get_active_id().and_then(|id| Game::from_id(&id));
+    dialog.destroy();
+
+    game.filter(|_| accepted)
+}
+
+/// Asks which of several servers matching a followed favorite's name it moved to. Returns `None` to keep
+/// the favorite where it is.
+pub fn choose_followed_server(
+    resources: &Rc<Resources>,
+    favorite: &Favorite,
+    candidates: &[(String, String)],
+) -> Option<String> {
+    let window = resources.ui.get_object::<MainWindow, _>().unwrap().0;
+
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("Favorite moved"),
+        Some(&window),
+        gtk::DialogFlags::MODAL,
+        &[
+            ("_Keep address", gtk::ResponseType::Cancel),
+            ("_Move", gtk::ResponseType::Accept),
+        ],
+    );
+    dialog.set_default_response(gtk::ResponseType::Accept);
+
+    let grid = gtk::Grid::new();
+    grid.set_row_spacing(5);
+    grid.set_column_spacing(10);
+    grid.set_property_margin(15);
+
+    let combo = gtk::ComboBoxText::new();
+    for (addr, name) in candidates {
+        combo.append(Some(addr), &format!("{} ({})", name, addr));
+    }
+    combo.set_active(Some(0));
+    grid.attach(
+        &gtk::Label::new(Some(&format!(
+            "Favorite {} did not reply, but several servers match its name.",
+            favorite.addr
+        ))),
+        0,
+        0,
+        2,
+        1,
+    );
+    grid.attach(&gtk::Label::new(Some("Server")), 0, 1, 1, 1);
+    grid.attach(&combo, 1, 1, 1, 1);
+
+    dialog.get_content_area().add(&grid);
+    dialog.show_all();
+
+    let accepted = dialog.run() == gtk::ResponseType::Accept;
+    let addr = combo.get_active_id().map(|id| id.to_string());
+    dialog.destroy();
+
+    addr.filter(|_| accepted)
+}
+
+/// Asks for the address of a server to add to the favorites, and its game if known.
+/// Returns `None` for the game if it is to be detected.
+pub fn add_server(resources: &Rc<Resources>) -> Option<(Option<Game>, String)> {
+    let window = resources.ui.get_object::<MainWindow, _>().unwrap().0;
+
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("Add server"),
+        Some(&window),
+        gtk::DialogFlags::MODAL,
+        &[
+            ("_Cancel", gtk::ResponseType::Cancel),
+            ("_Add", gtk::ResponseType::Accept),
+        ],
+    );
+    dialog.set_default_response(gtk::ResponseType::Accept);
+
+    let grid = gtk::Grid::new();
+    grid.set_row_spacing(5);
+    grid.set_column_spacing(10);
+    grid.set_property_margin(15);
+
+    let address = gtk::Entry::new();
+    address.set_placeholder_text(Some("host:port"));
+    address.set_activates_default(true);
+    grid.attach(&gtk::Label::new(Some("Address")), 0, 0, 1, 1);
+    grid.attach(&address, 1, 0, 1, 1);
+
+    let game = gtk::ComboBoxText::new();
+    game.append(Some(""), "Detect automatically");
+    {
+        let settings = resources.settings.lock().unwrap();
+        let mut games = resources.game_list.0.keys().copied().collect::<Vec<_>>();
+        games.sort_by_key(|game| settings.game_name(*game));
+        for id in games {
+            game.append(Some(id.id()), &settings.game_name(id));
+        }
+    }
+    game.set_active(Some(0));
+    game.set_tooltip_text(Some(
+        "Detection asks the server in the protocol of every game and picks the one it answers.",
+    ));
+    grid.attach(&gtk::Label::new(Some("Game")), 0, 1, 1, 1);
+    grid.attach(&game, 1, 1, 1, 1);
+
+    dialog.get_content_area().add(&grid);
+    dialog.show_all();
+
+    let accepted = dialog.run() == gtk::ResponseType::Accept;
+    let addr = address
+        .get_text()
+        .map(|s| s.trim().to_string())
+        .unwrap_or_default();
+    let game = game.get_active_id().and_then(|id| Game::from_id(&id));
+    dialog.destroy();
+
+    Some((game, addr)).filter(|(_, addr)| accepted && !addr.is_empty())
+}
+
+pub fn request_openttd_join(resources: &Rc<Resources>, need_pass: bool) -> Option<OpenTTDJoin> {
+    let window = resources.ui.get_object::<MainWindow, _>().unwrap().0;
+
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("Join OpenTTD server"),
+        Some(&window),
+        gtk::DialogFlags::MODAL,
+        &[
+            ("_Cancel", gtk::ResponseType::Cancel),
+            ("C_onnect", gtk::ResponseType::Accept),
+        ],
+    );
+    dialog.set_default_response(gtk::ResponseType::Accept);
+
+    let grid = gtk::Grid::new();
+    grid.set_row_spacing(5);
+    grid.set_column_spacing(10);
+    grid.set_property_margin(15);
+
+    let company = gtk::ComboBoxText::new();
+    company.append(Some(""), "Choose in game");
+    company.append(Some("spectator"), &Company::Spectator.to_string());
+    for i in 1..=MAX_COMPANIES {
+        company.append(Some(&i.to_string()), &Company::Number(i).to_string());
+    }
+    company.set_active_id(Some(""));
+    grid.attach(&gtk::Label::new(Some("Company")), 0, 0, 1, 1);
+    grid.attach(&company, 1, 0, 1, 1);
+
+    let password = gtk::Entry::new();
+    password.set_visibility(false);
+    password.set_activates_default(true);
+    password.set_sensitive(need_pass);
+    password.set_placeholder_text(Some(if need_pass {
+        "Required"
+    } else {
+        "Not required"
+    }));
+    grid.attach(&gtk::Label::new(Some("Server password")), 0, 1, 1, 1);
+    grid.attach(&password, 1, 1, 1, 1);
+
+    let company_password = gtk::Entry::new();
+    company_password.set_visibility(false);
+    company_password.set_activates_default(true);
+    company_password.set_placeholder_text(Some("Optional"));
+    grid.attach(&gtk::Label::new(Some("Company password")), 0, 2, 1, 1);
+    grid.attach(&company_password, 1, 2, 1, 1);
+
+    // Spectators do not join any company
+    company.connect_changed({
+        let company_password = company_password.clone();
+        move |w| {
+            company_password.set_sensitive(
+                w.get_active_id()
+                    .map(|id| id.as_str() != "spectator")
+                    .unwrap_or(true),
+            );
+        }
+    });
+
+    dialog.get_content_area().add(&grid);
+    dialog.show_all();
+
+    let accepted = dialog.run() == gtk::ResponseType::Accept;
+
+    let non_empty = |entry: &gtk::Entry| {
+        Some(entry)
+            .filter(|entry| entry.is_sensitive())
+            .and_then(|entry| entry.get_text())
+            .map(|s| s.to_string())
+            .filter(|s| !s.is_empty())
+    };
+
+    let out = OpenTTDJoin {
+        password: non_empty(&password),
+        company: company.get_active_id().and_then(|id| match id.as_str() {
+            "spectator" => Some(Company::Spectator),
+            other => other.parse().ok().map(Company::Number),
+        }),
+        company_password: non_empty(&company_password),
+    };
+
+    dialog.destroy();
+
+    if accepted {
+        Some(out)
+    } else {
+        None
+    }
+}
@@ -14,25 +14,255 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use std::collections::HashSet;
+use log::*;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
 use std::time::Duration;
 
-use crate::games::Game;
+use crate::countries;
+use crate::games::{self, Game};
+use crate::settings::Favorite;
 
+/// Case-insensitive subsequence match, so that "dm6" finds "q3dm6".
+pub fn fuzzy_match(haystack: &str, needle: &str) -> bool {
+    let mut haystack = haystack.chars().flat_map(char::to_lowercase);
+
+    needle
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|c| haystack.any(|h| h == c))
+}
+
+/// fzf-style subsequence score of `needle` in `haystack`, `None` if it does not match.
+///
+/// Consecutive matches and matches at word starts score higher, gaps are penalized.
+pub fn fuzzy_score(haystack: &str, needle: &str) -> Option<i64> {
+    let haystack = haystack
+        .chars()
+        .flat_map(char::to_lowercase)
+        .collect::<Vec<_>>();
+
+    let mut score = 0;
+    let mut pos = 0;
+    let mut last_match: Option<usize> = None;
+    for c in needle.chars().flat_map(char::to_lowercase) {
+        let found = pos + haystack[pos..].iter().position(|&h| h == c)?;
+
+        score += 16;
+        match last_match {
+            Some(last) if last + 1 == found => score += 8,
+            Some(last) => score -= (found - last - 1) as i64,
+            None => {}
+        }
+        if found == 0 || !haystack[found - 1].is_alphanumeric() {
+            score += 8;
+        }
+
+        last_match = Some(found);
+        pos = found + 1;
+    }
+
+    Some(score)
+}
+
+/// Best score of the search query over `fields`, `None` if none match.
+///
+/// Queries starting with `'` match exactly like in fzf, as a case-insensitive substring.
+pub fn search_score(query: &str, fields: &[&str]) -> Option<i64> {
+    let query = query.trim();
+
+    if query.starts_with('\'') {
+        let query = query[1..].to_lowercase();
+        return fields
+            .iter()
+            .find(|field| field.to_lowercase().contains(&query))
+            .map(|_| 0);
+    }
+
+    fields
+        .iter()
+        .filter_map(|field| fuzzy_score(field, query))
+        .max()
+}
+
+/// Maps `AntiCheatFilter` item ids to the filter value. Unknown ids are treated as "ignore".
+pub fn anticheat_from_id(id: Option<&str>) -> Option<bool> {
+    match id {
+        Some("enabled") => Some(true),
+        Some("disabled") => Some(false),
+        Some("ignore") => None,
+        other => {
+            warn!("Invalid anti-cheat filter variant: {:?}", other);
+            None
+        }
+    }
+}
+
+/// Lowercase two-letter code of the server's country, if known.
+pub fn country_code(srv: &rgs::models::Server) -> Option<String> {
+    srv.country
+        .as_ref()
+        .and_then(|v| serde_json::to_value(v).ok())
+        .and_then(|v| v.as_str().map(str::to_lowercase))
+}
+
+/// Servers and players in a country
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CountryStats {
+    /// Lowercase two-letter code, `None` for servers of unknown location
+    pub country: Option<String>,
+    pub servers: usize,
+    pub players: u64,
+}
+
+/// Servers and players per country, most servers first.
+pub fn country_breakdown<'a>(
+    servers: impl IntoIterator<Item = &'a rgs::models::Server>,
+) -> Vec<CountryStats> {
+    let mut stats = HashMap::<Option<String>, CountryStats>::new();
+    for srv in servers {
+        let country = country_code(srv);
+        let entry = stats
+            .entry(country.clone())
+            .or_insert_with(|| CountryStats {
+                country,
+                ..Default::default()
+            });
+        entry.servers += 1;
+        entry.players += srv.num_clients.unwrap_or(0);
+    }
+
+    let mut stats = stats.into_iter().map(|(_, v)| v).collect::<Vec<_>>();
+    stats.sort_by(|a, b| {
+        b.servers
+            .cmp(&a.servers)
+            .then_with(|| b.players.cmp(&a.players))
+            .then_with(|| a.country.cmp(&b.country))
+    });
+    stats
+}
+
+/// Parses a comma separated list of country names or codes into codes. Codes may also be separated by spaces.
+pub fn parse_countries(text: &str) -> HashSet<String> {
+    let mut out = HashSet::new();
+    for item in text.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        match countries::code(item) {
+            Some(code) => {
+                out.insert(code);
+            }
+            None => out.extend(item.split_whitespace().map(str::to_lowercase)),
+        }
+    }
+    out
+}
+
+/// Compiles a text filter pattern for regex mode. Empty patterns match anything.
+pub fn compile_pattern(pattern: &str) -> Result<Option<Regex>, regex::Error> {
+    if pattern.is_empty() {
+        Ok(None)
+    } else {
+        Regex::new(pattern).map(Some)
+    }
+}
+
+/// Checks a field against a text filter, which is a prefix unless regex mode is enabled.
+fn text_matches(value: &str, prefix: &str, regex: Option<&Regex>, regex_mode: bool) -> bool {
+    if regex_mode {
+        regex.map(|re| re.is_match(value)).unwrap_or(true)
+    } else {
+        value.starts_with(prefix)
+    }
+}
+
+/// Server list filters. Empty or unset fields match anything.
 #[derive(Clone, Debug, Default)]
 pub struct Filters {
     pub games: HashSet<Game>,
     pub game_mod: String,
     pub game_type: String,
     pub map: String,
+    pub map_fuzzy: bool,
     pub max_ping: Duration,
     pub anticheat: Option<bool>,
+    /// Games `anticheat` applies to, `None` for those of `games::reports_anticheat`. Servers of other games
+    /// pass it, as their secure flag means nothing.
+    pub anticheat_games: Option<HashSet<Game>>,
     pub not_full: bool,
     pub not_empty: bool,
     pub no_password: bool,
+    /// Have `no_password` hide servers that do not report whether they need a password, in any game
+    pub unknown_password_protected: bool,
+    /// Servers `no_password` keeps, as a password is saved for them. Keyed by `games::server_key`.
+    pub saved_passwords: HashSet<Favorite>,
+    /// Lowercase two-letter country codes, empty for any
+    pub countries: HashSet<String>,
+    pub stock_client_only: bool,
+    /// Hide listen servers, hosted by a playing client
+    pub dedicated_only: bool,
+    /// Hide servers running a version the installed game cannot join. Applied by the server list, which has
+    /// access to the configured versions.
+    pub compatible_only: bool,
+    /// Show favorite servers only. Applied by the server list, which has access to favorites.
+    pub favorites_only: bool,
+    /// Search box query, matched against displayed values by the server list
+    pub search: String,
+    /// Interpret mod, game type and map filters as regular expressions
+    pub regex_mode: bool,
+    /// Compiled filter patterns in regex mode, `None` if empty or invalid
+    pub game_mod_regex: Option<Regex>,
+    pub game_type_regex: Option<Regex>,
+    pub map_regex: Option<Regex>,
 }
 
 impl Filters {
+    /// Compiles the mod, game type and map filters for regex mode.
+    pub fn compile_patterns(&mut self) -> Result<(), regex::Error> {
+        self.game_mod_regex = compile_pattern(&self.game_mod)?;
+        self.game_type_regex = compile_pattern(&self.game_type)?;
+        self.map_regex = compile_pattern(&self.map)?;
+
+        Ok(())
+    }
+
+    /// Number of criteria narrowing down the list. Match modifiers, the search and the favorites view
+    /// do not count.
+    pub fn active_count(&self) -> usize {
+        [
+            !self.games.is_empty(),
+            !self.game_mod.is_empty(),
+            !self.game_type.is_empty(),
+            !self.map.is_empty(),
+            self.max_ping > Duration::from_millis(0),
+            self.anticheat.is_some(),
+            self.not_full,
+            self.not_empty,
+            self.no_password,
+            !self.countries.is_empty(),
+            self.stock_client_only,
+            self.dedicated_only,
+            self.compatible_only,
+        ]
+        .iter()
+        .filter(|active| **active)
+        .count()
+    }
+
+    /// Whether a text filter narrows down the list. Invalid patterns leave the compiled regex unset and match anything.
+    fn text_filter_active(&self, pattern: &str, regex: Option<&Regex>) -> bool {
+        if self.regex_mode {
+            regex.is_some()
+        } else {
+            !pattern.is_empty()
+        }
+    }
+
+    /// Whether the server passes the filters.
+    ///
+    /// Servers that do not report a field are excluded by an active text filter on it, but kept by numeric
+    /// and location filters. `no_password` and `anticheat` exclude such servers only for games that report
+    /// the flag, so that they never hide a whole game, unless `unknown_password_protected` is set. `anticheat`
+    /// leaves games without an anti-cheat alone altogether.
     pub fn matches(&self, game: Game, srv: &rgs::models::Server) -> bool {
         if !self.games.is_empty() {
             if !self.games.contains(&game) {
@@ -40,20 +270,46 @@ impl Filters {
             }
         }
 
-        if let Some(v) = srv.mod_name.as_ref() {
-            if !v.starts_with(&self.game_mod) {
+        if self.text_filter_active(&self.game_mod, self.game_mod_regex.as_ref()) {
+            let matched = srv.mod_name.as_ref().map_or(false, |v| {
+                text_matches(
+                    v,
+                    &self.game_mod,
+                    self.game_mod_regex.as_ref(),
+                    self.regex_mode,
+                )
+            });
+
+            if !matched {
                 return false;
             }
         }
 
-        if let Some(v) = srv.game_type.as_ref() {
-            if !v.starts_with(&self.game_type) {
+        if self.text_filter_active(&self.game_type, self.game_type_regex.as_ref()) {
+            let matched = srv.game_type.as_ref().map_or(false, |v| {
+                text_matches(
+                    v,
+                    &self.game_type,
+                    self.game_type_regex.as_ref(),
+                    self.regex_mode,
+                )
+            });
+
+            if !matched {
                 return false;
             }
         }
 
-        if let Some(v) = srv.map.as_ref() {
-            if !v.starts_with(&self.map) {
+        if self.text_filter_active(&self.map, self.map_regex.as_ref()) {
+            let matched = srv.map.as_ref().map_or(false, |v| {
+                if self.map_fuzzy && !self.regex_mode {
+                    fuzzy_match(v, &self.map)
+                } else {
+                    text_matches(v, &self.map, self.map_regex.as_ref(), self.regex_mode)
+                }
+            });
+
+            if !matched {
                 return false;
             }
         }
@@ -67,16 +323,21 @@ impl Filters {
         }
 
         if let Some(filter) = self.anticheat {
-            if let Some(value) = srv.secure {
-                if filter != value {
-                    return false;
-                }
+            let applies = match self.anticheat_games.as_ref() {
+                Some(anticheat_games) => anticheat_games.contains(&game),
+                None => games::reports_anticheat(game),
+            };
+
+            if applies && srv.secure != Some(filter) {
+                return false;
             }
         }
 
         if self.not_full {
             if let Some(num_clients) = srv.num_clients {
-                if let Some(max_clients) = srv.max_clients {
+                // Zero limit means that the server did not report it properly.
+                // Over-capacity servers are treated as full.
+                if let Some(max_clients) = srv.max_clients.filter(|&v| v > 0) {
                     if num_clients >= max_clients {
                         return false;
                     }
@@ -92,9 +353,46 @@ impl Filters {
             }
         }
 
-        if self.no_password {
-            if let Some(need_pass) = srv.need_pass {
-                if need_pass {
+        let password_saved = || {
+            self.saved_passwords.contains(&Favorite {
+                game,
+                addr: games::server_key(srv),
+            })
+        };
+        if self.no_password && (self.saved_passwords.is_empty() || !password_saved()) {
+            match srv.need_pass {
+                Some(need_pass) => {
+                    if need_pass {
+                        return false;
+                    }
+                }
+                None => {
+                    if self.unknown_password_protected || games::reports_password(game) {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        if !self.countries.is_empty() {
+            if let Some(v) = country_code(srv) {
+                if !self.countries.contains(&v) {
+                    return false;
+                }
+            }
+        }
+
+        if self.stock_client_only {
+            if let Some(compatible) = games::stock_client_compatible(game, srv) {
+                if !compatible {
+                    return false;
+                }
+            }
+        }
+
+        if self.dedicated_only {
+            if let Some(dedicated) = games::server_dedicated(game, srv) {
+                if !dedicated {
                     return false;
                 }
             }
@@ -103,3 +401,48 @@ impl Filters {
         true
     }
 }
+
+/// Hosts a name may appear on before further servers with it count as spam
+pub const MAX_SAME_NAME: usize = 3;
+
+/// Name without the number at its end, so that numbered copies on one host compare equal.
+fn spam_key(name: &str) -> &str {
+    name.trim_end()
+        .trim_end_matches(|c: char| c.is_ascii_digit())
+        .trim_end()
+}
+
+/// Hides clusters of near-identical servers, such as the redirect spam on OpenArena masters.
+///
+/// A server is hidden if its host already lists one with the same name, up to a number at its end, or if the
+/// exact name is already listed on `MAX_SAME_NAME` other hosts. Servers are expected once each, duplicates
+/// reported by several masters have to be dropped beforehand.
+#[derive(Clone, Debug, Default)]
+pub struct SpamFilter {
+    seen_hosts: HashSet<(Game, IpAddr, String)>,
+    seen_names: HashMap<(Game, String), usize>,
+    /// Servers hidden per game
+    pub hidden: HashMap<Game, usize>,
+}
+
+impl SpamFilter {
+    /// Whether the server should be listed. Called once per server, in the order they arrive.
+    pub fn admit(&mut self, game: Game, name: &str, addr: IpAddr) -> bool {
+        let key = spam_key(name);
+        if key.is_empty() {
+            return true;
+        }
+
+        let admitted = self.seen_hosts.insert((game, addr, key.to_string())) && {
+            let hosts = self.seen_names.entry((game, name.to_string())).or_insert(0);
+            *hosts += 1;
+            *hosts <= MAX_SAME_NAME
+        };
+
+        if !admitted {
+            *self.hidden.entry(game).or_insert(0) += 1;
+        }
+
+        admitted
+    }
+}
@@ -0,0 +1,112 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use gtk::prelude::*;
+use obozrenie::filters::Filters;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+use crate::static_resources::Resources;
+
+/// On/off filters that may be shown by more than one widget
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Toggle {
+    MapFuzzy,
+    NotFull,
+    NotEmpty,
+    NoPassword,
+    UnknownPassword,
+    StockClient,
+    Dedicated,
+    Compatible,
+}
+
+impl Toggle {
+    fn field(self, f: &mut Filters) -> &mut bool {
+        match self {
+            Toggle::MapFuzzy => &mut f.map_fuzzy,
+            Toggle::NotFull => &mut f.not_full,
+            Toggle::NotEmpty => &mut f.not_empty,
+            Toggle::NoPassword => &mut f.no_password,
+            Toggle::UnknownPassword => &mut f.unknown_password_protected,
+            Toggle::StockClient => &mut f.stock_client_only,
+            Toggle::Dedicated => &mut f.dedicated_only,
+            Toggle::Compatible => &mut f.compatible_only,
+        }
+    }
+}
+
+/// Toggle widgets bound to the filters. All changes go through `set`, which updates every widget showing
+/// the same filter, so the popover checkboxes and the quick filter chips stay in sync.
+pub struct FiltersUi {
+    resources: Rc<Resources>,
+    filter_data: Arc<Mutex<Filters>>,
+    toggles: Vec<(Toggle, gtk::ToggleButton)>,
+}
+
+impl FiltersUi {
+    pub fn new(resources: Rc<Resources>, filter_data: Arc<Mutex<Filters>>) -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(Self {
+            resources,
+            filter_data,
+            toggles: Vec::new(),
+        }))
+    }
+
+    /// Makes the widget drive the filter and show its changes made elsewhere.
+    pub fn bind<W: IsA<gtk::ToggleButton>>(this: &Rc<RefCell<Self>>, toggle: Toggle, widget: &W) {
+        let widget = widget.clone().upcast::<gtk::ToggleButton>();
+        {
+            let ui = this.borrow();
+            let value = *toggle.field(&mut ui.filter_data.lock().unwrap());
+            widget.set_active(value);
+        }
+        widget.connect_toggled({
+            let this = this.clone();
+            move |w| Self::set(&this, toggle, w.get_active())
+        });
+        this.borrow_mut().toggles.push((toggle, widget));
+    }
+
+    pub fn set(this: &Rc<RefCell<Self>>, toggle: Toggle, value: bool) {
+        let widgets = {
+            let ui = this.borrow();
+            {
+                let mut f = ui.filter_data.lock().unwrap();
+                let v = toggle.field(&mut f);
+
+                // Echo of the widgets synced below
+                if *v == value {
+                    return;
+                }
+                *v = value;
+            }
+            crate::apply_filters(&ui.resources, &ui.filter_data);
+
+            ui.toggles
+                .iter()
+                .filter(|(t, _)| *t == toggle)
+                .map(|(_, w)| w.clone())
+                .collect::<Vec<_>>()
+        };
+
+        // Not borrowed while their handlers run
+        for widget in widgets {
+            widget.set_active(value);
+        }
+    }
+}
@@ -0,0 +1,103 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use super::{native, LaunchData};
+
+use serde_json::{json, Value};
+use std::process::Command;
+
+/// Splits the template into arguments at whitespace and fills in the placeholders. Values are substituted
+/// after splitting and in a single pass, so that a password or player name stays a single argument and is
+/// taken literally.
+pub fn expand_template(template: &str, data: &LaunchData) -> Vec<String> {
+    let (host, port) = match data.addr.rfind(':') {
+        Some(i) => (&data.addr[..i], &data.addr[i + 1..]),
+        None => (data.addr.as_str(), ""),
+    };
+    let values = [
+        ("{addr}", data.addr.as_str()),
+        ("{host}", host),
+        ("{port}", port),
+        (
+            "{password}",
+            data.password.as_ref().map(String::as_str).unwrap_or(""),
+        ),
+        (
+            "{name}",
+            data.player_name.as_ref().map(String::as_str).unwrap_or(""),
+        ),
+    ];
+
+    template
+        .split_whitespace()
+        .map(|arg| {
+            let mut out = String::new();
+            let mut rest = arg;
+            while let Some(start) = rest.find('{') {
+                out.push_str(&rest[..start]);
+                rest = &rest[start..];
+                match values.iter().find(|(key, _)| rest.starts_with(key)) {
+                    Some((key, value)) => {
+                        out.push_str(value);
+                        rest = &rest[key.len()..];
+                    }
+                    None => {
+                        out.push('{');
+                        rest = &rest[1..];
+                    }
+                }
+            }
+            out.push_str(rest);
+            out
+        })
+        .collect()
+}
+
+/// Runs a command line set up by the user instead of the game's own launch arguments.
+#[derive(Clone)]
+pub struct Launcher {
+    pub template: String,
+}
+
+impl super::Launcher for Launcher {
+    fn launch_cmd(&self, data: &LaunchData) -> Option<Command> {
+        let mut args = expand_template(&self.template, data).into_iter();
+
+        args.next().map(|program| {
+            let mut cmd = Command::new(program);
+
+            cmd.args(args);
+            cmd.args(&data.extra_args);
+
+            cmd
+        })
+    }
+
+    fn is_available(&self) -> bool {
+        self.template
+            .split_whitespace()
+            .next()
+            .map(native::find_program)
+            .unwrap_or(false)
+    }
+
+    fn describe(&self) -> Value {
+        json!({
+            "kind": "custom",
+            "template": self.template,
+        })
+    }
+}
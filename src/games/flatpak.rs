@@ -16,6 +16,7 @@
 
 use super::{Game, LaunchData};
 
+use serde_json::{json, Value};
 use std::process::Command;
 use std::sync::Arc;
 
@@ -26,29 +27,93 @@ pub trait FlatpakIdentifiable: Send + Sync {
 impl FlatpakIdentifiable for Game {
     fn id(&self) -> Option<&'static str> {
         match self {
+            Game::Hedgewars => Some("org.hedgewars.Hedgewars"),
+            Game::Mindustry => Some("com.github.Anuken.Mindustry"),
             Game::OpenArena => Some("ws.openarena.OpenArena"),
             Game::OpenTTD => Some("org.openttd.OpenTTD"),
+            Game::RigsOfRods => Some("org.rigsofrods.RigsOfRods"),
+            Game::Unvanquished => Some("net.unvanquished.Unvanquished"),
             Game::Xonotic => Some("org.xonotic.Xonotic"),
             _ => None,
         }
     }
 }
 
+/// Architecture and branch launched unless overridden
+const DEFAULT_BRANCH: &str = "x86_64/stable";
+
+pub fn default_ref(flatpak_id: &str) -> String {
+    format!("{}/{}", flatpak_id, DEFAULT_BRANCH)
+}
+
 #[derive(Clone)]
 pub struct Launcher {
     pub id_source: Arc<dyn FlatpakIdentifiable>,
+    /// User-configured ref to launch instead of the default one
+    pub ref_override: Option<String>,
 }
 
-impl super::Launcher for Launcher {
-    fn launch_cmd(&self, _data: &LaunchData) -> Option<Command> {
+impl Launcher {
+    pub fn flatpak_ref(&self) -> Option<String> {
         self.id_source.id().map(|flatpak_id| {
+            self.ref_override
+                .clone()
+                .unwrap_or_else(|| default_ref(flatpak_id))
+        })
+    }
+}
+
+impl super::Launcher for Launcher {
+    fn launch_cmd(&self, data: &LaunchData) -> Option<Command> {
+        self.flatpak_ref().map(|flatpak_ref| {
             let mut cmd = Command::new("flatpak");
 
             cmd.arg("run");
 
-            cmd.arg(format!("{}/x86_64/stable", flatpak_id));
+            // A favorite's launch profile overrides the ref set for the game
+            cmd.arg(data.flatpak_ref.as_ref().unwrap_or(&flatpak_ref));
 
             cmd
         })
     }
+
+    fn launch_confirmation(&self, data: &LaunchData) -> Option<String> {
+        let flatpak_id = self.id_source.id()?;
+        let flatpak_ref = data.flatpak_ref.as_ref().or(self.ref_override.as_ref())?;
+
+        if *flatpak_ref == default_ref(flatpak_id) {
+            return None;
+        }
+
+        Some(format!(
+            "The game is about to be launched from a non-default flatpak ref:\n\n{}\n\nMake sure you trust it before continuing.",
+            flatpak_ref
+        ))
+    }
+
+    fn is_available(&self) -> bool {
+        self.id_source
+            .id()
+            .and_then(|flatpak_id| {
+                Command::new("flatpak")
+                    .arg("info")
+                    .arg(
+                        self.ref_override
+                            .as_ref()
+                            .map(|s| s.as_str())
+                            .unwrap_or(flatpak_id),
+                    )
+                    .output()
+                    .ok()
+            })
+            .map(|output| output.status.success())
+            .unwrap_or(false)
+    }
+
+    fn describe(&self) -> Value {
+        json!({
+            "kind": "flatpak",
+            "ref": self.flatpak_ref(),
+        })
+    }
 }
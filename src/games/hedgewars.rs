@@ -0,0 +1,266 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Room list of the official Hedgewars server. Games are hosted in rooms of a single server rather than on
+//! servers of their own, so every room is listed as a server at the lobby's address.
+
+use futures01::{future, prelude::*, stream};
+use rgs::{models::Server, ping::Pinger};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::TcpStream;
+use tokio_dns::CpuPoolResolver;
+
+/// Official server, `host:port`
+pub const SERVER_ADDR: (&str, u16) = ("netserver.hedgewars.org", 46631);
+
+/// Protocol version announced to the server, that of Hedgewars 1.0
+pub const PROTOCOL_VERSION: u32 = 60;
+
+/// Fields describing a room in a `ROOMS` message
+const ROOM_FIELDS: usize = 9;
+
+/// Rule holding the room name, which tells apart rooms at the same address, see `server_key`
+pub const ROOM_RULE: &str = "_room";
+
+/// Generated map types, which the server reports as placeholders
+const GENERATED_MAPS: &[(&str, &str)] = &[
+    ("+rnd+", "Random map"),
+    ("+maze+", "Random maze"),
+    ("+perlin+", "Random perlin"),
+    ("+drawn+", "Hand drawn map"),
+];
+
+/// Splits the complete messages off the front of `buf`, leaving a partial one in it. Messages are lines
+/// ending with an empty line.
+pub fn take_messages(buf: &mut Vec<u8>) -> Vec<Vec<String>> {
+    let mut messages = Vec::new();
+    while let Some(end) = buf.windows(2).position(|w| w == b"\n\n") {
+        let message = buf.drain(..end + 2).collect::<Vec<_>>();
+        messages.push(
+            String::from_utf8_lossy(&message[..end])
+                .split('\n')
+                .map(String::from)
+                .collect(),
+        );
+    }
+
+    messages
+}
+
+/// Room as listed by the server
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Room {
+    pub name: String,
+    pub players: u64,
+    pub teams: u64,
+    pub owner: String,
+    pub map: String,
+    pub script: String,
+    pub scheme: String,
+    pub weapons: String,
+    pub in_progress: bool,
+    pub need_pass: bool,
+    /// Only players that the room owner lets in may join
+    pub restricted_joins: bool,
+    pub registered_only: bool,
+}
+
+/// Rooms in the fields of a `ROOMS` message, without the message name. A trailing incomplete room is dropped.
+pub fn parse_rooms(fields: &[String]) -> Vec<Room> {
+    fields
+        .chunks(ROOM_FIELDS)
+        .filter(|room| room.len() == ROOM_FIELDS)
+        .map(|room| Room {
+            // Flags start with a dash, then one letter per flag set
+            in_progress: room[0].contains('g'),
+            need_pass: room[0].contains('p'),
+            restricted_joins: room[0].contains('j'),
+            registered_only: room[0].contains('r'),
+            name: room[1].clone(),
+            players: room[2].parse().unwrap_or(0),
+            teams: room[3].parse().unwrap_or(0),
+            owner: room[4].clone(),
+            map: room[5].clone(),
+            script: room[6].clone(),
+            scheme: room[7].clone(),
+            weapons: room[8].clone(),
+        })
+        .collect()
+}
+
+/// Lists the room as a server at `addr`, the lobby's address, resolved from `host` if it is known by name.
+pub fn room_server(
+    room: Room,
+    addr: SocketAddr,
+    host: Option<&str>,
+    ping: Option<Duration>,
+) -> Server {
+    let map = GENERATED_MAPS
+        .iter()
+        .find(|(placeholder, _)| *placeholder == room.map)
+        .map(|(_, name)| name.to_string())
+        .unwrap_or(room.map);
+
+    let mut rules = vec![
+        (ROOM_RULE, Value::from(room.name.clone())),
+        ("owner", Value::from(room.owner)),
+        ("script", Value::from(room.script)),
+        ("scheme", Value::from(room.scheme)),
+        ("weapons", Value::from(room.weapons)),
+        ("teams", Value::from(room.teams)),
+        ("in_progress", Value::from(room.in_progress)),
+        ("restricted_joins", Value::from(room.restricted_joins)),
+        ("registered_only", Value::from(room.registered_only)),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v))
+    .collect::<HashMap<_, _>>();
+    if let Some(host) = host {
+        rules.insert(super::HOST_RULE.to_string(), Value::from(host));
+    }
+
+    Server {
+        ping,
+        name: Some(room.name),
+        map: Some(map),
+        need_pass: Some(room.need_pass),
+        num_clients: Some(room.players),
+        rules: rules.into_iter().collect(),
+        ..Server::new(addr)
+    }
+}
+
+/// Nick for the lobby, which has to be unique. Registered nicks would be asked for their password.
+fn nick() -> String {
+    let salt = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|v| v.subsec_nanos())
+        .unwrap_or(0);
+
+    format!("Obozrenie{}", salt % 100_000)
+}
+
+/// What to do after the messages received so far
+enum Progress {
+    Waiting,
+    Listed(Vec<Room>),
+}
+
+fn handle_message(message: &[String]) -> Result<Progress, failure::Error> {
+    match message.get(0).map(String::as_str) {
+        Some("ROOMS") => Ok(Progress::Listed(parse_rooms(&message[1..]))),
+        Some("ASKPASSWORD") => Err(failure::err_msg(
+            "The server asked for a password, the nick is registered",
+        )),
+        Some("ERROR") | Some("BYE") => Err(failure::err_msg(format!(
+            "The server refused the connection: {}",
+            message[1..].join(" ")
+        ))),
+        // Notice 0 is a nick in use
+        Some("NOTICE") if message.get(1).map(String::as_str) == Some("0") => {
+            Err(failure::err_msg("The nick is already in use"))
+        }
+        _ => Ok(Progress::Waiting),
+    }
+}
+
+/// Logs into the lobby at `addr` and returns its rooms. Fails rather than waiting if the server closes the
+/// connection before listing them.
+pub fn list_rooms(
+    addr: SocketAddr,
+) -> impl Future<Item = Vec<Room>, Error = failure::Error> + Send {
+    let handshake = format!("NICK\n{}\n\nPROTO\n{}\n\n", nick(), PROTOCOL_VERSION);
+
+    TcpStream::connect(&addr)
+        .and_then(move |socket| tokio::io::write_all(socket, handshake.into_bytes()))
+        .map_err(failure::Error::from)
+        .and_then(|(socket, _)| {
+            future::loop_fn((socket, Vec::new()), |(socket, mut buf)| {
+                tokio::io::read(socket, vec![0; 4096])
+                    .map_err(failure::Error::from)
+                    .and_then(move |(socket, chunk, len)| {
+                        if len == 0 {
+                            return Err(failure::err_msg(
+                                "The server closed the connection before listing rooms",
+                            ));
+                        }
+
+                        buf.extend_from_slice(&chunk[..len]);
+                        for message in take_messages(&mut buf) {
+                            if let Progress::Listed(rooms) = handle_message(&message)? {
+                                return Ok(future::Loop::Break((socket, rooms)));
+                            }
+                        }
+
+                        Ok(future::Loop::Continue((socket, buf)))
+                    })
+            })
+        })
+        // Leaving is a courtesy, the rooms are there either way
+        .and_then(|(socket, rooms)| {
+            tokio::io::write_all(socket, b"QUIT\nObozrenie\n\n".to_vec()).then(|_| Ok(rooms))
+        })
+}
+
+#[derive(Clone)]
+pub struct Querier {
+    pub server_addr: (String, u16),
+    pub resolver: Arc<CpuPoolResolver>,
+    pub pinger: Arc<dyn Pinger>,
+}
+
+impl super::Querier for Querier {
+    fn query(&self) -> Box<dyn Stream<Item = rgs::models::Server, Error = failure::Error> + Send> {
+        let (host, port) = self.server_addr.clone();
+        let pinger = self.pinger.clone();
+
+        Box::new(
+            super::resolve_host(&self.resolver, host)
+                .and_then(move |(ip, host)| {
+                    let addr = SocketAddr::new(ip, port);
+                    list_rooms(addr)
+                        .join(pinger.ping(ip).then(|ping| Ok(ping.ok().and_then(|v| v))))
+                        .map(move |(rooms, ping)| {
+                            stream::iter_ok(
+                                rooms
+                                    .into_iter()
+                                    .map(|room| {
+                                        room_server(
+                                            room,
+                                            addr,
+                                            host.as_ref().map(String::as_str),
+                                            ping,
+                                        )
+                                    })
+                                    .collect::<Vec<_>>(),
+                            )
+                        })
+                })
+                .flatten_stream(),
+        )
+    }
+
+    fn describe(&self) -> Value {
+        json!({
+            "kind": "tcp",
+            "masters": [format!("{}:{}", self.server_addr.0, self.server_addr.1)],
+        })
+    }
+}
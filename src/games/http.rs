@@ -0,0 +1,81 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! HTTP client shared by the queriers of games with HTTP master servers.
+
+use log::warn;
+use reqwest::{
+    header::{HeaderMap, HeaderValue, USER_AGENT},
+    r#async::Client as HttpClient,
+    Proxy,
+};
+use std::time::Duration;
+
+/// Identifies us to the master server operators
+pub const HTTP_USER_AGENT: &str = concat!(
+    "obozrenie/",
+    env!("CARGO_PKG_VERSION"),
+    " (+https://github.com/vorot93/obozrenie-rs)"
+);
+
+/// Proxies of the client: the configured one for every scheme, otherwise those of the usual
+/// environment variables. Invalid variables are skipped, as they are not ours to report.
+fn proxies(proxy: Option<&str>) -> Result<Vec<Proxy>, reqwest::Error> {
+    if let Some(url) = proxy {
+        return Ok(vec![Proxy::all(url)?]);
+    }
+
+    let env = |names: &[&str]| {
+        names
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|v| !v.is_empty())
+    };
+
+    let mut out = Vec::new();
+    if let Some(url) = env(&["https_proxy", "HTTPS_PROXY"]) {
+        match Proxy::https(&url) {
+            Ok(v) => out.push(v),
+            Err(e) => warn!("Ignoring https_proxy {}: {}", url, e),
+        }
+    }
+    if let Some(url) = env(&["http_proxy", "HTTP_PROXY"]) {
+        match Proxy::http(&url) {
+            Ok(v) => out.push(v),
+            Err(e) => warn!("Ignoring http_proxy {}: {}", url, e),
+        }
+    }
+
+    Ok(out)
+}
+
+/// Builds the client for all HTTP queriers. Requests identify us, go through `proxy` if set and are
+/// abandoned after `timeout` in total.
+pub fn client(proxy: Option<&str>, timeout: Duration) -> Result<HttpClient, failure::Error> {
+    let mut headers = HeaderMap::new();
+    headers.insert(USER_AGENT, HeaderValue::from_static(HTTP_USER_AGENT));
+
+    // Advertise gzip support and decode compressed listings transparently
+    let mut builder = HttpClient::builder()
+        .gzip(true)
+        .default_headers(headers)
+        .timeout(timeout);
+    for proxy in proxies(proxy)? {
+        builder = builder.proxy(proxy);
+    }
+
+    Ok(builder.build()?)
+}
@@ -0,0 +1,163 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Cheap info queries, keeping the fields of a listed server that change during a match up to date.
+
+use futures01::{future, prelude::*};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use tokio::net::UdpSocket;
+
+use super::retransmit::{retransmit, Retransmit};
+use super::socks5;
+
+/// Info request of Quake III engine servers, answered without the rules and player list of a status request
+const Q3_INFO_REQUEST: &[u8] = b"\xff\xff\xff\xffgetinfo obozrenie";
+
+/// Header of the reply to `Q3_INFO_REQUEST`, followed by the info string
+const Q3_INFO_RESPONSE: &[u8] = b"\xff\xff\xff\xffinfoResponse\n";
+
+/// Fields of a server reported by an info query, `None` if not reported
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ServerInfo {
+    pub map: Option<String>,
+    pub num_clients: Option<u64>,
+    pub max_clients: Option<u64>,
+    /// Protocol version, telling apart games that share the protocol
+    pub protocol: Option<u32>,
+}
+
+fn update<T: Clone + PartialEq>(field: &mut Option<T>, reported: &Option<T>) -> bool {
+    match reported {
+        Some(v) if field.as_ref() != Some(v) => {
+            *field = Some(v.clone());
+            true
+        }
+        _ => false,
+    }
+}
+
+impl ServerInfo {
+    /// Takes the fields from a full server reply.
+    pub fn from_server(srv: &rgs::models::Server) -> Self {
+        Self {
+            map: srv.map.clone(),
+            num_clients: srv.num_clients,
+            max_clients: srv.max_clients,
+            protocol: None,
+        }
+    }
+
+    /// Copies the reported fields into `srv`. Returns whether any of them changed.
+    pub fn apply(&self, srv: &mut rgs::models::Server) -> bool {
+        let map = update(&mut srv.map, &self.map);
+        let num_clients = update(&mut srv.num_clients, &self.num_clients);
+        let max_clients = update(&mut srv.max_clients, &self.max_clients);
+
+        map || num_clients || max_clients
+    }
+}
+
+/// Reads the reply to `Q3_INFO_REQUEST`, `None` if it is something else.
+pub fn parse_q3_info(data: &[u8]) -> Option<ServerInfo> {
+    if !data.starts_with(Q3_INFO_RESPONSE) {
+        return None;
+    }
+
+    let text = String::from_utf8_lossy(&data[Q3_INFO_RESPONSE.len()..]);
+    let mut parts = text
+        .trim_end_matches(|c| c == '\n' || c == '\0')
+        .trim_start_matches('\\')
+        .split('\\');
+    let mut rules = HashMap::new();
+    while let (Some(k), Some(v)) = (parts.next(), parts.next()) {
+        rules.insert(k.to_lowercase(), v.to_string());
+    }
+
+    Some(ServerInfo {
+        map: rules.get("mapname").cloned(),
+        num_clients: rules.get("clients").and_then(|v| v.parse().ok()),
+        max_clients: rules.get("sv_maxclients").and_then(|v| v.parse().ok()),
+        protocol: rules.get("protocol").and_then(|v| v.parse().ok()),
+    })
+}
+
+/// Sends `Q3_INFO_REQUEST` to the server, resending it while no reply arrives. Goes through the SOCKS5
+/// proxy at `proxy` if set.
+pub fn query_q3(
+    addr: SocketAddr,
+    policy: Retransmit,
+    proxy: Option<String>,
+    bind_addr: Option<IpAddr>,
+) -> impl Future<Item = ServerInfo, Error = failure::Error> + Send {
+    retransmit(policy, move || {
+        if let Some(proxy) = proxy.as_ref() {
+            return Box::new(
+                socks5::associate(proxy)
+                    .and_then(move |relay| relay.send_to(Q3_INFO_REQUEST, addr))
+                    .and_then(move |relay| {
+                        future::loop_fn(relay, move |relay| {
+                            relay.recv_from().map(move |(relay, data, from)| {
+                                match Some(&data[..])
+                                    .filter(|_| from == addr)
+                                    .and_then(parse_q3_info)
+                                {
+                                    Some(info) => future::Loop::Break(info),
+                                    None => future::Loop::Continue(relay),
+                                }
+                            })
+                        })
+                    }),
+            )
+                as Box<dyn Future<Item = ServerInfo, Error = failure::Error> + Send>;
+        }
+
+        // Any port, so that a refresh running on the fixed one is not disturbed
+        let local = SocketAddr::new(
+            bind_addr.unwrap_or_else(|| {
+                if addr.is_ipv4() {
+                    Ipv4Addr::UNSPECIFIED.into()
+                } else {
+                    Ipv6Addr::UNSPECIFIED.into()
+                }
+            }),
+            0,
+        );
+
+        Box::new(
+            future::result(UdpSocket::bind(&local))
+                .and_then(move |socket| socket.send_dgram(Q3_INFO_REQUEST, &addr))
+                .map_err(failure::Error::from)
+                .and_then(move |(socket, _)| {
+                    // Stray datagrams are skipped until the server replies
+                    future::loop_fn(socket, move |socket| {
+                        socket
+                            .recv_dgram(vec![0; 1500])
+                            .map_err(failure::Error::from)
+                            .map(move |(socket, buf, len, from)| {
+                                match Some(&buf[..len])
+                                    .filter(|_| from == addr)
+                                    .and_then(parse_q3_info)
+                                {
+                                    Some(info) => future::Loop::Break(info),
+                                    None => future::Loop::Continue(socket),
+                                }
+                            })
+                    })
+                }),
+        ) as Box<dyn Future<Item = ServerInfo, Error = failure::Error> + Send>
+    })
+}
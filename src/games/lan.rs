@@ -0,0 +1,139 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Discovery of servers on the local network, for LAN parties and offline play.
+
+use futures01::{prelude::*, stream};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::{net::UdpSocket, reactor::Handle, timer::Delay};
+
+use super::Querier;
+
+/// Info request understood by Quake III engine servers
+pub const Q3_PROBE: &[u8] = b"\xff\xff\xff\xffgetinfo";
+
+/// How long to collect replies to the broadcast
+const REPLY_WINDOW: Duration = Duration::from_secs(2);
+
+/// Servers found on the network that are queried at once
+pub const MAX_CONCURRENT_QUERIES: usize = 16;
+
+/// Lists servers found by `Querier::scan_lan` instead of asking the master servers.
+pub struct LanQuerier(pub Arc<dyn Querier>);
+
+impl Querier for LanQuerier {
+    fn query(&self) -> Box<dyn Stream<Item = rgs::models::Server, Error = failure::Error> + Send> {
+        self.0.scan_lan()
+    }
+
+    /// Servers answer the broadcast directly
+    fn master_count(&self) -> usize {
+        0
+    }
+
+    fn can_query_server(&self) -> bool {
+        self.0.can_query_server()
+    }
+
+    fn query_server(
+        &self,
+        addr: SocketAddr,
+    ) -> Box<dyn Future<Item = rgs::models::Server, Error = failure::Error> + Send> {
+        self.0.query_server(addr)
+    }
+
+    fn can_query_info(&self) -> bool {
+        self.0.can_query_info()
+    }
+
+    fn query_info(
+        &self,
+        addr: SocketAddr,
+    ) -> Box<dyn Future<Item = super::ServerInfo, Error = failure::Error> + Send> {
+        self.0.query_info(addr)
+    }
+
+    fn recognizes(&self, info: &super::ServerInfo) -> bool {
+        self.0.recognizes(info)
+    }
+
+    fn describe(&self) -> Value {
+        json!({
+            "kind": "lan",
+            "querier": self.0.describe(),
+        })
+    }
+}
+
+fn broadcast(probe: &[u8], ports: RangeInclusive<u16>) -> Result<UdpSocket, failure::Error> {
+    let socket = std::net::UdpSocket::bind("0.0.0.0:0")?;
+    socket.set_broadcast(true)?;
+
+    for port in ports {
+        socket.send_to(probe, (Ipv4Addr::new(255, 255, 255, 255), port))?;
+    }
+
+    Ok(UdpSocket::from_std(socket, &Handle::default())?)
+}
+
+/// Broadcasts `probe` to `ports` on the local subnet and streams the addresses that reply.
+pub fn discover(
+    probe: &'static [u8],
+    ports: RangeInclusive<u16>,
+) -> Box<dyn Stream<Item = SocketAddr, Error = failure::Error> + Send> {
+    let mut socket = match broadcast(probe, ports) {
+        Ok(v) => v,
+        Err(e) => {
+            return Box::new(stream::once(Err(e)));
+        }
+    };
+
+    let mut seen = HashSet::new();
+    let mut buf = [0; 1500];
+    let replies = stream::poll_fn(move || loop {
+        match socket.poll_recv_from(&mut buf) {
+            Ok(Async::Ready((_, addr))) => {
+                // Servers on several interfaces reply more than once
+                if seen.insert(addr) {
+                    return Ok(Async::Ready(Some(Some(addr))));
+                }
+            }
+            Ok(Async::NotReady) => {
+                return Ok(Async::NotReady);
+            }
+            Err(e) => {
+                return Err(failure::Error::from(e));
+            }
+        }
+    });
+
+    let deadline = Delay::new(Instant::now() + REPLY_WINDOW)
+        .map(|_| None)
+        .map_err(failure::Error::from)
+        .into_stream();
+
+    Box::new(
+        replies
+            .select(deadline)
+            .take_while(|addr| Ok(addr.is_some()))
+            .filter_map(|addr| addr),
+    )
+}
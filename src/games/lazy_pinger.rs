@@ -0,0 +1,92 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use futures01::{future::Shared, prelude::*};
+use log::*;
+use rgs::ping::{DummyPinger, Pinger};
+use std::net::IpAddr;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+use std::time::Duration;
+
+const ICMP_UNKNOWN: usize = 0;
+const ICMP_AVAILABLE: usize = 1;
+const ICMP_UNAVAILABLE: usize = 2;
+
+/// Outcome of the last pinger spawn
+static ICMP_STATE: AtomicUsize = AtomicUsize::new(ICMP_UNKNOWN);
+
+/// Whether latency is measured with ICMP echo, `None` until the first ping.
+pub fn icmp_available() -> Option<bool> {
+    match ICMP_STATE.load(Ordering::SeqCst) {
+        ICMP_AVAILABLE => Some(true),
+        ICMP_UNAVAILABLE => Some(false),
+        _ => None,
+    }
+}
+
+type PingerFuture = Shared<Box<dyn Future<Item = Arc<dyn Pinger>, Error = ()> + Send>>;
+
+fn spawn_pinger() -> PingerFuture {
+    let pinger: Box<dyn Future<Item = Arc<dyn Pinger>, Error = ()> + Send> =
+        Box::new(tokio_ping::Pinger::new().then(|res| {
+            Ok(match res {
+                Ok(pinger) => {
+                    ICMP_STATE.store(ICMP_AVAILABLE, Ordering::SeqCst);
+                    Arc::new(pinger) as Arc<dyn Pinger>
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to spawn pinger: {}. Using manual latency measurement.",
+                        e
+                    );
+                    ICMP_STATE.store(ICMP_UNAVAILABLE, Ordering::SeqCst);
+                    Arc::new(DummyPinger) as Arc<dyn Pinger>
+                }
+            })
+        }));
+
+    pinger.shared()
+}
+
+/// Spawns the ICMP pinger on the first ping, on the runtime running the query, so that startup does not wait
+/// for the raw socket. Opening it may need elevated privileges, latency is measured manually if it fails.
+#[derive(Default)]
+pub struct LazyPinger {
+    pinger: Mutex<Option<PingerFuture>>,
+}
+
+impl Pinger for LazyPinger {
+    fn ping(
+        &self,
+        addr: IpAddr,
+    ) -> Box<dyn Future<Item = Option<Duration>, Error = failure::Error> + Send> {
+        let pinger = self
+            .pinger
+            .lock()
+            .unwrap()
+            .get_or_insert_with(spawn_pinger)
+            .clone();
+
+        Box::new(
+            pinger
+                .map_err(|_| failure::err_msg("Pinger failed to spawn"))
+                .and_then(move |pinger| pinger.ping(addr)),
+        )
+    }
+}
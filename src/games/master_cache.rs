@@ -0,0 +1,183 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Courtesy towards master servers shared by several games, such as dpmaster.deathmask.net.
+
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Server list of a master, keyed by master address and the tag telling apart the lists it keeps
+type Key = (String, String);
+
+struct Entry {
+    /// When the master was asked for the list
+    requested: Instant,
+    servers: Vec<SocketAddr>,
+    /// Refreshes that went without asking the master since
+    reused: usize,
+}
+
+/// Cached server list of a master, for the master servers dialog
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MasterStatus {
+    pub master: String,
+    pub tag: String,
+    pub servers: usize,
+    pub age: Duration,
+    pub fresh: bool,
+    pub reused: usize,
+    /// Wait before the master may be asked again
+    pub next_request: Duration,
+}
+
+/// Reuses server lists of recent master requests and spaces out requests to the same master, for all games
+/// at once.
+///
+/// Queriers do not learn which of their masters listed a server, so a list is stored under every master
+/// that was asked for it, and only reused while all of them are fresh. Lists are filled in as servers reply,
+/// so that a master that did not answer is asked again next time.
+pub struct MasterCache {
+    ttl: Duration,
+    min_interval: Duration,
+    entries: Mutex<HashMap<Key, Entry>>,
+    /// Earliest time each master may be asked again
+    next_request: Mutex<HashMap<String, Instant>>,
+}
+
+impl MasterCache {
+    /// Lists are reused for `ttl`, zero to always ask the masters. Requests to a master are at least
+    /// `min_interval` apart.
+    pub fn new(ttl: Duration, min_interval: Duration) -> Self {
+        Self {
+            ttl,
+            min_interval,
+            entries: Default::default(),
+            next_request: Default::default(),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    pub fn min_interval(&self) -> Duration {
+        self.min_interval
+    }
+
+    /// Servers listed by `masters` under `tag`, if every one of them was asked within the TTL. Counts as a
+    /// reuse of the lists.
+    pub fn get(&self, masters: &[String], tag: &str, now: Instant) -> Option<Vec<SocketAddr>> {
+        if masters.is_empty() {
+            return None;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        let fresh = masters.iter().all(|master| {
+            entries
+                .get(&(master.clone(), tag.to_string()))
+                .map(|entry| now.duration_since(entry.requested) < self.ttl)
+                .unwrap_or(false)
+        });
+        if !fresh {
+            return None;
+        }
+
+        let mut seen = HashSet::new();
+        let mut servers = Vec::new();
+        for master in masters {
+            let entry = entries.get_mut(&(master.clone(), tag.to_string())).unwrap();
+            entry.reused += 1;
+            servers.extend(entry.servers.iter().filter(|addr| seen.insert(**addr)));
+        }
+
+        Some(servers)
+    }
+
+    /// Records a server listed in reply to the request made to `masters` at `requested`, replacing the lists of
+    /// earlier requests.
+    pub fn record(&self, masters: &[String], tag: &str, requested: Instant, addr: SocketAddr) {
+        let mut entries = self.entries.lock().unwrap();
+        for master in masters {
+            let entry = entries
+                .entry((master.clone(), tag.to_string()))
+                .or_insert_with(|| Entry {
+                    requested,
+                    servers: Vec::new(),
+                    reused: 0,
+                });
+            if entry.requested < requested {
+                *entry = Entry {
+                    requested,
+                    servers: Vec::new(),
+                    reused: 0,
+                };
+            } else if entry.requested > requested {
+                // A later request has already replied
+                continue;
+            }
+
+            if !entry.servers.contains(&addr) {
+                entry.servers.push(addr);
+            }
+        }
+    }
+
+    /// Books the next request to all of `masters`, returning when it may be sent. Concurrent requests are
+    /// queued `min_interval` apart.
+    pub fn reserve(&self, masters: &[String], now: Instant) -> Instant {
+        let mut next_request = self.next_request.lock().unwrap();
+        let at = masters
+            .iter()
+            .filter_map(|master| next_request.get(master))
+            .fold(now, |at, next| at.max(*next));
+        for master in masters {
+            next_request.insert(master.clone(), at + self.min_interval);
+        }
+
+        at
+    }
+
+    /// Cached lists, sorted by master and tag.
+    pub fn status(&self, now: Instant) -> Vec<MasterStatus> {
+        let next_request = self.next_request.lock().unwrap();
+        let mut status = self
+            .entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|((master, tag), entry)| {
+                let age = now.duration_since(entry.requested);
+                MasterStatus {
+                    master: master.clone(),
+                    tag: tag.clone(),
+                    servers: entry.servers.len(),
+                    age,
+                    fresh: age < self.ttl,
+                    reused: entry.reused,
+                    next_request: next_request
+                        .get(master)
+                        .map(|next| next.duration_since(now.min(*next)))
+                        .unwrap_or_default(),
+                }
+            })
+            .collect::<Vec<_>>();
+        status.sort_by(|a, b| (&a.master, &a.tag).cmp(&(&b.master, &b.tag)));
+
+        status
+    }
+}
@@ -0,0 +1,369 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Mindustry servers from the community server list, which are pinged one by one as there is no master.
+
+use futures01::{future, prelude::*, stream};
+use log::warn;
+use reqwest::r#async::Client as HttpClient;
+use rgs::models::Server;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio_dns::CpuPoolResolver;
+
+use super::retransmit::{retransmit, Retransmit};
+use super::socks5;
+
+/// Community server list shown in the game
+pub const SERVER_LIST_URL: &str =
+    "https://raw.githubusercontent.com/Anuken/Mindustry/master/servers_v7.json";
+
+/// Port of servers listed without one
+pub const DEFAULT_PORT: u16 = 6567;
+
+/// Discovery request, answered with the server data
+pub const PING_REQUEST: &[u8] = &[0xfe, 0x01];
+
+/// Servers pinged at once
+const MAX_CONCURRENT_PINGS: usize = 32;
+
+/// Game modes in the order of their ids
+const GAMEMODES: &[&str] = &["survival", "sandbox", "attack", "pvp", "editor"];
+
+/// Group of servers in the community list
+#[derive(Deserialize)]
+struct Group {
+    name: Option<String>,
+    address: Vec<String>,
+}
+
+/// Hosts of the community list as `(host, port, group)`, in the order listed.
+pub fn parse_server_list(
+    data: &[u8],
+) -> Result<Vec<(String, u16, Option<String>)>, failure::Error> {
+    Ok(serde_json::from_slice::<Vec<Group>>(data)?
+        .into_iter()
+        .flat_map(|group| {
+            let name = group.name;
+            group
+                .address
+                .into_iter()
+                .filter_map(|addr| parse_host(&addr))
+                .map(move |(host, port)| (host, port, name.clone()))
+                .collect::<Vec<_>>()
+        })
+        .collect())
+}
+
+/// Splits `host:port`, defaulting to `DEFAULT_PORT`. IPv6 addresses with a port are bracketed.
+pub fn parse_host(addr: &str) -> Option<(String, u16)> {
+    let addr = addr.trim();
+    if addr.is_empty() {
+        return None;
+    }
+
+    if let Ok(ip) = addr.parse::<IpAddr>() {
+        return Some((ip.to_string(), DEFAULT_PORT));
+    }
+    if let Ok(addr) = addr.parse::<SocketAddr>() {
+        return Some((addr.ip().to_string(), addr.port()));
+    }
+
+    let mut parts = addr.rsplitn(2, ':');
+    match (parts.next(), parts.next()) {
+        (Some(port), Some(host)) => Some((host.to_string(), port.parse().ok()?)),
+        _ => Some((addr.to_string(), DEFAULT_PORT)),
+    }
+}
+
+/// Server data of a discovery reply
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ServerData {
+    pub name: String,
+    pub map: String,
+    pub players: u32,
+    pub wave: u32,
+    /// Build number, which clients have to match
+    pub version: i32,
+    /// Release channel, `official` for stock builds
+    pub version_type: String,
+    pub gamemode: Option<&'static str>,
+    /// 0 if unlimited
+    pub player_limit: u32,
+    pub description: String,
+    /// Name of a custom game mode
+    pub mode_name: Option<String>,
+}
+
+/// Reads strings prefixed with their length in a byte and big endian integers.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Some(head)
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let len = *self.bytes(1)?.first()? as usize;
+        Some(String::from_utf8_lossy(self.bytes(len)?).into_owned())
+    }
+
+    fn int(&mut self) -> Option<i32> {
+        let mut v = [0; 4];
+        v.copy_from_slice(self.bytes(4)?);
+        Some(i32::from_be_bytes(v))
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        self.bytes(1).map(|v| v[0])
+    }
+}
+
+/// Parses a discovery reply. Fields added by later versions are optional.
+pub fn parse_server_data(data: &[u8]) -> Option<ServerData> {
+    let mut reader = Reader(data);
+
+    let mut out = ServerData {
+        name: reader.string()?,
+        map: reader.string()?,
+        players: reader.int()?.max(0) as u32,
+        wave: reader.int()?.max(0) as u32,
+        version: reader.int()?,
+        version_type: reader.string()?,
+        ..Default::default()
+    };
+    out.gamemode = reader
+        .byte()
+        .and_then(|v| GAMEMODES.get(v as usize).copied());
+    out.player_limit = reader.int().unwrap_or(0).max(0) as u32;
+    out.description = reader.string().unwrap_or_default();
+    out.mode_name = reader.string().filter(|v| !v.is_empty());
+
+    Some(out)
+}
+
+/// Lists the server at `addr`, known by `host` if that is a name, with the latency of its reply.
+pub fn to_server(
+    data: ServerData,
+    addr: SocketAddr,
+    host: Option<&str>,
+    group: Option<&str>,
+    ping: Duration,
+) -> Server {
+    let mut rules = vec![
+        ("wave", Value::from(data.wave)),
+        ("version", Value::from(data.version.to_string())),
+        ("version_type", Value::from(data.version_type)),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v))
+    .collect::<HashMap<_, _>>();
+    if let Some(gamemode) = data.gamemode {
+        rules.insert("gamemode".into(), Value::from(gamemode));
+    }
+    if !data.description.is_empty() {
+        rules.insert("description".into(), Value::from(data.description));
+    }
+    if let Some(group) = group {
+        rules.insert("group".into(), Value::from(group));
+    }
+    if let Some(host) = host {
+        rules.insert(super::HOST_RULE.into(), Value::from(host));
+    }
+
+    Server {
+        ping: Some(ping),
+        name: Some(data.name),
+        map: Some(data.map),
+        // Custom modes are named by the server
+        mod_name: data.mode_name.or_else(|| data.gamemode.map(String::from)),
+        num_clients: Some(u64::from(data.players)),
+        max_clients: Some(data.player_limit).filter(|v| *v > 0).map(u64::from),
+        rules: rules.into_iter().collect(),
+        ..Server::new(addr)
+    }
+}
+
+/// Pings the server at `addr` through the SOCKS5 `proxy` or from `bind_addr` if set, returning its data and
+/// the latency of the reply.
+pub fn ping(
+    addr: SocketAddr,
+    policy: Retransmit,
+    proxy: Option<String>,
+    bind_addr: Option<IpAddr>,
+) -> impl Future<Item = (ServerData, Duration), Error = failure::Error> + Send {
+    retransmit(policy, move || {
+        let sent = Instant::now();
+
+        if let Some(proxy) = proxy.as_ref() {
+            return Box::new(
+                socks5::associate(proxy)
+                    .and_then(move |relay| relay.send_to(PING_REQUEST, addr))
+                    .and_then(move |relay| {
+                        future::loop_fn(relay, move |relay| {
+                            relay.recv_from().map(move |(relay, data, from)| {
+                                match Some(&data[..])
+                                    .filter(|_| from == addr)
+                                    .and_then(parse_server_data)
+                                {
+                                    Some(data) => future::Loop::Break((data, sent.elapsed())),
+                                    None => future::Loop::Continue(relay),
+                                }
+                            })
+                        })
+                    }),
+            )
+                as Box<dyn Future<Item = (ServerData, Duration), Error = failure::Error> + Send>;
+        }
+
+        let local = SocketAddr::new(
+            bind_addr.unwrap_or_else(|| {
+                if addr.is_ipv4() {
+                    Ipv4Addr::UNSPECIFIED.into()
+                } else {
+                    Ipv6Addr::UNSPECIFIED.into()
+                }
+            }),
+            0,
+        );
+
+        Box::new(
+            future::result(UdpSocket::bind(&local))
+                .and_then(move |socket| socket.send_dgram(PING_REQUEST, &addr))
+                .map_err(failure::Error::from)
+                .and_then(move |(socket, _)| {
+                    // Stray datagrams are skipped until the server replies
+                    future::loop_fn(socket, move |socket| {
+                        socket
+                            .recv_dgram(vec![0; 1500])
+                            .map_err(failure::Error::from)
+                            .map(move |(socket, buf, len, from)| {
+                                match Some(&buf[..len])
+                                    .filter(|_| from == addr)
+                                    .and_then(parse_server_data)
+                                {
+                                    Some(data) => future::Loop::Break((data, sent.elapsed())),
+                                    None => future::Loop::Continue(socket),
+                                }
+                            })
+                    })
+                }),
+        ) as Box<dyn Future<Item = (ServerData, Duration), Error = failure::Error> + Send>
+    })
+}
+
+#[derive(Clone)]
+pub struct Querier {
+    pub list_url: String,
+    /// Shared client, see `http::client`
+    pub client: HttpClient,
+    pub resolver: Arc<CpuPoolResolver>,
+    /// SOCKS5 proxy that pings go through, see `socks5`
+    pub socks5_proxy: Option<String>,
+    /// Local address pings go out from, see `Settings::bind_address`
+    pub bind_addr: Option<IpAddr>,
+    pub retransmit: Retransmit,
+}
+
+impl super::Querier for Querier {
+    /// Servers that cannot be resolved or do not reply are left out.
+    fn query(&self) -> Box<dyn Stream<Item = rgs::models::Server, Error = failure::Error> + Send> {
+        let resolver = self.resolver.clone();
+        let policy = self.retransmit;
+        let proxy = self.socks5_proxy.clone();
+        let bind_addr = self.bind_addr;
+
+        Box::new(
+            self.client
+                .get(&self.list_url)
+                .send()
+                .and_then(|rsp| rsp.error_for_status())
+                .and_then(|rsp| rsp.into_body().concat2())
+                .map_err(failure::Error::from)
+                .and_then(|body| parse_server_list(&body))
+                .map(move |hosts| {
+                    stream::iter_ok(hosts)
+                        .map(move |(host, port, group)| {
+                            let proxy = proxy.clone();
+                            super::resolve_host(&resolver, host)
+                                .and_then(move |(ip, host)| {
+                                    let addr = SocketAddr::new(ip, port);
+                                    ping(addr, policy, proxy, bind_addr).map(
+                                        move |(data, latency)| {
+                                            to_server(
+                                                data,
+                                                addr,
+                                                host.as_ref().map(String::as_str),
+                                                group.as_ref().map(String::as_str),
+                                                latency,
+                                            )
+                                        },
+                                    )
+                                })
+                                .then(|res| {
+                                    Ok(res
+                                        .map_err(|e| warn!("Mindustry server skipped: {}", e))
+                                        .ok())
+                                })
+                        })
+                        .buffer_unordered(MAX_CONCURRENT_PINGS)
+                        .filter_map(|srv| srv)
+                })
+                .flatten_stream(),
+        )
+    }
+
+    fn can_query_server(&self) -> bool {
+        true
+    }
+
+    fn query_server(
+        &self,
+        addr: SocketAddr,
+    ) -> Box<dyn Future<Item = rgs::models::Server, Error = failure::Error> + Send> {
+        Box::new(
+            ping(
+                addr,
+                self.retransmit,
+                self.socks5_proxy.clone(),
+                self.bind_addr,
+            )
+            .map(move |(data, latency)| to_server(data, addr, None, None, latency)),
+        )
+    }
+
+    fn describe(&self) -> Value {
+        json!({
+            "kind": "http+udp",
+            "masters": [self.list_url],
+            "socks5_proxy": self.socks5_proxy,
+            "bind_addr": self.bind_addr,
+            "retries": self.retransmit.retries,
+            "retry_window_ms": self.retransmit.window.as_millis() as u64,
+        })
+    }
+}
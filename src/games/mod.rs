@@ -16,32 +16,66 @@
 
 use enum_iter::EnumIterator;
 use futures01::prelude::*;
-use gdk_pixbuf::Pixbuf;
 use log::warn;
 use rgs::{
     dns::Resolver,
+    models::TProtocol,
     ping::{DummyPinger, Pinger},
 };
-use serde_json::Value;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::fmt::{self, Display, Formatter};
+use std::net::{IpAddr, SocketAddr};
+use std::ops::RangeInclusive;
 use std::process::Command;
 use std::sync::Arc;
-use tokio_core::reactor::Core;
+use std::time::Duration;
 
+use crate::settings::{LaunchMethod, Settings};
+
+mod custom;
 mod flatpak;
+pub mod hedgewars;
+pub mod http;
+pub mod info;
+mod lan;
+mod lazy_pinger;
+pub mod master_cache;
+pub mod mindustry;
+mod morph;
+mod native;
 mod openttd;
+mod ping_cache;
+mod ping_limit;
 mod quake;
+pub mod retransmit;
 mod rgs_support;
 mod rigsofrods;
+pub mod selftest;
+pub mod socks5;
+mod steam;
+
+pub use self::custom::expand_template;
+pub use self::info::ServerInfo;
+pub use self::lan::LanQuerier;
+pub use self::lazy_pinger::{icmp_available, LazyPinger};
+pub use self::morph::{ChainedMorpher, DecorationMorpher, RegexMorpher};
+pub use self::openttd::{Company, MAX_COMPANIES};
+pub use self::retransmit::Retransmit;
 
+/// Supported game. Serialized as its id, see `Game::id`.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, EnumIterator)]
 pub enum Game {
     OpenArena,
     OpenTTD,
     QuakeIII,
     RigsOfRods,
+    Unvanquished,
     Xonotic,
+    // Appended rather than sorted in, as query ports follow the order, see `GameList::new`
+    Hedgewars,
+    Mindustry,
 }
 
 impl Game {
@@ -51,7 +85,10 @@ impl Game {
             Game::OpenTTD => "openttd",
             Game::QuakeIII => "q3a",
             Game::RigsOfRods => "rigsofrods",
+            Game::Unvanquished => "unvanquished",
             Game::Xonotic => "xonotic",
+            Game::Hedgewars => "hedgewars",
+            Game::Mindustry => "mindustry",
         }
     }
 
@@ -61,7 +98,10 @@ impl Game {
             "openttd" => Game::OpenTTD,
             "q3a" => Game::QuakeIII,
             "rigsofrods" => Game::RigsOfRods,
+            "unvanquished" => Game::Unvanquished,
             "xonotic" => Game::Xonotic,
+            "hedgewars" => Game::Hedgewars,
+            "mindustry" => Game::Mindustry,
             _ => {
                 return None;
             }
@@ -69,6 +109,26 @@ impl Game {
     }
 }
 
+impl Serialize for Game {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.id())
+    }
+}
+
+impl<'de> Deserialize<'de> for Game {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let id = String::deserialize(deserializer)?;
+
+        Game::from_id(&id).ok_or_else(|| de::Error::custom(format!("Unknown game id: {}", id)))
+    }
+}
+
 impl Display for Game {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         use self::Game::*;
@@ -81,14 +141,125 @@ impl Display for Game {
                 OpenTTD => "OpenTTD",
                 QuakeIII => "Quake III Arena",
                 RigsOfRods => "Rigs of Rods",
+                Unvanquished => "Unvanquished",
                 Xonotic => "Xonotic",
+                Hedgewars => "Hedgewars",
+                Mindustry => "Mindustry",
             }
         )
     }
 }
 
+/// Called by `Querier::query_reporting` as a master server answers
+pub type MasterAnswered = Arc<dyn Fn() + Send + Sync>;
+
+/// Fetches the server list of a game.
 pub trait Querier: Send + Sync {
+    /// Streams servers as they are discovered. Every call starts a new query.
     fn query(&self) -> Box<dyn Stream<Item = rgs::models::Server, Error = failure::Error> + Send>;
+
+    /// Number of master servers `query` asks for the server list, for progress reporting.
+    fn master_count(&self) -> usize {
+        1
+    }
+
+    /// Streams servers like `query`, calling `master_answered` as each of the `master_count` masters answers.
+    /// Queriers that do not tell their masters apart report all of them with the first server.
+    fn query_reporting(
+        &self,
+        master_answered: MasterAnswered,
+    ) -> Box<dyn Stream<Item = rgs::models::Server, Error = failure::Error> + Send> {
+        let mut unreported = self.master_count();
+        Box::new(self.query().inspect(move |_| {
+            for _ in 0..unreported {
+                master_answered();
+            }
+            unreported = 0;
+        }))
+    }
+
+    /// Whether `scan_lan` is supported.
+    fn can_scan_lan(&self) -> bool {
+        false
+    }
+
+    /// Streams servers found on the local network by broadcast, without asking master servers.
+    fn scan_lan(
+        &self,
+    ) -> Box<dyn Stream<Item = rgs::models::Server, Error = failure::Error> + Send> {
+        Box::new(futures01::stream::empty())
+    }
+
+    /// Whether `query_server` is supported.
+    fn can_query_server(&self) -> bool {
+        false
+    }
+
+    /// Queries a single server afresh, including its players where the protocol reports them.
+    fn query_server(
+        &self,
+        _addr: SocketAddr,
+    ) -> Box<dyn Future<Item = rgs::models::Server, Error = failure::Error> + Send> {
+        Box::new(futures01::future::err(failure::err_msg(
+            "Querying single servers is not supported",
+        )))
+    }
+
+    /// Whether `query_info` is supported.
+    fn can_query_info(&self) -> bool {
+        self.can_query_server()
+    }
+
+    /// Queries the fields of a single server that change during a match. Cheaper than `query_server` where
+    /// the protocol has a separate info request, the same query otherwise.
+    fn query_info(
+        &self,
+        addr: SocketAddr,
+    ) -> Box<dyn Future<Item = ServerInfo, Error = failure::Error> + Send> {
+        Box::new(
+            self.query_server(addr)
+                .map(|srv| ServerInfo::from_server(&srv)),
+        )
+    }
+
+    /// Whether an info reply comes from a server of this game rather than of another one speaking the same
+    /// protocol.
+    fn recognizes(&self, _info: &ServerInfo) -> bool {
+        true
+    }
+
+    /// Server lists of the masters `query` asks that are kept in the master cache.
+    fn master_status(&self) -> Vec<master_cache::MasterStatus> {
+        Vec::new()
+    }
+
+    /// Where and how servers are looked up, for dumping the configuration. `Null` if there is nothing to tell.
+    fn describe(&self) -> Value {
+        Value::Null
+    }
+}
+
+/// Finds the game of a server by sending it the info query of every game that has one. Returns the games
+/// whose queriers recognize the reply, in the order given, empty if none did.
+pub fn detect_game(
+    queriers: Vec<(Game, Arc<dyn Querier>)>,
+    addr: SocketAddr,
+) -> impl Future<Item = Vec<Game>, Error = failure::Error> + Send {
+    futures01::future::join_all(
+        queriers
+            .into_iter()
+            .filter(|(_, querier)| querier.can_query_info())
+            .map(move |(game, querier)| {
+                querier.query_info(addr).then(move |res| {
+                    Ok(res
+                        .ok()
+                        .filter(|info| querier.recognizes(info))
+                        .map(|_| game))
+                })
+            })
+            .collect::<Vec<_>>(),
+    )
+    .map(|games| games.into_iter().filter_map(|game| game).collect())
 }
 
 /// Used to normalize server name.
@@ -96,36 +267,522 @@ pub trait NameMorpher: Send + Sync {
     fn morph(&self, v: String) -> String {
         v
     }
+
+    /// Normalization applied, for dumping the configuration. `Null` if names are kept as is.
+    fn describe(&self) -> Value {
+        Value::Null
+    }
 }
 
+/// Keeps server names as is.
 #[derive(Clone, Debug)]
 pub struct DummyMorpher;
 impl NameMorpher for DummyMorpher {}
 
+/// Finds no servers. Stands in for a game's querier in tests and tools.
+#[derive(Clone, Debug)]
+pub struct DummyQuerier;
+impl Querier for DummyQuerier {
+    fn query(&self) -> Box<dyn Stream<Item = rgs::models::Server, Error = failure::Error> + Send> {
+        Box::new(futures01::stream::empty())
+    }
+}
+
+/// First server port and number of ports above it scanned on the local network
+const LAN_PORTS: &[(Game, u16, u16)] = &[
+    (Game::OpenArena, 27960, 4),
+    (Game::QuakeIII, 27960, 4),
+    (Game::Unvanquished, 27960, 4),
+    (Game::Xonotic, 26000, 4),
+];
+
+/// Ports servers of the game listen on by default, `None` if the game cannot be scanned for.
+fn lan_ports(game: Game) -> Option<RangeInclusive<u16>> {
+    LAN_PORTS
+        .iter()
+        .find(|(g, _, _)| *g == game)
+        .map(|(_, first, count)| *first..=first + count - 1)
+}
+
+/// Rule holding the host name the server address was resolved from
+pub const HOST_RULE: &str = "_host";
+
+/// Whether the rule is added by Obozrenie rather than reported by the server, like `HOST_RULE`. Such rules
+/// are not shown along with the server's own.
+pub fn is_internal_rule(name: &str) -> bool {
+    name.starts_with('_')
+}
+
+/// Identifies the server across refreshes. Servers known by host name are keyed on it,
+/// so that all of its resolved addresses count as one server. Rooms sharing the address of their lobby are
+/// told apart by name.
+pub fn server_key(srv: &rgs::models::Server) -> String {
+    let addr = match srv.rules.get(HOST_RULE) {
+        Some(Value::String(host)) => format!("{}:{}", host, srv.addr.port()),
+        _ => srv.addr.to_string(),
+    };
+
+    match srv.rules.get(hedgewars::ROOM_RULE) {
+        Some(Value::String(room)) => format!("{}/{}", addr, room),
+        _ => addr,
+    }
+}
+
+/// Key of servers known by host name from before `server_key` used it, to carry over favorites and history
+/// stored under the address. `None` if the key is the same.
+pub fn legacy_server_key(srv: &rgs::models::Server) -> Option<String> {
+    if !srv.rules.contains_key(HOST_RULE) {
+        return None;
+    }
+
+    let addr = srv.addr.to_string();
+    Some(match srv.rules.get(hedgewars::ROOM_RULE) {
+        Some(Value::String(room)) => format!("{}/{}", addr, room),
+        _ => addr,
+    })
+}
+
+/// Address of `host`, along with its name unless it is an IP address, for `HOST_RULE`. Of several addresses
+/// the lowest one is picked, as resolution order is arbitrary.
+pub fn resolve_host(
+    resolver: &tokio_dns::CpuPoolResolver,
+    host: String,
+) -> Box<dyn Future<Item = (IpAddr, Option<String>), Error = failure::Error> + Send> {
+    use tokio_dns::Resolver as _;
+
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Box::new(futures01::future::ok((ip, None)));
+    }
+
+    Box::new(
+        resolver
+            .resolve(&host)
+            .map_err(failure::Error::from)
+            .and_then(move |addrs| {
+                addrs
+                    .into_iter()
+                    .min()
+                    .map(|ip| (ip, Some(host.clone())))
+                    .ok_or_else(|| failure::err_msg(format!("{} did not resolve", host)))
+            }),
+    )
+}
+
+/// Rules carrying a message of the day, in order of preference
+const MOTD_RULES: &[&str] = &["sv_motd", "g_motd", "motd"];
+
+/// Informational rules shown along with the message of the day
+const INFO_RULES: &[(&str, &str)] = &[
+    (".Admin", "Admin"),
+    (".Email", "Email"),
+    (".URL", "Website"),
+    (".Location", "Location"),
+    (hedgewars::ROOM_RULE, "Room"),
+];
+
+/// Message of the day and admin contacts, if the server provides any.
+pub fn server_description(srv: &rgs::models::Server) -> Option<String> {
+    let rule = |name: &str| match srv.rules.get(name) {
+        Some(Value::String(v)) if !v.trim().is_empty() => Some(v.trim().to_string()),
+        _ => None,
+    };
+
+    let lines = MOTD_RULES
+        .iter()
+        .filter_map(|name| rule(name))
+        .take(1)
+        .chain(
+            INFO_RULES
+                .iter()
+                .filter_map(|(name, title)| rule(name).map(|v| format!("{}: {}", title, v))),
+        )
+        .collect::<Vec<_>>();
+
+    if lines.is_empty() {
+        None
+    } else {
+        // Quake-style color codes are common in these too
+        Some(quake::NameMorpher::default().morph(lines.join("\n")))
+    }
+}
+
+/// Whether the server can be joined with a stock game install, `None` if this is unknown for the game.
+pub fn stock_client_compatible(game: Game, srv: &rgs::models::Server) -> Option<bool> {
+    match game {
+        Game::OpenArena => Some(quake::stock_client_compatible(&["baseoa"], true, srv)),
+        Game::QuakeIII => Some(quake::stock_client_compatible(&["baseq3"], true, srv)),
+        Game::Xonotic => Some(quake::stock_client_compatible(
+            &["data", "xonotic"],
+            false,
+            srv,
+        )),
+        _ => None,
+    }
+}
+
+/// Whether the game's servers report if they need a password, so that a missing flag means a bad reply.
+pub fn reports_password(game: Game) -> bool {
+    match game {
+        Game::OpenArena
+        | Game::OpenTTD
+        | Game::QuakeIII
+        | Game::RigsOfRods
+        | Game::Unvanquished
+        | Game::Hedgewars => true,
+        Game::Xonotic | Game::Mindustry => false,
+    }
+}
+
+/// Anti-cheat the game's servers report through the secure flag, `None` if the flag means nothing for the game.
+pub fn anticheat(game: Game) -> Option<&'static str> {
+    match game {
+        Game::QuakeIII => Some("PunkBuster"),
+        _ => None,
+    }
+}
+
+/// Whether the game's servers report if anticheat is enabled, see `anticheat`.
+pub fn reports_anticheat(game: Game) -> bool {
+    anticheat(game).is_some()
+}
+
+/// Rules telling a dedicated server from a listen server, and rules hinting at the server's OS.
+/// The dedicated rules report `0` for listen servers, Quake-style.
+const HOST_RULES: &[(Game, &[&str], &[&str])] = &[
+    (Game::OpenArena, &["dedicated"], &["version"]),
+    (Game::OpenTTD, &["dedicated"], &[]),
+    (Game::QuakeIII, &["dedicated"], &["version"]),
+    (Game::Unvanquished, &["dedicated"], &["version"]),
+    (
+        Game::Xonotic,
+        &["dedicated", "sv_dedicated"],
+        &["version", "sv_os"],
+    ),
+];
+
+/// OS names as spotted in version strings, checked in order
+const OS_NAMES: &[(&str, &str)] = &[
+    ("darwin", "macOS"),
+    ("macos", "macOS"),
+    ("osx", "macOS"),
+    ("freebsd", "FreeBSD"),
+    ("openbsd", "OpenBSD"),
+    ("linux", "Linux"),
+    ("win", "Windows"),
+];
+
+fn host_rules(game: Game) -> Option<(&'static [&'static str], &'static [&'static str])> {
+    HOST_RULES
+        .iter()
+        .find(|(g, _, _)| *g == game)
+        .map(|(_, dedicated, os)| (*dedicated, *os))
+}
+
+/// Whether the server is dedicated rather than hosted by a playing client, `None` if not reported.
+pub fn server_dedicated(game: Game, srv: &rgs::models::Server) -> Option<bool> {
+    host_rules(game)?
+        .0
+        .iter()
+        .filter_map(|name| quake::rule_flag(srv, name))
+        .next()
+}
+
+/// Rule tagging servers that a game's `ServerFilter` rejected while filters are bypassed, with the filter's name
+pub const FILTERED_OUT_RULE: &str = "filtered_out_by";
+
+/// Whether servers rejected by game filters are listed, tagged with `FILTERED_OUT_RULE`, to debug empty lists.
+/// Also enabled by setting `OBOZRENIE_NO_SERVER_FILTERS=1`.
+pub fn server_filters_bypassed(settings: &Settings) -> bool {
+    settings.bypass_server_filters
+        || std::env::var("OBOZRENIE_NO_SERVER_FILTERS")
+            .map(|v| v == "1")
+            .unwrap_or(false)
+}
+
+/// Name of the game filter that rejected the server, if it was kept because filters are bypassed.
+pub fn filtered_out_by(srv: &rgs::models::Server) -> Option<String> {
+    match srv.rules.get(FILTERED_OUT_RULE) {
+        Some(Value::String(v)) => Some(v.clone()),
+        _ => None,
+    }
+}
+
+/// Server filter dropping servers that fail `accept`, or tagging them with the filter's `name` if `bypass` is set.
+fn named_filter(
+    name: &'static str,
+    bypass: bool,
+    accept: fn(&rgs::models::Server) -> bool,
+) -> rgs::protocols::q3s::ServerFilter {
+    rgs::protocols::q3s::ServerFilter(Arc::new(move |mut srv: rgs::models::Server| {
+        if accept(&srv) {
+            Some(srv)
+        } else if bypass {
+            srv.rules
+                .insert(FILTERED_OUT_RULE.into(), Value::String(name.into()));
+            Some(srv)
+        } else {
+            None
+        }
+    }))
+}
+
+/// OpenArena shares masters with other Quake III engine games, which report other versions.
+fn is_openarena_server(srv: &rgs::models::Server) -> bool {
+    match srv.rules.get("version") {
+        Some(Value::String(ver)) => ver.starts_with("ioq3+oa"),
+        _ => false,
+    }
+}
+
+/// Operating system the server runs on, `None` if not reported.
+pub fn server_os(game: Game, srv: &rgs::models::Server) -> Option<String> {
+    host_rules(game)?
+        .1
+        .iter()
+        .filter_map(|name| match srv.rules.get(*name) {
+            Some(Value::String(v)) => {
+                let v = v.to_lowercase();
+                OS_NAMES
+                    .iter()
+                    .find(|(pattern, _)| v.contains(pattern))
+                    .map(|(_, os)| os.to_string())
+            }
+            _ => None,
+        })
+        .next()
+}
+
+/// Rules reporting the game version of servers, for games whose clients cannot join servers of other versions
+const VERSION_RULES: &[(Game, &[&str])] = &[
+    (Game::OpenTTD, &["server_revision", "revision"]),
+    (Game::Xonotic, &["gameversion"]),
+    (Game::Mindustry, &["version"]),
+];
+
+/// Game version the server runs, `None` if not reported or if the game's versions are not checked.
+pub fn server_version(game: Game, srv: &rgs::models::Server) -> Option<String> {
+    VERSION_RULES
+        .iter()
+        .find(|(g, _)| *g == game)?
+        .1
+        .iter()
+        .filter_map(|name| match srv.rules.get(*name) {
+            Some(Value::String(v)) if !v.trim().is_empty() => Some(v.trim().to_string()),
+            Some(Value::Number(v)) => Some(v.to_string()),
+            _ => None,
+        })
+        .next()
+}
+
+/// Whether servers of the game are checked against the installed version
+pub fn checks_version(game: Game) -> bool {
+    VERSION_RULES.iter().any(|(g, _)| *g == game)
+}
+
+/// Whether a client of the local version can join a server of the given version. Numeric components are
+/// compared, so `1.9` accepts any `1.9.x` server and leading text like `Xonotic` is ignored. Versions
+/// without numbers must match exactly.
+pub fn versions_compatible(server: &str, local: &str) -> bool {
+    let components = |v: &str| {
+        v.split(|c: char| !c.is_ascii_digit())
+            .filter_map(|part| part.parse::<u64>().ok())
+            .collect::<Vec<_>>()
+    };
+
+    let local_components = components(local);
+    if local_components.is_empty() {
+        return server.trim().eq_ignore_ascii_case(local.trim());
+    }
+
+    components(server).starts_with(&local_components)
+}
+
+/// Server data prepared for display. Built in the query pipeline to keep the GTK thread free.
+#[derive(Clone, Debug)]
+pub struct DisplayServer {
+    pub game_id: Game,
+    /// Normalized server name
+    pub name: String,
+    /// Serialized original data
+    pub json: String,
+    pub stock_client: Option<bool>,
+    pub dedicated: Option<bool>,
+    pub server_os: Option<String>,
+    /// See `server_version`
+    pub version: Option<String>,
+    /// Game filter that would have hidden the server, see `FILTERED_OUT_RULE`
+    pub filtered_out_by: Option<String>,
+    pub srv: rgs::models::Server,
+}
+
+impl DisplayServer {
+    pub fn new(game_id: Game, name_morpher: &dyn NameMorpher, srv: rgs::models::Server) -> Self {
+        Self {
+            game_id,
+            name: name_morpher.morph(srv.name.clone().unwrap_or_default()),
+            json: serde_json::to_string(&srv).unwrap(),
+            stock_client: stock_client_compatible(game_id, &srv),
+            dedicated: server_dedicated(game_id, &srv),
+            server_os: server_os(game_id, &srv),
+            version: server_version(game_id, &srv),
+            filtered_out_by: filtered_out_by(&srv),
+            srv,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct LaunchData {
     pub addr: String,
     pub password: Option<String>,
+    pub player_name: Option<String>,
+    /// OpenTTD company to join
+    pub company: Option<Company>,
+    /// OpenTTD company password, separate from the server password
+    pub company_password: Option<String>,
+    /// Flatpak ref to launch instead of the one set for the game
+    pub flatpak_ref: Option<String>,
+    /// Appended to the game's command line
+    pub extra_args: Vec<String>,
 }
 
+/// Builds the command that starts a game and connects it to a server.
 pub trait Launcher: Send + Sync {
+    /// `None` if the game cannot be launched.
     fn launch_cmd(&self, _data: &LaunchData) -> Option<Command> {
         None
     }
+
+    /// Warning to confirm with the user before launching, e.g. about an unusual launch target.
+    fn launch_confirmation(&self, _data: &LaunchData) -> Option<String> {
+        None
+    }
+
+    /// Command line `launch_cmd` would run, for display.
+    fn preview_cmd(&self, data: &LaunchData) -> Option<String> {
+        self.launch_cmd(data).map(|cmd| format!("{:?}", cmd))
+    }
+
+    /// Whether the game can be launched on this system.
+    fn is_available(&self) -> bool {
+        false
+    }
+
+    /// Config snippet adding `addrs` to the game's own favorites, `None` if the game has no such format.
+    fn favorites_config(&self, _addrs: &[String]) -> Option<String> {
+        None
+    }
+
+    /// Whether the launched process quits when the server rejects the connection, e.g. for a wrong password.
+    fn exits_on_rejection(&self) -> bool {
+        false
+    }
+
+    /// Whether connecting copies the server address for the user to join from the game instead.
+    fn copies_address(&self) -> bool {
+        false
+    }
+
+    /// Backend and what it runs, for dumping the configuration. `Null` for games without launch support.
+    fn describe(&self) -> Value {
+        Value::Null
+    }
 }
 
+/// Launches nothing, for games without launch support.
 #[derive(Clone)]
 pub struct DummyLauncher;
 impl Launcher for DummyLauncher {}
 
-pub trait GameIconSource {
-    fn get_icon(&self, game: Game) -> Pixbuf;
+/// Launches nothing, the server address is copied instead.
+#[derive(Clone)]
+pub struct CopyLauncher;
+impl Launcher for CopyLauncher {
+    fn copies_address(&self) -> bool {
+        true
+    }
+
+    fn describe(&self) -> Value {
+        json!({ "kind": "copy" })
+    }
+}
+
+/// Starts the game without joining, for games that cannot be told to join a server on the command line.
+/// The server is joined from the game then, e.g. Hedgewars rooms from its lobby, whose name is in the server
+/// details.
+#[derive(Clone)]
+pub struct StartLauncher {
+    /// Flatpak, native or Steam launcher running the game
+    pub base_launcher: Arc<dyn Launcher>,
+}
+
+impl Launcher for StartLauncher {
+    fn launch_cmd(&self, data: &LaunchData) -> Option<Command> {
+        self.base_launcher.launch_cmd(data).map(|mut cmd| {
+            cmd.args(&data.extra_args);
+
+            cmd
+        })
+    }
+
+    fn launch_confirmation(&self, data: &LaunchData) -> Option<String> {
+        self.base_launcher.launch_confirmation(data)
+    }
+
+    fn is_available(&self) -> bool {
+        self.base_launcher.is_available()
+    }
+
+    fn describe(&self) -> Value {
+        self.base_launcher.describe()
+    }
+}
+
+/// Launcher of the game for the launch method set in `settings`.
+pub fn launcher(id: Game, settings: &Settings) -> Arc<dyn Launcher> {
+    let base_launcher = match settings.launch_method(id) {
+        LaunchMethod::Flatpak => Arc::new(flatpak::Launcher {
+            id_source: Arc::new(id),
+            ref_override: settings.flatpak_refs.get(&id).cloned(),
+        }) as Arc<dyn Launcher>,
+        LaunchMethod::Native => Arc::new(native::Launcher {
+            program: settings
+                .launch_command(id)
+                .unwrap_or_else(|| native::default_program(id))
+                .to_string(),
+        }),
+        LaunchMethod::Steam => Arc::new(steam::Launcher {
+            app_id: steam::app_id(id),
+        }),
+        // The template replaces the game's own arguments
+        LaunchMethod::Custom => {
+            return Arc::new(custom::Launcher {
+                template: settings.launch_command(id).unwrap_or("").to_string(),
+            });
+        }
+        LaunchMethod::Copy => {
+            return Arc::new(CopyLauncher);
+        }
+    };
+
+    match id {
+        Game::QuakeIII | Game::Xonotic | Game::OpenArena | Game::Unvanquished => {
+            Arc::new(quake::Launcher {
+                base_launcher,
+                favorites_cvar: match id {
+                    Game::Xonotic => Some("net_slist_favorites"),
+                    _ => None,
+                },
+            })
+        }
+        Game::OpenTTD => Arc::new(openttd::Launcher { base_launcher }),
+        Game::RigsOfRods => Arc::new(rigsofrods::Launcher { base_launcher }),
+        Game::Hedgewars | Game::Mindustry => Arc::new(StartLauncher { base_launcher }),
+    }
 }
 
 #[derive(Clone)]
 pub struct GameEntry {
-    /// Game's icon
-    pub icon: Pixbuf,
     /// Fetches server list for this game
     pub querier: Arc<dyn Querier>,
     /// Adapts server name for the server list
@@ -134,135 +791,278 @@ pub struct GameEntry {
     pub launcher: Arc<dyn Launcher>,
 }
 
+/// Splits a `host:port` master address, warning about malformed ones.
+fn parse_master(addr: &str) -> Option<(String, u16)> {
+    let parsed = addr
+        .rfind(':')
+        .and_then(|i| Some((addr[..i].to_string(), addr[i + 1..].parse().ok()?)));
+    if parsed.is_none() {
+        warn!("Ignoring invalid master server address {}", addr);
+    }
+    parsed
+}
+
+/// Supported games with everything needed to query and launch them.
 #[derive(Clone)]
 pub struct GameList(pub HashMap<Game, GameEntry>);
 
 impl GameList {
-    pub fn new(icon_source: &dyn GameIconSource) -> GameList {
+    /// Sets up every game that is not hidden in `settings`. The pinger is spawned on first use, as it may need
+    /// elevated privileges and falls back to manual latency measurement.
+    pub fn new(settings: &Settings) -> GameList {
         let starting_port = 5600;
-        let pinger = Core::new()
-            .unwrap()
-            .run(tokio_ping::Pinger::new())
-            .map(|pinger| Arc::new(pinger) as Arc<dyn Pinger>)
-            .unwrap_or_else(|e| {
-                warn!(
-                    "Failed to spawn pinger: {}. Using manual latency measurement.",
-                    e
-                );
-                Arc::new(DummyPinger) as Arc<dyn Pinger>
-            });
+        let pinger = Arc::new(lazy_pinger::LazyPinger::default()) as Arc<dyn Pinger>;
+        let (pinger, over_limit) = if settings.ping_limit > 0 {
+            let pinger =
+                ping_limit::LimitedPinger::new(pinger, Duration::from_millis(settings.ping_limit));
+            let over_limit = pinger.over_limit();
+            (Arc::new(pinger) as Arc<dyn Pinger>, Some(over_limit))
+        } else {
+            (pinger, None)
+        };
+        // Server info is always queried afresh, only latency measurements are reused
+        let pinger = if settings.ping_cache_ttl > 0 {
+            Arc::new(ping_cache::CachingPinger::new(
+                pinger,
+                Duration::from_secs(settings.ping_cache_ttl),
+            )) as Arc<dyn Pinger>
+        } else {
+            pinger
+        };
 
         let resolver = Arc::new(tokio_dns::CpuPoolResolver::new(16)) as Arc<dyn Resolver>;
+        let bind_addr = settings.bind_address.as_ref().and_then(|addr| {
+            addr.parse::<IpAddr>()
+                .map_err(|e| warn!("Invalid bind address {}, querying from any: {}", addr, e))
+                .ok()
+        });
+        // Several games share dpmaster.deathmask.net
+        let master_cache = Arc::new(master_cache::MasterCache::new(
+            Duration::from_secs(settings.master_cache_ttl),
+            Duration::from_millis(settings.master_request_interval),
+        ));
+        // User morphs stack on top of per-game normalization
+        let user_morpher = Arc::new(ChainedMorpher({
+            let mut morphers = Vec::<Arc<dyn NameMorpher>>::new();
+            if settings.strip_name_decorations {
+                morphers.push(Arc::new(DecorationMorpher::default()));
+            }
+            morphers.push(Arc::new(RegexMorpher::new(&settings.name_replacements)));
+            morphers
+        })) as Arc<dyn NameMorpher>;
+
+        let bypass_filters = server_filters_bypassed(settings);
+
+        // Yields every address of a host, unlike the rgs resolver
+        let host_resolver = Arc::new(tokio_dns::CpuPoolResolver::new(4));
+
+        // One client for all HTTP masters, so that connections are reused
+        let http_timeout = Duration::from_secs(settings.http_timeout);
+        let http_client = http::client(
+            settings.http_proxy.as_ref().map(String::as_str),
+            http_timeout,
+        )
+        .unwrap_or_else(|e| {
+            warn!("Invalid HTTP proxy, connecting directly: {}", e);
+            http::client(None, http_timeout).expect("Failed to build the HTTP client")
+        });
 
         GameList(
             Game::enum_iter()
                 .enumerate()
+                // Filtered after enumeration to keep query ports stable
+                .filter(|(_, id)| !settings.hidden_games.contains(id))
                 .map(|(i, id)| {
                     (
                         id,
                         GameEntry {
-                            icon: icon_source.get_icon(id),
-                            launcher: {
-                                let flatpak_launcher = flatpak::Launcher { id_source: Arc::new(id) };
-                                match id {
-                                    Game::QuakeIII | Game::Xonotic | Game::OpenArena => Arc::new(quake::Launcher { flatpak_launcher }),
-                                    Game::OpenTTD => Arc::new(openttd::Launcher { flatpak_launcher }),
-                                    _ => Arc::new(DummyLauncher),
-                                }
-                            },
-                            name_morpher: match id {
-                                Game::QuakeIII | Game::OpenArena => Arc::new(quake::NameMorpher::default()),
-                                _ => Arc::new(DummyMorpher),
-                            },
+                            launcher: launcher(id, settings),
+                            name_morpher: Arc::new(ChainedMorpher(
+                                vec![
+                                    match id {
+                                        Game::QuakeIII | Game::OpenArena | Game::Unvanquished => {
+                                            Arc::new(quake::NameMorpher::default()) as Arc<dyn NameMorpher>
+                                        }
+                                        _ => Arc::new(DummyMorpher),
+                                    },
+                                    user_morpher.clone(),
+                                ],
+                            )),
                             querier: {
                                 let resolver = resolver.clone();
-                                let pinger = pinger.clone();
-                                match id {
+                                // Latency then comes from the query replies alone. ICMP cannot go through the proxy,
+                                // nor be bound to an address.
+                                let pinged = !settings.no_ping_games.contains(&id)
+                                    && settings.socks5_proxy.is_none()
+                                    && bind_addr.is_none();
+                                let pinger = if pinged {
+                                    pinger.clone()
+                                } else {
+                                    Arc::new(DummyPinger) as Arc<dyn Pinger>
+                                };
+                                let querier: Arc<dyn Querier> = match id {
+                                    Game::Hedgewars => Arc::new(hedgewars::Querier {
+                                        server_addr: (
+                                            hedgewars::SERVER_ADDR.0.to_string(),
+                                            hedgewars::SERVER_ADDR.1,
+                                        ),
+                                        resolver: host_resolver.clone(),
+                                        pinger,
+                                    }),
+                                    Game::Mindustry => Arc::new(mindustry::Querier {
+                                        list_url: mindustry::SERVER_LIST_URL.into(),
+                                        client: http_client.clone(),
+                                        resolver: host_resolver.clone(),
+                                        socks5_proxy: settings.socks5_proxy.clone(),
+                                        bind_addr,
+                                        retransmit: settings.query_retransmit(id),
+                                    }),
                                     Game::RigsOfRods => Arc::new(rigsofrods::Querier {
-                                        master_addr: "http://multiplayer.rigsofrods.org/server-list".into(),
-                                        resolver,
+                                        master_addr: settings
+                                            .rigsofrods_master
+                                            .clone()
+                                            .unwrap_or_else(|| rigsofrods::MASTER_ADDR.into()),
+                                        client: http_client.clone(),
+                                        resolver: host_resolver.clone(),
                                         pinger,
                                     }),
                                     _ => Arc::new({
                                         let protocols = rgs::protocols::make_default_protocols();
 
-                                        let (protocol, master_servers) = match id {
-                                            Game::OpenArena => (
-                                                {
-                                                    let version = 71 as u32;
+                                        let (protocol, server_protocol, protocol_version, master_servers) = match id {
+                                            Game::OpenArena => {
+                                                let version = 71 as u32;
+                                                let q3s_protocol: TProtocol = {
+                                                    let mut proto = rgs::protocols::q3s::ProtocolImpl {
+                                                        version,
+                                                        ..Default::default()
+                                                    };
+                                                    proto.rule_names.insert(rgs::protocols::q3s::Rule::Mod, "gamename".into());
+                                                    proto.server_filter =
+                                                        named_filter("openarena-version", bypass_filters, is_openarena_server);
+                                                    proto
+                                                }
+                                                .into();
+                                                (
                                                     rgs::protocols::q3m::ProtocolImpl {
-                                                        q3s_protocol: Some(
-                                                            {
-                                                                let mut proto = rgs::protocols::q3s::ProtocolImpl {
-                                                                    version,
-                                                                    ..Default::default()
-                                                                };
-                                                                proto.rule_names.insert(rgs::protocols::q3s::Rule::Mod, "gamename".into());
-                                                                proto.server_filter = rgs::protocols::q3s::ServerFilter(Arc::new(
-                                                                    |srv: rgs::models::Server| {
-                                                                        if let Some(ver) = srv.rules.get("version") {
-                                                                            if let Value::String(ver) = ver {
-                                                                                if ver.starts_with("ioq3+oa") {
-                                                                                    return Some(srv.clone());
-                                                                                }
-                                                                            }
-                                                                        }
-                                                                        None
-                                                                    },
-                                                                ));
-                                                                proto
-                                                            }
-                                                            .into(),
-                                                        ),
+                                                        q3s_protocol: Some(q3s_protocol.clone()),
                                                         version,
                                                         ..Default::default()
                                                     }
-                                                    .into()
-                                                },
-                                                vec![
-                                                    ("master3.idsoftware.com", 27950),
-                                                    ("master.ioquake3.org", 27950),
-                                                    ("dpmaster.deathmask.net", 27950),
-                                                ],
+                                                    .into(),
+                                                    Some(q3s_protocol),
+                                                    Some(version),
+                                                    vec![
+                                                        ("master3.idsoftware.com", 27950),
+                                                        ("master.ioquake3.org", 27950),
+                                                        ("dpmaster.deathmask.net", 27950),
+                                                    ],
+                                                )
+                                            }
+                                            Game::OpenTTD => (protocols["openttdm"].clone(), None, None, vec![("master.openttd.org", 3978)]),
+                                            Game::QuakeIII => (
+                                                protocols["q3m"].clone(),
+                                                Some(protocols["q3s"].clone()),
+                                                Some(68),
+                                                vec![("master3.idsoftware.com", 27950)],
                                             ),
-                                            Game::OpenTTD => (protocols["openttdm"].clone(), vec![("master.openttd.org", 3978)]),
-                                            Game::QuakeIII => (protocols["q3m"].clone(), vec![("master3.idsoftware.com", 27950)]),
-                                            Game::Xonotic => (
-                                                {
-                                                    let version = 3 as u32;
+                                            Game::Xonotic => {
+                                                let version = 3 as u32;
+                                                let q3s_protocol: TProtocol = {
+                                                    let mut proto = rgs::protocols::q3s::ProtocolImpl::default();
+                                                    proto
+                                                        .rule_names
+                                                        .insert(rgs::protocols::q3s::Rule::ServerName, "hostname".into());
+                                                    proto.rule_names.insert(rgs::protocols::q3s::Rule::Mod, "modname".into());
+                                                    proto
+                                                }
+                                                .into();
+                                                (
                                                     rgs::protocols::q3m::ProtocolImpl {
                                                         request_tag: Some("Xonotic".to_string()),
                                                         version,
-                                                        q3s_protocol: Some(
-                                                            {
-                                                                let mut proto = rgs::protocols::q3s::ProtocolImpl::default();
-                                                                proto
-                                                                    .rule_names
-                                                                    .insert(rgs::protocols::q3s::Rule::ServerName, "hostname".into());
-                                                                proto.rule_names.insert(rgs::protocols::q3s::Rule::Mod, "modname".into());
-                                                                proto
-                                                            }
-                                                            .into(),
-                                                        ),
+                                                        q3s_protocol: Some(q3s_protocol.clone()),
                                                     }
+                                                    .into(),
+                                                    Some(q3s_protocol),
+                                                    Some(version),
+                                                    vec![("dpmaster.deathmask.net", 27950)],
+                                                )
+                                            }
+                                            Game::Unvanquished => {
+                                                let version = 86 as u32;
+                                                let q3s_protocol: TProtocol = {
+                                                    let mut proto = rgs::protocols::q3s::ProtocolImpl {
+                                                        version,
+                                                        ..Default::default()
+                                                    };
+                                                    proto
+                                                        .rule_names
+                                                        .insert(rgs::protocols::q3s::Rule::ServerName, "sv_hostname".into());
+                                                    proto.rule_names.insert(rgs::protocols::q3s::Rule::Mod, "gamename".into());
+                                                    // Expose team stages under stable names for the details pane
+                                                    proto.server_filter = rgs::protocols::q3s::ServerFilter(Arc::new(
+                                                        |mut srv: rgs::models::Server| {
+                                                            for (cvar, rule) in &[("g_alienStage", "alien_stage"), ("g_humanStage", "human_stage")] {
+                                                                if let Some(v) = srv.rules.get(*cvar).cloned() {
+                                                                    srv.rules.insert(rule.to_string(), v);
+                                                                }
+                                                            }
+                                                            Some(srv)
+                                                        },
+                                                    ));
+                                                    proto
                                                 }
-                                                .into(),
-                                                vec![("dpmaster.deathmask.net", 27950)],
-                                            ),
+                                                .into();
+                                                (
+                                                    rgs::protocols::q3m::ProtocolImpl {
+                                                        request_tag: Some("UNVANQUISHED".to_string()),
+                                                        version,
+                                                        q3s_protocol: Some(q3s_protocol.clone()),
+                                                    }
+                                                    .into(),
+                                                    Some(q3s_protocol),
+                                                    Some(version),
+                                                    vec![("master.unvanquished.net", 27950)],
+                                                )
+                                            }
                                             _ => unreachable!(),
                                         };
 
-                                        let master_servers =
-                                            master_servers.into_iter().map(|(addr, port)| (addr.to_string(), port)).collect();
+                                        let master_servers = match settings.master_servers.get(&id) {
+                                            Some(masters) => masters.iter().filter_map(|addr| parse_master(addr)).collect(),
+                                            None => master_servers
+                                                .into_iter()
+                                                .map(|(addr, port)| (addr.to_string(), port))
+                                                .collect(),
+                                        };
 
                                         rgs_support::Querier {
                                             protocol,
+                                            server_protocol,
+                                            protocol_version,
                                             master_servers,
+                                            master_tag: id.id().to_string(),
+                                            master_cache: master_cache.clone(),
+                                            socks5_proxy: settings.socks5_proxy.clone(),
+                                            bind_addr,
+                                            lan_ports: lan_ports(id),
                                             port: starting_port + i as u16,
+                                            retransmit: settings.query_retransmit(id),
                                             pinger,
                                             resolver,
                                         }
                                     }),
+                                };
+                                // Mindustry measures latency with its own query
+                                match over_limit.as_ref() {
+                                    Some(over_limit) if pinged && id != Game::Mindustry => {
+                                        Arc::new(ping_limit::LimitedQuerier {
+                                            inner: querier,
+                                            over_limit: over_limit.clone(),
+                                        })
+                                    }
+                                    _ => querier,
                                 }
                             },
                         },
@@ -271,4 +1071,26 @@ impl GameList {
                 .collect(),
         )
     }
+
+    /// Effective configuration of every game, keyed by game id.
+    pub fn describe(&self, settings: &Settings) -> Value {
+        Value::Object(
+            self.0
+                .iter()
+                .map(|(&id, entry)| {
+                    (
+                        id.id().to_string(),
+                        json!({
+                            "name": settings.game_name(id),
+                            "querier": entry.querier.describe(),
+                            "name_morpher": entry.name_morpher.describe(),
+                            "launcher": entry.launcher.describe(),
+                            "launcher_available": entry.launcher.is_available(),
+                            "flatpak_id": flatpak::FlatpakIdentifiable::id(&id),
+                        }),
+                    )
+                })
+                .collect(),
+        )
+    }
 }
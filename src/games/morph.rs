@@ -0,0 +1,138 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use log::warn;
+use regex::Regex;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use super::NameMorpher;
+use crate::settings::NameReplacement;
+
+/// Applies morphers one after another.
+#[derive(Clone)]
+pub struct ChainedMorpher(pub Vec<Arc<dyn NameMorpher>>);
+
+impl NameMorpher for ChainedMorpher {
+    fn morph(&self, v: String) -> String {
+        self.0.iter().fold(v, |v, morpher| morpher.morph(v))
+    }
+
+    /// Morphers in the order they apply, nested chains flattened
+    fn describe(&self) -> Value {
+        Value::Array(
+            self.0
+                .iter()
+                .flat_map(|morpher| match morpher.describe() {
+                    Value::Array(v) => v,
+                    Value::Null => vec![],
+                    v => vec![v],
+                })
+                .collect(),
+        )
+    }
+}
+
+/// User-defined regex replacements
+#[derive(Clone, Debug, Default)]
+pub struct RegexMorpher(pub Vec<(Regex, String)>);
+
+impl RegexMorpher {
+    /// Invalid patterns are logged and skipped.
+    pub fn new(replacements: &[NameReplacement]) -> Self {
+        let (out, errors) = Self::compile(replacements);
+        for e in errors {
+            warn!("Skipping invalid name pattern. {}", e);
+        }
+        out
+    }
+
+    /// Compiles the valid patterns, and describes the invalid ones.
+    pub fn compile(replacements: &[NameReplacement]) -> (Self, Vec<String>) {
+        let mut patterns = Vec::new();
+        let mut errors = Vec::new();
+        for entry in replacements {
+            match Regex::new(&entry.pattern) {
+                Ok(pattern) => patterns.push((pattern, entry.replacement.clone())),
+                Err(e) => errors.push(format!("{:?}: {}", entry.pattern, e)),
+            }
+        }
+
+        (RegexMorpher(patterns), errors)
+    }
+}
+
+impl NameMorpher for RegexMorpher {
+    fn morph(&self, v: String) -> String {
+        self.0.iter().fold(v, |v, (pattern, replacement)| {
+            pattern.replace_all(&v, replacement.as_str()).into_owned()
+        })
+    }
+
+    fn describe(&self) -> Value {
+        if self.0.is_empty() {
+            return Value::Null;
+        }
+
+        json!({
+            "replacements": self
+                .0
+                .iter()
+                .map(|(pattern, replacement)| json!({
+                    "pattern": pattern.as_str(),
+                    "replacement": replacement,
+                }))
+                .collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// Strips decorations like `-=[ ]=-` and short clan tags off the beginning, and decorations off the end.
+#[derive(Clone, Debug)]
+pub struct DecorationMorpher {
+    pub leading: Regex,
+    pub trailing: Regex,
+}
+
+impl Default for DecorationMorpher {
+    fn default() -> Self {
+        Self {
+            leading: Regex::new(
+                r"^(?:[\s\-=~*_.:|#+]+|\[[^\]]{0,16}\]|\([^)]{0,16}\)|\{[^}]{0,16}\}|<[^>]{0,16}>)+",
+            )
+            .unwrap(),
+            trailing: Regex::new(r"[\s\-=~*_.:|#+]+$").unwrap(),
+        }
+    }
+}
+
+impl NameMorpher for DecorationMorpher {
+    fn morph(&self, v: String) -> String {
+        let stripped = self.leading.replace(&v, "");
+        let out = self.trailing.replace(&stripped, "");
+
+        // Keep names that are nothing but decorations
+        if out.is_empty() {
+            v
+        } else {
+            out.into_owned()
+        }
+    }
+
+    fn describe(&self) -> Value {
+        "decorations".into()
+    }
+}
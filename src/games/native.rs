@@ -0,0 +1,70 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use super::{Game, LaunchData};
+
+use serde_json::{json, Value};
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+/// Executable of the game as usually installed by distribution packages
+pub fn default_program(game: Game) -> &'static str {
+    match game {
+        Game::Hedgewars => "hedgewars",
+        Game::Mindustry => "mindustry",
+        Game::OpenArena => "openarena",
+        Game::OpenTTD => "openttd",
+        Game::QuakeIII => "quake3",
+        Game::RigsOfRods => "RoR",
+        Game::Unvanquished => "unvanquished",
+        Game::Xonotic => "xonotic-sdl",
+    }
+}
+
+/// Whether `program` is a path to an existing file or can be found in `PATH`.
+pub fn find_program(program: &str) -> bool {
+    if program.contains('/') {
+        return Path::new(program).is_file();
+    }
+
+    env::var_os("PATH")
+        .map(|paths| env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
+
+/// Runs the game's executable installed on the system.
+#[derive(Clone)]
+pub struct Launcher {
+    pub program: String,
+}
+
+impl super::Launcher for Launcher {
+    fn launch_cmd(&self, _data: &LaunchData) -> Option<Command> {
+        Some(Command::new(&self.program))
+    }
+
+    fn is_available(&self) -> bool {
+        find_program(&self.program)
+    }
+
+    fn describe(&self) -> Value {
+        json!({
+            "kind": "native",
+            "program": self.program,
+        })
+    }
+}
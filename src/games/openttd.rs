@@ -14,27 +14,91 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use super::{flatpak, LaunchData};
+use super::LaunchData;
 
+use serde_json::Value;
+use std::fmt::{self, Display, Formatter};
 use std::process::Command;
+use std::sync::Arc;
+
+/// Number of regular companies in a game
+pub const MAX_COMPANIES: u8 = 15;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Company {
+    Spectator,
+    /// One-based company number as shown in game
+    Number(u8),
+}
+
+impl Display for Company {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Company::Spectator => write!(f, "Spectator"),
+            Company::Number(v) => write!(f, "Company {}", v),
+        }
+    }
+}
+
+impl Company {
+    fn arg(self) -> u8 {
+        match self {
+            Company::Spectator => 255,
+            Company::Number(v) => v,
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct Launcher {
-    pub flatpak_launcher: flatpak::Launcher,
+    /// Starts the game, before the join arguments
+    pub base_launcher: Arc<dyn super::Launcher>,
 }
 
 impl super::Launcher for Launcher {
     fn launch_cmd(&self, data: &LaunchData) -> Option<Command> {
-        self.flatpak_launcher.launch_cmd(data).map(|mut cmd| {
+        self.base_launcher.launch_cmd(data).map(|mut cmd| {
             cmd.arg("-n");
-            cmd.arg(&data.addr);
+            match data.company {
+                Some(company) => cmd.arg(format!("{}#{}", data.addr, company.arg())),
+                None => cmd.arg(&data.addr),
+            };
 
+            // Server password
             if let Some(pass) = data.password.as_ref() {
                 cmd.arg("-p");
                 cmd.arg(pass);
             }
 
+            if let Some(pass) = data.company_password.as_ref() {
+                cmd.arg("-P");
+                cmd.arg(pass);
+            }
+
+            cmd.args(&data.extra_args);
+
             cmd
         })
     }
+
+    fn launch_confirmation(&self, data: &LaunchData) -> Option<String> {
+        self.base_launcher.launch_confirmation(data)
+    }
+
+    /// Server list section of `openttd.cfg`.
+    fn favorites_config(&self, addrs: &[String]) -> Option<String> {
+        Some(
+            std::iter::once("[servers]\n".to_string())
+                .chain(addrs.iter().map(|addr| format!("{} = \n", addr)))
+                .collect(),
+        )
+    }
+
+    fn is_available(&self) -> bool {
+        self.base_launcher.is_available()
+    }
+
+    fn describe(&self) -> Value {
+        self.base_launcher.describe()
+    }
 }
@@ -0,0 +1,66 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use futures01::{future, prelude::*};
+use rgs::ping::Pinger;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Reuses recent latency measurements instead of pinging the same address on every refresh.
+///
+/// Only successful measurements are cached, unreachable hosts are pinged again next time.
+pub struct CachingPinger {
+    inner: Arc<dyn Pinger>,
+    ttl: Duration,
+    cache: Arc<Mutex<HashMap<IpAddr, (Instant, Duration)>>>,
+}
+
+impl CachingPinger {
+    pub fn new(inner: Arc<dyn Pinger>, ttl: Duration) -> Self {
+        Self {
+            inner,
+            ttl,
+            cache: Default::default(),
+        }
+    }
+}
+
+impl Pinger for CachingPinger {
+    fn ping(
+        &self,
+        addr: IpAddr,
+    ) -> Box<dyn Future<Item = Option<Duration>, Error = failure::Error> + Send> {
+        {
+            let mut cache = self.cache.lock().unwrap();
+            if let Some((measured, ping)) = cache.get(&addr).cloned() {
+                if measured.elapsed() < self.ttl {
+                    return Box::new(future::ok(Some(ping)));
+                }
+                cache.remove(&addr);
+            }
+        }
+
+        let cache = self.cache.clone();
+        Box::new(self.inner.ping(addr).map(move |ping| {
+            if let Some(ping) = ping {
+                cache.lock().unwrap().insert(addr, (Instant::now(), ping));
+            }
+            ping
+        }))
+    }
+}
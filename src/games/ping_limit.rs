@@ -0,0 +1,158 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use futures01::prelude::*;
+use rgs::ping::Pinger;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::timer::Timeout;
+
+use super::{master_cache, MasterAnswered, Querier, ServerInfo};
+
+/// Addresses whose last ping went over the limit
+pub type OverLimit = Arc<Mutex<HashSet<IpAddr>>>;
+
+/// Stops waiting for a ping reply once it exceeds the limit, so slow servers do not hold up large lists.
+///
+/// Such servers are reported without a ping and noted in `over_limit`, for `LimitedQuerier` to drop them.
+pub struct LimitedPinger {
+    inner: Arc<dyn Pinger>,
+    limit: Duration,
+    over_limit: OverLimit,
+}
+
+impl LimitedPinger {
+    pub fn new(inner: Arc<dyn Pinger>, limit: Duration) -> Self {
+        Self {
+            inner,
+            limit,
+            over_limit: Default::default(),
+        }
+    }
+
+    pub fn over_limit(&self) -> OverLimit {
+        self.over_limit.clone()
+    }
+}
+
+impl Pinger for LimitedPinger {
+    fn ping(
+        &self,
+        addr: IpAddr,
+    ) -> Box<dyn Future<Item = Option<Duration>, Error = failure::Error> + Send> {
+        let over_limit = self.over_limit.clone();
+
+        Box::new(
+            Timeout::new(self.inner.ping(addr), self.limit).then(move |res| match res {
+                Ok(ping) => {
+                    over_limit.lock().unwrap().remove(&addr);
+                    Ok(ping)
+                }
+                Err(ref e) if e.is_elapsed() => {
+                    over_limit.lock().unwrap().insert(addr);
+                    Ok(None)
+                }
+                Err(e) => Err(e
+                    .into_inner()
+                    .unwrap_or_else(|| failure::err_msg("Ping timer failed"))),
+            }),
+        )
+    }
+}
+
+/// Leaves out of the server list the servers that `LimitedPinger` gave up on. Servers asked for by address
+/// are still queried.
+pub struct LimitedQuerier {
+    pub inner: Arc<dyn Querier>,
+    pub over_limit: OverLimit,
+}
+
+impl Querier for LimitedQuerier {
+    fn query(&self) -> Box<dyn Stream<Item = rgs::models::Server, Error = failure::Error> + Send> {
+        let over_limit = self.over_limit.clone();
+        Box::new(
+            self.inner
+                .query()
+                .filter(move |srv| !over_limit.lock().unwrap().contains(&srv.addr.ip())),
+        )
+    }
+
+    fn master_count(&self) -> usize {
+        self.inner.master_count()
+    }
+
+    fn query_reporting(
+        &self,
+        master_answered: MasterAnswered,
+    ) -> Box<dyn Stream<Item = rgs::models::Server, Error = failure::Error> + Send> {
+        let over_limit = self.over_limit.clone();
+        Box::new(
+            self.inner
+                .query_reporting(master_answered)
+                .filter(move |srv| !over_limit.lock().unwrap().contains(&srv.addr.ip())),
+        )
+    }
+
+    fn can_scan_lan(&self) -> bool {
+        self.inner.can_scan_lan()
+    }
+
+    fn scan_lan(
+        &self,
+    ) -> Box<dyn Stream<Item = rgs::models::Server, Error = failure::Error> + Send> {
+        self.inner.scan_lan()
+    }
+
+    fn can_query_server(&self) -> bool {
+        self.inner.can_query_server()
+    }
+
+    fn query_server(
+        &self,
+        addr: SocketAddr,
+    ) -> Box<dyn Future<Item = rgs::models::Server, Error = failure::Error> + Send> {
+        self.inner.query_server(addr)
+    }
+
+    fn can_query_info(&self) -> bool {
+        self.inner.can_query_info()
+    }
+
+    fn query_info(
+        &self,
+        addr: SocketAddr,
+    ) -> Box<dyn Future<Item = ServerInfo, Error = failure::Error> + Send> {
+        self.inner.query_info(addr)
+    }
+
+    fn recognizes(&self, info: &ServerInfo) -> bool {
+        self.inner.recognizes(info)
+    }
+
+    fn master_status(&self) -> Vec<master_cache::MasterStatus> {
+        self.inner.master_status()
+    }
+
+    fn describe(&self) -> Value {
+        json!({
+            "kind": "ping_limit",
+            "querier": self.inner.describe(),
+        })
+    }
+}
@@ -17,7 +17,48 @@
 use super::LaunchData;
 
 use regex::Regex;
+use serde_json::Value;
 use std::process::Command;
+use std::sync::Arc;
+
+/// Reads a numeric or boolean rule as a flag.
+pub fn rule_flag(srv: &rgs::models::Server, name: &str) -> Option<bool> {
+    match srv.rules.get(name)? {
+        Value::Bool(v) => Some(*v),
+        Value::Number(v) => v.as_i64().map(|v| v != 0),
+        Value::String(v) => v.trim().parse::<i64>().ok().map(|v| v != 0),
+        _ => None,
+    }
+}
+
+/// Whether a client with only `base_mods` installed can join.
+///
+/// Servers running the base game are always joinable. Servers running anything else are joinable
+/// only if they let clients download the missing paks (`sv_allowDownload`). With `pure_downloads`, as in
+/// ioquake3-based games, clients only learn which paks to download from pure servers, so `sv_pure 0` rules
+/// the downloads out. Servers not reporting `sv_pure` are taken for pure, the engine default.
+pub fn stock_client_compatible(
+    base_mods: &[&str],
+    pure_downloads: bool,
+    srv: &rgs::models::Server,
+) -> bool {
+    // Servers running the base game often do not report the mod at all
+    let game_mod = srv
+        .mod_name
+        .as_ref()
+        .map(|v| v.to_lowercase())
+        .unwrap_or_default();
+
+    if game_mod.is_empty() || base_mods.contains(&game_mod.as_str()) {
+        return true;
+    }
+
+    if pure_downloads && !rule_flag(srv, "sv_pure").unwrap_or(true) {
+        return false;
+    }
+
+    rule_flag(srv, "sv_allowDownload").unwrap_or(false)
+}
 
 /// Scrubs color codes off the server names
 #[derive(Clone)]
@@ -37,16 +78,23 @@ impl super::NameMorpher for NameMorpher {
     fn morph(&self, v: String) -> String {
         self.scrubbing_pattern.replace_all(&v, "").into_owned()
     }
+
+    fn describe(&self) -> Value {
+        "color-codes".into()
+    }
 }
 
 #[derive(Clone)]
 pub struct Launcher {
-    pub flatpak_launcher: super::flatpak::Launcher,
+    /// Starts the engine, the connect commands are appended to its command line
+    pub base_launcher: Arc<dyn super::Launcher>,
+    /// Cvar the engine keeps favorite servers in, if any
+    pub favorites_cvar: Option<&'static str>,
 }
 
 impl super::Launcher for Launcher {
     fn launch_cmd(&self, data: &LaunchData) -> Option<Command> {
-        self.flatpak_launcher.launch_cmd(data).map(|mut cmd| {
+        self.base_launcher.launch_cmd(data).map(|mut cmd| {
             cmd.arg("+connect");
             cmd.arg(&data.addr);
 
@@ -55,7 +103,42 @@ impl super::Launcher for Launcher {
                 cmd.arg(password);
             }
 
+            if let Some(player_name) = data.player_name.as_ref() {
+                cmd.arg("+name");
+                cmd.arg(player_name);
+            }
+
+            cmd.args(&data.extra_args);
+
             cmd
         })
     }
+
+    fn launch_confirmation(&self, data: &LaunchData) -> Option<String> {
+        self.base_launcher.launch_confirmation(data)
+    }
+
+    fn exits_on_rejection(&self) -> bool {
+        true
+    }
+
+    /// Sets the favorites cvar, or defines `vstr`-able connect commands for engines without one.
+    fn favorites_config(&self, addrs: &[String]) -> Option<String> {
+        Some(match self.favorites_cvar {
+            Some(cvar) => format!("seta {} \"{}\"\n", cvar, addrs.join(" ")),
+            None => addrs
+                .iter()
+                .enumerate()
+                .map(|(i, addr)| format!("set favorite{} \"connect {}\"\n", i + 1, addr))
+                .collect(),
+        })
+    }
+
+    fn is_available(&self) -> bool {
+        self.base_launcher.is_available()
+    }
+
+    fn describe(&self) -> Value {
+        self.base_launcher.describe()
+    }
 }
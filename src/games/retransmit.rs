@@ -0,0 +1,99 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Resending of UDP queries whose request or reply got lost.
+
+use futures01::{future, prelude::*, stream};
+use std::time::Duration;
+use tokio::timer::Timeout;
+
+/// How often and how soon a query without a reply is sent again
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Retransmit {
+    /// Sends after the first one, 0 to give up after a single window
+    pub retries: u32,
+    /// Time to wait for a reply to each send
+    pub window: Duration,
+}
+
+impl Default for Retransmit {
+    fn default() -> Self {
+        Self {
+            retries: 2,
+            window: Duration::from_millis(1500),
+        }
+    }
+}
+
+/// Runs the query built by `attempt`, starting it over while no reply arrives within the window.
+///
+/// Errors of an attempt other than running out of time are returned right away, as resending would not
+/// change them.
+pub fn retransmit<F, R>(
+    policy: Retransmit,
+    attempt: F,
+) -> impl Future<Item = R::Item, Error = failure::Error> + Send
+where
+    F: Fn() -> R + Send + 'static,
+    R: Future<Error = failure::Error> + Send + 'static,
+    R::Item: Send + 'static,
+{
+    future::loop_fn(0, move |sent| {
+        Timeout::new(attempt(), policy.window).then(move |res| match res {
+            Ok(v) => Ok(future::Loop::Break(v)),
+            Err(e) => match e.into_inner() {
+                Some(e) => Err(e),
+                None if sent < policy.retries => Ok(future::Loop::Continue(sent + 1)),
+                None => Err(failure::err_msg("Server did not reply in time")),
+            },
+        })
+    })
+}
+
+/// Runs the stream built by `attempt`, starting it over while its first item does not arrive within the
+/// window.
+///
+/// Meant for master queries, whose servers only start to arrive once a master replied. Later items are not
+/// waited for, as the server list has arrived by then, and servers that do not answer are not asked again.
+/// The last attempt is left to the caller's timeout, as a master may list no servers at all.
+pub fn retransmit_stream<F, S>(
+    policy: Retransmit,
+    attempt: F,
+) -> impl Stream<Item = S::Item, Error = failure::Error> + Send
+where
+    F: Fn() -> S + Send + 'static,
+    S: Stream<Error = failure::Error> + Send + 'static,
+    S::Item: Send + 'static,
+{
+    future::loop_fn(0, move |sent| {
+        let first = attempt().into_future().map_err(|(e, _)| e);
+        if sent >= policy.retries {
+            return future::Either::A(first.map(future::Loop::Break));
+        }
+
+        future::Either::B(
+            Timeout::new(first, policy.window).then(move |res| match res {
+                Ok(v) => Ok(future::Loop::Break(v)),
+                Err(e) => match e.into_inner() {
+                    Some(e) => Err(e),
+                    None => Ok(future::Loop::Continue(sent + 1)),
+                },
+            }),
+        )
+    })
+    .map(|(first, rest)| stream::iter_ok(first).chain(rest))
+    .flatten_stream()
+}
@@ -14,37 +14,321 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use futures01::prelude::*;
+use futures01::{future, prelude::*};
 use rgs::{dns::Resolver, models::TProtocol, ping::Pinger};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::ops::RangeInclusive;
 use std::sync::Arc;
-use tokio::net::UdpSocket;
+use std::time::Instant;
+use tokio::{net::UdpSocket, timer::Delay};
+
+use super::master_cache::{MasterCache, MasterStatus};
+use super::retransmit::{retransmit, retransmit_stream, Retransmit};
+use super::{info, lan, socks5, MasterAnswered, Querier as _, ServerInfo};
+
+/// Servers as queries stream them
+type Servers = Box<dyn Stream<Item = rgs::models::Server, Error = failure::Error> + Send>;
 
 #[derive(Clone)]
 pub struct Querier {
     pub protocol: TProtocol,
+    /// Protocol for querying a single server, `None` if not supported
+    pub server_protocol: Option<TProtocol>,
+    /// Protocol version reported in info replies, telling apart games that share the protocol.
+    /// `None` to accept any.
+    pub protocol_version: Option<u32>,
     pub master_servers: Vec<(String, u16)>,
+    /// Tells apart the server lists of games sharing a master in `master_cache`
+    pub master_tag: String,
+    /// Shared by the queriers of all games, so that their masters are spared
+    pub master_cache: Arc<MasterCache>,
+    /// SOCKS5 proxy that queries go through, see `socks5`
+    pub socks5_proxy: Option<String>,
+    /// Local address queries go out from instead of any, see `Settings::bind_address`
+    pub bind_addr: Option<IpAddr>,
+    /// Server ports scanned on the local network, `None` if not supported
+    pub lan_ports: Option<RangeInclusive<u16>>,
     pub port: u16,
+    /// Resending of queries without a reply. Masters are asked again while none of them has answered, see
+    /// `retransmit_stream`.
+    pub retransmit: Retransmit,
     pub resolver: Arc<dyn Resolver>,
     pub pinger: Arc<dyn Pinger>,
 }
 
 impl super::Querier for Querier {
     fn query(&self) -> Box<dyn Stream<Item = rgs::models::Server, Error = failure::Error> + Send> {
-        let mut query_builder = rgs::UdpQueryBuilder::default();
+        self.query_reporting(Arc::new(|| {}))
+    }
+
+    fn master_count(&self) -> usize {
+        self.master_servers.len()
+    }
+
+    /// Asks every master from a socket of its own, as replies do not tell which master listed a server.
+    /// Servers listed by several masters are queried by each of them, but reported once.
+    fn query_reporting(
+        &self,
+        master_answered: MasterAnswered,
+    ) -> Box<dyn Stream<Item = rgs::models::Server, Error = failure::Error> + Send> {
+        let masters = self.master_addrs();
+        let now = Instant::now();
+
+        // Listed servers are queried directly while the masters' lists are fresh
+        if let Some(server_protocol) = self.server_protocol.clone() {
+            if let Some(servers) = self.master_cache.get(&masters, &self.master_tag, now) {
+                for _ in &masters {
+                    master_answered();
+                }
+                return self.send_queries(
+                    self.port,
+                    servers
+                        .into_iter()
+                        .map(|addr| rgs::models::UserQuery {
+                            protocol: server_protocol.clone(),
+                            host: (addr.ip().to_string(), addr.port()).into(),
+                        })
+                        .collect(),
+                );
+            }
+        }
+
+        let requested = self.master_cache.reserve(&masters, now);
+        let querier = self.clone();
+        Box::new(
+            Delay::new(requested)
+                .map_err(failure::Error::from)
+                .map(move |_| {
+                    retransmit_stream(querier.retransmit, move || {
+                        let servers = querier.master_servers.iter().enumerate().fold(
+                            Box::new(futures01::stream::empty()) as Servers,
+                            |servers, (i, entry)| {
+                                // The game's port goes to the first master, the others get any
+                                let port = if i == 0 { querier.port } else { 0 };
+                                let listed =
+                                    querier.ask_master(port, entry, master_answered.clone());
+                                Box::new(servers.select(listed)) as Servers
+                            },
+                        );
+
+                        let querier = querier.clone();
+                        let masters = masters.clone();
+                        let mut seen = HashSet::new();
+                        servers
+                            .filter(move |srv| seen.insert(srv.addr))
+                            .map(move |srv| {
+                                querier.master_cache.record(
+                                    &masters,
+                                    &querier.master_tag,
+                                    requested,
+                                    srv.addr,
+                                );
+                                srv
+                            })
+                    })
+                })
+                .flatten_stream(),
+        )
+    }
+
+    fn master_status(&self) -> Vec<MasterStatus> {
+        let masters = self.master_addrs();
+        self.master_cache
+            .status(Instant::now())
+            .into_iter()
+            .filter(|status| status.tag == self.master_tag && masters.contains(&status.master))
+            .collect()
+    }
+
+    fn can_scan_lan(&self) -> bool {
+        // Broadcasts stay on the local network
+        self.server_protocol.is_some() && self.lan_ports.is_some() && self.socks5_proxy.is_none()
+    }
+
+    /// Finds servers by broadcasting an info request, then queries them one by one like `query_server`.
+    fn scan_lan(
+        &self,
+    ) -> Box<dyn Stream<Item = rgs::models::Server, Error = failure::Error> + Send> {
+        let ports = match self.lan_ports.clone() {
+            Some(v) if self.can_scan_lan() => v,
+            _ => {
+                return Box::new(futures01::stream::empty());
+            }
+        };
+
+        let querier = self.clone();
+        Box::new(
+            lan::discover(lan::Q3_PROBE, ports)
+                .map(move |addr| querier.query_server(addr).then(|res| Ok(res.ok())))
+                .buffer_unordered(lan::MAX_CONCURRENT_QUERIES)
+                .filter_map(|srv| srv),
+        )
+    }
+
+    fn can_query_server(&self) -> bool {
+        self.server_protocol.is_some()
+    }
 
-        query_builder = query_builder.with_pinger(self.pinger.clone());
+    fn query_server(
+        &self,
+        addr: SocketAddr,
+    ) -> Box<dyn Future<Item = rgs::models::Server, Error = failure::Error> + Send> {
+        let protocol = match self.server_protocol.clone() {
+            Some(v) => v,
+            None => {
+                return Box::new(futures01::future::err(failure::err_msg(
+                    "Querying single servers is not supported",
+                )));
+            }
+        };
+        let querier = self.clone();
+        Box::new(retransmit(self.retransmit, move || {
+            // Any port, so that a refresh running on the fixed one is not disturbed
+            querier
+                .send_queries(
+                    0,
+                    vec![rgs::models::UserQuery {
+                        protocol: protocol.clone(),
+                        host: (addr.ip().to_string(), addr.port()).into(),
+                    }],
+                )
+                .into_future()
+                .map_err(|(e, _)| e)
+                .and_then(|(srv, _)| srv.ok_or_else(|| failure::err_msg("Server did not reply")))
+        }))
+    }
+
+    fn can_query_info(&self) -> bool {
+        self.server_protocol.is_some()
+    }
+
+    /// Single servers speak the Quake III protocol, whose info request is answered without rules and players.
+    fn query_info(
+        &self,
+        addr: SocketAddr,
+    ) -> Box<dyn Future<Item = ServerInfo, Error = failure::Error> + Send> {
+        if self.server_protocol.is_none() {
+            return Box::new(futures01::future::err(failure::err_msg(
+                "Querying single servers is not supported",
+            )));
+        }
+
+        Box::new(info::query_q3(
+            addr,
+            self.retransmit,
+            self.socks5_proxy.clone(),
+            self.bind_addr,
+        ))
+    }
+
+    fn recognizes(&self, info: &ServerInfo) -> bool {
+        self.protocol_version
+            .map(|version| info.protocol == Some(version))
+            .unwrap_or(true)
+    }
+
+    fn describe(&self) -> Value {
+        json!({
+            "kind": "udp",
+            "masters": self
+                .master_servers
+                .iter()
+                .map(|(host, port)| format!("{}:{}", host, port))
+                .collect::<Vec<_>>(),
+            "protocol_version": self.protocol_version,
+            "single_server_queries": self.server_protocol.is_some(),
+            "lan_ports": self
+                .lan_ports
+                .as_ref()
+                .map(|ports| format!("{}-{}", ports.start(), ports.end())),
+            "port": self.port,
+            "socks5_proxy": self.socks5_proxy,
+            "bind_addr": self.bind_addr,
+            "master_cache_ttl_s": self.master_cache.ttl().as_secs(),
+            "master_request_interval_ms": self.master_cache.min_interval().as_millis() as u64,
+            "retries": self.retransmit.retries,
+            "retry_window_ms": self.retransmit.window.as_millis() as u64,
+        })
+    }
+}
+
+impl Querier {
+    /// Masters as `host:port`, the way `master_cache` knows them
+    fn master_addrs(&self) -> Vec<String> {
+        self.master_servers
+            .iter()
+            .map(|(host, port)| format!("{}:{}", host, port))
+            .collect()
+    }
+
+    /// Asks the master at `entry` for its server list, calling `master_answered` with the first server listed.
+    fn ask_master(
+        &self,
+        port: u16,
+        entry: &(String, u16),
+        master_answered: MasterAnswered,
+    ) -> Servers {
+        let mut answered = false;
+        Box::new(
+            self.send_queries(
+                port,
+                vec![rgs::models::UserQuery {
+                    protocol: self.protocol.clone(),
+                    host: entry.clone().into(),
+                }],
+            )
+            .inspect(move |_| {
+                if !answered {
+                    answered = true;
+                    master_answered();
+                }
+            }),
+        )
+    }
+
+    /// Sends `queries` from a socket bound to `port`, or through the SOCKS5 proxy if set, yielding the
+    /// servers that reply.
+    fn send_queries(&self, port: u16, queries: Vec<rgs::models::UserQuery>) -> Servers {
+        let query_builder = rgs::UdpQueryBuilder::default().with_pinger(self.pinger.clone());
 
-        let socket = UdpSocket::bind(&format!("[::]:{}", self.port).parse().unwrap()).unwrap();
-        let mut q = query_builder.build(socket);
+        if let Some(proxy) = self.socks5_proxy.as_ref() {
+            return Box::new(
+                socks5::associate(proxy)
+                    .map(move |relay| {
+                        let mut q = query_builder.build(relay.into_socket());
+                        for query in queries {
+                            q.start_send(query).unwrap();
+                        }
 
-        for entry in &self.master_servers {
-            q.start_send(rgs::models::UserQuery {
-                protocol: self.protocol.clone(),
-                host: entry.clone().into(),
-            })
-            .unwrap();
+                        q.map(|e| e.data)
+                    })
+                    .flatten_stream(),
+            );
         }
 
-        Box::new(q.map(|e| e.data))
+        Box::new(
+            future::result(UdpSocket::bind(&self.local_addr(port)))
+                .map_err(failure::Error::from)
+                .map(move |socket| {
+                    let mut q = query_builder.build(socket);
+                    for query in queries {
+                        q.start_send(query).unwrap();
+                    }
+
+                    q.map(|e| e.data)
+                })
+                .flatten_stream(),
+        )
+    }
+
+    /// Address to bind the socket for `port` to, any unless set otherwise
+    fn local_addr(&self, port: u16) -> SocketAddr {
+        SocketAddr::new(
+            self.bind_addr
+                .unwrap_or_else(|| Ipv6Addr::UNSPECIFIED.into()),
+            port,
+        )
     }
 }
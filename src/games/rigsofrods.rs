@@ -18,16 +18,21 @@ use failure::Error;
 use futures::{compat::*, prelude::*};
 use futures01::{Poll, Stream};
 use gen_stream::*;
-use log::error;
-use reqwest::r#async::Client as HttpClient;
-use rgs::{
-    dns::Resolver,
-    models::{Host, Server, StringAddr},
-    ping::Pinger,
-};
+use log::{error, warn};
+use reqwest::{r#async::Client as HttpClient, Url};
+use rgs::{models::Server, ping::Pinger};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use std::{fmt::Display, sync::Arc};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::process::Command;
+use std::sync::Arc;
+use tokio_dns::{CpuPoolResolver, Resolver};
+
+use super::LaunchData;
+
+/// Default master server address
+pub const MASTER_ADDR: &str = "http://multiplayer.rigsofrods.org/server-list";
 
 #[derive(Serialize, Deserialize)]
 struct ServerEntry {
@@ -52,52 +57,90 @@ struct Query {
 }
 
 impl Query {
-    pub fn new<S>(master_addr: S, dns: Arc<dyn Resolver>, pinger: Arc<dyn Pinger>) -> Self
-    where
-        S: Display + Send + 'static,
-    {
+    pub fn new(
+        master_addr: String,
+        client: HttpClient,
+        dns: Arc<CpuPoolResolver>,
+        pinger: Arc<dyn Pinger>,
+    ) -> Self {
         use std::task::Poll;
 
         Self {
             inner: Box::new(
                 Box::pin(GenTryStream::from(static move || {
-                    let mut rsp = gen_await!(HttpClient::new()
-                        .get(&format!("{}?json=true", master_addr))
-                        .send()
-                        .compat())?;
+                    // Master address may already contain query parameters
+                    let mut url = Url::parse(&master_addr)?;
+                    url.query_pairs_mut().append_pair("json", "true");
+
+                    let mut rsp = gen_await!(client.get(url).send().compat())?;
 
                     let data = gen_await!(rsp.json::<Vec<ServerEntry>>().compat())?;
 
                     for entry in data {
-                        if let Ok(addr) = gen_await!(dns
-                            .resolve(Host::S(StringAddr {
-                                host: entry.ip,
-                                port: entry.port
-                            }))
-                            .compat())
-                        {
-                            let ping =
-                                gen_await!(pinger.ping(addr.ip()).compat()).unwrap_or_else(|e| {
-                                    error!("Failed to ping {}: {}", addr, e);
-                                    None
-                                });
-
-                            yield Poll::Ready(Server {
-                                ping,
-                                name: Some(entry.name),
-                                map: Some(entry.terrain_name),
-                                num_clients: Some(u64::from(entry.current_users)),
-                                max_clients: Some(u64::from(entry.max_clients)),
-                                rules: vec![
-                                    ("is_official", Value::from(entry.is_official)),
-                                    ("verified", Value::from(entry.verified)),
-                                ]
-                                .into_iter()
-                                .map(|(k, v)| (k.to_string(), v))
-                                .collect(),
-                                ..Server::new(addr)
-                            });
+                        // The master lists either IP addresses or host names
+                        let (mut addrs, host) = match entry.ip.parse::<IpAddr>() {
+                            Ok(ip) => (vec![ip], None),
+                            Err(_) => match gen_await!(dns.resolve(&entry.ip).compat()) {
+                                Ok(addrs) => (addrs, Some(entry.ip.clone())),
+                                Err(e) => {
+                                    error!("Failed to resolve {}: {}", entry.ip, e);
+                                    continue;
+                                }
+                            },
+                        };
+
+                        // Resolution order is arbitrary, sort to pick deterministically
+                        addrs.sort();
+                        addrs.dedup();
+
+                        if addrs.len() > 1 {
+                            warn!(
+                                "{} resolves to multiple addresses {:?}, they will be listed as one server",
+                                entry.ip, addrs
+                            );
+                        }
+
+                        // Prefer the first address that answers pings
+                        let mut chosen = None;
+                        for ip in &addrs {
+                            match gen_await!(pinger.ping(*ip).compat()) {
+                                Ok(Some(ping)) => {
+                                    chosen = Some((*ip, Some(ping)));
+                                    break;
+                                }
+                                Ok(None) => {}
+                                Err(e) => error!("Failed to ping {}: {}", ip, e),
+                            }
                         }
+
+                        let (ip, ping) = match chosen.or_else(|| addrs.first().map(|ip| (*ip, None))) {
+                            Some(v) => v,
+                            None => {
+                                continue;
+                            }
+                        };
+
+                        let mut rules = vec![
+                            ("is_official", Value::from(entry.is_official)),
+                            ("verified", Value::from(entry.verified)),
+                        ]
+                        .into_iter()
+                        .map(|(k, v)| (k.to_string(), v))
+                        .collect::<HashMap<_, _>>();
+                        if let Some(host) = host {
+                            rules.insert(super::HOST_RULE.to_string(), Value::from(host));
+                        }
+
+                        yield Poll::Ready(Server {
+                            ping,
+                            name: Some(entry.name),
+                            map: Some(entry.terrain_name),
+                            need_pass: Some(entry.has_password),
+                            num_clients: Some(u64::from(entry.current_users)),
+                            max_clients: Some(u64::from(entry.max_clients)),
+                            rules: rules.into_iter().collect(),
+                            ..Server::new(SocketAddr::new(ip, entry.port))
+                        });
                     }
 
                     Ok(())
@@ -120,7 +163,9 @@ impl Stream for Query {
 #[derive(Clone)]
 pub struct Querier {
     pub master_addr: String,
-    pub resolver: Arc<dyn Resolver>,
+    /// Shared client, see `http::client`
+    pub client: HttpClient,
+    pub resolver: Arc<CpuPoolResolver>,
     pub pinger: Arc<dyn Pinger>,
 }
 
@@ -128,8 +173,53 @@ impl super::Querier for Querier {
     fn query(&self) -> Box<dyn Stream<Item = rgs::models::Server, Error = failure::Error> + Send> {
         Box::new(Query::new(
             self.master_addr.clone(),
+            self.client.clone(),
             self.resolver.clone(),
             self.pinger.clone(),
         ))
     }
+
+    fn describe(&self) -> Value {
+        json!({
+            "kind": "http",
+            "masters": [self.master_addr],
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct Launcher {
+    /// Flatpak, native or Steam launcher running the game
+    pub base_launcher: Arc<dyn super::Launcher>,
+}
+
+impl super::Launcher for Launcher {
+    /// Joins with `-joinserver host:port`, passing the server password with `-password`.
+    fn launch_cmd(&self, data: &LaunchData) -> Option<Command> {
+        self.base_launcher.launch_cmd(data).map(|mut cmd| {
+            cmd.arg("-joinserver");
+            cmd.arg(&data.addr);
+
+            if let Some(pass) = data.password.as_ref() {
+                cmd.arg("-password");
+                cmd.arg(pass);
+            }
+
+            cmd.args(&data.extra_args);
+
+            cmd
+        })
+    }
+
+    fn launch_confirmation(&self, data: &LaunchData) -> Option<String> {
+        self.base_launcher.launch_confirmation(data)
+    }
+
+    fn is_available(&self) -> bool {
+        self.base_launcher.is_available()
+    }
+
+    fn describe(&self) -> Value {
+        self.base_launcher.describe()
+    }
 }
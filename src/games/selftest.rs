@@ -0,0 +1,184 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Checks whether query replies make it through the firewall, for users who get no servers at all.
+
+use futures01::{future, prelude::*};
+use rgs::{dns::Resolver, ping::DummyPinger};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use std::time::{Duration, Instant};
+use tokio::timer::Timeout;
+
+use super::master_cache::MasterCache;
+use super::retransmit::Retransmit;
+use super::Querier as _;
+
+/// Known responsive master, queried with the Quake III protocol
+pub const PROBE_MASTER: (&str, u16) = ("dpmaster.deathmask.net", 27950);
+/// Fixed source port like the game queries use, just below theirs so the test can run during a refresh
+pub const PROBE_PORT: u16 = 5599;
+/// How long each stage of the test waits for replies
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+const MASTER_REQUEST: &[u8] = b"\xff\xff\xff\xffgetservers 68 empty full";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Works,
+    /// Nothing arrived, not even from the master
+    Blocked,
+    /// The master replied but game servers did not
+    Partial,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SelfTest {
+    /// Packets received from the master
+    pub master_packets: usize,
+    /// Servers that answered a query
+    pub servers: usize,
+}
+
+impl SelfTest {
+    pub fn verdict(&self) -> Verdict {
+        if self.master_packets == 0 {
+            Verdict::Blocked
+        } else if self.servers == 0 {
+            Verdict::Partial
+        } else {
+            Verdict::Works
+        }
+    }
+
+    /// What the user should do about the result.
+    pub fn advice(&self) -> String {
+        let counts = format!(
+            "{} packets from the master server, {} game servers replied.",
+            self.master_packets, self.servers
+        );
+
+        match self.verdict() {
+            Verdict::Works => format!("Server queries work. {}", counts),
+            Verdict::Blocked => format!(
+                "No replies arrived. {} Check that the firewall allows incoming UDP on ports {} and up, or that the network allows outgoing UDP at all.",
+                counts, PROBE_PORT
+            ),
+            Verdict::Partial => format!(
+                "The master server replied but game servers did not. {} The firewall or router most likely drops replies from hosts it has not seen yet. Allow incoming UDP on ports {} and up, or forward them to this computer.",
+                counts, PROBE_PORT
+            ),
+        }
+    }
+}
+
+/// Requests the server list from the probe port and counts reply packets.
+fn probe_master() -> Result<usize, failure::Error> {
+    let master = PROBE_MASTER
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| failure::err_msg("Master server address did not resolve"))?;
+    let local: SocketAddr = if master.is_ipv4() {
+        ([0, 0, 0, 0], PROBE_PORT).into()
+    } else {
+        ([0u16; 8], PROBE_PORT).into()
+    };
+
+    let socket = UdpSocket::bind(local)?;
+    socket.send_to(MASTER_REQUEST, master)?;
+
+    let started = Instant::now();
+    let mut packets = 0;
+    let mut buf = [0; 2048];
+    while let Some(remaining) = PROBE_TIMEOUT.checked_sub(started.elapsed()) {
+        if remaining == Duration::from_secs(0) {
+            break;
+        }
+        socket.set_read_timeout(Some(remaining))?;
+        match socket.recv_from(&mut buf) {
+            Ok((_, from)) if from == master => packets += 1,
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+
+    Ok(packets)
+}
+
+/// Queries the probe master like a refresh would and counts the servers that reply.
+fn query_servers(resolver: Arc<dyn Resolver>) -> impl Future<Item = usize, Error = failure::Error> {
+    let querier = super::rgs_support::Querier {
+        protocol: rgs::protocols::make_default_protocols()["q3m"].clone(),
+        server_protocol: None,
+        protocol_version: None,
+        master_servers: vec![(PROBE_MASTER.0.to_string(), PROBE_MASTER.1)],
+        // The test always asks the master
+        master_tag: "selftest".into(),
+        master_cache: Arc::new(MasterCache::new(
+            Duration::from_secs(0),
+            Duration::from_secs(0),
+        )),
+        lan_ports: None,
+        socks5_proxy: None,
+        bind_addr: None,
+        port: PROBE_PORT,
+        retransmit: Retransmit::default(),
+        resolver,
+        pinger: Arc::new(DummyPinger),
+    };
+
+    let servers = Arc::new(AtomicUsize::new(0));
+    // Deferred so that the socket is bound within the runtime
+    future::lazy({
+        let servers = servers.clone();
+        move || {
+            Timeout::new(
+                querier
+                    .query()
+                    .then(|res| Ok::<_, failure::Error>(res.is_ok()))
+                    .for_each(move |replied| {
+                        if replied {
+                            servers.fetch_add(1, Ordering::SeqCst);
+                        }
+                        Ok(())
+                    }),
+                PROBE_TIMEOUT,
+            )
+        }
+    })
+    .then(move |res| match res {
+        Ok(()) => Ok(servers.load(Ordering::SeqCst)),
+        Err(ref e) if e.is_elapsed() => Ok(servers.load(Ordering::SeqCst)),
+        Err(e) => Err(e
+            .into_inner()
+            .unwrap_or_else(|| failure::err_msg("Self-test timer failed"))),
+    })
+}
+
+/// Runs the self-test. Blocks for up to twice `PROBE_TIMEOUT`, so call it off the UI thread.
+pub fn run() -> Result<SelfTest, failure::Error> {
+    let master_packets = probe_master()?;
+    let resolver = Arc::new(tokio_dns::CpuPoolResolver::new(1)) as Arc<dyn Resolver>;
+    let servers = tokio::runtime::current_thread::block_on_all(query_servers(resolver))?;
+
+    Ok(SelfTest {
+        master_packets,
+        servers,
+    })
+}
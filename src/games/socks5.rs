@@ -0,0 +1,308 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Relaying of UDP queries through a SOCKS5 proxy (RFC 1928), for networks that let traffic out only
+//! through one. The proxy has to support `UDP ASSOCIATE`, authentication is not supported.
+
+use futures01::{future, prelude::*, try_ready, AsyncSink, StartSend};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs};
+use tokio::net::{TcpStream, UdpSocket};
+
+const VERSION: u8 = 5;
+const NO_AUTHENTICATION: u8 = 0;
+const UDP_ASSOCIATE: u8 = 3;
+const ATYP_IPV4: u8 = 1;
+const ATYP_IPV6: u8 = 4;
+const REP_SUCCEEDED: u8 = 0;
+const REP_COMMAND_NOT_SUPPORTED: u8 = 7;
+
+fn write_addr(out: &mut Vec<u8>, addr: SocketAddr) {
+    match addr.ip() {
+        IpAddr::V4(ip) => {
+            out.push(ATYP_IPV4);
+            out.extend_from_slice(&ip.octets());
+        }
+        IpAddr::V6(ip) => {
+            out.push(ATYP_IPV6);
+            out.extend_from_slice(&ip.octets());
+        }
+    }
+    out.extend_from_slice(&addr.port().to_be_bytes());
+}
+
+/// Reads an address written by `write_addr`, with the number of bytes it takes. Domain names are not
+/// supported.
+fn read_addr(data: &[u8]) -> Option<(SocketAddr, usize)> {
+    let (ip, len) = match *data.get(0)? {
+        ATYP_IPV4 => {
+            let mut octets = [0; 4];
+            octets.copy_from_slice(data.get(1..5)?);
+            (IpAddr::from(octets), 5)
+        }
+        ATYP_IPV6 => {
+            let mut octets = [0; 16];
+            octets.copy_from_slice(data.get(1..17)?);
+            (IpAddr::from(octets), 17)
+        }
+        _ => {
+            return None;
+        }
+    };
+    let port = data.get(len..len + 2)?;
+
+    Some((
+        SocketAddr::new(ip, u16::from_be_bytes([port[0], port[1]])),
+        len + 2,
+    ))
+}
+
+/// Wraps a datagram to `dest` for the relay.
+pub fn encapsulate(dest: SocketAddr, data: &[u8]) -> Vec<u8> {
+    // Reserved bytes, then fragment number, unused
+    let mut out = vec![0, 0, 0];
+    write_addr(&mut out, dest);
+    out.extend_from_slice(data);
+    out
+}
+
+/// Unwraps a datagram from the relay into its source and payload, `None` if malformed or fragmented.
+pub fn decapsulate(data: &[u8]) -> Option<(SocketAddr, &[u8])> {
+    if data.get(..3)? != [0, 0, 0] {
+        return None;
+    }
+    let (addr, len) = read_addr(&data[3..])?;
+
+    Some((addr, &data[3 + len..]))
+}
+
+fn refusal(rep: u8) -> failure::Error {
+    match rep {
+        REP_COMMAND_NOT_SUPPORTED => failure::err_msg("The SOCKS5 proxy does not support UDP"),
+        v => failure::format_err!("The SOCKS5 proxy refused to relay UDP, reply code {}", v),
+    }
+}
+
+/// UDP association with a SOCKS5 proxy, ended when dropped.
+pub struct UdpRelay {
+    /// The association lasts as long as this connection
+    _control: TcpStream,
+    socket: UdpSocket,
+    relay: SocketAddr,
+}
+
+impl UdpRelay {
+    /// Sends a datagram to `dest` through the relay.
+    pub fn send_to(
+        self,
+        data: &[u8],
+        dest: SocketAddr,
+    ) -> impl Future<Item = Self, Error = failure::Error> + Send {
+        let UdpRelay {
+            _control,
+            socket,
+            relay,
+        } = self;
+        socket
+            .send_dgram(encapsulate(dest, data), &relay)
+            .map_err(failure::Error::from)
+            .map(move |(socket, _)| UdpRelay {
+                _control,
+                socket,
+                relay,
+            })
+    }
+
+    /// Receives the next datagram relayed from a remote host, with its source.
+    pub fn recv_from(
+        self,
+    ) -> impl Future<Item = (Self, Vec<u8>, SocketAddr), Error = failure::Error> + Send {
+        future::loop_fn(self, |this| {
+            let UdpRelay {
+                _control,
+                socket,
+                relay,
+            } = this;
+            socket
+                .recv_dgram(vec![0; 65536])
+                .map_err(failure::Error::from)
+                .map(move |(socket, buf, len, from)| {
+                    let this = UdpRelay {
+                        _control,
+                        socket,
+                        relay,
+                    };
+                    // Datagrams not passing the relay are not ours
+                    let datagram = Some(&buf[..len])
+                        .filter(|_| from == relay)
+                        .and_then(decapsulate)
+                        .map(|(source, data)| (data.to_vec(), source));
+                    match datagram {
+                        Some((data, source)) => future::Loop::Break((this, data, source)),
+                        None => future::Loop::Continue(this),
+                    }
+                })
+        })
+    }
+
+    /// Turns the association into a socket for `rgs` queries.
+    pub fn into_socket(self) -> RelayedSocket {
+        RelayedSocket {
+            relay: self,
+            pending: None,
+            buf: vec![0; 65536],
+        }
+    }
+}
+
+/// Stream of datagrams relayed from remote hosts with their sources, and sink of datagrams to send to
+/// them, the way `UdpFramed` is for a plain socket.
+pub struct RelayedSocket {
+    relay: UdpRelay,
+    /// Encapsulated datagram waiting for the socket
+    pending: Option<Vec<u8>>,
+    buf: Vec<u8>,
+}
+
+impl Stream for RelayedSocket {
+    type Item = (Vec<u8>, SocketAddr);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            let (len, from) = try_ready!(self.relay.socket.poll_recv_from(&mut self.buf));
+            // Datagrams not passing the relay are not ours
+            if from != self.relay.relay {
+                continue;
+            }
+            if let Some((source, data)) = decapsulate(&self.buf[..len]) {
+                return Ok(Async::Ready(Some((data.to_vec(), source))));
+            }
+        }
+    }
+}
+
+impl Sink for RelayedSocket {
+    type SinkItem = (Vec<u8>, SocketAddr);
+    type SinkError = io::Error;
+
+    fn start_send(&mut self, item: Self::SinkItem) -> StartSend<Self::SinkItem, Self::SinkError> {
+        if self.pending.is_some() {
+            self.poll_complete()?;
+            if self.pending.is_some() {
+                return Ok(AsyncSink::NotReady(item));
+            }
+        }
+        let (data, dest) = item;
+        self.pending = Some(encapsulate(dest, &data));
+
+        Ok(AsyncSink::Ready)
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), Self::SinkError> {
+        if let Some(datagram) = self.pending.as_ref() {
+            try_ready!(self.relay.socket.poll_send_to(datagram, &self.relay.relay));
+            self.pending = None;
+        }
+
+        Ok(Async::Ready(()))
+    }
+}
+
+/// Sets up a UDP association with the proxy at `proxy`, given as `host:port`.
+pub fn associate(proxy: &str) -> impl Future<Item = UdpRelay, Error = failure::Error> + Send {
+    let proxy = proxy.to_string();
+
+    future::lazy(move || -> Result<SocketAddr, failure::Error> {
+        proxy
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| failure::format_err!("Failed to resolve the SOCKS5 proxy {}", proxy))
+    })
+    .and_then(|proxy| {
+        TcpStream::connect(&proxy)
+            .and_then(|control| tokio::io::write_all(control, [VERSION, 1, NO_AUTHENTICATION]))
+            .and_then(|(control, _)| tokio::io::read_exact(control, [0; 2]))
+            .map_err(failure::Error::from)
+            .and_then(move |(control, reply)| {
+                if reply != [VERSION, NO_AUTHENTICATION] {
+                    return Err(failure::err_msg(
+                        "The SOCKS5 proxy requires authentication, which is not supported",
+                    ));
+                }
+
+                Ok((control, proxy))
+            })
+    })
+    .and_then(|(control, proxy)| {
+        // Our address is not known before the first datagram, hence left unspecified
+        let mut request = vec![VERSION, UDP_ASSOCIATE, 0];
+        write_addr(
+            &mut request,
+            SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0),
+        );
+
+        tokio::io::write_all(control, request)
+            .and_then(|(control, _)| tokio::io::read_exact(control, [0; 4]))
+            .map_err(failure::Error::from)
+            .and_then(move |(control, header)| {
+                if header[0] != VERSION {
+                    return Err(failure::err_msg("The proxy does not speak SOCKS5"));
+                }
+                if header[1] != REP_SUCCEEDED {
+                    return Err(refusal(header[1]));
+                }
+                let len = match header[3] {
+                    ATYP_IPV4 => 4 + 2,
+                    ATYP_IPV6 => 16 + 2,
+                    _ => {
+                        return Err(failure::err_msg(
+                            "The SOCKS5 proxy named its relay by domain, which is not supported",
+                        ));
+                    }
+                };
+
+                Ok((control, header[3], len, proxy))
+            })
+    })
+    .and_then(|(control, atyp, len, proxy)| {
+        tokio::io::read_exact(control, vec![0; len])
+            .map_err(failure::Error::from)
+            .and_then(move |(control, rest)| {
+                let mut encoded = vec![atyp];
+                encoded.extend_from_slice(&rest);
+                let (mut relay, _) = read_addr(&encoded)
+                    .ok_or_else(|| failure::err_msg("Malformed SOCKS5 relay address"))?;
+                // Proxies commonly answer with an unspecified address, meaning their own
+                if relay.ip().is_unspecified() {
+                    relay.set_ip(proxy.ip());
+                }
+
+                let local = if relay.is_ipv4() {
+                    "0.0.0.0:0"
+                } else {
+                    "[::]:0"
+                };
+                let socket = UdpSocket::bind(&local.parse().unwrap())?;
+
+                Ok(UdpRelay {
+                    _control: control,
+                    socket,
+                    relay,
+                })
+            })
+    })
+}
@@ -0,0 +1,58 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use super::{native, Game, LaunchData};
+
+use serde_json::{json, Value};
+use std::process::Command;
+
+/// Steam application id of the game, `None` if it is not sold on Steam
+pub fn app_id(game: Game) -> Option<u32> {
+    match game {
+        Game::QuakeIII => Some(2200),
+        _ => None,
+    }
+}
+
+/// Starts the game through the Steam client, which passes on the arguments that follow.
+#[derive(Clone)]
+pub struct Launcher {
+    pub app_id: Option<u32>,
+}
+
+impl super::Launcher for Launcher {
+    fn launch_cmd(&self, _data: &LaunchData) -> Option<Command> {
+        self.app_id.map(|app_id| {
+            let mut cmd = Command::new("steam");
+
+            cmd.arg("-applaunch");
+            cmd.arg(app_id.to_string());
+
+            cmd
+        })
+    }
+
+    fn is_available(&self) -> bool {
+        self.app_id.is_some() && native::find_program("steam")
+    }
+
+    fn describe(&self) -> Value {
+        json!({
+            "kind": "steam",
+            "app_id": self.app_id,
+        })
+    }
+}
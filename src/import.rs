@@ -0,0 +1,115 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+
+use crate::games::Game;
+use crate::settings::Favorite;
+
+/// qstat / XQF server type codes
+const GAME_IDS: &[(&str, Game)] = &[
+    ("q3s", Game::QuakeIII),
+    ("oas", Game::OpenArena),
+    ("openarenas", Game::OpenArena),
+    ("xonotics", Game::Xonotic),
+    ("openttds", Game::OpenTTD),
+];
+
+pub fn map_game_id(id: &str) -> Option<Game> {
+    GAME_IDS
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(id))
+        .map(|(_, game)| *game)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImportEntry {
+    /// Server type code as found in the file
+    pub game_id: String,
+    pub addr: String,
+}
+
+/// Parses both XQF favorites (`Q3S 1.2.3.4:27960`) and qstat lists (`-q3s 1.2.3.4:27960`).
+///
+/// Comments, blank lines and lines without an address are skipped.
+pub fn parse(data: &str) -> Vec<ImportEntry> {
+    data.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let game_id = fields.next()?.trim_start_matches('-');
+            let addr = fields.next()?;
+
+            Some(ImportEntry {
+                game_id: game_id.to_lowercase(),
+                addr: addr.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub imported: usize,
+    pub duplicates: usize,
+    pub unknown: usize,
+}
+
+impl Display for ImportSummary {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Imported {}, skipped {} duplicates, {} unknown games",
+            self.imported, self.duplicates, self.unknown
+        )
+    }
+}
+
+/// Appends entries to favorites. `overrides` maps type codes unknown to `map_game_id` as chosen by the user.
+pub fn merge(
+    favorites: &mut Vec<Favorite>,
+    entries: &[ImportEntry],
+    overrides: &HashMap<String, Game>,
+) -> ImportSummary {
+    let mut summary = ImportSummary::default();
+
+    for entry in entries {
+        let game =
+            match map_game_id(&entry.game_id).or_else(|| overrides.get(&entry.game_id).copied()) {
+                Some(game) => game,
+                None => {
+                    summary.unknown += 1;
+                    continue;
+                }
+            };
+
+        let favorite = Favorite {
+            game,
+            addr: entry.addr.clone(),
+        };
+
+        if favorites.contains(&favorite) {
+            summary.duplicates += 1;
+        } else {
+            favorites.push(favorite);
+            summary.imported += 1;
+        }
+    }
+
+    summary
+}
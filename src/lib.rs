@@ -0,0 +1,35 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Game server browsing backend of Obozrenie. Does not depend on GTK.
+//!
+//! `games::GameList` sets up the supported games with their queriers and launchers, `refresher::start` queries
+//! them and `filters::Filters` narrows down the results.
+
+#![feature(generators, gen_future)]
+
+pub mod activity;
+pub mod countries;
+pub mod filters;
+pub mod games;
+pub mod import;
+pub mod logging;
+pub mod refresher;
+pub mod score;
+pub mod session;
+pub mod settings;
+pub mod share;
+pub mod state;
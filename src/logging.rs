@@ -0,0 +1,239 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Logging set up from the command line. Records go to `env_logger` as usual and are also kept in memory for
+//! the debug log window, and optionally written to a file.
+
+use log::{Level, LevelFilter, Log, Metadata};
+use std::collections::VecDeque;
+use std::fmt::{self, Display, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::activity;
+
+/// Records kept for the debug log window
+pub const DEFAULT_CAPACITY: usize = 1000;
+
+/// Logging switches taken from the command line
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Options {
+    /// Number of `-v` given
+    pub verbosity: usize,
+    pub log_file: Option<PathBuf>,
+}
+
+impl Options {
+    /// Takes the logging switches out of `args`, returning the remaining ones in order.
+    /// `-v` may be repeated or combined as `-vv`, `--log-file` takes the path as the next argument or after `=`.
+    pub fn parse(args: Vec<String>) -> (Self, Vec<String>) {
+        let mut options = Self::default();
+        let mut rest = Vec::new();
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "--verbose" {
+                options.verbosity += 1;
+            } else if arg.len() > 1 && arg.starts_with('-') && arg[1..].chars().all(|c| c == 'v') {
+                options.verbosity += arg.len() - 1;
+            } else if arg == "--log-file" {
+                options.log_file = args.next().map(PathBuf::from);
+            } else if arg.starts_with("--log-file=") {
+                options.log_file = Some(PathBuf::from(&arg["--log-file=".len()..]));
+            } else {
+                rest.push(arg);
+            }
+        }
+
+        (options, rest)
+    }
+
+    /// Level set by `-v`, `None` to leave it to `RUST_LOG`
+    pub fn level(&self) -> Option<LevelFilter> {
+        match self.verbosity {
+            0 => None,
+            1 => Some(LevelFilter::Info),
+            2 => Some(LevelFilter::Debug),
+            _ => Some(LevelFilter::Trace),
+        }
+    }
+
+    /// Level of records kept in memory and written to the log file. Informational ones are always kept, so
+    /// that the debug log window is of use without restarting.
+    pub fn capture_level(&self) -> LevelFilter {
+        self.level()
+            .map(|level| level.max(LevelFilter::Info))
+            .unwrap_or(LevelFilter::Info)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Record {
+    /// Seconds since the Unix epoch
+    pub time: u64,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+impl Display for Record {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {:<5} {}: {}",
+            self.time, self.level, self.target, self.message
+        )
+    }
+}
+
+/// Most recent records, the oldest dropped first once full
+#[derive(Clone, Debug)]
+pub struct RingBuffer {
+    records: VecDeque<Record>,
+    capacity: usize,
+}
+
+impl RingBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            records: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, record: Record) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        while self.records.len() >= self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+
+    /// Oldest first
+    pub fn records(&self) -> impl Iterator<Item = &Record> {
+        self.records.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// One record per line, oldest first
+    pub fn to_text(&self) -> String {
+        self.records
+            .iter()
+            .map(|record| format!("{}\n", record))
+            .collect()
+    }
+}
+
+/// Passes records on to `env_logger` and captures the ones up to `level` on the side.
+pub struct TeeLogger {
+    inner: env_logger::Logger,
+    level: LevelFilter,
+    buffer: Arc<Mutex<RingBuffer>>,
+    file: Option<Mutex<File>>,
+}
+
+impl TeeLogger {
+    pub fn new(
+        inner: env_logger::Logger,
+        level: LevelFilter,
+        buffer: Arc<Mutex<RingBuffer>>,
+        file: Option<File>,
+    ) -> Self {
+        Self {
+            inner,
+            level,
+            buffer,
+            file: file.map(Mutex::new),
+        }
+    }
+
+    /// Most verbose level either side logs at
+    pub fn max_level(&self) -> LevelFilter {
+        self.inner.filter().max(self.level)
+    }
+}
+
+impl Log for TeeLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level || self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.inner.matches(record) {
+            self.inner.log(record);
+        }
+
+        if record.level() <= self.level {
+            let record = Record {
+                time: activity::now(),
+                level: record.level(),
+                target: record.target().to_string(),
+                message: record.args().to_string(),
+            };
+            if let Some(file) = self.file.as_ref() {
+                // Nowhere to report a failure to
+                let _ = writeln!(file.lock().unwrap(), "{}", record);
+            }
+            self.buffer.lock().unwrap().push(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+        if let Some(file) = self.file.as_ref() {
+            let _ = file.lock().unwrap().flush();
+        }
+    }
+}
+
+/// Installs the tee logger as the global one. Returns the buffer of captured records.
+pub fn init(options: &Options) -> io::Result<Arc<Mutex<RingBuffer>>> {
+    let mut builder = env_logger::Builder::from_default_env();
+    if let Some(level) = options.level() {
+        builder.filter_level(level);
+    }
+
+    let file = match options.log_file.as_ref() {
+        Some(path) => Some(OpenOptions::new().create(true).append(true).open(path)?),
+        None => None,
+    };
+
+    let buffer = Arc::new(Mutex::new(RingBuffer::new(DEFAULT_CAPACITY)));
+    let logger = TeeLogger::new(
+        builder.build(),
+        options.capture_level(),
+        buffer.clone(),
+        file,
+    );
+
+    log::set_max_level(logger.max_level());
+    log::set_boxed_logger(Box::new(logger))
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+
+    Ok(buffer)
+}
@@ -16,12 +16,23 @@
 
 #![feature(async_await, generators, gen_future)]
 
+use enum_iter::EnumIterator;
 use futures::{compat::*, prelude::*};
+use futures01::sync::oneshot;
 use gio::prelude::*;
 use gtk::prelude::*;
 use log::*;
+use obozrenie::{
+    activity::{self, DataFile},
+    filters, games, logging, refresher,
+    refresher::RefreshEvent,
+    score, session, settings, share,
+};
+use rgs::ping::Pinger;
 use static_resources::Resources;
-use std::collections::{HashMap, HashSet};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::net::ToSocketAddrs;
 use std::rc::Rc;
 use std::sync::{
     atomic::{AtomicUsize, Ordering},
@@ -29,65 +40,92 @@ use std::sync::{
     Arc, Mutex,
 };
 use std::time::{Duration, Instant};
-use tokio::prelude::StreamExt;
 
-mod filters;
-mod games;
+mod diagnostics;
+mod dialogs;
+mod filters_ui;
+mod secrets;
+mod shortcuts;
 mod static_resources;
+mod tray;
+#[cfg(feature = "tui")]
+mod tui;
 mod widgets;
 
+use crate::filters_ui::{FiltersUi, Toggle};
 use crate::widgets::*;
 
-#[derive(Clone, Debug)]
-enum AppEvent {
-    AddServer((games::Game, rgs::models::Server)),
-    RefreshComplete,
-}
-
 #[derive(Clone)]
 enum AppCommand {
-    StartRefresh(HashMap<games::Game, Arc<dyn games::Querier>>),
+    /// Queriers with name morphers to run, their timeouts and the maximum number of games queried at once,
+    /// zero for unlimited
+    StartRefresh(
+        HashMap<games::Game, (Arc<dyn games::Querier>, Arc<dyn games::NameMorpher>)>,
+        HashMap<games::Game, Duration>,
+        usize,
+    ),
+    /// Aborts the queries of the refresh in flight
+    CancelRefresh,
 }
 
-fn build_filters(resources: &Rc<Resources>) {
-    let filter_model = resources.ui.get_object::<ServerListFilter, _>().0;
+/// Sort column id of the search relevance, outside of the store columns
+const RELEVANCE_SORT_ID: u32 = 1000;
+
+fn build_filters(resources: &Rc<Resources>) -> Arc<Mutex<filters::Filters>> {
+    let filter_model = resources.ui.get_object::<ServerListFilter, _>().unwrap().0;
 
-    let filter_toggle = resources.ui.get_object::<FilterToggle, _>().0;
-    let filters = resources.ui.get_object::<FiltersPopover, _>().0;
+    let filter_toggle = resources.ui.get_object::<FilterToggle, _>().unwrap().0;
+    let filters = resources.ui.get_object::<FiltersPopover, _>().unwrap().0;
 
     // Fill list of games in filter menu
-    let game_list = resources.ui.get_object::<GameListStore, _>();
+    let game_list = resources.ui.get_object::<GameListStore, _>().unwrap();
 
-    for (id, entry) in resources.game_list.0.iter() {
-        game_list.append_game(*id, entry.icon.clone());
+    {
+        let settings = resources.settings.lock().unwrap();
+        for id in resources.game_list.0.keys() {
+            game_list.append_game(
+                *id,
+                &settings.game_name(*id),
+                resources.game_icons.icon(*id),
+            );
+        }
     }
 
-    let filter_data = Arc::new(Mutex::new(filters::Filters::default()));
+    let filter_data = Arc::new(Mutex::new(filters::Filters {
+        anticheat_games: Some(anticheat_games(resources)),
+        ..Default::default()
+    }));
+    update_anticheat_filter(resources, &HashSet::new());
 
-    // Refilter on changes
-    resources
+    let game_selection = resources
         .ui
         .get_object::<GameListView, _>()
+        .unwrap()
         .0
-        .get_selection()
-        .connect_changed({
-            let filter_data = filter_data.clone();
-            let filter_model = filter_model.clone();
-            let game_list = game_list.clone();
-            move |s| {
-                {
-                    let value = {
-                        let (selection, model) = s.get_selected_rows();
+        .get_selection();
 
-                        selection
-                            .into_iter()
-                            .map(|path| {
-                                let iter = model.get_iter(&path).unwrap();
+    // Refilter on changes
+    let game_selection_handler = Rc::new(game_selection.connect_changed({
+        let resources = resources.clone();
+        let filter_data = filter_data.clone();
+        let game_list = game_list.clone();
+        move |s| {
+            {
+                let value = {
+                    let (selection, model) = s.get_selected_rows();
 
-                                game_list.get_game(&iter).0
-                            })
-                            .collect::<HashSet<_>>()
-                    };
+                    selection
+                        .into_iter()
+                        .map(|path| {
+                            let iter = model.get_iter(&path).unwrap();
+
+                            game_list.get_game(&iter).0
+                        })
+                        .collect::<HashSet<_>>()
+                };
+                let installed_only = selects_installed_games(&resources, &value);
+                update_anticheat_filter(&resources, &value);
+                {
                     let mut f = filter_data.lock().unwrap();
 
                     let v = &mut (*f).games;
@@ -95,16 +133,69 @@ fn build_filters(resources: &Rc<Resources>) {
                     *v = value;
                 }
 
-                filter_model.refilter();
+                resources
+                    .ui
+                    .get_object::<InstalledGamesChip, _>()
+                    .unwrap()
+                    .0
+                    .set_active(installed_only);
+            }
+
+            apply_filters(&resources, &filter_data);
+        }
+    }));
+
+    resources
+        .ui
+        .get_object::<SelectAllGames, _>()
+        .unwrap()
+        .0
+        .connect_clicked({
+            let game_selection = game_selection.clone();
+            move |_| game_selection.select_all()
+        });
+    resources
+        .ui
+        .get_object::<SelectInstalledGames, _>()
+        .unwrap()
+        .0
+        .connect_clicked({
+            let resources = resources.clone();
+            let game_selection = game_selection.clone();
+            let game_list = game_list.clone();
+            move |_| {
+                let game_selection = game_selection.clone();
+                let game_list = game_list.clone();
+                let game_selection_handler = game_selection_handler.clone();
+                resources.with_installed_games(move |installed_games| {
+                    // Apply the new selection with a single refilter
+                    game_selection.block_signal(&game_selection_handler);
+                    game_selection.unselect_all();
+                    if let Some(iter) = game_list.0.get_iter_first() {
+                        loop {
+                            let (game, _) = game_list.get_game(&iter);
+                            if installed_games.contains(&game) {
+                                game_selection.select_iter(&iter);
+                            }
+
+                            if !game_list.0.iter_next(&iter) {
+                                break;
+                            }
+                        }
+                    }
+                    game_selection.unblock_signal(&game_selection_handler);
+                    game_selection.emit("changed", &[]).unwrap();
+                });
             }
         });
     resources
         .ui
         .get_object::<ModFilter, _>()
+        .unwrap()
         .0
         .connect_changed({
+            let resources = resources.clone();
             let filter_data = filter_data.clone();
-            let filter_model = filter_model.clone();
             move |w| {
                 {
                     let value = w
@@ -113,20 +204,22 @@ fn build_filters(resources: &Rc<Resources>) {
                         .unwrap_or_else(String::new);
                     let mut f = filter_data.lock().unwrap();
 
+                    (*f).game_mod_regex = compile_filter_entry(w, f.regex_mode);
                     let v = &mut (*f).game_mod;
 
                     *v = value;
                 }
-                filter_model.refilter();
+                apply_filters(&resources, &filter_data);
             }
         });
     resources
         .ui
         .get_object::<GameTypeFilter, _>()
+        .unwrap()
         .0
         .connect_changed({
+            let resources = resources.clone();
             let filter_data = filter_data.clone();
-            let filter_model = filter_model.clone();
             move |w| {
                 {
                     let value = w
@@ -135,20 +228,22 @@ fn build_filters(resources: &Rc<Resources>) {
                         .unwrap_or_else(String::new);
                     let mut f = filter_data.lock().unwrap();
 
+                    (*f).game_type_regex = compile_filter_entry(w, f.regex_mode);
                     let v = &mut (*f).game_type;
 
                     *v = value;
                 }
-                filter_model.refilter();
+                apply_filters(&resources, &filter_data);
             }
         });
     resources
         .ui
         .get_object::<MapFilter, _>()
+        .unwrap()
         .0
         .connect_changed({
+            let resources = resources.clone();
             let filter_data = filter_data.clone();
-            let filter_model = filter_model.clone();
             move |w| {
                 {
                     let value = w
@@ -157,20 +252,150 @@ fn build_filters(resources: &Rc<Resources>) {
                         .unwrap_or_else(String::new);
                     let mut f = filter_data.lock().unwrap();
 
+                    (*f).map_regex = compile_filter_entry(w, f.regex_mode);
                     let v = &mut (*f).map;
 
                     *v = value;
                 }
-                filter_model.refilter();
+                apply_filters(&resources, &filter_data);
+            }
+        });
+
+    // Shared by the popover checkboxes and the quick filter chips
+    let filters_ui = FiltersUi::new(resources.clone(), filter_data.clone());
+    FiltersUi::bind(
+        &filters_ui,
+        Toggle::MapFuzzy,
+        &resources.ui.get_object::<FuzzyMapFilter, _>().unwrap().0,
+    );
+    FiltersUi::bind(
+        &filters_ui,
+        Toggle::NotFull,
+        &resources.ui.get_object::<NotFullFilter, _>().unwrap().0,
+    );
+    FiltersUi::bind(
+        &filters_ui,
+        Toggle::NotEmpty,
+        &resources.ui.get_object::<NotEmptyFilter, _>().unwrap().0,
+    );
+    FiltersUi::bind(
+        &filters_ui,
+        Toggle::NoPassword,
+        &resources.ui.get_object::<NoPasswordFilter, _>().unwrap().0,
+    );
+    FiltersUi::bind(
+        &filters_ui,
+        Toggle::UnknownPassword,
+        &resources
+            .ui
+            .get_object::<UnknownPasswordFilter, _>()
+            .unwrap()
+            .0,
+    );
+    FiltersUi::bind(
+        &filters_ui,
+        Toggle::StockClient,
+        &resources.ui.get_object::<StockClientFilter, _>().unwrap().0,
+    );
+    FiltersUi::bind(
+        &filters_ui,
+        Toggle::Dedicated,
+        &resources.ui.get_object::<DedicatedFilter, _>().unwrap().0,
+    );
+    FiltersUi::bind(
+        &filters_ui,
+        Toggle::Compatible,
+        &resources.ui.get_object::<CompatibleFilter, _>().unwrap().0,
+    );
+
+    // Quick filter chips above the server list
+    FiltersUi::bind(
+        &filters_ui,
+        Toggle::NotEmpty,
+        &resources.ui.get_object::<NotEmptyChip, _>().unwrap().0,
+    );
+    FiltersUi::bind(
+        &filters_ui,
+        Toggle::NotFull,
+        &resources.ui.get_object::<NotFullChip, _>().unwrap().0,
+    );
+    FiltersUi::bind(
+        &filters_ui,
+        Toggle::NoPassword,
+        &resources.ui.get_object::<NoPasswordChip, _>().unwrap().0,
+    );
+    resources
+        .ui
+        .get_object::<InstalledGamesChip, _>()
+        .unwrap()
+        .0
+        .connect_toggled({
+            let resources = resources.clone();
+            let filter_data = filter_data.clone();
+            let game_selection = game_selection.clone();
+            move |w| {
+                let value = w.get_active();
+
+                // Set by the selection handler
+                let installed_only =
+                    selects_installed_games(&resources, &filter_data.lock().unwrap().games);
+                if value == installed_only {
+                    return;
+                }
+
+                if value {
+                    resources
+                        .ui
+                        .get_object::<SelectInstalledGames, _>()
+                        .unwrap()
+                        .0
+                        .clicked();
+                } else {
+                    game_selection.select_all();
+                }
+            }
+        });
+    resources
+        .ui
+        .get_object::<RegexFilterToggle, _>()
+        .unwrap()
+        .0
+        .connect_toggled({
+            let resources = resources.clone();
+            let filter_data = filter_data.clone();
+            move |w| {
+                {
+                    let value = w.get_active();
+
+                    let mut f = filter_data.lock().unwrap();
+
+                    (*f).game_mod_regex = compile_filter_entry(
+                        &resources.ui.get_object::<ModFilter, _>().unwrap().0,
+                        value,
+                    );
+                    (*f).game_type_regex = compile_filter_entry(
+                        &resources.ui.get_object::<GameTypeFilter, _>().unwrap().0,
+                        value,
+                    );
+                    (*f).map_regex = compile_filter_entry(
+                        &resources.ui.get_object::<MapFilter, _>().unwrap().0,
+                        value,
+                    );
+                    let v = &mut (*f).regex_mode;
+
+                    *v = value;
+                }
+                apply_filters(&resources, &filter_data);
             }
         });
     resources
         .ui
         .get_object::<PingFilter, _>()
+        .unwrap()
         .0
         .connect_value_changed({
+            let resources = resources.clone();
             let filter_data = filter_data.clone();
-            let filter_model = filter_model.clone();
             move |w| {
                 {
                     let value = std::time::Duration::from_millis(w.get_value_as_int() as u64);
@@ -180,24 +405,21 @@ fn build_filters(resources: &Rc<Resources>) {
 
                     *v = value;
                 }
-                filter_model.refilter();
+                apply_filters(&resources, &filter_data);
             }
         });
     resources
         .ui
         .get_object::<AntiCheatFilter, _>()
+        .unwrap()
         .0
         .connect_changed({
+            let resources = resources.clone();
             let filter_data = filter_data.clone();
-            let filter_model = filter_model.clone();
             move |w| {
                 {
-                    let value = match w.get_active_text().unwrap().as_str() {
-                        "Enabled" => Some(true),
-                        "Disabled" => Some(false),
-                        "Ignore" => None,
-                        other => unreachable!(format!("Invalid variant: {}", other)),
-                    };
+                    let value =
+                        filters::anticheat_from_id(w.get_active_id().as_ref().map(|s| s.as_str()));
 
                     let mut f = filter_data.lock().unwrap();
 
@@ -205,75 +427,224 @@ fn build_filters(resources: &Rc<Resources>) {
 
                     *v = value;
                 }
-                filter_model.refilter();
+                apply_filters(&resources, &filter_data);
             }
         });
     resources
         .ui
-        .get_object::<NotFullFilter, _>()
+        .get_object::<CountryFilter, _>()
+        .unwrap()
         .0
-        .connect_toggled({
+        .connect_changed({
+            let resources = resources.clone();
             let filter_data = filter_data.clone();
-            let filter_model = filter_model.clone();
             move |w| {
                 {
-                    let value = w.get_active();
-
+                    let value = filters::parse_countries(
+                        &w.get_text()
+                            .map(|s| s.to_string())
+                            .unwrap_or_else(String::new),
+                    );
                     let mut f = filter_data.lock().unwrap();
 
-                    let v = &mut (*f).not_full;
+                    let v = &mut (*f).countries;
 
                     *v = value;
                 }
-                filter_model.refilter();
+                apply_filters(&resources, &filter_data);
             }
         });
+
+    // Preset driving the individual filter widgets
     resources
         .ui
-        .get_object::<NotEmptyFilter, _>()
+        .get_object::<JoinableFilter, _>()
+        .unwrap()
         .0
         .connect_toggled({
+            let resources = resources.clone();
             let filter_data = filter_data.clone();
-            let filter_model = filter_model.clone();
             move |w| {
-                {
-                    let value = w.get_active();
-
-                    let mut f = filter_data.lock().unwrap();
+                let value = w.get_active();
 
-                    let v = &mut (*f).not_empty;
+                // Applied along with the password filter below
+                filter_data.lock().unwrap().saved_passwords = if value {
+                    resources.settings.lock().unwrap().saved_passwords()
+                } else {
+                    HashSet::new()
+                };
 
-                    *v = value;
-                }
-                filter_model.refilter();
+                resources
+                    .ui
+                    .get_object::<NotFullFilter, _>()
+                    .unwrap()
+                    .0
+                    .set_active(value);
+                resources
+                    .ui
+                    .get_object::<NoPasswordFilter, _>()
+                    .unwrap()
+                    .0
+                    .set_active(value);
+                resources
+                    .ui
+                    .get_object::<CompatibleFilter, _>()
+                    .unwrap()
+                    .0
+                    .set_active(value);
+                resources
+                    .ui
+                    .get_object::<PingFilter, _>()
+                    .unwrap()
+                    .0
+                    .set_value(if value {
+                        f64::from(resources.settings.lock().unwrap().joinable_max_ping)
+                    } else {
+                        0.0
+                    });
             }
         });
+
     resources
         .ui
-        .get_object::<NoPasswordFilter, _>()
+        .get_object::<ResetFilters, _>()
+        .unwrap()
         .0
-        .connect_toggled({
+        .connect_clicked({
+            let resources = resources.clone();
             let filter_data = filter_data.clone();
-            let filter_model = filter_model.clone();
-            move |w| {
-                {
-                    let value = w.get_active();
+            let game_selection = game_selection.clone();
+            move |_| {
+                // Widgets first, as their handlers write to the filters
+                for entry in &[
+                    resources.ui.get_object::<ModFilter, _>().unwrap().0,
+                    resources.ui.get_object::<GameTypeFilter, _>().unwrap().0,
+                    resources.ui.get_object::<MapFilter, _>().unwrap().0,
+                    resources.ui.get_object::<CountryFilter, _>().unwrap().0,
+                ] {
+                    entry.set_text("");
+                }
+                for toggle in &[
+                    resources
+                        .ui
+                        .get_object::<JoinableFilter, _>()
+                        .unwrap()
+                        .0
+                        .upcast::<gtk::ToggleButton>(),
+                    resources
+                        .ui
+                        .get_object::<NotFullFilter, _>()
+                        .unwrap()
+                        .0
+                        .upcast(),
+                    resources
+                        .ui
+                        .get_object::<NotEmptyFilter, _>()
+                        .unwrap()
+                        .0
+                        .upcast(),
+                    resources
+                        .ui
+                        .get_object::<NoPasswordFilter, _>()
+                        .unwrap()
+                        .0
+                        .upcast(),
+                    resources
+                        .ui
+                        .get_object::<UnknownPasswordFilter, _>()
+                        .unwrap()
+                        .0
+                        .upcast(),
+                    resources
+                        .ui
+                        .get_object::<FuzzyMapFilter, _>()
+                        .unwrap()
+                        .0
+                        .upcast(),
+                    resources
+                        .ui
+                        .get_object::<RegexFilterToggle, _>()
+                        .unwrap()
+                        .0
+                        .upcast(),
+                    resources
+                        .ui
+                        .get_object::<StockClientFilter, _>()
+                        .unwrap()
+                        .0
+                        .upcast(),
+                    resources
+                        .ui
+                        .get_object::<DedicatedFilter, _>()
+                        .unwrap()
+                        .0
+                        .upcast(),
+                    resources
+                        .ui
+                        .get_object::<CompatibleFilter, _>()
+                        .unwrap()
+                        .0
+                        .upcast(),
+                ] {
+                    toggle.set_active(false);
+                }
+                resources
+                    .ui
+                    .get_object::<PingFilter, _>()
+                    .unwrap()
+                    .0
+                    .set_value(0.0);
+                resources
+                    .ui
+                    .get_object::<AntiCheatFilter, _>()
+                    .unwrap()
+                    .0
+                    .set_active_id(Some("ignore"));
+                game_selection.unselect_all();
 
+                // Search and the favorites view are not part of the popover
+                {
                     let mut f = filter_data.lock().unwrap();
-
-                    let v = &mut (*f).no_password;
-
-                    *v = value;
+                    *f = filters::Filters {
+                        search: f.search.clone(),
+                        favorites_only: f.favorites_only,
+                        anticheat_games: f.anticheat_games.clone(),
+                        ..Default::default()
+                    };
                 }
-                filter_model.refilter();
+                apply_filters(&resources, &filter_data);
             }
         });
 
+    // Display mode rather than a filter, so it is persisted
+    let dim_empty_toggle = resources.ui.get_object::<DimEmptyToggle, _>().unwrap().0;
+    dim_empty_toggle.set_active(resources.settings.lock().unwrap().dim_empty_servers);
+    dim_empty_toggle.connect_toggled({
+        let resources = resources.clone();
+        move |w| {
+            {
+                let mut settings = resources.settings.lock().unwrap();
+                settings.dim_empty_servers = w.get_active();
+                if let Err(e) = settings.save() {
+                    warn!("Failed to save settings: {}", e);
+                }
+            }
+            resources
+                .ui
+                .get_object::<ServerListView, _>()
+                .unwrap()
+                .0
+                .queue_draw();
+        }
+    });
+
     filter_toggle.connect_toggled({
         let filters = filters.clone();
         move |toggle| {
             if toggle.get_active() {
                 filters.popup();
+                // The popover is modal, keyboard focus stays within it until closed
+                filters.child_focus(gtk::DirectionType::TabForward);
             } else {
                 filters.popdown();
             }
@@ -284,212 +655,3746 @@ fn build_filters(resources: &Rc<Resources>) {
         let filter_toggle = filter_toggle.clone();
         move |_| {
             filter_toggle.set_active(false);
+            filter_toggle.grab_focus();
         }
     });
 
     filter_model.set_visible_func({
+        let resources = resources.clone();
         let filter_data = filter_data.clone();
         move |model, iter| {
+            // Evaluated once the batch is complete, see `InsertBatch`
+            if resources.inserting_servers.get() {
+                return true;
+            }
+
             let list_store = model.clone().downcast::<gtk::ListStore>().unwrap();
 
             let (game, server) = ServerStore(list_store).get_server(iter.into());
 
             trace!("Refiltering... {:?}", server);
 
-            filter_data.lock().unwrap().matches(game, &server)
+            let f = filter_data.lock().unwrap();
+
+            if !f.search.is_empty() && row_search_score(model, iter, &f.search).is_none() {
+                return false;
+            }
+
+            if f.favorites_only || f.compatible_only {
+                let settings = resources.settings.lock().unwrap();
+
+                if f.favorites_only && !settings.is_favorite(game, &games::server_key(&server)) {
+                    return false;
+                }
+
+                if f.compatible_only && version_mismatch(&settings, model, iter).is_some() {
+                    return false;
+                }
+            }
+
+            f.matches(game, &server)
         }
     });
-}
 
-fn build_ui(
-    app: &gtk::Application,
-    executor: tokio::runtime::TaskExecutor,
-    resources: &Rc<Resources>,
-) {
-    let (cmd_sink, cmd_faucet) = channel::<AppCommand>();
-    let (event_sink, event_faucet) = channel::<AppEvent>();
+    // Relevance is a synthetic sort column, computed from the search query on the fly
+    let sort_model = resources.ui.get_object::<ServerListSort, _>().unwrap().0;
+    sort_model.set_sort_func(gtk::SortColumn::Index(RELEVANCE_SORT_ID), {
+        let sort_model = sort_model.clone();
+        let filter_data = filter_data.clone();
+        move |model, a, b| {
+            let query = filter_data.lock().unwrap().search.clone();
 
-    let refresher = resources.ui.get_object::<RefreshButton, _>().0;
+            pinned_first(&sort_model, model.upcast_ref(), a, b).unwrap_or_else(|| {
+                row_search_score(model.upcast_ref(), a, &query).cmp(&row_search_score(
+                    model.upcast_ref(),
+                    b,
+                    &query,
+                ))
+            })
+        }
+    });
 
-    let server_list = resources.ui.get_object::<ServerStore, _>();
+    // Replace the default sorting to keep pinned servers on top and unknown pings in place
+    for (i, column) in ServerStoreColumn::enum_iter().enumerate() {
+        sort_model.set_sort_func(gtk::SortColumn::Index(i as u32), {
+            let resources = resources.clone();
+            let sort_model = sort_model.clone();
+            move |model, a, b| {
+                pinned_first(&sort_model, model.upcast_ref(), a, b)
+                    .or_else(|| match column {
+                        ServerStoreColumn::Ping => {
+                            unknown_ping_order(&resources, &sort_model, model.upcast_ref(), a, b)
+                        }
+                        _ => None,
+                    })
+                    .unwrap_or_else(|| compare_column(model.upcast_ref(), a, b, column))
+            }
+        });
+    }
 
-    let server_list_view = resources.ui.get_object::<ServerListView, _>().0;
+    // Arrival order tells nothing, so start out sorted by relevance
+    sort_model.set_sort_column_id(
+        gtk::SortColumn::Index(SMART_SORT_COLUMN as u32),
+        gtk::SortType::Descending,
+    );
 
-    server_list_view.connect_row_activated({
-        let resources = resources.clone();
-        let server_list = server_list.clone();
-        move |_, path, _| {
-            let (
-                game_id,
-                rgs::models::Server {
-                    addr, need_pass, ..
-                },
-            ) = server_list.get_server(&server_list.0.get_iter(path).unwrap());
+    let saved_sort = Arc::new(Mutex::new(None));
+    resources
+        .ui
+        .get_object::<ServerSearch, _>()
+        .unwrap()
+        .0
+        .connect_search_changed({
+            let filter_data = filter_data.clone();
+            let filter_model = filter_model.clone();
+            move |w| {
+                let query = w
+                    .get_text()
+                    .map(|s| s.trim().to_string())
+                    .unwrap_or_else(String::new);
+                let was_active = {
+                    let mut f = filter_data.lock().unwrap();
 
-            let f = Rc::new({
-                let addr = addr.clone();
-                let game_launcher = resources.game_list.0[&game_id].launcher.clone();
+                    let v = &mut (*f).search;
 
-                move |password: Option<String>| {
-                    let addr = addr.clone();
-                    let game_launcher = game_launcher.clone();
+                    let was_active = !v.is_empty();
+                    *v = query.clone();
+                    was_active
+                };
+                filter_model.refilter();
 
-                    println!("Connecting to {} server at {}", game_id, addr);
+                if !query.is_empty() {
+                    if !was_active {
+                        *saved_sort.lock().unwrap() = sort_model.get_sort_column_id();
+                    }
 
-                    std::thread::spawn({
-                        move || {
-                            game_launcher
-                                .launch_cmd(&games::LaunchData {
-                                    addr: addr.to_string(),
-                                    password,
-                                })
-                                .map(|mut cmd| cmd.spawn());
-                        }
-                    });
+                    // Setting the same sort column again does not resort, so flip the order first
+                    let relevance = gtk::SortColumn::Index(RELEVANCE_SORT_ID);
+                    sort_model.set_sort_column_id(relevance, gtk::SortType::Ascending);
+                    sort_model.set_sort_column_id(relevance, gtk::SortType::Descending);
+                } else if was_active {
+                    if let Some((column, order)) = saved_sort.lock().unwrap().take() {
+                        sort_model.set_sort_column_id(column, order);
+                    }
                 }
-            }) as Rc<dyn Fn(Option<String>)>;
+            }
+        });
 
-            if let Some(true) = need_pass {
-                let password_request = resources.ui.get_object::<PasswordRequest, _>().0;
-                let password_entry = resources.ui.get_object::<PasswordEntry, _>().0;
-                let connect_button = resources.ui.get_object::<ConnectWithPassword, _>().0;
+    filter_data
+}
 
-                password_entry.connect_changed({
-                    let connect_button = connect_button.clone();
-                    let password_entry = password_entry.clone();
-                    move |_| {
-                        connect_button.set_sensitive(password_entry.get_text_length() > 0);
-                    }
-                });
+/// Ranks pinned rows first in either sort order, `None` if both rows are pinned or unpinned.
+fn pinned_first(
+    sort_model: &gtk::TreeModelSort,
+    model: &gtk::TreeModel,
+    a: &gtk::TreeIter,
+    b: &gtk::TreeIter,
+) -> Option<std::cmp::Ordering> {
+    let pinned = |iter| {
+        model
+            .get_value(iter, ServerStoreColumn::Pinned as i32)
+            .get::<bool>()
+            .unwrap_or(false)
+    };
 
-                connect_button.connect_clicked({
-                    let f = f.clone();
-                    move |_| (f)(password_entry.get_text().map(|s| s.to_string()))
-                });
+    let ordering = pinned(b).cmp(&pinned(a));
+    if ordering == std::cmp::Ordering::Equal {
+        return None;
+    }
 
-                password_request.popup();
-            } else {
-                (f)(None)
-            }
-        }
-    });
+    // The view reverses whatever we return for descending order
+    Some(match sort_model.get_sort_column_id() {
+        Some((_, gtk::SortType::Descending)) => ordering.reverse(),
+        _ => ordering,
+    })
+}
 
-    let present_servers = Arc::new(Mutex::new(HashSet::new()));
+/// Ranks servers with unknown ping according to the setting in either sort order, `None` if both pings are
+/// known or unknown.
+fn unknown_ping_order(
+    resources: &Resources,
+    sort_model: &gtk::TreeModelSort,
+    model: &gtk::TreeModel,
+    a: &gtk::TreeIter,
+    b: &gtk::TreeIter,
+) -> Option<std::cmp::Ordering> {
+    let unknown = |iter| {
+        model
+            .get_value(iter, ServerStoreColumn::Ping as i32)
+            .get::<i32>()
+            .map(|v| v == UNKNOWN_PING)
+            .unwrap_or(true)
+    };
 
-    refresher.connect_clicked({
-        let cmd_sink = cmd_sink.clone();
-        let refresher = refresher.clone();
-        let resources = resources.clone();
-        let server_list = server_list.clone();
-        let present_servers = present_servers.clone();
-        move |_| {
-            refresher.set_sensitive(false);
-            server_list.0.clear();
-            present_servers.lock().unwrap().clear();
+    let ordering = unknown(a).cmp(&unknown(b));
+    if ordering == std::cmp::Ordering::Equal {
+        return None;
+    }
 
-            cmd_sink
-                .send(AppCommand::StartRefresh(
-                    resources
-                        .game_list
-                        .clone()
-                        .0
-                        .into_iter()
-                        .map(|(id, e)| (id, e.querier))
-                        .collect(),
-                ))
-                .unwrap();
-        }
-    });
+    let ordering = match resources.settings.lock().unwrap().unknown_ping {
+        settings::UnknownPing::Last => ordering,
+        settings::UnknownPing::First => ordering.reverse(),
+    };
 
-    build_filters(resources);
+    Some(match sort_model.get_sort_column_id() {
+        Some((_, gtk::SortType::Descending)) => ordering.reverse(),
+        _ => ordering,
+    })
+}
 
-    executor.spawn({
-        let cmd_sink = cmd_sink.clone();
-        let event_sink = event_sink.clone();
+/// Sort column of the smart sort, see `score::score`
+const SMART_SORT_COLUMN: ServerStoreColumn = ServerStoreColumn::Score;
 
-        async move {
-            use TryRecvError::*;
+/// Recomputes the smart sort score of the row.
+fn update_score(resources: &Resources, server_list: &ServerStore, iter: &gtk::TreeIter) {
+    let score = row_score(&resources.settings.lock().unwrap(), server_list, iter);
 
-            let _ = cmd_sink.clone();
+    server_list.set_score(iter, score);
+}
 
-            loop {
-                match cmd_faucet.try_recv() {
-                    Ok(cmd) => match cmd {
-                        AppCommand::StartRefresh(task_list) => {
-                            let total_queried = Arc::new(AtomicUsize::new(0));
-
-                            let timeout = std::time::Duration::from_secs(10);
-
-                            debug!("Starting query");
-
-                            tokio::spawn({
-                                use futures01::{future as future01, prelude::*};
-
-                                future01::join_all(task_list.into_iter().map({
-                                    let event_sink = event_sink.clone();
-                                    let total_queried = total_queried.clone();
-
-                                    move |(game_id, querier)| {
-                                        querier
-                                            .query()
-                                            .inspect({
-                                                let event_sink = event_sink.clone();
-                                                let total_queried = total_queried.clone();
-                                                move |srv| {
-                                                    event_sink
-                                                        .send(AppEvent::AddServer((
-                                                            game_id,
-                                                            srv.clone(),
-                                                        )))
-                                                        .unwrap();
-                                                    total_queried.fetch_add(1, Ordering::Relaxed);
-                                                }
-                                            })
-                                            .map_err(move |e| {
-                                                debug!(
-                                                    "Error while querying {} returned an error: {:?}",
-                                                    game_id, e
-                                                );
-                                                e
-                                            })
-                                            .timeout(timeout)
-                                            .for_each(|_| Ok(()))
-                                            .inspect(move |_| debug!("{} query complete", game_id))
-                                    }
-                                }))
-                                    .then({
-                                        let event_sink = event_sink.clone();
-                                        move |_| {
-                                            debug!(
-                                                "Queried {} servers",
-                                                total_queried.load(Ordering::Relaxed)
-                                            );
-
-                                            event_sink.send(AppEvent::RefreshComplete).unwrap();
-
-                                            Ok(())
-                                        }
-                                    })
-                            });
-                        }
-                    },
-                    Err(e) => match e {
-                        Empty => {}
-                        Disconnected => {
-                            return;
-                        }
-                    },
-                }
+/// Smart sort score of the row. The settings lock must not be held while the score is set, as changing a row
+/// refilters it and the list filter takes the lock.
+fn row_score(
+    settings: &settings::Settings,
+    server_list: &ServerStore,
+    iter: &gtk::TreeIter,
+) -> i32 {
+    let (game, srv) = server_list.get_server(iter);
 
-                tokio::timer::Delay::new(Instant::now() + Duration::from_millis(100))
-                    .compat()
-                    .await
-                    .unwrap()
+    score::score(
+        &settings.smart_sort,
+        &score::ScoreInput {
+            favorite: settings.is_favorite(game, &games::server_key(&srv)),
+            num_clients: srv.num_clients,
+            max_clients: srv.max_clients,
+            ping: srv.ping,
+        },
+    )
+}
+
+/// Refresh events handled per tick of the event loop
+const MAX_EVENT_BATCH: usize = 500;
+
+/// Refresh results inserted into the server list in one go. Settings are read once for the whole batch, and
+/// the list filter lets rows through unchecked until `finish` evaluates each of them once.
+struct InsertBatch {
+    settings: settings::Settings,
+    rows: Vec<gtk::TreeIter>,
+    started: Instant,
+}
+
+impl InsertBatch {
+    fn start(resources: &Resources) -> Self {
+        resources.inserting_servers.set(true);
+
+        Self {
+            settings: resources.settings.lock().unwrap().clone(),
+            rows: Vec::new(),
+            started: Instant::now(),
+        }
+    }
+
+    fn finish(self, resources: &Resources, server_list: &ServerStore) {
+        resources.inserting_servers.set(false);
+
+        for iter in &self.rows {
+            if let Some(path) = server_list.0.get_path(iter) {
+                server_list.0.row_changed(&path, iter);
+            }
+        }
+
+        debug!(
+            "Inserted {} servers in {:?}",
+            self.rows.len(),
+            self.started.elapsed()
+        );
+    }
+}
+
+/// Shows the ping in milliseconds, or a dash if it is unknown.
+fn render_ping(cell: &gtk::CellRenderer, model: &gtk::TreeModel, iter: &gtk::TreeIter) {
+    let text = match model
+        .get_value(iter, ServerStoreColumn::Ping as i32)
+        .get::<i32>()
+    {
+        Some(ping) if ping != UNKNOWN_PING => ping.to_string(),
+        _ => "—".into(),
+    };
+
+    cell.set_property("text", &text).unwrap();
+}
+
+/// Server and local version if the row's server runs a version the installed game cannot join.
+fn version_mismatch(
+    settings: &settings::Settings,
+    model: &gtk::TreeModel,
+    iter: &gtk::TreeIter,
+) -> Option<(String, String)> {
+    let game = model
+        .get_value(iter, ServerStoreColumn::GameId as i32)
+        .get::<String>()
+        .and_then(|id| games::Game::from_id(&id))?;
+    let local = settings.local_version(game)?;
+    let server = model
+        .get_value(iter, ServerStoreColumn::Version as i32)
+        .get::<String>()?;
+
+    if games::versions_compatible(&server, local) {
+        None
+    } else {
+        Some((server, local.to_string()))
+    }
+}
+
+/// Default ordering of a server list column.
+fn compare_column(
+    model: &gtk::TreeModel,
+    a: &gtk::TreeIter,
+    b: &gtk::TreeIter,
+    column: ServerStoreColumn,
+) -> std::cmp::Ordering {
+    let a = model.get_value(a, column as i32);
+    let b = model.get_value(b, column as i32);
+
+    match a.type_() {
+        glib::Type::String => a
+            .get::<String>()
+            .map(|v| v.to_lowercase())
+            .cmp(&b.get::<String>().map(|v| v.to_lowercase())),
+        glib::Type::Bool => a.get::<bool>().cmp(&b.get::<bool>()),
+        _ => a.get::<i32>().cmp(&b.get::<i32>()),
+    }
+}
+
+/// Whether the games filter is what the installed games button selects. An empty selection lists all games.
+fn selects_installed_games(resources: &Resources, selection: &HashSet<games::Game>) -> bool {
+    match &*resources.installed_games.lock().unwrap() {
+        Some(installed_games) => !selection.is_empty() && installed_games == selection,
+        None => false,
+    }
+}
+
+/// Games whose servers report an anti-cheat through the secure flag, see `Settings::anticheat_name`.
+fn anticheat_games(resources: &Resources) -> HashSet<games::Game> {
+    let settings = resources.settings.lock().unwrap();
+
+    resources
+        .game_list
+        .0
+        .keys()
+        .filter(|game| settings.anticheat_name(**game).is_some())
+        .cloned()
+        .collect()
+}
+
+/// Names the anti-cheat filter after the anti-cheat of the selected games, of all games if none is selected,
+/// and disables it if none of them has one.
+fn update_anticheat_filter(resources: &Resources, selection: &HashSet<games::Game>) {
+    let names = {
+        let settings = resources.settings.lock().unwrap();
+        resources
+            .game_list
+            .0
+            .keys()
+            .filter(|game| selection.is_empty() || selection.contains(game))
+            .filter_map(|game| settings.anticheat_name(*game))
+            .collect::<BTreeSet<_>>()
+    };
+
+    let label = resources.ui.get_object::<AntiCheatLabel, _>().unwrap().0;
+    let filter = resources.ui.get_object::<AntiCheatFilter, _>().unwrap().0;
+    filter.set_sensitive(!names.is_empty());
+    match names.iter().next() {
+        Some(name) if names.len() == 1 => {
+            label.set_text(name);
+            filter.set_tooltip_text(None);
+        }
+        Some(_) => {
+            label.set_text("Anti-cheat");
+            filter.set_tooltip_text(Some(&format!(
+                "Applies to servers with {}.",
+                names.into_iter().collect::<Vec<_>>().join(", ")
+            )));
+        }
+        None => {
+            label.set_text("Anti-cheat");
+            filter.set_tooltip_text(Some("None of the selected games have an anti-cheat."));
+        }
+    }
+}
+
+/// Refilters the server list and shows the number of active filters on the filter toggle.
+fn apply_filters(resources: &Resources, filter_data: &Mutex<filters::Filters>) {
+    let count = filter_data.lock().unwrap().active_count();
+
+    let filter_toggle = resources.ui.get_object::<FilterToggle, _>().unwrap().0;
+    if count > 0 {
+        filter_toggle.set_label(&count.to_string());
+        filter_toggle.set_tooltip_text(Some(&format!("{} filters active.", count)));
+    } else {
+        filter_toggle.set_label("");
+        filter_toggle.set_tooltip_text(Some("Set up server filters."));
+    }
+
+    resources
+        .ui
+        .get_object::<ServerListFilter, _>()
+        .unwrap()
+        .0
+        .refilter();
+}
+
+/// Compiles the entry's pattern in regex mode. Invalid patterns are highlighted and ignored.
+fn compile_filter_entry(entry: &gtk::Entry, regex_mode: bool) -> Option<regex::Regex> {
+    let style = entry.get_style_context();
+    style.remove_class("error");
+    entry.set_tooltip_text(None);
+
+    if !regex_mode {
+        return None;
+    }
+
+    let pattern = entry
+        .get_text()
+        .map(|s| s.to_string())
+        .unwrap_or_else(String::new);
+    match filters::compile_pattern(&pattern) {
+        Ok(v) => v,
+        Err(e) => {
+            style.add_class("error");
+            entry.set_tooltip_text(Some(&e.to_string()));
+            None
+        }
+    }
+}
+
+/// Score of the row's displayed values against the search query.
+fn row_search_score(model: &gtk::TreeModel, iter: &gtk::TreeIter, query: &str) -> Option<i64> {
+    let fields = [
+        ServerStoreColumn::Name,
+        ServerStoreColumn::Map,
+        ServerStoreColumn::GameMod,
+        ServerStoreColumn::Host,
+    ]
+    .iter()
+    .filter_map(|col| model.get_value(iter, *col as i32).get::<String>())
+    .collect::<Vec<_>>();
+
+    filters::search_score(
+        query,
+        &fields.iter().map(String::as_str).collect::<Vec<_>>(),
+    )
+}
+
+/// Collects live data for favorite servers, most populated first.
+fn favorite_entries(resources: &Rc<Resources>, server_list: &ServerStore) -> Vec<tray::TrayEntry> {
+    let servers = server_list.get_servers();
+
+    let mut entries = resources
+        .settings
+        .lock()
+        .unwrap()
+        .favorites
+        .iter()
+        .map(|favorite| {
+            let srv = servers
+                .iter()
+                .find(|(game_id, srv)| {
+                    *game_id == favorite.game && games::server_key(srv) == favorite.addr
+                })
+                .map(|(_, srv)| srv);
+
+            tray::TrayEntry {
+                favorite: favorite.clone(),
+                name: srv.and_then(|srv| srv.name.clone()),
+                num_clients: srv.and_then(|srv| srv.num_clients),
+                max_clients: srv.and_then(|srv| srv.max_clients),
+                need_pass: srv.and_then(|srv| srv.need_pass),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.num_clients));
+
+    entries
+}
+
+/// Moves favorites followed by name to the server now carrying the name, see `settings::FollowName`. Where
+/// several servers match, the user is told and chooses later, see `show_follow_prompt`.
+fn follow_favorites(
+    resources: &Rc<Resources>,
+    server_list: &ServerStore,
+    present_servers: &HashSet<String>,
+) {
+    let followed = resources
+        .settings
+        .lock()
+        .unwrap()
+        .favorite_details
+        .iter()
+        .filter_map(|details| {
+            details
+                .follow_name
+                .clone()
+                .map(|follow| (details.server.clone(), follow))
+        })
+        .collect::<Vec<_>>();
+    if followed.is_empty() {
+        show_follow_prompt(resources, Vec::new());
+        return;
+    }
+
+    let servers = server_list.get_servers();
+    let mut moved = false;
+    let mut ambiguous = Vec::new();
+    for (favorite, follow) in followed {
+        let listed = servers
+            .iter()
+            .filter(|(game_id, srv)| {
+                *game_id == favorite.game && present_servers.contains(&games::server_key(srv))
+            })
+            .map(|(game_id, srv)| {
+                (
+                    games::server_key(srv),
+                    resources.game_list.0[game_id]
+                        .name_morpher
+                        .morph(srv.name.clone().unwrap_or_default()),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        match follow.locate(&favorite.addr, &listed) {
+            settings::FollowResult::Stay => {}
+            settings::FollowResult::Moved(addr) => {
+                move_followed_favorite(resources, &favorite, addr);
+                moved = true;
+            }
+            settings::FollowResult::Ambiguous(candidates) => {
+                let candidates = listed
+                    .into_iter()
+                    .filter(|(addr, _)| candidates.contains(addr))
+                    .collect::<Vec<_>>();
+                ambiguous.push((favorite, candidates));
+            }
+        }
+    }
+
+    // Favorites view and marks follow the new addresses
+    if moved {
+        resources
+            .ui
+            .get_object::<ServerListFilter, _>()
+            .unwrap()
+            .0
+            .refilter();
+    }
+    show_follow_prompt(resources, ambiguous);
+}
+
+fn move_followed_favorite(
+    resources: &Rc<Resources>,
+    favorite: &settings::Favorite,
+    new_addr: String,
+) {
+    info!(
+        "Favorite {} followed its server to {}",
+        favorite.addr, new_addr
+    );
+    let mut settings = resources.settings.lock().unwrap();
+    settings.move_favorite(favorite.game, &favorite.addr, new_addr);
+    if let Err(e) = settings.save() {
+        warn!("Failed to save settings: {}", e);
+    }
+}
+
+/// Tells about followed favorites that several servers match, whose new address is then chosen on request,
+/// see `Resources::ambiguous_follows`.
+fn show_follow_prompt(
+    resources: &Rc<Resources>,
+    ambiguous: Vec<(settings::Favorite, Vec<(String, String)>)>,
+) {
+    let follow_prompt = resources.ui.get_object::<FollowPrompt, _>().unwrap().0;
+    if ambiguous.is_empty() {
+        follow_prompt.hide();
+    } else {
+        resources
+            .ui
+            .get_object::<FollowPromptLabel, _>()
+            .unwrap()
+            .0
+            .set_text(&match ambiguous.as_slice() {
+                [(favorite, _)] => format!(
+                    "Favorite {} did not reply, but several servers match its name.",
+                    favorite.addr
+                ),
+                _ => format!(
+                    "{} favorites did not reply, but several servers match their names.",
+                    ambiguous.len()
+                ),
+            });
+        follow_prompt.show();
+    }
+    *resources.ambiguous_follows.borrow_mut() = ambiguous;
+}
+
+/// Moves the favorite, pin and history of servers that came back under another address in an incremental
+/// refresh, see `settings::AddressMigration`. Returns the old addresses, whose rows are stale.
+fn migrate_moved_servers(
+    resources: &Rc<Resources>,
+    server_list: &ServerStore,
+    previous_servers: &HashMap<String, games::DisplayServer>,
+    present_servers: &HashSet<String>,
+    activity_log: &mut activity::ActivityLog,
+    ping_history: &mut activity::PingHistory,
+) -> Vec<String> {
+    let migration = {
+        let settings = resources.settings.lock().unwrap();
+        if !settings.incremental_refresh {
+            return Vec::new();
+        }
+        settings.address_migration.clone()
+    };
+
+    let identity = |game_id: games::Game, srv: &rgs::models::Server| {
+        let key = games::server_key(srv);
+        let name = resources.game_list.0[&game_id]
+            .name_morpher
+            .morph(srv.name.clone().unwrap_or_default());
+        migration
+            .identity(game_id, &key, &name, &srv.rules)
+            .map(|identity| (key, identity))
+    };
+
+    let gone = previous_servers
+        .values()
+        .filter(|data| !present_servers.contains(&games::server_key(&data.srv)))
+        .filter_map(|data| identity(data.game_id, &data.srv))
+        .collect::<Vec<_>>();
+    if gone.is_empty() {
+        return Vec::new();
+    }
+
+    let new = server_list
+        .get_servers()
+        .iter()
+        .filter(|(_, srv)| {
+            let key = games::server_key(srv);
+            present_servers.contains(&key) && !previous_servers.contains_key(&key)
+        })
+        .filter_map(|(game_id, srv)| identity(*game_id, srv))
+        .collect::<Vec<_>>();
+
+    let moves = migration.find_moves(&gone, &new);
+    if moves.is_empty() {
+        return Vec::new();
+    }
+
+    let moves = moves
+        .into_iter()
+        .map(|(old_addr, new_addr)| {
+            let game = previous_servers[&old_addr].game_id;
+            info!("{} server {} moved to {}", game, old_addr, new_addr);
+            (game, old_addr, new_addr)
+        })
+        .collect::<Vec<_>>();
+    move_servers(resources, server_list, &moves, activity_log, ping_history);
+
+    moves.into_iter().map(|(_, old_addr, _)| old_addr).collect()
+}
+
+/// Moves favorites and history stored under the address of servers now keyed by host name, see
+/// `games::legacy_server_key`.
+fn migrate_host_keys(
+    resources: &Rc<Resources>,
+    server_list: &ServerStore,
+    activity_log: &mut activity::ActivityLog,
+    ping_history: &mut activity::PingHistory,
+) {
+    let moves = {
+        let settings = resources.settings.lock().unwrap();
+        let recent_servers = resources.recent_servers.lock().unwrap();
+        server_list
+            .get_servers()
+            .into_iter()
+            .filter_map(|(game, srv)| {
+                let old_addr = games::legacy_server_key(&srv)?;
+                let stored = settings.is_favorite(game, &old_addr)
+                    || settings.is_pinned(game, &old_addr)
+                    || settings.favorite_details(game, &old_addr).is_some()
+                    || recent_servers
+                        .iter()
+                        .any(|recent| recent.server.game == game && recent.server.addr == old_addr);
+                if stored {
+                    Some((game, old_addr, games::server_key(&srv)))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+    if moves.is_empty() {
+        return;
+    }
+
+    for (game, old_addr, new_addr) in &moves {
+        info!("{} server {} is now known as {}", game, old_addr, new_addr);
+    }
+    move_servers(resources, server_list, &moves, activity_log, ping_history);
+}
+
+/// Moves the favorite, pin and history of servers from the old address to the new one, and updates the rows
+/// at the new addresses.
+fn move_servers(
+    resources: &Rc<Resources>,
+    server_list: &ServerStore,
+    moves: &[(games::Game, String, String)],
+    activity_log: &mut activity::ActivityLog,
+    ping_history: &mut activity::PingHistory,
+) {
+    {
+        let mut settings = resources.settings.lock().unwrap();
+        let mut recent_servers = resources.recent_servers.lock().unwrap();
+        for (game, old_addr, new_addr) in moves {
+            let from = settings::Favorite {
+                game: *game,
+                addr: old_addr.clone(),
+            };
+            let to = settings::Favorite {
+                game: *game,
+                addr: new_addr.clone(),
+            };
+            settings.move_favorite(*game, old_addr, new_addr.clone());
+            recent_servers.rename(&from, &to);
+            activity_log.rename(&from, &to);
+            ping_history.rename(&from, &to);
+        }
+        if let Err(e) = settings.save() {
+            warn!("Failed to save settings: {}", e);
+        }
+        if let Err(e) = recent_servers.save() {
+            warn!("Failed to save recent servers: {}", e);
+        }
+    }
+
+    // Rows at the new addresses were listed before the pin and alias moved over
+    let new_addrs = moves
+        .iter()
+        .map(|(_, _, new_addr)| new_addr.clone())
+        .collect::<HashSet<_>>();
+    server_list.0.foreach(|_, _, iter| {
+        let (game_id, srv) = server_list.get_server(iter);
+        let key = games::server_key(&srv);
+        if new_addrs.contains(&key) {
+            // Released before touching the row, whose refilter locks the settings again
+            let (pinned, alias) = {
+                let settings = resources.settings.lock().unwrap();
+                (
+                    settings.is_pinned(game_id, &key),
+                    settings
+                        .favorite_details(game_id, &key)
+                        .and_then(|details| details.alias.clone()),
+                )
+            };
+            server_list.set_pinned(iter, pinned);
+            if let Some(alias) = alias {
+                server_list.set_name(iter, &alias);
+            }
+        }
+        false
+    });
+    resources
+        .ui
+        .get_object::<ServerListFilter, _>()
+        .unwrap()
+        .0
+        .refilter();
+}
+
+/// Notifies about favorites that got players since the last refresh while the window is hidden.
+fn notify_active_favorites(
+    app: &gtk::Application,
+    resources: &Rc<Resources>,
+    favorite_counts: &mut HashMap<settings::Favorite, u64>,
+    entries: &[tray::TrayEntry],
+) {
+    let window = resources.ui.get_object::<MainWindow, _>().unwrap().0;
+    let enabled = resources.settings.lock().unwrap().notify_favorites && !window.is_visible();
+
+    for entry in entries {
+        let num_clients = entry.num_clients.unwrap_or(0);
+
+        // Nothing to compare against on the first refresh
+        let was_empty = favorite_counts.insert(entry.favorite.clone(), num_clients) == Some(0);
+
+        if enabled && was_empty && num_clients > 0 {
+            let notification =
+                gio::Notification::new(entry.name.as_ref().unwrap_or(&entry.favorite.addr));
+            notification.set_body(Some(&format!(
+                "{} players on {} server",
+                num_clients, entry.favorite.game
+            )));
+            app.send_notification(
+                Some(&format!(
+                    "{}/{}",
+                    entry.favorite.game.id(),
+                    entry.favorite.addr
+                )),
+                &notification,
+            );
+        }
+    }
+}
+
+fn update_activity_chart(
+    resources: &Rc<Resources>,
+    favorite: &settings::Favorite,
+    activity_log: &activity::ActivityLog,
+    chart: &ActivityChart,
+) {
+    let span = match resources
+        .ui
+        .get_object::<ActivitySpan, _>()
+        .unwrap()
+        .0
+        .get_active_id()
+        .as_ref()
+        .map(|s| s.as_str())
+    {
+        Some("week") => activity::WEEK,
+        _ => activity::DAY,
+    };
+
+    chart.set_samples(
+        &activity_log.samples(favorite, activity::now().saturating_sub(span)),
+        span,
+    );
+}
+
+/// Fills and shows the server details popover.
+fn show_server_info(
+    resources: &Rc<Resources>,
+    server_list: &ServerStore,
+    iter: &gtk::TreeIter,
+    activity_log: &activity::ActivityLog,
+    chart: &ActivityChart,
+    ping_history: &activity::PingHistory,
+    sparkline: &PingSparkline,
+) {
+    let (game, srv) = server_list.get_server(iter);
+    let favorite = settings::Favorite {
+        game,
+        addr: games::server_key(&srv),
+    };
+
+    let name = server_list
+        .0
+        .get_value(iter, ServerStoreColumn::Name as i32)
+        .get::<String>()
+        .unwrap_or_default();
+
+    resources
+        .ui
+        .get_object::<ServerInfoName, _>()
+        .unwrap()
+        .0
+        .set_text(&name);
+    resources
+        .ui
+        .get_object::<ServerInfoHost, _>()
+        .unwrap()
+        .0
+        .set_text(&favorite.addr);
+    resources
+        .ui
+        .get_object::<ServerInfoGame, _>()
+        .unwrap()
+        .0
+        .set_text(&resources.settings.lock().unwrap().game_name(game));
+    resources
+        .ui
+        .get_object::<ServerInfoGameId, _>()
+        .unwrap()
+        .0
+        .set_text(game.id());
+    resources
+        .ui
+        .get_object::<ServerInfoMap, _>()
+        .unwrap()
+        .0
+        .set_text(srv.map.as_ref().map(|s| s.as_str()).unwrap_or(""));
+    resources
+        .ui
+        .get_object::<ServerInfoPlayers, _>()
+        .unwrap()
+        .0
+        .set_text(&format!(
+            "{}/{}",
+            srv.num_clients.unwrap_or(0),
+            srv.max_clients.unwrap_or(0)
+        ));
+    resources
+        .ui
+        .get_object::<ServerInfoPing, _>()
+        .unwrap()
+        .0
+        .set_text(
+            &srv.ping
+                .map(|dur| format!("{} ms", dur.as_millis()))
+                .unwrap_or_default(),
+        );
+
+    let os = games::server_os(game, &srv);
+    resources
+        .ui
+        .get_object::<ServerInfoOSTitle, _>()
+        .unwrap()
+        .0
+        .set_visible(os.is_some());
+    let os_label = resources.ui.get_object::<ServerInfoOS, _>().unwrap().0;
+    os_label.set_visible(os.is_some());
+    os_label.set_text(os.as_ref().map(|s| s.as_str()).unwrap_or(""));
+
+    let description = games::server_description(&srv);
+    resources
+        .ui
+        .get_object::<ServerInfoDescriptionTitle, _>()
+        .unwrap()
+        .0
+        .set_visible(description.is_some());
+    let description_label = resources
+        .ui
+        .get_object::<ServerInfoDescription, _>()
+        .unwrap()
+        .0;
+    description_label.set_visible(description.is_some());
+    description_label.set_text(description.as_ref().map(|s| s.as_str()).unwrap_or(""));
+
+    let show_ping_history = resources.settings.lock().unwrap().show_ping_history;
+    resources
+        .ui
+        .get_object::<ServerInfoPingHistoryTitle, _>()
+        .unwrap()
+        .0
+        .set_visible(show_ping_history);
+    sparkline.area.set_visible(show_ping_history);
+    if show_ping_history {
+        sparkline.set_samples(
+            ping_history
+                .samples(&favorite)
+                .into_iter()
+                .map(Some)
+                .collect(),
+        );
+    }
+
+    // Activity is only recorded for favorites
+    let is_favorite = resources
+        .settings
+        .lock()
+        .unwrap()
+        .is_favorite(game, &favorite.addr);
+    resources
+        .ui
+        .get_object::<ActivityBox, _>()
+        .unwrap()
+        .0
+        .set_visible(is_favorite);
+    if is_favorite {
+        update_activity_chart(resources, &favorite, activity_log, chart);
+    }
+
+    resources
+        .ui
+        .get_object::<ServerInfoPopover, _>()
+        .unwrap()
+        .0
+        .popup();
+}
+
+/// Player lists are queried again once older than this
+const PLAYERS_CACHE_TTL: Duration = Duration::from_secs(30);
+
+type PlayersResult = Result<Vec<rgs::models::Player>, String>;
+
+fn fill_players_popover(resources: &Resources, game: games::Game, result: &PlayersResult) {
+    let spinner = resources.ui.get_object::<PlayersSpinner, _>().unwrap().0;
+    let status = resources.ui.get_object::<PlayersStatus, _>().unwrap().0;
+    let list = resources.ui.get_object::<PlayersList, _>().unwrap().0;
+
+    spinner.stop();
+    spinner.hide();
+    for row in list.get_children() {
+        list.remove(&row);
+    }
+
+    match result {
+        Ok(players) if players.is_empty() => {
+            status.set_text("No players");
+            status.show();
+        }
+        Ok(players) => {
+            status.hide();
+
+            let name_morpher = &resources.game_list.0[&game].name_morpher;
+            for player in players {
+                let score = match player.info.get("score") {
+                    Some(serde_json::Value::String(v)) => Some(v.clone()),
+                    Some(serde_json::Value::Number(v)) => Some(v.to_string()),
+                    _ => None,
+                };
+                let details = score
+                    .map(|v| format!("score {}", v))
+                    .into_iter()
+                    .chain(player.ping.map(|v| format!("{} ms", v)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                let label = gtk::Label::new(Some(&format!(
+                    "{}  <small>{}</small>",
+                    glib::markup_escape_text(&name_morpher.morph(player.name.clone())),
+                    details
+                )));
+                label.set_use_markup(true);
+                label.set_xalign(0.0);
+                list.add(&label);
+            }
+        }
+        Err(e) => {
+            status.set_text(&format!("Failed to query players: {}", e));
+            status.show();
+        }
+    }
+
+    list.show_all();
+}
+
+/// Server whose details are currently shown, if any.
+fn shown_server_info(resources: &Resources) -> Option<settings::Favorite> {
+    if !resources
+        .ui
+        .get_object::<ServerInfoPopover, _>()
+        .unwrap()
+        .0
+        .is_visible()
+    {
+        return None;
+    }
+
+    let game = resources
+        .ui
+        .get_object::<ServerInfoGameId, _>()
+        .unwrap()
+        .0
+        .get_text()
+        .and_then(|id| games::Game::from_id(&id))?;
+    let addr = resources
+        .ui
+        .get_object::<ServerInfoHost, _>()
+        .unwrap()
+        .0
+        .get_text()?
+        .to_string();
+
+    Some(settings::Favorite { game, addr })
+}
+
+/// Launch options common to all games, before asking for passwords.
+/// Launch data for `addr` with the player name and, if `server` is a favorite, its launch profile.
+fn launch_data(
+    resources: &Resources,
+    server: &settings::Favorite,
+    addr: String,
+) -> games::LaunchData {
+    let settings = resources.settings.lock().unwrap();
+    let profile = settings
+        .favorite_details(server.game, &server.addr)
+        .map(|details| details.profile.clone())
+        .unwrap_or_default();
+
+    games::LaunchData {
+        addr,
+        password: None,
+        player_name: Some(settings.player_name.clone()).filter(|v| !v.is_empty()),
+        company: None,
+        company_password: None,
+        flatpak_ref: profile.flatpak_ref,
+        extra_args: profile.extra_args,
+    }
+}
+
+/// Hands the password of the favorite stored in the keyring, if any, to `f`. The keyring is asked in the
+/// background, as it may take a while or prompt to be unlocked.
+fn with_stored_password<F>(resources: &Resources, server: &settings::Favorite, f: F)
+where
+    F: FnOnce(Option<String>) + 'static,
+{
+    let password_ref = resources
+        .settings
+        .lock()
+        .unwrap()
+        .favorite_details(server.game, &server.addr)
+        .and_then(|details| details.password_ref.clone());
+
+    match password_ref {
+        Some(password_ref) => run_in_background(
+            move || secrets::lookup(&password_ref),
+            |res| {
+                f(res.unwrap_or_else(|e| {
+                    warn!("Failed to look up the stored password: {}", e);
+                    None
+                }))
+            },
+        ),
+        None => f(None),
+    }
+}
+
+/// Seconds between checks whether a launched game is still running
+const LAUNCH_WATCH_INTERVAL: u64 = 1;
+
+/// Decides about an instance of the game launched earlier that is still running, asking unless the choice
+/// was remembered. Returns whether to launch another one.
+fn check_already_running(resources: &Rc<Resources>, game_id: games::Game) -> bool {
+    let (pid, remembered) = {
+        let running_games = resources.running_games.lock().unwrap();
+        (
+            running_games.latest(game_id),
+            running_games.decision(game_id),
+        )
+    };
+
+    let pid = match pid {
+        Some(v) => v,
+        None => {
+            return true;
+        }
+    };
+
+    let decision = match remembered {
+        Some(v) => v,
+        None => match dialogs::ask_already_running(resources, game_id) {
+            Some((decision, remember)) => {
+                if remember {
+                    resources
+                        .running_games
+                        .lock()
+                        .unwrap()
+                        .remember(game_id, decision);
+                }
+                decision
+            }
+            None => {
+                info!("Launch of {} cancelled", game_id);
+                return false;
+            }
+        },
+    };
+
+    match decision {
+        session::AlreadyRunning::LaunchAnyway => true,
+        session::AlreadyRunning::BringToFront => {
+            if !session::bring_to_front(pid) {
+                warn!("Found no window of {} to bring to front", game_id);
+                dialogs::show_message(
+                    &resources.ui.get_object::<MainWindow, _>().unwrap().0,
+                    gtk::MessageType::Info,
+                    "The game window could not be brought to front. Switch to it from the desktop.",
+                );
+            }
+            false
+        }
+    }
+}
+
+/// Launcher of the game, `None` after telling the user if the game is hidden. Favorites and recent servers of
+/// hidden games are still listed.
+fn game_launcher(
+    resources: &Rc<Resources>,
+    game_id: games::Game,
+) -> Option<Arc<dyn games::Launcher>> {
+    match resources.game_list.0.get(&game_id) {
+        Some(entry) => Some(entry.launcher.clone()),
+        None => {
+            dialogs::show_message(
+                &resources.ui.get_object::<MainWindow, _>().unwrap().0,
+                gtk::MessageType::Error,
+                &format!(
+                    "{} is hidden. Show it in Preferences and restart to join its servers.",
+                    resources.settings.lock().unwrap().game_name(game_id)
+                ),
+            );
+            None
+        }
+    }
+}
+
+fn launch_game(
+    resources: &Rc<Resources>,
+    game_id: games::Game,
+    data: games::LaunchData,
+    need_pass: Option<bool>,
+) {
+    let game_launcher = match game_launcher(resources, game_id) {
+        Some(v) => v,
+        None => {
+            return;
+        }
+    };
+
+    if !check_already_running(resources, game_id) {
+        return;
+    }
+
+    if let Some(warning) = game_launcher.launch_confirmation(&data) {
+        if !dialogs::confirm(resources, &warning) {
+            info!("Launch of {} cancelled", game_id);
+            return;
+        }
+    }
+
+    println!("Connecting to {} server at {}", game_id, data.addr);
+
+    {
+        let mut recent_servers = resources.recent_servers.lock().unwrap();
+        recent_servers.record(
+            settings::Favorite {
+                game: game_id,
+                addr: data.addr.clone(),
+            },
+            need_pass,
+        );
+        if let Err(e) = recent_servers.save() {
+            warn!("Failed to save recent servers: {}", e);
+        }
+    }
+
+    // A game quitting right after joining with a password was most likely rejected
+    let retry_window =
+        Duration::from_secs(resources.settings.lock().unwrap().password_retry_window);
+    let watch_exit = data.password.is_some()
+        && game_launcher.exits_on_rejection()
+        && retry_window > Duration::from_secs(0);
+
+    let (exit_sink, exit_faucet) = channel();
+    std::thread::spawn({
+        let data = data.clone();
+        let running_games = resources.running_games.clone();
+        move || {
+            let started = Instant::now();
+            let mut child = match game_launcher.launch_cmd(&data).map(|mut cmd| cmd.spawn()) {
+                Some(Ok(child)) => child,
+                Some(Err(e)) => {
+                    warn!("Failed to launch {}: {}", game_id, e);
+                    return;
+                }
+                None => {
+                    return;
+                }
+            };
+
+            let pid = child.id();
+            running_games.lock().unwrap().started(game_id, pid);
+
+            // Dropped once the retry window is over, which ends the watch on the UI side
+            let mut exit_sink = Some(exit_sink).filter(|_| watch_exit);
+            loop {
+                match child.try_wait() {
+                    Ok(None) => {}
+                    Ok(Some(_)) => break,
+                    Err(e) => {
+                        warn!("Lost track of {}: {}", game_id, e);
+                        break;
+                    }
+                }
+                if started.elapsed() >= retry_window {
+                    exit_sink = None;
+                }
+                std::thread::sleep(Duration::from_secs(LAUNCH_WATCH_INTERVAL));
+            }
+
+            running_games.lock().unwrap().exited(game_id, pid);
+            if let Some(exit_sink) = exit_sink {
+                let _ = exit_sink.send(());
+            }
+        }
+    });
+
+    if watch_exit {
+        gtk::timeout_add_seconds(LAUNCH_WATCH_INTERVAL as u32, {
+            let resources = resources.clone();
+            move || {
+                glib::Continue(match exit_faucet.try_recv() {
+                    Ok(()) => {
+                        info!(
+                            "{} quit right after launch, asking for the password again",
+                            game_id
+                        );
+                        request_password(&resources, game_id, data.clone());
+                        false
+                    }
+                    Err(TryRecvError::Empty) => true,
+                    Err(TryRecvError::Disconnected) => false,
+                })
+            }
+        });
+    }
+}
+
+/// Asks for the server password, then launches the game. A password in `data` is from a failed attempt and is offered again.
+fn request_password(resources: &Rc<Resources>, game_id: games::Game, data: games::LaunchData) {
+    let password_request = resources.ui.get_object::<PasswordRequest, _>().unwrap().0;
+    let password_entry = resources.ui.get_object::<PasswordEntry, _>().unwrap().0;
+    let connect_button = resources
+        .ui
+        .get_object::<ConnectWithPassword, _>()
+        .unwrap()
+        .0;
+
+    match data.password.as_ref() {
+        Some(password) => {
+            password_entry.set_text(password);
+            password_entry.select_region(0, -1);
+            password_entry.set_tooltip_text(Some(
+                "The game quit right after joining, the password may be wrong.",
+            ));
+        }
+        None => password_entry.set_tooltip_text(None),
+    }
+    connect_button.set_sensitive(password_entry.get_text_length() > 0);
+
+    let changed_handler = password_entry.connect_changed({
+        let connect_button = connect_button.clone();
+        move |w| {
+            connect_button.set_sensitive(w.get_text_length() > 0);
+        }
+    });
+
+    let clicked_handler = connect_button.connect_clicked({
+        let resources = resources.clone();
+        let password_request = password_request.clone();
+        let password_entry = password_entry.clone();
+        move |_| {
+            password_request.popdown();
+            launch_game(
+                &resources,
+                game_id,
+                games::LaunchData {
+                    password: password_entry.get_text().map(|s| s.to_string()),
+                    ..data.clone()
+                },
+                Some(true),
+            )
+        }
+    });
+
+    // Handlers are bound to this request, drop them once the popover is gone
+    let handlers = Rc::new(RefCell::new(Some((changed_handler, clicked_handler))));
+    let closed_handler = Rc::new(RefCell::new(None));
+    *closed_handler.borrow_mut() = Some(password_request.connect_closed({
+        let password_entry = password_entry.clone();
+        let closed_handler = closed_handler.clone();
+        move |w| {
+            if let Some((changed_handler, clicked_handler)) = handlers.borrow_mut().take() {
+                password_entry.disconnect(changed_handler);
+                connect_button.disconnect(clicked_handler);
+            }
+            if let Some(handler) = closed_handler.borrow_mut().take() {
+                w.disconnect(handler);
+            }
+        }
+    }));
+
+    password_request.popup();
+}
+
+/// Joins the server at `addr`, with the password and launch profile stored for it as the `server` favorite.
+fn connect_to_server(
+    resources: &Rc<Resources>,
+    server: settings::Favorite,
+    addr: String,
+    need_pass: Option<bool>,
+) {
+    let game_id = server.game;
+    let game_launcher = match game_launcher(resources, game_id) {
+        Some(v) => v,
+        None => {
+            return;
+        }
+    };
+
+    // Joined from the game by the user
+    if game_launcher.copies_address() {
+        gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(&addr);
+        info!("Copied address of {} server {}", game_id, addr);
+        return;
+    }
+
+    with_stored_password(resources, &server.clone(), {
+        let resources = resources.clone();
+        move |password| {
+            let data = games::LaunchData {
+                password: password.clone(),
+                ..launch_data(&resources, &server, addr)
+            };
+
+            if game_id == games::Game::OpenTTD {
+                // OpenTTD distinguishes server and company passwords
+                let ask_password = need_pass == Some(true) && password.is_none();
+                if let Some(join) = dialogs::request_openttd_join(&resources, ask_password) {
+                    launch_game(
+                        &resources,
+                        game_id,
+                        games::LaunchData {
+                            password: join.password.or(password),
+                            company: join.company,
+                            company_password: join.company_password,
+                            ..data
+                        },
+                        need_pass,
+                    )
+                }
+            } else if password.is_some() {
+                launch_game(&resources, game_id, data, need_pass)
+            } else if let Some(true) = need_pass {
+                request_password(&resources, game_id, data)
+            } else {
+                launch_game(&resources, game_id, data, need_pass)
+            }
+        }
+    });
+}
+
+/// Runs `work` on a background thread and hands its result to `f` on the UI thread.
+fn run_in_background<T, W, F>(work: W, f: F)
+where
+    T: Send + 'static,
+    W: FnOnce() -> T + Send + 'static,
+    F: FnOnce(T) + 'static,
+{
+    let (result_sink, result_faucet) = channel();
+    std::thread::spawn(move || {
+        let _ = result_sink.send(work());
+    });
+
+    let mut f = Some(f);
+    gtk::timeout_add(100, move || {
+        glib::Continue(match result_faucet.try_recv() {
+            Ok(result) => {
+                if let Some(f) = f.take() {
+                    f(result);
+                }
+                false
+            }
+            Err(TryRecvError::Empty) => true,
+            Err(TryRecvError::Disconnected) => false,
+        })
+    });
+}
+
+/// Runs the network self-test in the background and hands the outcome to `f` on the UI thread.
+fn run_network_test<F>(f: F)
+where
+    F: FnOnce(Result<games::selftest::SelfTest, String>) + 'static,
+{
+    run_in_background(|| games::selftest::run().map_err(|e| e.to_string()), f)
+}
+
+/// Interval between pings of the latency monitor, in seconds
+const MONITOR_INTERVAL: u32 = 1;
+/// Pings without a reply within this time count as lost
+const MONITOR_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Pings the server every second until stopped, plotting and logging the replies, then shows a summary.
+fn monitor_latency(
+    resources: &Rc<Resources>,
+    executor: tokio::runtime::TaskExecutor,
+    addr: std::net::IpAddr,
+    title: &str,
+) {
+    let window = resources.ui.get_object::<MainWindow, _>().unwrap().0;
+
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some(&format!("Latency of {}", title)),
+        Some(&window),
+        gtk::DialogFlags::DESTROY_WITH_PARENT,
+        &[("_Stop", gtk::ResponseType::Close)],
+    );
+
+    let grid = gtk::Grid::new();
+    grid.set_row_spacing(5);
+    grid.set_column_spacing(10);
+    grid.set_property_margin(15);
+
+    let area = gtk::DrawingArea::new();
+    area.set_size_request(300, 60);
+    area.set_hexpand(true);
+    let sparkline = PingSparkline::new(area.clone());
+    grid.attach(&area, 0, 0, 1, 1);
+
+    let status = gtk::Label::new(Some("Waiting for the first reply…"));
+    status.set_halign(gtk::Align::Start);
+    grid.attach(&status, 0, 1, 1, 1);
+
+    dialog.get_content_area().add(&grid);
+    dialog.show_all();
+
+    // A pinger of its own, as the shared one caches and limits pings
+    let pinger = Arc::new(games::LazyPinger::default());
+    let samples = Rc::new(RefCell::new(Vec::<Option<u64>>::new()));
+    let (ping_sink, ping_faucet) = channel::<Option<u64>>();
+    let running = Rc::new(Cell::new(true));
+
+    gtk::timeout_add_seconds(MONITOR_INTERVAL, {
+        let samples = samples.clone();
+        let running = running.clone();
+        move || {
+            if !running.get() {
+                return glib::Continue(false);
+            }
+
+            for ping in ping_faucet.try_iter() {
+                match ping {
+                    Some(ping) => info!("Ping of {}: {} ms", addr, ping),
+                    None => info!("Ping of {}: lost", addr),
+                }
+
+                let mut samples = samples.borrow_mut();
+                samples.push(ping);
+
+                let recent = samples
+                    .iter()
+                    .rev()
+                    .take(activity::MAX_PING_SAMPLES)
+                    .rev()
+                    .copied()
+                    .collect();
+                sparkline.set_samples(recent);
+                status.set_text(&activity::PingStats::new(&samples).to_string());
+            }
+
+            if games::icmp_available() == Some(false) {
+                status.set_text("Pings cannot be measured without ICMP access.");
+                return glib::Continue(false);
+            }
+
+            let ping = tokio::timer::Timeout::new(pinger.ping(addr), MONITOR_TIMEOUT);
+            let ping_sink = ping_sink.clone();
+            executor.spawn(
+                async move {
+                    let _ = ping_sink.send(match ping.compat().await {
+                        Ok(Some(ping)) => Some(ping.as_millis() as u64),
+                        _ => None,
+                    });
+                }
+                .map(|_| Ok(()))
+                .boxed()
+                .compat(),
+            );
+
+            glib::Continue(true)
+        }
+    });
+
+    dialog.connect_response({
+        let resources = resources.clone();
+        let title = title.to_string();
+        move |dialog, _| {
+            running.set(false);
+            dialog.destroy();
+
+            let stats = activity::PingStats::new(&samples.borrow());
+            info!("Latency of {}: {}", addr, stats);
+            dialogs::show_message(
+                &resources.ui.get_object::<MainWindow, _>().unwrap().0,
+                gtk::MessageType::Info,
+                &format!("Latency of {}\n\n{}", title, stats),
+            );
+        }
+    });
+}
+
+fn fill_recent_list(resources: &Rc<Resources>) {
+    let list = resources.ui.get_object::<RecentList, _>().unwrap().0;
+
+    for row in list.get_children() {
+        list.remove(&row);
+    }
+
+    let settings = resources.settings.lock().unwrap();
+    let recent_servers = resources.recent_servers.lock().unwrap();
+    for recent in recent_servers.iter() {
+        let time = glib::DateTime::new_from_unix_local(recent.time as i64)
+            .format("%x %R")
+            .map(|s| s.to_string())
+            .unwrap_or_default();
+
+        let label = gtk::Label::new(Some(&format!(
+            "<b>{}</b>  {}\n<small>{}</small>",
+            glib::markup_escape_text(&settings.game_name(recent.server.game)),
+            glib::markup_escape_text(&recent.server.addr),
+            time
+        )));
+        label.set_use_markup(true);
+        label.set_xalign(0.0);
+        label.set_margin_start(6);
+        label.set_margin_end(6);
+        list.add(&label);
+    }
+
+    if recent_servers.iter().next().is_none() {
+        let label = gtk::Label::new(Some("No recent servers"));
+        label.set_sensitive(false);
+        label.set_margin_start(6);
+        label.set_margin_end(6);
+        list.add(&label);
+    }
+
+    list.show_all();
+}
+
+/// Brings the window in line with imported state, see `dialogs::import_state`. Games hidden or shown and
+/// settings applied on restart take effect on the next start, as queriers are set up at startup.
+fn reload_state(
+    resources: &Rc<Resources>,
+    server_list: &ServerStore,
+    filter_data: &Mutex<filters::Filters>,
+) {
+    fill_recent_list(resources);
+
+    resources
+        .ui
+        .get_object::<DimEmptyToggle, _>()
+        .unwrap()
+        .0
+        .set_active(resources.settings.lock().unwrap().dim_empty_servers);
+
+    // Favorites, pins and aliases
+    if let Some(iter) = server_list.0.get_iter_first() {
+        loop {
+            let (game, srv) = server_list.get_server(&iter);
+            let addr = games::server_key(&srv);
+            let (pinned, alias) = {
+                let settings = resources.settings.lock().unwrap();
+                (
+                    settings.is_pinned(game, &addr),
+                    settings
+                        .favorite_details(game, &addr)
+                        .and_then(|details| details.alias.clone()),
+                )
+            };
+
+            server_list.set_pinned(&iter, pinned);
+            server_list.set_name(
+                &iter,
+                &alias.unwrap_or_else(|| {
+                    resources.game_list.0[&game]
+                        .name_morpher
+                        .morph(srv.name.clone().unwrap_or_default())
+                }),
+            );
+            update_score(resources, server_list, &iter);
+
+            if !server_list.0.iter_next(&iter) {
+                break;
+            }
+        }
+    }
+
+    apply_filters(resources, filter_data);
+}
+
+/// Window width below which the automatic layout switches to compact rows
+const COMPACT_LAYOUT_WIDTH: i32 = 720;
+
+/// Brings the compact server list in line with the sorted and filtered model of the table. Existing rows are
+/// updated in place, rows are only added or removed as the number of servers changes.
+fn fill_compact_list(resources: &Rc<Resources>, rows: &mut Vec<CompactServerRow>) {
+    let list = resources.ui.get_object::<CompactServerList, _>().unwrap().0;
+    let sort_model = resources.ui.get_object::<ServerListSort, _>().unwrap().0;
+    let filter_model = resources.ui.get_object::<ServerListFilter, _>().unwrap().0;
+    let server_list = resources.ui.get_object::<ServerStore, _>().unwrap();
+
+    let mut count = 0;
+    sort_model.foreach(|_, _, iter| {
+        let filter_iter = sort_model.convert_iter_to_child_iter(iter);
+        let data =
+            server_list.get_display_server(&filter_model.convert_iter_to_child_iter(&filter_iter));
+        let icon = resources.game_icons.icon(data.game_id);
+
+        match rows.get_mut(count) {
+            Some(row) => row.update(&data, Some(&icon)),
+            None => {
+                let row = CompactServerRow::new(&data, Some(&icon));
+                list.add(&row.row);
+                rows.push(row);
+            }
+        }
+        count += 1;
+
+        false
+    });
+
+    for row in rows.drain(count..) {
+        list.remove(&row.row);
+    }
+}
+
+/// Sets up the compact server list and switching between it and the table.
+fn build_compact_list(app: &gtk::Application, resources: &Rc<Resources>) {
+    let window = resources.ui.get_object::<MainWindow, _>().unwrap().0;
+    let stack = resources.ui.get_object::<ServerListStack, _>().unwrap().0;
+    let sort_model = resources.ui.get_object::<ServerListSort, _>().unwrap().0;
+    let rows = Rc::new(RefCell::new(Vec::<CompactServerRow>::new()));
+
+    let apply_layout = Rc::new({
+        let resources = resources.clone();
+        let stack = stack.clone();
+        move |width: i32| {
+            let compact = match resources.settings.lock().unwrap().list_layout {
+                settings::ListLayout::Auto => width < COMPACT_LAYOUT_WIDTH,
+                settings::ListLayout::Wide => false,
+                settings::ListLayout::Compact => true,
+            };
+
+            stack.set_visible_child_name(if compact { "compact" } else { "wide" });
+        }
+    });
+
+    window.connect_configure_event({
+        let apply_layout = apply_layout.clone();
+        move |_, ev| {
+            apply_layout(ev.get_size().0 as i32);
+            Inhibit(false)
+        }
+    });
+
+    let list_layout = gio::SimpleAction::new_stateful(
+        "list-layout",
+        glib::VariantTy::new("s").ok(),
+        &resources
+            .settings
+            .lock()
+            .unwrap()
+            .list_layout
+            .id()
+            .to_variant(),
+    );
+    list_layout.connect_activate({
+        let resources = resources.clone();
+        let window = window.clone();
+        move |action, param| {
+            if let Some(layout) = param
+                .and_then(|v| v.get_str())
+                .and_then(settings::ListLayout::from_id)
+            {
+                action.set_state(&layout.id().to_variant());
+
+                let mut settings = resources.settings.lock().unwrap();
+                settings.list_layout = layout;
+                if let Err(e) = settings.save() {
+                    warn!("Failed to save settings: {}", e);
+                }
+            }
+
+            apply_layout(window.get_allocated_width());
+        }
+    });
+    app.add_action(&list_layout);
+
+    // Rebuilding on every inserted row would be too slow, so changes are picked up periodically
+    let dirty = Rc::new(Cell::new(true));
+    sort_model.connect_row_inserted({
+        let dirty = dirty.clone();
+        move |_, _, _| dirty.set(true)
+    });
+    sort_model.connect_row_deleted({
+        let dirty = dirty.clone();
+        move |_, _| dirty.set(true)
+    });
+    sort_model.connect_row_changed({
+        let dirty = dirty.clone();
+        move |_, _, _| dirty.set(true)
+    });
+    sort_model.connect_rows_reordered({
+        let dirty = dirty.clone();
+        move |_, _, _, _| dirty.set(true)
+    });
+    gtk::timeout_add(500, {
+        let resources = resources.clone();
+        let rows = rows.clone();
+        move || {
+            if dirty.get()
+                && stack.get_visible_child_name().as_ref().map(|s| s.as_str()) == Some("compact")
+            {
+                dirty.set(false);
+                fill_compact_list(&resources, &mut rows.borrow_mut());
+            }
+            glib::Continue(true)
+        }
+    });
+
+    resources
+        .ui
+        .get_object::<CompactServerList, _>()
+        .unwrap()
+        .0
+        .connect_row_activated({
+            let resources = resources.clone();
+            move |_, row| {
+                let server = rows.borrow().get(row.get_index() as usize).cloned();
+
+                if let Some(server) = server {
+                    connect_to_server(
+                        &resources,
+                        settings::Favorite {
+                            game: server.game_id,
+                            addr: server.key,
+                        },
+                        server.addr,
+                        server.need_pass,
+                    );
+                }
+            }
+        });
+}
+
+/// Sets up the side pane listing one game next to the main list.
+///
+/// The pane shares the server store with the main list but ignores the filters,
+/// so two games can be browsed at once.
+fn build_side_pane(app: &gtk::Application, resources: &Rc<Resources>) {
+    let pane = resources.ui.get_object::<SidePane, _>().unwrap().0;
+    let game_chooser = resources.ui.get_object::<SidePaneGame, _>().unwrap().0;
+    let filter_model = resources.ui.get_object::<SidePaneFilter, _>().unwrap().0;
+    let sort_model = resources.ui.get_object::<SidePaneSort, _>().unwrap().0;
+    let server_list = resources.ui.get_object::<ServerStore, _>().unwrap();
+
+    {
+        let settings = resources.settings.lock().unwrap();
+        for (id, _) in resources.game_list.0.iter() {
+            game_chooser.append(Some(id.id()), &settings.game_name(*id));
+        }
+    }
+    game_chooser.set_active(Some(0));
+
+    filter_model.set_visible_func({
+        let game_chooser = game_chooser.clone();
+        let server_list = server_list.clone();
+        move |model, iter| {
+            let game_id = model
+                .get_value(iter, ServerStoreColumn::GameId as i32)
+                .get::<String>();
+
+            match (game_id, game_chooser.get_active_id()) {
+                (Some(game_id), Some(active)) => {
+                    game_id == active.as_str() && server_list.get_change(iter) != RowChange::Gone
+                }
+                _ => false,
+            }
+        }
+    });
+
+    game_chooser.connect_changed({
+        let filter_model = filter_model.clone();
+        move |_| filter_model.refilter()
+    });
+
+    sort_model.set_sort_func(gtk::SortColumn::Index(ServerStoreColumn::Ping as u32), {
+        let resources = resources.clone();
+        let sort_model = sort_model.clone();
+        move |model, a, b| {
+            unknown_ping_order(&resources, &sort_model, model.upcast_ref(), a, b).unwrap_or_else(
+                || compare_column(model.upcast_ref(), a, b, ServerStoreColumn::Ping),
+            )
+        }
+    });
+
+    resources
+        .ui
+        .get_object::<SidePanePingColumn, _>()
+        .unwrap()
+        .0
+        .set_cell_data_func(
+            &resources
+                .ui
+                .get_object::<SidePanePingRenderer, _>()
+                .unwrap()
+                .0,
+            Some(Box::new(|_, cell, model, iter| {
+                render_ping(cell, model, iter)
+            })),
+        );
+
+    let side_pane = gio::SimpleAction::new_stateful("side-pane", None, &false.to_variant());
+    side_pane.connect_activate(move |action, _| {
+        let value = !action
+            .get_state()
+            .and_then(|v| v.get::<bool>())
+            .unwrap_or(false);
+        action.set_state(&value.to_variant());
+
+        if value {
+            pane.show_all();
+        } else {
+            pane.hide();
+        }
+    });
+    app.add_action(&side_pane);
+
+    resources
+        .ui
+        .get_object::<SidePaneView, _>()
+        .unwrap()
+        .0
+        .connect_row_activated({
+            let resources = resources.clone();
+            move |_, path, _| {
+                let iter = filter_model.convert_iter_to_child_iter(
+                    &sort_model.convert_iter_to_child_iter(&sort_model.get_iter(path).unwrap()),
+                );
+                let (game_id, srv) = server_list.get_server(&iter);
+
+                connect_to_server(
+                    &resources,
+                    settings::Favorite::of_server(game_id, &srv),
+                    srv.addr.to_string(),
+                    srv.need_pass,
+                );
+            }
+        });
+}
+
+/// Seconds between the rounds of info queries in live mode
+const LIVE_UPDATE_INTERVAL: u32 = 45;
+
+/// Servers from the top of the list kept up to date in live mode
+const MAX_LIVE_SERVERS: usize = 100;
+
+/// Info queries of a live mode round in flight at once
+const MAX_LIVE_QUERIES: usize = 16;
+
+/// Sets up live mode, which keeps player counts and maps of the top listed servers up to date between
+/// refreshes. Rounds are skipped while a refresh runs, and their replies dropped if one started meanwhile.
+fn build_live_update(
+    app: &gtk::Application,
+    executor: tokio::runtime::TaskExecutor,
+    resources: &Rc<Resources>,
+    refresh_generation: Arc<AtomicUsize>,
+) {
+    let live_update = gio::SimpleAction::new_stateful(
+        "live-update",
+        None,
+        &resources.settings.lock().unwrap().live_update.to_variant(),
+    );
+    live_update.connect_activate({
+        let resources = resources.clone();
+        move |action, _| {
+            let value = !action
+                .get_state()
+                .and_then(|v| v.get::<bool>())
+                .unwrap_or(false);
+            action.set_state(&value.to_variant());
+
+            let mut settings = resources.settings.lock().unwrap();
+            settings.live_update = value;
+            if let Err(e) = settings.save() {
+                warn!("Failed to save settings: {}", e);
+            }
+        }
+    });
+    app.add_action(&live_update);
+
+    let refresher = resources.ui.get_object::<RefreshButton, _>().unwrap().0;
+    let server_list = resources.ui.get_object::<ServerStore, _>().unwrap();
+    let filter_model = resources.ui.get_object::<ServerListFilter, _>().unwrap().0;
+    let sort_model = resources.ui.get_object::<ServerListSort, _>().unwrap().0;
+    // Set while the replies of a round are awaited
+    let in_flight = Rc::new(Cell::new(false));
+
+    gtk::timeout_add_seconds(LIVE_UPDATE_INTERVAL, {
+        let resources = resources.clone();
+        move || {
+            if !resources.settings.lock().unwrap().live_update
+                || !refresher.is_sensitive()
+                || in_flight.get()
+            {
+                return glib::Continue(true);
+            }
+
+            // Top of the list as shown, after filters and sorting
+            let mut targets = Vec::new();
+            sort_model.foreach(|_, _, iter| {
+                let store_iter = filter_model
+                    .convert_iter_to_child_iter(&sort_model.convert_iter_to_child_iter(iter));
+                if server_list.get_change(&store_iter) != RowChange::Gone {
+                    let (game, srv) = server_list.get_server(&store_iter);
+                    let querier = resources.game_list.0[&game].querier.clone();
+                    if querier.can_query_info() {
+                        targets.push((game, srv.addr, querier));
+                    }
+                }
+                targets.len() >= MAX_LIVE_SERVERS
+            });
+            if targets.is_empty() {
+                return glib::Continue(true);
+            }
+
+            in_flight.set(true);
+            let generation = refresh_generation.load(Ordering::SeqCst);
+            let (result_sink, result_faucet) =
+                channel::<Vec<(games::Game, String, games::ServerInfo)>>();
+            executor.spawn(
+                async move {
+                    let replies = futures::stream::iter(targets)
+                        .map(|(game, addr, querier)| async move {
+                            let res = querier.query_info(addr).compat().await;
+                            res.ok().map(|info| (game, addr.to_string(), info))
+                        })
+                        .buffer_unordered(MAX_LIVE_QUERIES)
+                        .filter_map(futures::future::ready)
+                        .collect::<Vec<_>>()
+                        .await;
+                    let _ = result_sink.send(replies);
+                }
+                .map(|_| Ok(()))
+                .boxed()
+                .compat(),
+            );
+
+            gtk::timeout_add(100, {
+                let resources = resources.clone();
+                let server_list = server_list.clone();
+                let in_flight = in_flight.clone();
+                let refresh_generation = refresh_generation.clone();
+                move || {
+                    let replies = match result_faucet.try_recv() {
+                        Ok(v) => v,
+                        Err(TryRecvError::Empty) => {
+                            return glib::Continue(true);
+                        }
+                        Err(TryRecvError::Disconnected) => {
+                            in_flight.set(false);
+                            return glib::Continue(false);
+                        }
+                    };
+                    in_flight.set(false);
+
+                    // The rows were replaced by a refresh in the meantime
+                    if refresh_generation.load(Ordering::SeqCst) != generation {
+                        return glib::Continue(false);
+                    }
+
+                    let replies = replies
+                        .into_iter()
+                        .map(|(game, host, info)| ((game.id().to_string(), host), info))
+                        .collect::<HashMap<_, _>>();
+                    let mut updated = false;
+                    server_list.0.foreach(|model, _, iter| {
+                        let key = (
+                            model
+                                .get_value(iter, ServerStoreColumn::GameId as i32)
+                                .get::<String>()
+                                .unwrap_or_default(),
+                            model
+                                .get_value(iter, ServerStoreColumn::Host as i32)
+                                .get::<String>()
+                                .unwrap_or_default(),
+                        );
+                        if let Some(info) = replies.get(&key) {
+                            let change = server_list.get_change(iter);
+                            if change != RowChange::Gone && server_list.update_info(iter, info) {
+                                update_score(&resources, &server_list, iter);
+                                // New servers keep their highlight
+                                if change == RowChange::Unchanged {
+                                    server_list.set_change(iter, RowChange::Updated);
+                                }
+                                updated = true;
+                            }
+                        }
+                        false
+                    });
+
+                    if updated {
+                        gtk::timeout_add_seconds(CHANGE_HIGHLIGHT_DURATION, {
+                            let server_list = server_list.clone();
+                            let refresh_generation = refresh_generation.clone();
+                            move || {
+                                if refresh_generation.load(Ordering::SeqCst) == generation {
+                                    server_list.0.foreach(|_, _, iter| {
+                                        if server_list.get_change(iter) == RowChange::Updated {
+                                            server_list.set_change(iter, RowChange::Unchanged);
+                                        }
+                                        false
+                                    });
+                                }
+                                glib::Continue(false)
+                            }
+                        });
+                    }
+
+                    glib::Continue(false)
+                }
+            });
+
+            glib::Continue(true)
+        }
+    });
+}
+
+type CellDataFunc = Box<dyn Fn(&gtk::CellRenderer, &gtk::TreeModel, &gtk::TreeIter)>;
+
+/// Seconds new, disappeared and live-updated servers stay highlighted
+const CHANGE_HIGHLIGHT_DURATION: u32 = 5;
+
+/// Styling shared by all cells of a server row. Servers of unknown player count are not dimmed as empty.
+fn style_row(
+    dim_empty: bool,
+    cell: &gtk::CellRenderer,
+    model: &gtk::TreeModel,
+    iter: &gtk::TreeIter,
+) {
+    let num_clients = model
+        .get_value(iter, ServerStoreColumn::PlayerCount as i32)
+        .get::<i32>()
+        .unwrap_or(UNKNOWN_COUNT);
+    let change = model
+        .get_value(iter, ServerStoreColumn::Change as i32)
+        .get::<i32>()
+        .unwrap_or(0);
+    let filtered_out = model
+        .get_value(iter, ServerStoreColumn::FilteredOutBy as i32)
+        .get::<String>()
+        .is_some();
+
+    let background = if change == RowChange::New as i32 {
+        Some("rgba(46, 194, 126, 0.25)")
+    } else if change == RowChange::Gone as i32 {
+        Some("rgba(224, 27, 36, 0.15)")
+    } else if change == RowChange::Updated as i32 {
+        Some("rgba(53, 132, 228, 0.12)")
+    } else {
+        None
+    };
+
+    cell.set_property(
+        "sensitive",
+        &!((dim_empty && num_clients == 0) || change == RowChange::Gone as i32 || filtered_out),
+    )
+    .unwrap();
+    cell.set_property("cell-background", &background).unwrap();
+    cell.set_property("cell-background-set", &background.is_some())
+        .unwrap();
+}
+
+fn build_server_list_renderers(resources: &Rc<Resources>) {
+    let icon_func = |column: ServerStoreColumn, icon_name: &'static str| -> CellDataFunc {
+        Box::new(move |cell, model, iter| {
+            let value = model
+                .get_value(iter, column as i32)
+                .get::<bool>()
+                .unwrap_or(false);
+
+            cell.set_property("icon-name", &if value { Some(icon_name) } else { None })
+                .unwrap();
+        })
+    };
+
+    let mut renderer_funcs: Vec<(gtk::CellRenderer, CellDataFunc)> = vec![
+        // Icons are resolved on render instead of being stored in every row
+        (
+            resources
+                .ui
+                .get_object::<GameIconRenderer, _>()
+                .unwrap()
+                .0
+                .upcast(),
+            Box::new({
+                let resources = resources.clone();
+                move |cell, model, iter| {
+                    let icon = model
+                        .get_value(iter, ServerStoreColumn::GameId as i32)
+                        .get::<String>()
+                        .and_then(|id| games::Game::from_id(&id))
+                        .map(|id| resources.game_icons.icon(id));
+
+                    cell.set_property("pixbuf", &icon).unwrap();
+                }
+            }),
+        ),
+        (
+            resources
+                .ui
+                .get_object::<LockIconRenderer, _>()
+                .unwrap()
+                .0
+                .upcast(),
+            // Unknown is shown dimmed, as it may well be protected
+            Box::new(|cell, model, iter| {
+                let need_pass = model
+                    .get_value(iter, ServerStoreColumn::NeedPass as i32)
+                    .get::<i32>()
+                    .unwrap_or(UNKNOWN_FLAG);
+
+                cell.set_property(
+                    "icon-name",
+                    &match need_pass {
+                        1 => Some("network-wireless-encrypted-symbolic"),
+                        UNKNOWN_FLAG => Some("dialog-question-symbolic"),
+                        _ => None,
+                    },
+                )
+                .unwrap();
+                cell.set_property("sensitive", &(need_pass != UNKNOWN_FLAG))
+                    .unwrap();
+            }),
+        ),
+        (
+            resources
+                .ui
+                .get_object::<VersionWarningRenderer, _>()
+                .unwrap()
+                .0
+                .upcast(),
+            Box::new({
+                let resources = resources.clone();
+                move |cell, model, iter| {
+                    let mismatch =
+                        version_mismatch(&resources.settings.lock().unwrap(), model, iter);
+
+                    cell.set_property("icon-name", &mismatch.map(|_| "dialog-warning-symbolic"))
+                        .unwrap();
+                }
+            }),
+        ),
+        (
+            resources
+                .ui
+                .get_object::<SecureIconRenderer, _>()
+                .unwrap()
+                .0
+                .upcast(),
+            // Only where the flag stands for an anti-cheat
+            Box::new({
+                let anticheat_games = anticheat_games(resources);
+                move |cell, model, iter| {
+                    let secure = model
+                        .get_value(iter, ServerStoreColumn::Secure as i32)
+                        .get::<bool>()
+                        .unwrap_or(false)
+                        && model
+                            .get_value(iter, ServerStoreColumn::GameId as i32)
+                            .get::<String>()
+                            .and_then(|id| games::Game::from_id(&id))
+                            .map(|game| anticheat_games.contains(&game))
+                            .unwrap_or(false);
+
+                    cell.set_property(
+                        "icon-name",
+                        &if secure {
+                            Some("security-high-symbolic")
+                        } else {
+                            None
+                        },
+                    )
+                    .unwrap();
+                }
+            }),
+        ),
+        (
+            resources
+                .ui
+                .get_object::<StockClientRenderer, _>()
+                .unwrap()
+                .0
+                .upcast(),
+            icon_func(ServerStoreColumn::StockClient, "emblem-ok-symbolic"),
+        ),
+        (
+            resources
+                .ui
+                .get_object::<ListenServerRenderer, _>()
+                .unwrap()
+                .0
+                .upcast(),
+            icon_func(ServerStoreColumn::Listen, "input-gaming-symbolic"),
+        ),
+        (
+            resources
+                .ui
+                .get_object::<PingRenderer, _>()
+                .unwrap()
+                .0
+                .upcast(),
+            Box::new(render_ping),
+        ),
+        // Greyed out for games that cannot list players on demand
+        (
+            resources
+                .ui
+                .get_object::<PlayersIconRenderer, _>()
+                .unwrap()
+                .0
+                .upcast(),
+            Box::new({
+                let player_games = resources
+                    .game_list
+                    .0
+                    .iter()
+                    .filter(|(_, entry)| entry.querier.can_query_server())
+                    .map(|(id, _)| *id)
+                    .collect::<HashSet<_>>();
+                move |cell, model, iter| {
+                    let supported = model
+                        .get_value(iter, ServerStoreColumn::GameId as i32)
+                        .get::<String>()
+                        .and_then(|id| games::Game::from_id(&id))
+                        .map(|id| player_games.contains(&id))
+                        .unwrap_or(false);
+
+                    cell.set_property("icon-name", &Some("system-users-symbolic"))
+                        .unwrap();
+                    cell.set_property("sensitive", &supported).unwrap();
+                }
+            }),
+        ),
+        // Shown as "cur/max", sorted by the numeric player count
+        (
+            resources
+                .ui
+                .get_object::<PlayerCountRenderer, _>()
+                .unwrap()
+                .0
+                .upcast(),
+            Box::new(|cell, model, iter| {
+                let num_clients = model
+                    .get_value(iter, ServerStoreColumn::PlayerCount as i32)
+                    .get::<i32>()
+                    .unwrap_or(UNKNOWN_COUNT);
+                let max_clients = model
+                    .get_value(iter, ServerStoreColumn::PlayerLimit as i32)
+                    .get::<i32>()
+                    .unwrap_or(0);
+
+                let text = if num_clients == UNKNOWN_COUNT {
+                    format!("—/{}", max_clients)
+                } else {
+                    format!("{}/{}", num_clients, max_clients)
+                };
+                cell.set_property("text", &text).unwrap();
+
+                // Highlight buggy servers reporting more players than their limit
+                cell.set_property("foreground", &"#c01c28").unwrap();
+                cell.set_property(
+                    "foreground-set",
+                    &(max_clients > 0 && num_clients > max_clients),
+                )
+                .unwrap();
+            }),
+        ),
+    ];
+
+    // Read once per draw rather than for every cell
+    let dim_empty = Rc::new(Cell::new(false));
+    let server_list_view = resources.ui.get_object::<ServerListView, _>().unwrap().0;
+    server_list_view.connect_draw({
+        let resources = resources.clone();
+        let dim_empty = dim_empty.clone();
+        move |_, _| {
+            dim_empty.set(resources.settings.lock().unwrap().dim_empty_servers);
+            Inhibit(false)
+        }
+    });
+
+    for column in server_list_view.get_columns() {
+        for cell in column.get_cells() {
+            let f = renderer_funcs
+                .iter()
+                .position(|(renderer, _)| *renderer == cell)
+                .map(|i| renderer_funcs.remove(i).1);
+
+            column.set_cell_data_func(
+                &cell,
+                Some(Box::new({
+                    let dim_empty = dim_empty.clone();
+                    move |_, cell, model, iter| {
+                        style_row(dim_empty.get(), cell, model, iter);
+
+                        if let Some(f) = f.as_ref() {
+                            f(cell, model, iter);
+                        }
+                    }
+                })),
+            );
+        }
+    }
+}
+
+fn build_ui(
+    app: &gtk::Application,
+    executor: tokio::runtime::TaskExecutor,
+    resources: &Rc<Resources>,
+) {
+    let (cmd_sink, cmd_faucet) = channel::<AppCommand>();
+    let (event_sink, event_faucet) = channel::<RefreshEvent>();
+
+    let refresher = resources.ui.get_object::<RefreshButton, _>().unwrap().0;
+
+    let server_list = resources.ui.get_object::<ServerStore, _>().unwrap();
+
+    let server_list_view = resources.ui.get_object::<ServerListView, _>().unwrap().0;
+
+    // Type-ahead jumps to the first server whose name starts with the typed text
+    server_list_view.set_search_column(ServerStoreColumn::Name as i32);
+    server_list_view.set_search_equal_func(|model, column, key, iter| {
+        let name = model
+            .get_value(iter, column)
+            .get::<String>()
+            .unwrap_or_default();
+
+        // GTK expects false on match
+        !name.to_lowercase().starts_with(&key.to_lowercase())
+    });
+
+    // Explains the version warning and anti-cheat icon of the hovered row
+    server_list_view.set_has_tooltip(true);
+    server_list_view.connect_query_tooltip({
+        let resources = resources.clone();
+        move |view, x, y, keyboard_tip, tooltip| {
+            let (mut x, mut y) = (x, y);
+            let (model, path, iter) = match view.get_tooltip_context(&mut x, &mut y, keyboard_tip) {
+                Some((Some(model), path, iter)) => (model, path, iter),
+                _ => return false,
+            };
+
+            let settings = resources.settings.lock().unwrap();
+            let mut lines = Vec::new();
+            if let Some((server, local)) = version_mismatch(&settings, &model, &iter) {
+                lines.push(format!("Server version {}, your version {}", server, local));
+            }
+
+            let secure = model
+                .get_value(&iter, ServerStoreColumn::Secure as i32)
+                .get::<bool>()
+                .unwrap_or(false);
+            let anticheat = model
+                .get_value(&iter, ServerStoreColumn::GameId as i32)
+                .get::<String>()
+                .and_then(|id| games::Game::from_id(&id))
+                .and_then(|game| settings.anticheat_name(game));
+            if let (true, Some(anticheat)) = (secure, anticheat) {
+                lines.push(format!("{} enabled", anticheat));
+            }
+
+            if lines.is_empty() {
+                return false;
+            }
+
+            tooltip.set_text(Some(&lines.join("\n")));
+            view.set_tooltip_row(tooltip, &path);
+            true
+        }
+    });
+
+    build_server_list_renderers(resources);
+    build_compact_list(app, resources);
+    build_side_pane(app, resources);
+
+    server_list_view.connect_row_activated({
+        let resources = resources.clone();
+        let server_list = server_list.clone();
+        move |_, path, _| {
+            let (game_id, srv) = server_list.get_server(&server_list.0.get_iter(path).unwrap());
+
+            connect_to_server(
+                &resources,
+                settings::Favorite::of_server(game_id, &srv),
+                srv.addr.to_string(),
+                srv.need_pass,
+            );
+        }
+    });
+
+    // The list is rebuilt on every opening to pick up new connections and fresh timestamps
+    resources
+        .ui
+        .get_object::<RecentButton, _>()
+        .unwrap()
+        .0
+        .connect_toggled({
+            let resources = resources.clone();
+            move |w| {
+                if w.get_active() {
+                    fill_recent_list(&resources);
+                }
+            }
+        });
+
+    resources
+        .ui
+        .get_object::<RecentList, _>()
+        .unwrap()
+        .0
+        .connect_row_activated({
+            let resources = resources.clone();
+            move |_, row| {
+                let recent = resources
+                    .recent_servers
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .nth(row.get_index() as usize)
+                    .cloned();
+
+                if let Some(recent) = recent {
+                    resources
+                        .ui
+                        .get_object::<RecentButton, _>()
+                        .unwrap()
+                        .0
+                        .set_active(false);
+                    connect_to_server(
+                        &resources,
+                        recent.server.clone(),
+                        recent.server.addr,
+                        recent.need_pass,
+                    );
+                }
+            }
+        });
+
+    resources
+        .ui
+        .get_object::<ImportButton, _>()
+        .unwrap()
+        .0
+        .connect_clicked({
+            let resources = resources.clone();
+            move |_| dialogs::import_favorites(&resources)
+        });
+
+    for (name, f) in vec![
+        (
+            "export-settings",
+            dialogs::export_state as fn(&Rc<Resources>),
+        ),
+        ("export-game-favorites", dialogs::export_game_favorites),
+        ("preferences", dialogs::show_preferences),
+        ("shortcuts", dialogs::show_shortcuts),
+        ("about", dialogs::show_about),
+    ] {
+        let action = gio::SimpleAction::new(name, None);
+        action.connect_activate({
+            let resources = resources.clone();
+            move |_, _| f(&resources)
+        });
+        app.add_action(&action);
+    }
+
+    let quit = gio::SimpleAction::new("quit", None);
+    quit.connect_activate({
+        let app = app.clone();
+        move |_, _| app.quit()
+    });
+    app.add_action(&quit);
+
+    app.connect_shutdown({
+        let cmd_sink = cmd_sink.clone();
+        move |_| {
+            let _ = cmd_sink.send(AppCommand::CancelRefresh);
+        }
+    });
+
+    shortcuts::register_accels(app);
+
+    shortcuts::populate_group(&resources.ui.get_object::<ShortcutsGroup, _>().unwrap().0);
+    resources
+        .ui
+        .get_object::<ShortcutsWindow, _>()
+        .unwrap()
+        .0
+        .connect_delete_event(|w, _| w.hide_on_delete());
+
+    let share_button = resources.ui.get_object::<ShareButton, _>().unwrap().0;
+
+    let info_button = resources.ui.get_object::<InfoButton, _>().unwrap().0;
+
+    let connect_button = resources.ui.get_object::<ConnectButton, _>().unwrap().0;
+
+    server_list_view.get_selection().connect_changed({
+        let resources = resources.clone();
+        let server_list = server_list.clone();
+        let share_button = share_button.clone();
+        let info_button = info_button.clone();
+        let connect_button = connect_button.clone();
+        move |s| {
+            let selected = s.count_selected_rows() > 0;
+            share_button.set_sensitive(selected);
+            info_button.set_sensitive(selected);
+
+            // Show what is about to run, passwords are asked for later
+            let preview = resources
+                .ui
+                .get_object::<ServerListView, _>()
+                .unwrap()
+                .get_selected_store_iter()
+                .map(|iter| {
+                    let (game_id, srv) = server_list.get_server(&iter);
+                    resources.game_list.0[&game_id]
+                        .launcher
+                        .preview_cmd(&launch_data(
+                            &resources,
+                            &settings::Favorite::of_server(game_id, &srv),
+                            srv.addr.to_string(),
+                        ))
+                        .ok_or_else(|| {
+                            format!(
+                                "{} cannot be launched from Obozrenie.",
+                                resources.settings.lock().unwrap().game_name(game_id)
+                            )
+                        })
+                });
+            connect_button.set_sensitive(preview.as_ref().map(Result::is_ok).unwrap_or(false));
+            connect_button.set_tooltip_text(Some(&match preview {
+                Some(Ok(cmd)) => format!("Connect to the selected server.\n\nRuns: {}", cmd),
+                Some(Err(reason)) => reason,
+                None => "Connect to the selected server.".to_string(),
+            }));
+        }
+    });
+
+    connect_button.connect_clicked({
+        let resources = resources.clone();
+        let server_list = server_list.clone();
+        move |_| {
+            if let Some(iter) = resources
+                .ui
+                .get_object::<ServerListView, _>()
+                .unwrap()
+                .get_selected_store_iter()
+            {
+                let (game_id, srv) = server_list.get_server(&iter);
+                connect_to_server(
+                    &resources,
+                    settings::Favorite::of_server(game_id, &srv),
+                    srv.addr.to_string(),
+                    srv.need_pass,
+                );
+            }
+        }
+    });
+
+    let data_warnings = &resources.data_warnings;
+    let activity_log = resources.activity_log.clone();
+
+    let data_warning = resources.ui.get_object::<DataWarning, _>().unwrap().0;
+    data_warning.connect_response(|w, _| w.hide());
+
+    let follow_prompt = resources.ui.get_object::<FollowPrompt, _>().unwrap().0;
+    follow_prompt.add_button("_Choose…", gtk::ResponseType::Accept);
+    follow_prompt.connect_response({
+        let resources = resources.clone();
+        move |w, response| {
+            w.hide();
+            let ambiguous = resources.ambiguous_follows.replace(Vec::new());
+            if response != gtk::ResponseType::Accept {
+                return;
+            }
+
+            let mut moved = false;
+            for (favorite, candidates) in ambiguous {
+                if let Some(new_addr) =
+                    dialogs::choose_followed_server(&resources, &favorite, &candidates)
+                {
+                    move_followed_favorite(&resources, &favorite, new_addr);
+                    moved = true;
+                }
+            }
+            if moved {
+                resources
+                    .ui
+                    .get_object::<ServerListFilter, _>()
+                    .unwrap()
+                    .0
+                    .refilter();
+            }
+        }
+    });
+    if !data_warnings.is_empty() {
+        resources
+            .ui
+            .get_object::<DataWarningLabel, _>()
+            .unwrap()
+            .0
+            .set_text(&format!(
+                "Some saved data could not be read:\n{}",
+                data_warnings.join("\n")
+            ));
+        data_warning.show();
+    }
+    let activity_chart =
+        ActivityChart::new(resources.ui.get_object::<ActivityChartArea, _>().unwrap().0);
+    let ping_history = Arc::new(Mutex::new(activity::PingHistory::default()));
+    let ping_sparkline = PingSparkline::new(
+        resources
+            .ui
+            .get_object::<ServerInfoPingHistory, _>()
+            .unwrap()
+            .0,
+    );
+
+    info_button.connect_clicked({
+        let resources = resources.clone();
+        let server_list = server_list.clone();
+        let activity_log = activity_log.clone();
+        let activity_chart = activity_chart.clone();
+        let ping_history = ping_history.clone();
+        let ping_sparkline = ping_sparkline.clone();
+        move |_| {
+            if let Some(iter) = resources
+                .ui
+                .get_object::<ServerListView, _>()
+                .unwrap()
+                .get_selected_store_iter()
+            {
+                show_server_info(
+                    &resources,
+                    &server_list,
+                    &iter,
+                    &activity_log.lock().unwrap(),
+                    &activity_chart,
+                    &ping_history.lock().unwrap(),
+                    &ping_sparkline,
+                );
+            }
+        }
+    });
+
+    resources
+        .ui
+        .get_object::<ActivitySpan, _>()
+        .unwrap()
+        .0
+        .connect_changed({
+            let resources = resources.clone();
+            let server_list = server_list.clone();
+            let activity_log = activity_log.clone();
+            let activity_chart = activity_chart.clone();
+            move |_| {
+                if let Some(iter) = resources
+                    .ui
+                    .get_object::<ServerListView, _>()
+                    .unwrap()
+                    .get_selected_store_iter()
+                {
+                    let (game, srv) = server_list.get_server(&iter);
+                    update_activity_chart(
+                        &resources,
+                        &settings::Favorite {
+                            game,
+                            addr: games::server_key(&srv),
+                        },
+                        &activity_log.lock().unwrap(),
+                        &activity_chart,
+                    );
+                }
+            }
+        });
+
+    share_button.connect_clicked({
+        let resources = resources.clone();
+        let server_list = server_list.clone();
+        let server_list_view = resources.ui.get_object::<ServerListView, _>().unwrap();
+        move |_| {
+            if let Some(iter) = server_list_view.get_selected_store_iter() {
+                let (game_id, srv) = server_list.get_server(&iter);
+
+                let text = share::format_server_info(
+                    &resources.settings.lock().unwrap().share_template,
+                    game_id,
+                    &srv,
+                );
+
+                gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(&text);
+            }
+        }
+    });
+
+    // Players are queried on demand when the people icon of a row is clicked
+    server_list_view.connect_button_press_event({
+        let resources = resources.clone();
+        let server_list = server_list.clone();
+        let executor = executor.clone();
+        let players_column = resources.ui.get_object::<PlayersIconColumn, _>().unwrap().0;
+        let players_cache = Rc::new(RefCell::new(HashMap::<
+            (games::Game, std::net::SocketAddr),
+            (Instant, Vec<rgs::models::Player>),
+        >::new()));
+        // Server the popover was last opened for, so that late replies do not replace newer lists
+        let shown_players = Rc::new(Cell::new(None));
+        move |view, ev| {
+            if ev.get_button() != 1 {
+                return Inhibit(false);
+            }
+
+            let (x, y) = ev.get_position();
+            let path = match view.get_path_at_pos(x as i32, y as i32) {
+                Some((Some(path), Some(column), _, _)) if column == players_column => path,
+                _ => {
+                    return Inhibit(false);
+                }
+            };
+            view.get_selection().select_path(&path);
+
+            let (game, srv) = match ServerListView(view.clone()).get_selected_store_iter() {
+                Some(iter) => server_list.get_server(&iter),
+                None => {
+                    return Inhibit(true);
+                }
+            };
+            let querier = resources.game_list.0[&game].querier.clone();
+            if !querier.can_query_server() {
+                return Inhibit(true);
+            }
+            let key = (game, srv.addr);
+            shown_players.set(Some(key));
+
+            let popover = resources.ui.get_object::<PlayersPopover, _>().unwrap().0;
+            let rect = view.get_cell_area(Some(&path), Some(&players_column));
+            let (rect_x, rect_y) = view.convert_bin_window_to_widget_coords(rect.x, rect.y);
+            popover.set_pointing_to(&gdk::Rectangle {
+                x: rect_x,
+                y: rect_y,
+                width: rect.width,
+                height: rect.height,
+            });
+
+            let cached = players_cache
+                .borrow()
+                .get(&key)
+                .filter(|(time, _)| time.elapsed() < PLAYERS_CACHE_TTL)
+                .map(|(_, players)| players.clone());
+            match cached {
+                Some(players) => fill_players_popover(&resources, game, &Ok(players)),
+                None => {
+                    let spinner = resources.ui.get_object::<PlayersSpinner, _>().unwrap().0;
+                    spinner.show();
+                    spinner.start();
+                    resources
+                        .ui
+                        .get_object::<PlayersStatus, _>()
+                        .unwrap()
+                        .0
+                        .hide();
+                    let list = resources.ui.get_object::<PlayersList, _>().unwrap().0;
+                    for row in list.get_children() {
+                        list.remove(&row);
+                    }
+
+                    let (result_sink, result_faucet) = channel::<PlayersResult>();
+                    executor.spawn(
+                        async move {
+                            let res = querier.query_server(srv.addr).compat().await;
+                            let _ = result_sink.send(
+                                res.map(|srv| srv.players.unwrap_or_default())
+                                    .map_err(|e| e.to_string()),
+                            );
+                        }
+                        .map(|_| Ok(()))
+                        .boxed()
+                        .compat(),
+                    );
+
+                    gtk::timeout_add(100, {
+                        let resources = resources.clone();
+                        let players_cache = players_cache.clone();
+                        let shown_players = shown_players.clone();
+                        move || {
+                            glib::Continue(match result_faucet.try_recv() {
+                                Ok(result) => {
+                                    if let Ok(players) = result.as_ref() {
+                                        players_cache
+                                            .borrow_mut()
+                                            .insert(key, (Instant::now(), players.clone()));
+                                    }
+                                    if shown_players.get() == Some(key) {
+                                        fill_players_popover(&resources, game, &result);
+                                    }
+                                    false
+                                }
+                                Err(TryRecvError::Empty) => true,
+                                Err(TryRecvError::Disconnected) => false,
+                            })
+                        }
+                    });
+                }
+            }
+
+            popover.popup();
+
+            Inhibit(true)
+        }
+    });
+
+    server_list_view.connect_button_press_event({
+        let resources = resources.clone();
+        let server_list = server_list.clone();
+        let executor = executor.clone();
+        move |view, ev| {
+            if ev.get_button() != 3 {
+                return Inhibit(false);
+            }
+
+            let (x, y) = ev.get_position();
+            if let Some((Some(path), _, _, _)) = view.get_path_at_pos(x as i32, y as i32) {
+                view.get_selection().select_path(&path);
+            }
+
+            if let Some(iter) = ServerListView(view.clone()).get_selected_store_iter() {
+                let (game_id, srv) = server_list.get_server(&iter);
+                let addr = games::server_key(&srv);
+
+                let (is_favorite, is_pinned) = {
+                    let settings = resources.settings.lock().unwrap();
+                    (
+                        settings.is_favorite(game_id, &addr),
+                        settings.is_pinned(game_id, &addr),
+                    )
+                };
+
+                let menu = gtk::Menu::new();
+
+                let favorite_item = gtk::MenuItem::new_with_label(if is_favorite {
+                    "Remove from favorites"
+                } else {
+                    "Add to favorites"
+                });
+                favorite_item.connect_activate({
+                    let resources = resources.clone();
+                    let server_list = server_list.clone();
+                    let iter = iter.clone();
+                    let addr = addr.clone();
+                    move |_| {
+                        let mut settings = resources.settings.lock().unwrap();
+
+                        // Details are dropped with the favorite, so is its password
+                        if let Some(password_ref) = settings
+                            .favorite_details(game_id, &addr)
+                            .and_then(|details| details.password_ref.clone())
+                        {
+                            secrets::clear_in_background(password_ref);
+                        }
+
+                        settings.toggle_favorite(game_id, addr.clone());
+                        if let Err(e) = settings.save() {
+                            warn!("Failed to save settings: {}", e);
+                        }
+                        drop(settings);
+
+                        update_score(&resources, &server_list, &iter);
+                    }
+                });
+                menu.append(&favorite_item);
+
+                if is_favorite {
+                    let edit_item = gtk::MenuItem::new_with_label("Edit favorite…");
+                    edit_item.connect_activate({
+                        let resources = resources.clone();
+                        let server_list = server_list.clone();
+                        let iter = iter.clone();
+                        let addr = addr.clone();
+                        let srv = srv.clone();
+                        move |_| {
+                            let details = dialogs::edit_favorite(
+                                &resources,
+                                settings::Favorite {
+                                    game: game_id,
+                                    addr: addr.clone(),
+                                },
+                            );
+
+                            if let Some(details) = details {
+                                let name = details.alias.unwrap_or_else(|| {
+                                    games::DisplayServer::new(
+                                        game_id,
+                                        &*resources.game_list.0[&game_id].name_morpher,
+                                        srv.clone(),
+                                    )
+                                    .name
+                                });
+                                server_list.set_name(&iter, &name);
+                            }
+                        }
+                    });
+                    menu.append(&edit_item);
+                }
+
+                let pin_item =
+                    gtk::MenuItem::new_with_label(if is_pinned { "Unpin" } else { "Pin to top" });
+                pin_item.connect_activate({
+                    let resources = resources.clone();
+                    let server_list = server_list.clone();
+                    move |_| {
+                        let mut settings = resources.settings.lock().unwrap();
+                        settings.toggle_pinned(game_id, addr.clone());
+                        if let Err(e) = settings.save() {
+                            warn!("Failed to save settings: {}", e);
+                        }
+                        drop(settings);
+
+                        server_list.set_pinned(&iter, !is_pinned);
+                    }
+                });
+                menu.append(&pin_item);
+
+                let monitor_item = gtk::MenuItem::new_with_label("Monitor latency…");
+                monitor_item.connect_activate({
+                    let resources = resources.clone();
+                    let executor = executor.clone();
+                    let ip = srv.addr.ip();
+                    let title = server_list.get_display_server(&iter).name;
+                    move |_| {
+                        monitor_latency(&resources, executor.clone(), ip, &title);
+                    }
+                });
+                menu.append(&monitor_item);
+
+                let compare_item = gtk::MenuItem::new_with_label("Compare with…");
+                compare_item.connect_activate({
+                    let resources = resources.clone();
+                    let server_list = server_list.clone();
+                    let name = server_list.get_display_server(&iter).name;
+                    move |_| {
+                        let key = games::server_key(&srv);
+                        let name_morpher = &resources.game_list.0[&game_id].name_morpher;
+                        let mut candidates = server_list
+                            .get_servers()
+                            .into_iter()
+                            .filter(|(game, other)| {
+                                *game == game_id && games::server_key(other) != key
+                            })
+                            .map(|(_, other)| {
+                                (
+                                    name_morpher.morph(other.name.clone().unwrap_or_default()),
+                                    other,
+                                )
+                            })
+                            .collect::<Vec<_>>();
+                        candidates.sort_by(|a, b| a.0.cmp(&b.0));
+
+                        dialogs::compare_servers(
+                            &resources,
+                            game_id,
+                            (name.clone(), srv.clone()),
+                            candidates,
+                        );
+                    }
+                });
+                menu.append(&compare_item);
+
+                menu.show_all();
+                menu.popup_at_pointer(Some(&**ev));
+
+                return Inhibit(true);
+            }
+
+            Inhibit(false)
+        }
+    });
+
+    let window = resources.ui.get_object::<MainWindow, _>().unwrap().0;
+
+    let (tray_sink, tray_faucet) = channel::<tray::TrayEvent>();
+    let tray_handle = if resources.settings.lock().unwrap().close_to_tray {
+        Some(
+            ksni::TrayService::new(tray::Tray {
+                entries: Vec::new(),
+                event_sink: tray_sink,
+            })
+            .spawn(),
+        )
+    } else {
+        None
+    };
+
+    gtk::timeout_add(100, {
+        let app = app.clone();
+        let resources = resources.clone();
+        let window = window.clone();
+        move || {
+            use TryRecvError::*;
+
+            glib::Continue(match tray_faucet.try_recv() {
+                Ok(ev) => {
+                    match ev {
+                        tray::TrayEvent::ShowWindow => window.present(),
+                        tray::TrayEvent::Connect(favorite, need_pass) => connect_to_server(
+                            &resources,
+                            favorite.clone(),
+                            favorite.addr,
+                            need_pass,
+                        ),
+                        tray::TrayEvent::Quit => app.quit(),
+                    }
+                    true
+                }
+                Err(e) => match e {
+                    Empty => true,
+                    Disconnected => false,
+                },
+            })
+        }
+    });
+
+    let present_servers = Arc::new(Mutex::new(HashSet::new()));
+    let previous_servers = Arc::new(Mutex::new(HashMap::new()));
+    // Rows kept by an incremental refresh, to update in place
+    let listed_rows = Rc::new(RefCell::new(HashMap::<String, gtk::TreeIter>::new()));
+    // Server selected when the refresh started, selected again once it is listed
+    let reselect_server = Rc::new(RefCell::new(None::<String>));
+    let refresh_generation = Arc::new(AtomicUsize::new(0));
+    build_live_update(app, executor.clone(), resources, refresh_generation.clone());
+    let favorite_counts = Arc::new(Mutex::new(HashMap::new()));
+    let last_errors = Arc::new(Mutex::new(HashMap::new()));
+    // Servers of the current refresh kept despite game filters, by game and filter
+    let filter_rejections = Arc::new(Mutex::new(
+        HashMap::<games::Game, HashMap<String, usize>>::new(),
+    ));
+    let spam_filter = Arc::new(Mutex::new(filters::SpamFilter::default()));
+
+    let copy_diagnostics = gio::SimpleAction::new("copy-diagnostics", None);
+    copy_diagnostics.connect_activate({
+        let resources = resources.clone();
+        let server_list = server_list.clone();
+        let last_errors = last_errors.clone();
+        move |_, _| {
+            let mut server_counts = HashMap::new();
+            for (game_id, _) in server_list.get_servers() {
+                *server_counts.entry(game_id).or_insert(0) += 1;
+            }
+
+            let text = diagnostics::Diagnostics {
+                settings: &resources.settings.lock().unwrap(),
+                last_errors: &last_errors.lock().unwrap(),
+                server_counts: &server_counts,
+                selected_server: resources
+                    .ui
+                    .get_object::<ServerListView, _>()
+                    .unwrap()
+                    .get_selected_store_iter()
+                    .map(|iter| server_list.get_json(&iter)),
+            }
+            .to_text();
+
+            gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(&text);
+        }
+    });
+    app.add_action(&copy_diagnostics);
+
+    let debug_log = gio::SimpleAction::new("debug-log", None);
+    debug_log.connect_activate({
+        let resources = resources.clone();
+        move |_, _| dialogs::show_debug_log(&resources)
+    });
+    app.add_action(&debug_log);
+
+    let network_test = gio::SimpleAction::new("network-test", None);
+    network_test.connect_activate({
+        let resources = resources.clone();
+        move |action, _| {
+            action.set_enabled(false);
+            run_network_test({
+                let resources = resources.clone();
+                let action = action.clone();
+                move |result| {
+                    action.set_enabled(true);
+                    dialogs::show_network_test(&resources, &result);
+                }
+            });
+        }
+    });
+    app.add_action(&network_test);
+
+    let network_warning = resources.ui.get_object::<NetworkWarning, _>().unwrap().0;
+    network_warning.connect_response(|w, _| w.hide());
+
+    // Next refresh scans the local network instead of asking master servers
+    let lan_scan = Rc::new(Cell::new(false));
+    // Next refresh queries all games regardless of the sidebar selection
+    let refresh_all = Rc::new(Cell::new(false));
+
+    let filter_data = build_filters(resources);
+
+    let import_settings = gio::SimpleAction::new("import-settings", None);
+    import_settings.connect_activate({
+        let resources = resources.clone();
+        let server_list = server_list.clone();
+        let filter_data = filter_data.clone();
+        move |_, _| {
+            if dialogs::import_state(&resources) {
+                reload_state(&resources, &server_list, &filter_data);
+            }
+        }
+    });
+    app.add_action(&import_settings);
+
+    refresher.connect_clicked({
+        let cmd_sink = cmd_sink.clone();
+        let refresher = refresher.clone();
+        let resources = resources.clone();
+        let server_list = server_list.clone();
+        let present_servers = present_servers.clone();
+        let previous_servers = previous_servers.clone();
+        let listed_rows = listed_rows.clone();
+        let refresh_generation = refresh_generation.clone();
+        let spam_filter = spam_filter.clone();
+        let filter_rejections = filter_rejections.clone();
+        let lan_scan = lan_scan.clone();
+        let refresh_all = refresh_all.clone();
+        let filter_data = filter_data.clone();
+        let reselect_server = reselect_server.clone();
+        move |_| {
+            // Keyboard focus would be lost on the insensitive button
+            let window = resources.ui.get_object::<MainWindow, _>().unwrap().0;
+            let server_list_view = resources.ui.get_object::<ServerListView, _>().unwrap();
+            if window
+                .get_focus()
+                .map(|w| w == *refresher.upcast_ref::<gtk::Widget>())
+                .unwrap_or(true)
+            {
+                server_list_view.0.grab_focus();
+            }
+            refresher.set_sensitive(false);
+
+            *reselect_server.borrow_mut() = server_list_view
+                .get_selected_store_iter()
+                .map(|iter| games::server_key(&server_list.get_server(&iter).1));
+
+            let (incremental, refresh_selected_game) = {
+                let settings = resources.settings.lock().unwrap();
+                (settings.incremental_refresh, settings.refresh_selected_game)
+            };
+
+            // Same selection as the games filter, a single selected game is refreshed alone
+            let refresh_all = refresh_all.replace(false)
+                || gtk::get_current_event_state()
+                    .map(|state| state.contains(gdk::ModifierType::SHIFT_MASK))
+                    .unwrap_or(false);
+            let scope = if refresh_all || !refresh_selected_game {
+                None
+            } else {
+                let games = &filter_data.lock().unwrap().games;
+                if games.len() == 1 {
+                    games.iter().next().cloned()
+                } else {
+                    None
+                }
+            };
+            let in_scope = |game: games::Game| scope.map(|v| v == game).unwrap_or(true);
+
+            // Keep the current result to compare the new one against
+            let mut gone = Vec::new();
+            let mut stale = Vec::new();
+            let mut kept = Vec::new();
+            {
+                let mut previous_servers = previous_servers.lock().unwrap();
+                let mut listed_rows = listed_rows.borrow_mut();
+                previous_servers.clear();
+                listed_rows.clear();
+                server_list.0.foreach(|_, _, iter| {
+                    match server_list.get_change(iter) {
+                        RowChange::Gone => gone.push(iter.clone()),
+                        change => {
+                            let data = server_list.get_display_server(iter);
+                            let key = games::server_key(&data.srv);
+
+                            if !in_scope(data.game_id) {
+                                // Other games keep their servers as they are
+                                if change != RowChange::Unchanged {
+                                    server_list.set_change(iter, RowChange::Unchanged);
+                                }
+                                kept.push(key);
+                                return false;
+                            }
+
+                            if incremental {
+                                // Highlights of the previous refresh are not faded by its timer anymore
+                                if change != RowChange::Unchanged {
+                                    server_list.set_change(iter, RowChange::Unchanged);
+                                }
+                                listed_rows.insert(key.clone(), iter.clone());
+                            } else {
+                                stale.push(iter.clone());
+                            }
+                            previous_servers.insert(key, data);
+                        }
+                    }
+                    false
+                });
+            }
+            refresh_generation.fetch_add(1, Ordering::SeqCst);
+
+            if incremental || scope.is_some() {
+                for iter in gone.into_iter().chain(stale) {
+                    server_list.0.remove(&iter);
+                }
+            } else {
+                server_list.0.clear();
+            }
+            {
+                let mut present_servers = present_servers.lock().unwrap();
+                present_servers.clear();
+                present_servers.extend(kept);
+            }
+            *spam_filter.lock().unwrap() = Default::default();
+            filter_rejections.lock().unwrap().clear();
+
+            // Statuses of the previous refresh
+            let game_list = resources.ui.get_object::<GameListStore, _>().unwrap();
+            for game in resources.game_list.0.keys().filter(|game| in_scope(**game)) {
+                game_list.set_status(*game, None, None);
+            }
+            resources
+                .ui
+                .get_object::<NetworkWarning, _>()
+                .unwrap()
+                .0
+                .hide();
+
+            let progress_bar = resources.ui.get_object::<RefreshProgress, _>().unwrap().0;
+            progress_bar.set_fraction(0.0);
+            progress_bar.set_text(Some("Starting refresh"));
+            progress_bar.show();
+
+            let (disabled_games, max_concurrent, timeouts) = {
+                let settings = resources.settings.lock().unwrap();
+                let refresh_stats = resources.refresh_stats.lock().unwrap();
+                (
+                    settings.disabled_games.clone(),
+                    settings.max_concurrent_refreshes,
+                    resources
+                        .game_list
+                        .0
+                        .keys()
+                        .map(|id| {
+                            (
+                                *id,
+                                refresher::query_timeout(*id, &settings, &refresh_stats),
+                            )
+                        })
+                        .collect(),
+                )
+            };
+
+            let lan_scan = lan_scan.get();
+            cmd_sink
+                .send(AppCommand::StartRefresh(
+                    resources
+                        .game_list
+                        .clone()
+                        .0
+                        .into_iter()
+                        .filter(|(id, _)| !disabled_games.contains(id) && in_scope(*id))
+                        .filter(|(_, e)| !lan_scan || e.querier.can_scan_lan())
+                        .map(|(id, e)| {
+                            let querier = if lan_scan {
+                                Arc::new(games::LanQuerier(e.querier)) as Arc<dyn games::Querier>
+                            } else {
+                                e.querier
+                            };
+                            (id, (querier, e.name_morpher))
+                        })
+                        .collect(),
+                    timeouts,
+                    max_concurrent,
+                ))
+                .unwrap();
+        }
+    });
+
+    let refresh = gio::SimpleAction::new("refresh", None);
+    refresh.connect_activate({
+        let refresher = refresher.clone();
+        move |_, _| {
+            // Same path as the button, which is insensitive while refreshing
+            if refresher.is_sensitive() {
+                refresher.clicked();
+            } else {
+                info!("Refresh is already in progress");
+            }
+        }
+    });
+    app.add_action(&refresh);
+
+    let refresh_all_action = gio::SimpleAction::new("refresh-all", None);
+    refresh_all_action.connect_activate({
+        let refresher = refresher.clone();
+        let refresh_all = refresh_all.clone();
+        move |_, _| {
+            if refresher.is_sensitive() {
+                refresh_all.set(true);
+                refresher.clicked();
+            } else {
+                info!("Refresh is already in progress");
+            }
+        }
+    });
+    app.add_action(&refresh_all_action);
+
+    let scan_lan = gio::SimpleAction::new("scan-lan", None);
+    scan_lan.connect_activate({
+        let refresher = refresher.clone();
+        let lan_scan = lan_scan.clone();
+        move |_, _| {
+            if refresher.is_sensitive() {
+                lan_scan.set(true);
+                refresher.clicked();
+            } else {
+                info!("Refresh is already in progress");
+            }
+        }
+    });
+    app.add_action(&scan_lan);
+
+    let quick_connect = gio::SimpleAction::new("quick-connect", None);
+    quick_connect.connect_activate({
+        let resources = resources.clone();
+        let server_list = server_list.clone();
+        move |_, _| {
+            // Most populated favorite that responded to the last refresh
+            match favorite_entries(&resources, &server_list)
+                .into_iter()
+                .find(|entry| entry.num_clients.is_some())
+            {
+                Some(entry) => connect_to_server(
+                    &resources,
+                    entry.favorite.clone(),
+                    entry.favorite.addr,
+                    entry.need_pass,
+                ),
+                None => info!("No favorite servers online, nothing to connect to"),
+            }
+        }
+    });
+    app.add_action(&quick_connect);
+
+    let join_clipboard = gio::SimpleAction::new("join-clipboard", None);
+    join_clipboard.connect_activate({
+        let resources = resources.clone();
+        move |_, _| {
+            let window = resources.ui.get_object::<MainWindow, _>().unwrap().0;
+
+            let link = gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD)
+                .wait_for_text()
+                .and_then(|text| share::parse_join_link(&text));
+            let link = match link {
+                Some(v) => v,
+                None => {
+                    dialogs::show_message(
+                        &window,
+                        gtk::MessageType::Info,
+                        "The clipboard holds no server address or join link.",
+                    );
+                    return;
+                }
+            };
+
+            let game = match link
+                .game
+                .filter(|game| resources.game_list.0.contains_key(game))
+            {
+                Some(v) => v,
+                None => match dialogs::choose_game(&resources, &link.addr) {
+                    Some(v) => v,
+                    None => {
+                        return;
+                    }
+                },
+            };
+
+            let server = settings::Favorite {
+                game,
+                addr: link.addr.clone(),
+            };
+            match link.password {
+                Some(password) => launch_game(
+                    &resources,
+                    game,
+                    games::LaunchData {
+                        password: Some(password),
+                        ..launch_data(&resources, &server, link.addr)
+                    },
+                    Some(true),
+                ),
+                None => connect_to_server(&resources, server, link.addr, None),
+            }
+        }
+    });
+    app.add_action(&join_clipboard);
+
+    let add_server = gio::SimpleAction::new("add-server", None);
+    add_server.connect_activate({
+        let resources = resources.clone();
+        let server_list = server_list.clone();
+        let executor = executor.clone();
+        move |_, _| {
+            let window = resources.ui.get_object::<MainWindow, _>().unwrap().0;
+
+            let (game, host) = match dialogs::add_server(&resources) {
+                Some(v) => v,
+                None => {
+                    return;
+                }
+            };
+            let addr = match host.to_socket_addrs().ok().and_then(|mut v| v.next()) {
+                Some(v) => v,
+                None => {
+                    dialogs::show_message(
+                        &window,
+                        gtk::MessageType::Error,
+                        &format!("Failed to resolve {}.", host),
+                    );
+                    return;
+                }
+            };
+
+            // Detection prefers games in their usual order
+            let queriers = games::Game::enum_iter()
+                .filter(|id| game.map(|game| game == *id).unwrap_or(true))
+                .filter_map(|id| {
+                    resources
+                        .game_list
+                        .0
+                        .get(&id)
+                        .map(|entry| (id, entry.querier.clone()))
+                })
+                .collect::<Vec<_>>();
+
+            let (result_sink, result_faucet) =
+                channel::<(Option<games::Game>, Option<rgs::models::Server>)>();
+            executor.spawn(
+                async move {
+                    let game = match game {
+                        Some(game) => Some(game),
+                        None => games::detect_game(queriers.clone(), addr)
+                            .compat()
+                            .await
+                            .ok()
+                            .and_then(|games| games.into_iter().next()),
+                    };
+                    // Listed right away where the game can be queried for a single server
+                    let querier = game.and_then(|game| {
+                        queriers
+                            .iter()
+                            .find(|(id, _)| *id == game)
+                            .map(|(_, querier)| querier.clone())
+                    });
+                    let srv = match querier {
+                        Some(querier) if querier.can_query_server() => {
+                            querier.query_server(addr).compat().await.ok()
+                        }
+                        _ => None,
+                    };
+                    let _ = result_sink.send((game, srv));
+                }
+                .map(|_| Ok(()))
+                .boxed()
+                .compat(),
+            );
+
+            gtk::timeout_add(100, {
+                let resources = resources.clone();
+                let server_list = server_list.clone();
+                move || {
+                    let (game, srv) = match result_faucet.try_recv() {
+                        Ok(v) => v,
+                        Err(TryRecvError::Empty) => {
+                            return glib::Continue(true);
+                        }
+                        Err(TryRecvError::Disconnected) => {
+                            return glib::Continue(false);
+                        }
+                    };
+
+                    let game = match game {
+                        Some(v) => v,
+                        None => {
+                            dialogs::show_message(
+                                &window,
+                                gtk::MessageType::Warning,
+                                &format!("No server of a supported game answered at {}.", host),
+                            );
+                            return glib::Continue(false);
+                        }
+                    };
+
+                    let key = srv
+                        .as_ref()
+                        .map(games::server_key)
+                        .unwrap_or_else(|| addr.to_string());
+                    {
+                        let mut settings = resources.settings.lock().unwrap();
+                        if !settings.is_favorite(game, &key) {
+                            settings.toggle_favorite(game, key.clone());
+                            if let Err(e) = settings.save() {
+                                warn!("Failed to save settings: {}", e);
+                            }
+                        }
+                    }
+                    info!("Added {} server {} to favorites", game, key);
+
+                    if let Some(srv) = srv {
+                        let listed = server_list
+                            .get_servers()
+                            .iter()
+                            .any(|(id, listed)| *id == game && games::server_key(listed) == key);
+                        if !listed {
+                            let name_morpher = resources.game_list.0[&game].name_morpher.clone();
+                            let iter = server_list.append_server(games::DisplayServer::new(
+                                game,
+                                &*name_morpher,
+                                srv,
+                            ));
+                            update_score(&resources, &server_list, &iter);
+                            server_list.set_change(&iter, RowChange::New);
+                        }
+                    }
+
+                    glib::Continue(false)
+                }
+            });
+        }
+    });
+    app.add_action(&add_server);
+
+    let smart_sort = gio::SimpleAction::new("smart-sort", None);
+    smart_sort.connect_activate({
+        let resources = resources.clone();
+        move |_, _| {
+            resources
+                .ui
+                .get_object::<ServerListSort, _>()
+                .unwrap()
+                .0
+                .set_sort_column_id(
+                    gtk::SortColumn::Index(SMART_SORT_COLUMN as u32),
+                    gtk::SortType::Descending,
+                );
+        }
+    });
+    app.add_action(&smart_sort);
+
+    let country_breakdown = gio::SimpleAction::new("country-breakdown", None);
+    country_breakdown.connect_activate({
+        let resources = resources.clone();
+        let server_list = server_list.clone();
+        move |_, _| {
+            // Servers passing the filters, i.e. those of the selected games
+            let filter_model = resources.ui.get_object::<ServerListFilter, _>().unwrap().0;
+            let mut servers = Vec::new();
+            filter_model.foreach(|_, _, iter| {
+                let store_iter = filter_model.convert_iter_to_child_iter(iter);
+                if server_list.get_change(&store_iter) != RowChange::Gone {
+                    servers.push(server_list.get_server(&store_iter).1);
+                }
+                false
+            });
+
+            dialogs::show_country_breakdown(&resources, &filters::country_breakdown(&servers));
+        }
+    });
+    app.add_action(&country_breakdown);
+
+    let master_servers = gio::SimpleAction::new("master-servers", None);
+    master_servers.connect_activate({
+        let resources = resources.clone();
+        let last_errors = last_errors.clone();
+        let filter_rejections = filter_rejections.clone();
+        move |_, _| {
+            dialogs::show_master_servers(
+                &resources,
+                &last_errors.lock().unwrap(),
+                &filter_rejections.lock().unwrap(),
+            )
+        }
+    });
+    app.add_action(&master_servers);
+
+    let show_favorites =
+        gio::SimpleAction::new_stateful("show-favorites", None, &false.to_variant());
+    show_favorites.connect_activate({
+        let window = window.clone();
+        let filter_data = filter_data.clone();
+        let filter_model = resources.ui.get_object::<ServerListFilter, _>().unwrap().0;
+        move |action, _| {
+            let value = !action
+                .get_state()
+                .and_then(|v| v.get::<bool>())
+                .unwrap_or(false);
+            action.set_state(&value.to_variant());
+
+            filter_data.lock().unwrap().favorites_only = value;
+            filter_model.refilter();
+
+            window.present();
+        }
+    });
+    app.add_action(&show_favorites);
+
+    executor.spawn({
+        let cmd_sink = cmd_sink.clone();
+        let event_sink = event_sink.clone();
+
+        async move {
+            use TryRecvError::*;
+
+            let _ = cmd_sink.clone();
+
+            let mut cancel_refresh: Option<oneshot::Sender<()>> = None;
+
+            loop {
+                match cmd_faucet.try_recv() {
+                    Ok(cmd) => match cmd {
+                        AppCommand::StartRefresh(task_list, timeouts, max_concurrent) => {
+                            // Supersedes the refresh in flight, if any
+                            if let Some(cancel) = cancel_refresh.take() {
+                                let _ = cancel.send(());
+                            }
+
+                            let (cancel_sink, cancelled) = oneshot::channel();
+                            cancel_refresh = Some(cancel_sink);
+
+                            refresher::start(
+                                task_list,
+                                timeouts,
+                                max_concurrent,
+                                cancelled,
+                                event_sink.clone(),
+                            );
+                        }
+                        AppCommand::CancelRefresh => {
+                            if let Some(cancel) = cancel_refresh.take() {
+                                debug!("Cancelling query");
+                                let _ = cancel.send(());
+                            }
+                        }
+                    },
+                    Err(e) => match e {
+                        Empty => {}
+                        Disconnected => {
+                            return;
+                        }
+                    },
+                }
+
+                tokio::timer::Delay::new(Instant::now() + Duration::from_millis(100))
+                    .compat()
+                    .await
+                    .unwrap()
             }
         }
-            .map(|_| Ok(()))
-            .boxed()
-            .compat()
+        .map(|_| Ok(()))
+        .boxed()
+        .compat()
     });
 
     gtk::timeout_add(10, {
@@ -498,69 +4403,514 @@ fn build_ui(
         let server_list = server_list.clone();
         let resources = resources.clone();
         let present_servers = present_servers.clone();
+        let last_errors = last_errors.clone();
+        let filter_rejections = filter_rejections.clone();
+        let game_list = resources.ui.get_object::<GameListStore, _>().unwrap();
+        let app = app.clone();
+        let activity_log = activity_log.clone();
+        let ping_history = ping_history.clone();
+        let ping_sparkline = ping_sparkline.clone();
+        let favorite_counts = favorite_counts.clone();
+        let previous_servers = previous_servers.clone();
+        let listed_rows = listed_rows.clone();
+        let refresh_generation = refresh_generation.clone();
+        let tray_handle = tray_handle.clone();
+        let server_list_view = server_list_view.clone();
+        let reselect_server = reselect_server.clone();
+        let spam_filter = spam_filter.clone();
+        let server_list_sort = resources.ui.get_object::<ServerListSort, _>().unwrap().0;
+        // Start of the current second and servers inserted within it
+        let insert_rate = Cell::new((Instant::now(), 0));
         move || {
             use TryRecvError::*;
 
             let _ = event_sink.clone();
 
-            glib::Continue({
+            // Whatever has arrived is handled at once, so that consecutive servers make up a batch
+            let mut events = Vec::new();
+            let connected = loop {
                 match event_faucet.try_recv() {
-                    // Insert new server entry and continue
                     Ok(ev) => {
-                        match ev {
-                            AppEvent::AddServer((game_id, srv)) => {
-                                // Prevent duplicates
-                                if present_servers.lock().unwrap().insert(srv.addr) {
-                                    let game_entry = resources.game_list.0[&game_id].clone();
-                                    server_list.append_server(
-                                        game_id,
-                                        game_entry.icon.clone(),
-                                        game_entry.name_morpher.clone(),
-                                        srv,
-                                    );
+                        events.push(ev);
+                        if events.len() == MAX_EVENT_BATCH {
+                            break true;
+                        }
+                    }
+                    Err(Empty) => break true,
+                    Err(Disconnected) => break false,
+                }
+            };
+
+            let mut batch: Option<InsertBatch> = None;
+            for ev in events {
+                // Other events may look at the list, which has to be filtered by then
+                match ev {
+                    RefreshEvent::AddServer(_) => {}
+                    _ => {
+                        if let Some(batch) = batch.take() {
+                            batch.finish(&resources, &server_list);
+                        }
+                    }
+                }
+
+                match ev {
+                    RefreshEvent::AddServer(data) => {
+                        let key = games::server_key(&data.srv);
+                        let batch = batch.get_or_insert_with(|| InsertBatch::start(&resources));
+
+                        // Prevent duplicates. Dropped before the spam filter sees them, so that servers
+                        // listed by several masters do not count as spam.
+                        let is_listed = !present_servers.lock().unwrap().insert(key.clone());
+                        let is_spam = !is_listed
+                            && batch.settings.collapse_spam_games.contains(&data.game_id)
+                            && !spam_filter.lock().unwrap().admit(
+                                data.game_id,
+                                &data.name,
+                                data.srv.addr.ip(),
+                            );
+
+                        if !is_listed && !is_spam {
+                            // One sample per refresh, however many masters list the server
+                            if let Some(ping) = data.srv.ping {
+                                let server = settings::Favorite {
+                                    game: data.game_id,
+                                    addr: key.clone(),
+                                };
+                                let mut ping_history = ping_history.lock().unwrap();
+                                ping_history.record(&server, ping.as_millis() as u64);
+
+                                // Keep the open details of this server live
+                                if shown_server_info(&resources).as_ref() == Some(&server) {
+                                    ping_sparkline.set_samples(ping_history.samples(&server));
                                 }
                             }
-                            AppEvent::RefreshComplete => {
-                                refresher.set_sensitive(true);
+
+                            if let Some(filter) = data.filtered_out_by.clone() {
+                                *filter_rejections
+                                    .lock()
+                                    .unwrap()
+                                    .entry(data.game_id)
+                                    .or_default()
+                                    .entry(filter)
+                                    .or_insert(0) += 1;
+                            }
+
+                            let is_new = {
+                                let previous_servers = previous_servers.lock().unwrap();
+                                !previous_servers.is_empty() && !previous_servers.contains_key(&key)
+                            };
+
+                            let is_pinned = batch.settings.is_pinned(data.game_id, &key);
+                            let alias = batch
+                                .settings
+                                .favorite_details(data.game_id, &key)
+                                .and_then(|details| details.alias.clone());
+
+                            let listed = listed_rows.borrow().get(&key).cloned();
+                            let iter = server_list.upsert_server(
+                                listed.as_ref(),
+                                games::DisplayServer {
+                                    name: alias.unwrap_or(data.name),
+                                    ..data
+                                },
+                            );
+                            server_list.set_pinned(&iter, is_pinned);
+                            server_list
+                                .set_score(&iter, row_score(&batch.settings, &server_list, &iter));
+                            if is_new {
+                                server_list.set_change(&iter, RowChange::New);
+                            }
+                            batch.rows.push(iter.clone());
+
+                            // Unless the user has selected another server meanwhile
+                            if reselect_server.borrow().as_ref() == Some(&key)
+                                && server_list_view.get_selection().count_selected_rows() == 0
+                                && ServerListView(server_list_view.clone()).select_store_iter(&iter)
+                            {
+                                reselect_server.replace(None);
                             }
-                        };
 
-                        true
+                            // Spare the view from re-filtering and re-sorting on every row of a flood
+                            let (since, count) = insert_rate.get();
+                            let (since, count) = if since.elapsed() >= Duration::from_secs(1) {
+                                (Instant::now(), 1)
+                            } else {
+                                (since, count + 1)
+                            };
+                            insert_rate.set((since, count));
+
+                            let detach_rate = batch.settings.detach_view_rate;
+                            if detach_rate > 0
+                                && count > detach_rate as usize
+                                && server_list_view.get_model().is_some()
+                            {
+                                info!("Over {} servers per second, detaching the server list until the refresh completes", detach_rate);
+                                server_list_view.set_model(None::<&gtk::TreeModel>);
+                            }
+                        }
                     }
-                    Err(e) => match e {
-                        Empty => true,
-                        Disconnected => false,
+                    RefreshEvent::QueryStarted(game_id) => {
+                        game_list.set_status(
+                            game_id,
+                            Some("content-loading-symbolic"),
+                            Some("Refreshing"),
+                        );
+                    }
+                    RefreshEvent::QueryDuration((game_id, duration)) => {
+                        // Broadcast replies arrive in a fixed window unrelated to the master
+                        if !lan_scan.get() {
+                            resources
+                                .refresh_stats
+                                .lock()
+                                .unwrap()
+                                .record(game_id, duration);
+                        }
+                    }
+                    RefreshEvent::GameRefreshComplete((game_id, res)) => match res {
+                        Ok(count) => game_list.set_status(
+                            game_id,
+                            Some("emblem-ok-symbolic"),
+                            Some(&{
+                                let mut status = format!("{} servers", count);
+                                if let Some(hidden) =
+                                    spam_filter.lock().unwrap().hidden.get(&game_id)
+                                {
+                                    status += &format!(", {} hidden as spam", hidden);
+                                }
+                                if let Some(rejected) =
+                                    filter_rejections.lock().unwrap().get(&game_id)
+                                {
+                                    status += &format!(
+                                        ", {} rejected by filters",
+                                        rejected.values().sum::<usize>()
+                                    );
+                                }
+                                status
+                            }),
+                        ),
+                        Err(e) => {
+                            game_list.set_status(game_id, Some("dialog-error-symbolic"), Some(&e));
+                            last_errors.lock().unwrap().insert(game_id, e);
+                        }
                     },
-                }
-            })
+                    RefreshEvent::Progress(progress) => {
+                        let progress_bar =
+                            resources.ui.get_object::<RefreshProgress, _>().unwrap().0;
+                        progress_bar.set_fraction(progress.fraction());
+                        progress_bar.set_text(Some(&progress.to_string()));
+                    }
+                    RefreshEvent::PingerReady(icmp) => {
+                        let label = gtk::Label::new(Some(if icmp { "Ping" } else { "Ping*" }));
+                        label.set_tooltip_text(Some(if icmp {
+                                    "Measured with ICMP echo."
+                                } else {
+                                    "ICMP is unavailable, so latency is measured from query replies and includes server processing time."
+                                }));
+                        label.show();
+                        resources
+                            .ui
+                            .get_object::<PingColumn, _>()
+                            .unwrap()
+                            .0
+                            .set_widget(Some(&label));
+                    }
+                    RefreshEvent::RefreshComplete => {
+                        refresher.set_sensitive(true);
+                        resources
+                            .ui
+                            .get_object::<RefreshProgress, _>()
+                            .unwrap()
+                            .0
+                            .hide();
+
+                        if let Err(e) = resources.refresh_stats.lock().unwrap().save() {
+                            warn!("Failed to save refresh statistics: {}", e);
+                        }
+
+                        if server_list_view.get_model().is_none() {
+                            server_list_view.set_model(Some(&server_list_sort));
+                        }
+
+                        // Rows arrived while the view was detached
+                        if let Some(key) = reselect_server.replace(None) {
+                            if server_list_view.get_selection().count_selected_rows() == 0 {
+                                server_list.0.foreach(|_, _, iter| {
+                                    games::server_key(&server_list.get_server(iter).1) == key
+                                        && ServerListView(server_list_view.clone())
+                                            .select_store_iter(iter)
+                                });
+                            }
+                        }
+
+                        // No servers at all usually means replies are being dropped,
+                        // but an empty local network is nothing unusual
+                        if !lan_scan.replace(false) && present_servers.lock().unwrap().is_empty() {
+                            run_network_test({
+                                let resources = resources.clone();
+                                move |result| {
+                                    if let Ok(test) = result {
+                                        if test.verdict() != games::selftest::Verdict::Works {
+                                            resources
+                                                .ui
+                                                .get_object::<NetworkWarningLabel, _>()
+                                                .unwrap()
+                                                .0
+                                                .set_text(&format!(
+                                                    "No servers found. {}",
+                                                    test.advice()
+                                                ));
+                                            resources
+                                                .ui
+                                                .get_object::<NetworkWarning, _>()
+                                                .unwrap()
+                                                .0
+                                                .show();
+                                        }
+                                    }
+                                }
+                            });
+                        }
+
+                        migrate_host_keys(
+                            &resources,
+                            &server_list,
+                            &mut activity_log.lock().unwrap(),
+                            &mut ping_history.lock().unwrap(),
+                        );
+
+                        // Not locked while a chooser may be shown
+                        let present = present_servers.lock().unwrap().clone();
+                        follow_favorites(&resources, &server_list, &present);
+
+                        let moved = migrate_moved_servers(
+                            &resources,
+                            &server_list,
+                            &previous_servers.lock().unwrap(),
+                            &present,
+                            &mut activity_log.lock().unwrap(),
+                            &mut ping_history.lock().unwrap(),
+                        );
+                        for old_addr in moved {
+                            // Not shown as gone, the server is listed at its new address
+                            previous_servers.lock().unwrap().remove(&old_addr);
+                            if let Some(iter) = listed_rows.borrow_mut().remove(&old_addr) {
+                                server_list.0.remove(&iter);
+                            }
+                        }
+
+                        ping_history
+                            .lock()
+                            .unwrap()
+                            .prune(activity::now().saturating_sub(activity::PING_HISTORY_MAX_AGE));
+
+                        let entries = favorite_entries(&resources, &server_list);
+
+                        // Only favorites are sampled to keep the log small
+                        {
+                            let now = activity::now();
+                            let mut activity_log = activity_log.lock().unwrap();
+                            for entry in &entries {
+                                if let Some(num_clients) = entry.num_clients {
+                                    activity_log.record(
+                                        &entry.favorite,
+                                        activity::Sample(now, num_clients),
+                                    );
+                                }
+                            }
+                            activity_log.retain(&resources.settings.lock().unwrap().favorites);
+                            if let Err(e) = activity_log.save() {
+                                warn!("Failed to save activity log: {}", e);
+                            }
+                        }
+
+                        notify_active_favorites(
+                            &app,
+                            &resources,
+                            &mut favorite_counts.lock().unwrap(),
+                            &entries,
+                        );
+
+                        if let Some(tray_handle) = tray_handle.as_ref() {
+                            tray_handle.update(move |tray| tray.entries = entries);
+                        }
+
+                        // Show disappeared servers until the highlight fades
+                        {
+                            let present_servers = present_servers.lock().unwrap();
+                            for (key, data) in previous_servers.lock().unwrap().drain() {
+                                if !present_servers.contains(&key) {
+                                    let is_pinned = resources
+                                        .settings
+                                        .lock()
+                                        .unwrap()
+                                        .is_pinned(data.game_id, &key);
+
+                                    // Rows kept by an incremental refresh are already listed
+                                    let listed = listed_rows.borrow_mut().remove(&key);
+                                    let iter =
+                                        listed.unwrap_or_else(|| server_list.append_server(data));
+                                    server_list.set_pinned(&iter, is_pinned);
+                                    update_score(&resources, &server_list, &iter);
+                                    server_list.set_change(&iter, RowChange::Gone);
+                                }
+                            }
+                            listed_rows.borrow_mut().clear();
+                        }
+
+                        gtk::timeout_add_seconds(CHANGE_HIGHLIGHT_DURATION, {
+                            let server_list = server_list.clone();
+                            let generation = refresh_generation.load(Ordering::SeqCst);
+                            let refresh_generation = refresh_generation.clone();
+                            move || {
+                                // The list was refreshed again in the meantime
+                                if refresh_generation.load(Ordering::SeqCst) == generation {
+                                    let mut gone = Vec::new();
+                                    server_list.0.foreach(|_, _, iter| {
+                                        match server_list.get_change(iter) {
+                                            RowChange::New | RowChange::Updated => {
+                                                server_list.set_change(iter, RowChange::Unchanged)
+                                            }
+                                            RowChange::Gone => gone.push(iter.clone()),
+                                            RowChange::Unchanged => {}
+                                        }
+                                        false
+                                    });
+                                    for iter in gone {
+                                        server_list.0.remove(&iter);
+                                    }
+                                }
+
+                                glib::Continue(false)
+                            }
+                        });
+                    }
+                };
+            }
+            if let Some(batch) = batch.take() {
+                batch.finish(&resources, &server_list);
+            }
+
+            glib::Continue(connected)
         }
     });
 
-    refresher.clicked();
+    let auto_refresh_interval = resources.settings.lock().unwrap().auto_refresh_interval;
+    if auto_refresh_interval > 0 {
+        gtk::timeout_add_seconds(auto_refresh_interval, {
+            let refresher = refresher.clone();
+            move || {
+                if refresher.is_sensitive() {
+                    refresher.clicked();
+                }
+                glib::Continue(true)
+            }
+        });
+    }
+
+    if settings::Settings::exists() {
+        if resources.settings.lock().unwrap().refresh_on_startup {
+            refresher.clicked();
+        }
+    } else {
+        dialogs::run_welcome_assistant(resources, {
+            let refresher = refresher.clone();
+            let resources = resources.clone();
+            move || {
+                if resources.settings.lock().unwrap().refresh_on_startup {
+                    refresher.clicked();
+                }
+            }
+        });
+    }
+
+    window.connect_delete_event(move |w, _| {
+        // Keep running in background, the tray menu allows to bring the window back
+        if tray_handle.is_some() {
+            w.hide();
+            return Inhibit(true);
+        }
 
-    let window = resources.ui.get_object::<MainWindow, _>().0;
-    window.connect_delete_event(|_, _| Inhibit(false));
+        Inhibit(false)
+    });
 
     window.show_all();
 
     app.add_window(&window);
 }
 
+/// Command line switches activating application actions, used by the desktop file
+const ACTION_SWITCHES: &[(&str, &str)] = &[
+    ("--refresh", "refresh"),
+    ("--quick-connect", "quick-connect"),
+    ("--show-favorites", "show-favorites"),
+];
+
 fn main() {
-    env_logger::init();
+    let (log_options, args) = logging::Options::parse(std::env::args().collect());
+    let log_buffer = logging::init(&log_options).unwrap_or_else(|e| {
+        eprintln!("Failed to set up logging: {}", e);
+        std::process::exit(1);
+    });
+
+    if args.iter().any(|arg| arg == "--dump-config") {
+        let settings = settings::Settings::load();
+        let game_list = games::GameList::new(&settings);
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&game_list.describe(&settings)).unwrap()
+        );
+        return;
+    }
 
     let rt = tokio::runtime::Runtime::new().unwrap();
 
-    let application =
-        gtk::Application::new(Some("io.obozrenie"), gio::ApplicationFlags::empty()).unwrap();
-    let resources = static_resources::init().expect("GResource initialization failed.");
+    #[cfg(feature = "tui")]
+    {
+        if args.iter().any(|arg| arg == "--tui") {
+            if let Err(e) = tui::run(rt.executor()) {
+                error!("Terminal frontend failed: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+    }
+
+    let application = gtk::Application::new(
+        Some("io.obozrenie"),
+        gio::ApplicationFlags::HANDLES_COMMAND_LINE,
+    )
+    .unwrap();
+    let resources = static_resources::init(log_buffer).expect("GResource initialization failed.");
     application.connect_startup({
         let executor = rt.executor();
         move |app| {
+            if let Err(missing) = resources.ui.validate() {
+                dialogs::show_missing_widgets(&missing);
+                std::process::exit(1);
+            }
+
             build_ui(app, executor.clone(), &resources);
         }
     });
-    application.connect_activate(|_| {});
+    // Brings the window back from the tray, e.g. when a notification is clicked
+    application.connect_activate(|app| {
+        if let Some(window) = app.get_active_window() {
+            window.present();
+        }
+    });
+    // Switches are forwarded from secondary instances, so desktop file actions reach the running one
+    application.connect_command_line(|app, cmdline| {
+        for arg in cmdline.get_arguments() {
+            if let Some((_, action)) = ACTION_SWITCHES
+                .iter()
+                .find(|(switch, _)| arg.to_str() == Some(*switch))
+            {
+                app.activate_action(action, None);
+            }
+        }
+        app.activate();
+        0
+    });
 
-    application.run(&std::env::args().collect::<Vec<_>>());
+    application.run(&args);
 }
@@ -0,0 +1,365 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Refresh of the server list, independent of the frontend.
+
+use futures01::{future as future01, sync::oneshot};
+use log::*;
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{self, Display, Formatter};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    mpsc::Sender,
+    Arc, Mutex,
+};
+use std::time::{Duration, Instant};
+use tokio::prelude::StreamExt;
+
+use crate::activity;
+use crate::games::{self, DisplayServer, Game, MasterAnswered, NameMorpher, Querier};
+use crate::settings::Settings;
+
+/// Limit for a game's query until it has been refreshed
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Bounds of tuned timeouts, so that one fast or stalled refresh does not throw them off
+pub const MIN_TIMEOUT: Duration = Duration::from_secs(5);
+pub const MAX_TIMEOUT: Duration = Duration::from_secs(60);
+/// Tuned timeouts are this many times the average refresh duration
+pub const TIMEOUT_FACTOR: u32 = 3;
+
+/// Timeout for a game whose recent refreshes took `durations`.
+pub fn auto_timeout(durations: &[Duration]) -> Duration {
+    match activity::average(durations) {
+        Some(average) => (average * TIMEOUT_FACTOR).max(MIN_TIMEOUT).min(MAX_TIMEOUT),
+        None => DEFAULT_TIMEOUT,
+    }
+}
+
+/// Timeout set by the user for the game, or else one tuned from its past refreshes.
+pub fn query_timeout(game: Game, settings: &Settings, stats: &activity::RefreshStats) -> Duration {
+    settings
+        .query_timeouts
+        .get(&game)
+        .map(|secs| Duration::from_secs(*secs))
+        .unwrap_or_else(|| auto_timeout(&stats.durations(game)))
+}
+
+/// Progress of a refresh, reported as it happens
+#[derive(Clone, Debug)]
+pub enum RefreshEvent {
+    AddServer(DisplayServer),
+    QueryStarted(Game),
+    /// Time the game took to refresh, sent before its successful completion
+    QueryDuration((Game, Duration)),
+    /// Number of servers found or the error the query failed with
+    GameRefreshComplete((Game, Result<usize, String>)),
+    /// Whether latency is measured with ICMP echo, sent once the pinger has been spawned
+    PingerReady(bool),
+    /// Games and master servers done so far, sent at the start, as masters answer and after each game
+    Progress(Progress),
+    /// All games have completed or timed out
+    RefreshComplete,
+}
+
+/// Completion of a refresh. Masters are done as they answer, see `Querier::query_reporting`, and those that
+/// did not once their game completes, fails or times out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Progress {
+    pub games_done: usize,
+    pub games_total: usize,
+    pub masters_done: usize,
+    pub masters_total: usize,
+}
+
+impl Progress {
+    /// Share of the refresh that is done, from 0 to 1. Games weigh as much as the masters they ask.
+    pub fn fraction(&self) -> f64 {
+        if self.masters_total > 0 {
+            self.masters_done as f64 / self.masters_total as f64
+        } else if self.games_total > 0 {
+            self.games_done as f64 / self.games_total as f64
+        } else {
+            1.0
+        }
+    }
+}
+
+impl Display for Progress {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}/{} games done", self.games_done, self.games_total)?;
+        if self.masters_total > 0 {
+            write!(f, ", {}/{} masters", self.masters_done, self.masters_total)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Queries `games`, at most `max_concurrent` at once or all of them if zero, reporting to `event_sink`.
+/// Games missing from `timeouts` are limited to `DEFAULT_TIMEOUT`.
+///
+/// Must be called within a tokio runtime. Resolving or dropping the sender of `cancelled` aborts the queries
+/// in flight and drops the queued ones.
+pub fn start(
+    games: HashMap<Game, (Arc<dyn Querier>, Arc<dyn NameMorpher>)>,
+    timeouts: HashMap<Game, Duration>,
+    max_concurrent: usize,
+    cancelled: oneshot::Receiver<()>,
+    event_sink: Sender<RefreshEvent>,
+) {
+    // Zero means all games at once
+    let concurrency = if max_concurrent > 0 {
+        max_concurrent
+    } else {
+        games.len()
+    };
+
+    debug!("Starting query");
+
+    if games.is_empty() {
+        event_sink.send(RefreshEvent::RefreshComplete).unwrap();
+        return;
+    }
+
+    let task = Arc::new(RefreshTask::new(games, timeouts, cancelled, event_sink));
+    task.send(RefreshEvent::Progress(*task.progress.lock().unwrap()));
+    for _ in 0..concurrency {
+        spawn_next_query(task.clone());
+    }
+}
+
+/// Text of a panic payload, which is a string unless a value was passed to `std::panic::panic_any`.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|v| v.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".into())
+}
+
+/// Turns a panic into an error, logging the payload with a hex dump at debug level. Replies the protocol
+/// parsers choke on tend to end up in the message.
+fn panic_error(game: Game, payload: Box<dyn Any + Send>) -> failure::Error {
+    let message = panic_message(&*payload);
+    warn!("Query of {} panicked: {}", game, message);
+    debug!(
+        "Panic payload of {} query: {}",
+        game,
+        message
+            .bytes()
+            .map(|b| format!("{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+
+    failure::err_msg(format!("Query failed unexpectedly: {}", message))
+}
+
+/// Fails the stream instead of unwinding through the task when polling it panics, so that a malformed reply
+/// only takes down the query of its game. The stream ends with the error.
+struct CatchPanics<S> {
+    game: Game,
+    inner: S,
+}
+
+impl<S> futures01::Stream for CatchPanics<S>
+where
+    S: futures01::Stream<Error = failure::Error>,
+{
+    type Item = S::Item;
+    type Error = failure::Error;
+
+    fn poll(&mut self) -> futures01::Poll<Option<Self::Item>, Self::Error> {
+        let inner = &mut self.inner;
+        panic::catch_unwind(AssertUnwindSafe(|| inner.poll()))
+            .unwrap_or_else(|payload| Err(panic_error(self.game, payload)))
+    }
+}
+
+/// Game with the querier and name morpher to refresh it with
+pub type GameQuery = (Game, (Arc<dyn Querier>, Arc<dyn NameMorpher>));
+
+/// Queries of a single refresh. Every game runs in a task of its own, which starts the next queued game once
+/// done, so that one slow game holds back neither the others nor their results.
+struct RefreshTask {
+    queue: Mutex<VecDeque<GameQuery>>,
+    /// Games that have not completed yet, including queued ones
+    remaining: AtomicUsize,
+    total_queried: AtomicUsize,
+    progress: Mutex<Progress>,
+    pinger_reported: AtomicBool,
+    /// Limits for a single game's query
+    timeouts: HashMap<Game, Duration>,
+    /// Resolves when the refresh is cancelled or superseded
+    cancelled: future01::Shared<oneshot::Receiver<()>>,
+    event_sink: Mutex<Sender<RefreshEvent>>,
+}
+
+impl RefreshTask {
+    fn new(
+        games: HashMap<Game, (Arc<dyn Querier>, Arc<dyn NameMorpher>)>,
+        timeouts: HashMap<Game, Duration>,
+        cancelled: oneshot::Receiver<()>,
+        event_sink: Sender<RefreshEvent>,
+    ) -> Self {
+        use futures01::prelude::*;
+
+        Self {
+            remaining: AtomicUsize::new(games.len()),
+            progress: Mutex::new(Progress {
+                games_total: games.len(),
+                masters_total: games
+                    .values()
+                    .map(|(querier, _)| querier.master_count())
+                    .sum(),
+                ..Default::default()
+            }),
+            queue: Mutex::new(games.into_iter().collect()),
+            total_queried: AtomicUsize::new(0),
+            pinger_reported: AtomicBool::new(false),
+            timeouts,
+            cancelled: cancelled.shared(),
+            event_sink: Mutex::new(event_sink),
+        }
+    }
+
+    fn send(&self, event: RefreshEvent) {
+        self.event_sink.lock().unwrap().send(event).unwrap();
+    }
+}
+
+/// Takes the next queued game and spawns its query.
+fn spawn_next_query(task: Arc<RefreshTask>) {
+    use futures01::prelude::*;
+
+    let (game_id, (querier, name_morpher)) = match task.queue.lock().unwrap().pop_front() {
+        Some(v) => v,
+        None => {
+            return;
+        }
+    };
+
+    task.send(RefreshEvent::QueryStarted(game_id));
+
+    let started = Instant::now();
+    let timeout = task
+        .timeouts
+        .get(&game_id)
+        .copied()
+        .unwrap_or(DEFAULT_TIMEOUT);
+
+    let master_count = querier.master_count();
+    let masters_answered = Arc::new(AtomicUsize::new(0));
+    let master_answered: MasterAnswered = Arc::new({
+        let task = task.clone();
+        let masters_answered = masters_answered.clone();
+        move || {
+            // Not trusting the querier to report no more masters than it counts
+            if masters_answered.fetch_add(1, Ordering::SeqCst) < master_count {
+                let progress = {
+                    let mut progress = task.progress.lock().unwrap();
+                    progress.masters_done += 1;
+                    *progress
+                };
+                task.send(RefreshEvent::Progress(progress));
+            }
+        }
+    });
+    let game_queried = Arc::new(AtomicUsize::new(0));
+    let servers: Box<dyn Stream<Item = rgs::models::Server, Error = failure::Error> + Send> =
+        match panic::catch_unwind(AssertUnwindSafe(|| {
+            querier.query_reporting(master_answered)
+        })) {
+            Ok(v) => v,
+            Err(payload) => Box::new(futures01::stream::once(Err(panic_error(game_id, payload)))),
+        };
+    let query = servers
+        .inspect({
+            let task = task.clone();
+            let game_queried = game_queried.clone();
+            move |srv| {
+                task.send(RefreshEvent::AddServer(DisplayServer::new(
+                    game_id,
+                    &*name_morpher,
+                    srv.clone(),
+                )));
+                task.total_queried.fetch_add(1, Ordering::Relaxed);
+                game_queried.fetch_add(1, Ordering::Relaxed);
+            }
+        })
+        .map_err(move |e| {
+            debug!(
+                "Error while querying {} returned an error: {:?}",
+                game_id, e
+            );
+            e
+        });
+    let query = CatchPanics {
+        game: game_id,
+        inner: query,
+    }
+    .timeout(timeout)
+    .for_each(|_| Ok(()))
+    .then(move |res| {
+        Ok::<_, ()>(
+            res.map(|_| game_queried.load(Ordering::Relaxed))
+                .map_err(|e| e.to_string()),
+        )
+    });
+
+    tokio::spawn(query.select2(task.cancelled.clone()).then(move |res| {
+        match res {
+            Ok(future01::Either::A((res, _))) => {
+                debug!("{} query complete", game_id);
+                if let Some(icmp) = games::icmp_available() {
+                    if !task.pinger_reported.swap(true, Ordering::SeqCst) {
+                        task.send(RefreshEvent::PingerReady(icmp));
+                    }
+                }
+                if res.is_ok() {
+                    task.send(RefreshEvent::QueryDuration((game_id, started.elapsed())));
+                }
+                task.send(RefreshEvent::GameRefreshComplete((game_id, res)));
+
+                // Masters that never replied are done with the timeout
+                let progress = {
+                    let mut progress = task.progress.lock().unwrap();
+                    progress.games_done += 1;
+                    progress.masters_done +=
+                        master_count.saturating_sub(masters_answered.load(Ordering::SeqCst));
+                    *progress
+                };
+                task.send(RefreshEvent::Progress(progress));
+
+                if task.remaining.fetch_sub(1, Ordering::SeqCst) == 1 {
+                    debug!(
+                        "Queried {} servers",
+                        task.total_queried.load(Ordering::Relaxed)
+                    );
+                    task.send(RefreshEvent::RefreshComplete);
+                } else {
+                    spawn_next_query(task);
+                }
+            }
+            // Queued games are dropped together with the task
+            _ => debug!("{} query cancelled", game_id),
+        }
+
+        Ok(())
+    }));
+}
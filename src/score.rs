@@ -0,0 +1,108 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Relevance score behind the default "smart" sort of the server list.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Weights of the score components, in points
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScoreWeights {
+    /// Added for favorite servers
+    pub favorite: f64,
+    /// Added for a server filled within the sweet spot, less the further it is from it
+    pub population: f64,
+    /// Range of player to slot ratios that gets the full population weight, as there is someone to play
+    /// with and a free slot
+    pub sweet_spot: (f64, f64),
+    /// Subtracted per 100 ms of ping
+    pub ping: f64,
+    /// Subtracted for servers whose ping is unknown
+    pub unknown_ping: f64,
+    /// Subtracted for servers without players
+    pub empty: f64,
+}
+
+impl Default for ScoreWeights {
+    fn default() -> Self {
+        Self {
+            favorite: 1000.0,
+            population: 100.0,
+            sweet_spot: (0.5, 0.8),
+            ping: 20.0,
+            unknown_ping: 40.0,
+            empty: 50.0,
+        }
+    }
+}
+
+/// Server data the score is computed from
+#[derive(Clone, Debug, Default)]
+pub struct ScoreInput {
+    pub favorite: bool,
+    pub num_clients: Option<u64>,
+    pub max_clients: Option<u64>,
+    pub ping: Option<Duration>,
+}
+
+/// Share of the population weight for the ratio of players to slots, peaking within the sweet spot and
+/// falling to zero for empty and full servers.
+fn population_share(ratio: f64, (low, high): (f64, f64)) -> f64 {
+    let ratio = ratio.max(0.0).min(1.0);
+
+    if ratio < low {
+        ratio / low
+    } else if ratio <= high {
+        1.0
+    } else if high < 1.0 {
+        (1.0 - ratio) / (1.0 - high)
+    } else {
+        1.0
+    }
+}
+
+/// Relevance of the server, higher is better.
+pub fn score(weights: &ScoreWeights, input: &ScoreInput) -> i32 {
+    let mut score = 0.0;
+
+    if input.favorite {
+        score += weights.favorite;
+    }
+
+    match (input.num_clients, input.max_clients) {
+        (Some(0), _) => {
+            score -= weights.empty;
+        }
+        (Some(num_clients), Some(max_clients)) if max_clients > 0 => {
+            score += weights.population
+                * population_share(num_clients as f64 / max_clients as f64, weights.sweet_spot);
+        }
+        _ => {}
+    }
+
+    match input.ping {
+        Some(ping) => {
+            score -= weights.ping * ping.as_millis() as f64 / 100.0;
+        }
+        None => {
+            score -= weights.unknown_ping;
+        }
+    }
+
+    score.round() as i32
+}
@@ -0,0 +1,95 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Server passwords kept in the keyring with libsecret's `secret-tool`.
+
+use log::*;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use obozrenie::settings::Favorite;
+
+/// Attribute the passwords are stored under, with the key as its value
+const ATTRIBUTE: &str = "obozrenie-server";
+
+/// Keyring key of the server's password.
+pub fn key(server: &Favorite) -> String {
+    format!("{}/{}", server.game.id(), server.addr)
+}
+
+pub fn store(key: &str, password: &str) -> Result<(), failure::Error> {
+    let mut child = Command::new("secret-tool")
+        .arg("store")
+        .arg(format!("--label=Obozrenie: {}", key))
+        .arg(ATTRIBUTE)
+        .arg(key)
+        .stdin(Stdio::piped())
+        .spawn()?;
+
+    // Passed on stdin to keep it out of the process list
+    child
+        .stdin
+        .take()
+        .ok_or_else(|| failure::err_msg("secret-tool has no stdin"))?
+        .write_all(password.as_bytes())?;
+
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(failure::err_msg(format!(
+            "secret-tool store exited with {}",
+            status
+        )));
+    }
+
+    Ok(())
+}
+
+/// `None` if no password is stored under the key.
+pub fn lookup(key: &str) -> Result<Option<String>, failure::Error> {
+    let output = Command::new("secret-tool")
+        .arg("lookup")
+        .arg(ATTRIBUTE)
+        .arg(key)
+        .output()?;
+
+    // Exits with failure when nothing matches
+    Ok(Some(String::from_utf8(output.stdout)?).filter(|_| output.status.success()))
+}
+
+pub fn clear(key: &str) -> Result<(), failure::Error> {
+    let status = Command::new("secret-tool")
+        .arg("clear")
+        .arg(ATTRIBUTE)
+        .arg(key)
+        .status()?;
+    if !status.success() {
+        return Err(failure::err_msg(format!(
+            "secret-tool clear exited with {}",
+            status
+        )));
+    }
+
+    Ok(())
+}
+
+/// Removes the password without waiting for the keyring, which may take a while or prompt to be unlocked.
+pub fn clear_in_background(key: String) {
+    std::thread::spawn(move || {
+        if let Err(e) = clear(&key) {
+            warn!("Failed to remove the password from the keyring: {}", e);
+        }
+    });
+}
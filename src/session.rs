@@ -0,0 +1,110 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Games launched in this session, so that a second instance is not started by accident.
+
+use std::collections::HashMap;
+use std::process::Command;
+
+use crate::games::Game;
+
+/// What to do when launching a game that is still running
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlreadyRunning {
+    /// Raise the window of the running instance instead of launching
+    BringToFront,
+    /// Start another instance
+    LaunchAnyway,
+}
+
+/// Launched games that have not exited yet, and the choices made about them. Kept in memory only.
+#[derive(Clone, Debug, Default)]
+pub struct RunningGames {
+    /// Process ids in the order of launch
+    pids: HashMap<Game, Vec<u32>>,
+    decisions: HashMap<Game, AlreadyRunning>,
+}
+
+impl RunningGames {
+    pub fn started(&mut self, game: Game, pid: u32) {
+        self.pids.entry(game).or_default().push(pid);
+    }
+
+    pub fn exited(&mut self, game: Game, pid: u32) {
+        if let Some(pids) = self.pids.get_mut(&game) {
+            pids.retain(|&v| v != pid);
+            if pids.is_empty() {
+                self.pids.remove(&game);
+            }
+        }
+    }
+
+    /// Most recently launched instance of the game that is still running
+    pub fn latest(&self, game: Game) -> Option<u32> {
+        self.pids.get(&game).and_then(|pids| pids.last().cloned())
+    }
+
+    /// Choice to apply without asking, if one was remembered
+    pub fn decision(&self, game: Game) -> Option<AlreadyRunning> {
+        self.decisions.get(&game).cloned()
+    }
+
+    pub fn remember(&mut self, game: Game, decision: AlreadyRunning) {
+        self.decisions.insert(game, decision);
+    }
+}
+
+/// Ids of the windows owned by `pid` in the output of `wmctrl -lp`.
+pub fn windows_of(wmctrl_output: &str, pid: u32) -> Vec<String> {
+    wmctrl_output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let id = fields.next()?;
+            let _desktop = fields.next()?;
+            let owner = fields.next()?.parse::<u32>().ok()?;
+
+            if owner == pid {
+                Some(id.to_string())
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Raises a window of the process with `wmctrl`. Best effort: it needs `wmctrl` and an X11 window manager,
+/// and finds nothing for games started through a wrapper like flatpak or Steam, whose windows belong to
+/// another process. Returns whether a window was raised.
+pub fn bring_to_front(pid: u32) -> bool {
+    let output = match Command::new("wmctrl").arg("-lp").output() {
+        Ok(v) if v.status.success() => v,
+        _ => {
+            return false;
+        }
+    };
+
+    windows_of(&String::from_utf8_lossy(&output.stdout), pid)
+        .first()
+        .map(|id| {
+            Command::new("wmctrl")
+                .args(&["-i", "-a", id])
+                .status()
+                .map(|status| status.success())
+                .unwrap_or(false)
+        })
+        .unwrap_or(false)
+}
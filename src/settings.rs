@@ -0,0 +1,813 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use log::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::games::{Game, Retransmit};
+use crate::score::ScoreWeights;
+use crate::share;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Favorite {
+    pub game: Game,
+    pub addr: String,
+}
+
+impl Favorite {
+    /// Favorite entry of the listed server, keyed by `games::server_key` rather than the address it replied
+    /// from.
+    pub fn of_server(game: Game, srv: &rgs::models::Server) -> Self {
+        Self {
+            game,
+            addr: crate::games::server_key(srv),
+        }
+    }
+}
+
+/// How a favorite is launched instead of the game's defaults
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LaunchProfile {
+    /// Flatpak ref to launch instead of the one set for the game
+    pub flatpak_ref: Option<String>,
+    /// Appended to the game's command line
+    pub extra_args: Vec<String>,
+}
+
+/// Name a favorite recognizes its server by, so that it follows the server to another address
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FollowName {
+    /// Normalized server name, ignoring case
+    pub pattern: String,
+    /// Match servers whose name contains the pattern instead of equals it
+    #[serde(default)]
+    pub substring: bool,
+}
+
+/// Where a followed favorite was found after a refresh
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FollowResult {
+    /// Still at its address, or not found at all
+    Stay,
+    Moved(String),
+    /// Several servers match, for the user to choose from
+    Ambiguous(Vec<String>),
+}
+
+impl FollowName {
+    pub fn matches(&self, name: &str) -> bool {
+        let name = name.trim().to_lowercase();
+        let pattern = self.pattern.trim().to_lowercase();
+
+        if pattern.is_empty() {
+            false
+        } else if self.substring {
+            name.contains(&pattern)
+        } else {
+            name == pattern
+        }
+    }
+
+    /// Finds the favorite at `addr` among the listed `(address, normalized name)` pairs. It stays if its address
+    /// is listed. Of several matches the only one on the same host wins.
+    pub fn locate(&self, addr: &str, servers: &[(String, String)]) -> FollowResult {
+        if servers.iter().any(|(listed, _)| listed == addr) {
+            return FollowResult::Stay;
+        }
+
+        let candidates = servers
+            .iter()
+            .filter(|(_, name)| self.matches(name))
+            .map(|(candidate, _)| candidate.clone())
+            .collect::<Vec<_>>();
+
+        if candidates.is_empty() {
+            return FollowResult::Stay;
+        }
+
+        if candidates.len() == 1 {
+            return FollowResult::Moved(candidates[0].clone());
+        }
+
+        let same_host = candidates
+            .iter()
+            .filter(|candidate| addr_host(candidate) == addr_host(addr))
+            .collect::<Vec<_>>();
+        if same_host.len() == 1 {
+            FollowResult::Moved(same_host[0].clone())
+        } else {
+            FollowResult::Ambiguous(candidates)
+        }
+    }
+}
+
+/// Host part of a `host:port` address
+fn addr_host(addr: &str) -> &str {
+    addr.rsplitn(2, ':').last().unwrap_or(addr)
+}
+
+/// Recognizing a server that came back under another address, e.g. on a dynamic IP, so that its favorite, pin
+/// and history move along. Applies to incremental refreshes, which would otherwise list the stale address until
+/// the list is cleared.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AddressMigration {
+    pub enabled: bool,
+    /// Require the same port, which a server usually keeps when its IP changes
+    pub same_port: bool,
+    /// Rules that must agree, such as a host id. A rule reported by one of the servers only tells them apart.
+    pub id_rules: Vec<String>,
+}
+
+impl Default for AddressMigration {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            same_port: true,
+            id_rules: vec!["hostid".into(), "sv_hostid".into()],
+        }
+    }
+}
+
+/// What stays the same when a server changes address, see `AddressMigration`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ServerIdentity {
+    pub game: Game,
+    /// Normalized server name, ignoring case
+    pub name: String,
+    pub port: Option<u16>,
+    /// Values of `AddressMigration::id_rules`, `None` where not reported
+    pub ids: Vec<Option<String>>,
+}
+
+impl AddressMigration {
+    /// Identity of the server at `addr`. `None` for servers without a name or, if the port counts, without one,
+    /// which cannot be told apart.
+    pub fn identity(
+        &self,
+        game: Game,
+        addr: &str,
+        name: &str,
+        rules: &HashMap<String, Value>,
+    ) -> Option<ServerIdentity> {
+        let name = name.trim().to_lowercase();
+        if name.is_empty() {
+            return None;
+        }
+
+        let port = if self.same_port {
+            Some(addr.rsplitn(2, ':').next()?.parse().ok()?)
+        } else {
+            None
+        };
+
+        Some(ServerIdentity {
+            game,
+            name,
+            port,
+            ids: self
+                .id_rules
+                .iter()
+                .map(|rule| {
+                    rules.get(rule).map(|v| match v {
+                        Value::String(v) => v.clone(),
+                        v => v.to_string(),
+                    })
+                })
+                .collect(),
+        })
+    }
+
+    /// Pairs servers gone in a refresh with new ones of the same identity, as `(old address, new address)`.
+    /// An identity shared by several gone or several new servers is left alone, as it may be a network of
+    /// servers named alike rather than one that moved.
+    pub fn find_moves(
+        &self,
+        gone: &[(String, ServerIdentity)],
+        new: &[(String, ServerIdentity)],
+    ) -> Vec<(String, String)> {
+        if !self.enabled {
+            return Vec::new();
+        }
+
+        let unique = |servers: &[(String, ServerIdentity)]| {
+            let mut out = HashMap::<&ServerIdentity, Option<&String>>::new();
+            for (addr, identity) in servers {
+                out.entry(identity)
+                    .and_modify(|v| *v = None)
+                    .or_insert(Some(addr));
+            }
+            out
+        };
+
+        let new = unique(new);
+        let mut out = unique(gone)
+            .into_iter()
+            .filter_map(|(identity, old_addr)| {
+                let new_addr = new.get(identity).and_then(|v| *v)?;
+                Some((old_addr?.clone(), new_addr.clone()))
+            })
+            .collect::<Vec<_>>();
+        out.sort();
+
+        out
+    }
+}
+
+/// Addresses kept in a favorite's history
+const MAX_PAST_ADDRS: usize = 10;
+
+/// User-supplied details of a favorite server
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FavoriteDetails {
+    pub server: Favorite,
+    /// Shown in the server list instead of the reported name
+    #[serde(default)]
+    pub alias: Option<String>,
+    #[serde(default)]
+    pub note: String,
+    /// Key of the server password in the keyring, `None` if no password is stored
+    #[serde(default)]
+    pub password_ref: Option<String>,
+    #[serde(default)]
+    pub profile: LaunchProfile,
+    /// Follow the server by name when it moves, `None` to stay at the address
+    #[serde(default)]
+    pub follow_name: Option<FollowName>,
+    /// Addresses the favorite was moved from by following its server, oldest first
+    #[serde(default)]
+    pub past_addrs: Vec<String>,
+}
+
+impl FavoriteDetails {
+    pub fn new(server: Favorite) -> Self {
+        Self {
+            server,
+            alias: None,
+            note: String::new(),
+            password_ref: None,
+            profile: LaunchProfile::default(),
+            follow_name: None,
+            past_addrs: Vec::new(),
+        }
+    }
+
+    /// Whether there is nothing worth keeping.
+    pub fn is_empty(&self) -> bool {
+        self.alias.is_none()
+            && self.note.is_empty()
+            && self.password_ref.is_none()
+            && self.profile == LaunchProfile::default()
+            && self.follow_name.is_none()
+            && self.past_addrs.is_empty()
+    }
+}
+
+/// Regex replacement applied to server names
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NameReplacement {
+    pub pattern: String,
+    pub replacement: String,
+}
+
+/// Presentation of the server list
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListLayout {
+    /// Compact rows in narrow windows, the table otherwise
+    Auto,
+    Wide,
+    Compact,
+}
+
+impl ListLayout {
+    pub fn id(self) -> &'static str {
+        match self {
+            ListLayout::Auto => "auto",
+            ListLayout::Wide => "wide",
+            ListLayout::Compact => "compact",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Some(match id {
+            "auto" => ListLayout::Auto,
+            "wide" => ListLayout::Wide,
+            "compact" => ListLayout::Compact,
+            _ => {
+                return None;
+            }
+        })
+    }
+}
+
+/// Place of servers with unknown ping in the list, whatever the sort order
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnknownPing {
+    Last,
+    First,
+}
+
+impl UnknownPing {
+    pub fn id(self) -> &'static str {
+        match self {
+            UnknownPing::Last => "last",
+            UnknownPing::First => "first",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Some(match id {
+            "last" => UnknownPing::Last,
+            "first" => UnknownPing::First,
+            _ => {
+                return None;
+            }
+        })
+    }
+}
+
+/// How games are started to join a server
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LaunchMethod {
+    Flatpak,
+    /// Executable installed on the system, see `Settings::launch_commands`
+    Native,
+    Steam,
+    /// Command line template, see `Settings::launch_commands`
+    Custom,
+    /// Copy the server address for joining from the game
+    Copy,
+}
+
+impl LaunchMethod {
+    pub const ALL: &'static [LaunchMethod] = &[
+        LaunchMethod::Flatpak,
+        LaunchMethod::Native,
+        LaunchMethod::Steam,
+        LaunchMethod::Custom,
+        LaunchMethod::Copy,
+    ];
+
+    pub fn id(self) -> &'static str {
+        match self {
+            LaunchMethod::Flatpak => "flatpak",
+            LaunchMethod::Native => "native",
+            LaunchMethod::Steam => "steam",
+            LaunchMethod::Custom => "custom",
+            LaunchMethod::Copy => "copy",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Some(match id {
+            "flatpak" => LaunchMethod::Flatpak,
+            "native" => LaunchMethod::Native,
+            "steam" => LaunchMethod::Steam,
+            "custom" => LaunchMethod::Custom,
+            "copy" => LaunchMethod::Copy,
+            _ => {
+                return None;
+            }
+        })
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            LaunchMethod::Flatpak => "Flatpak",
+            LaunchMethod::Native => "Installed executable",
+            LaunchMethod::Steam => "Steam",
+            LaunchMethod::Custom => "Custom command",
+            LaunchMethod::Copy => "Copy address",
+        }
+    }
+}
+
+/// Current schema version of the settings file. Files written before it was recorded are of version 1.
+pub const SETTINGS_VERSION: u32 = 1;
+
+/// User-configurable application settings, persisted as JSON in the user config directory.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Schema version of the settings file, see `SETTINGS_VERSION`
+    pub version: u32,
+    /// Template used by the "Share server info" action
+    pub share_template: String,
+    /// Servers marked as favorite by the user
+    pub favorites: Vec<Favorite>,
+    /// Aliases, notes, passwords and launch profiles of favorites
+    pub favorite_details: Vec<FavoriteDetails>,
+    /// Servers kept at the top of the list whatever the sort order
+    pub pinned: Vec<Favorite>,
+    /// Hide the window into the system tray instead of quitting on close
+    pub close_to_tray: bool,
+    /// Notify when favorite servers get players while the window is hidden
+    pub notify_favorites: bool,
+    /// Interval between automatic refreshes in seconds, 0 to disable
+    pub auto_refresh_interval: u32,
+    /// Latency limit applied by the "Joinable now" filter preset, in milliseconds
+    pub joinable_max_ping: u32,
+    /// Games that are not queried on refresh
+    pub disabled_games: HashSet<Game>,
+    /// Games left out of the application entirely, applied on restart
+    pub hidden_games: HashSet<Game>,
+    /// Player name passed to games that support it, empty to keep the game's own
+    pub player_name: String,
+    /// Two-letter code of the user's country
+    pub home_country: String,
+    pub refresh_on_startup: bool,
+    /// Overrides the Rigs of Rods master server address
+    pub rigsofrods_master: Option<String>,
+    /// Replaces the master servers of UDP games, as `host:port`. Applied on restart.
+    pub master_servers: HashMap<Game, Vec<String>>,
+    /// Proxy for HTTP master servers, e.g. `http://proxy:3128`. The `http_proxy` and `https_proxy`
+    /// environment variables are used if unset. Applied on restart.
+    pub http_proxy: Option<String>,
+    /// Time an HTTP master server request may take in total, in seconds. Applied on restart.
+    pub http_timeout: u64,
+    /// SOCKS5 proxy for game server queries as `host:port`, which has to relay UDP. Servers are pinged by
+    /// the queries themselves then. Applied on restart.
+    pub socks5_proxy: Option<String>,
+    /// Local address that game server queries go out from, e.g. that of a VPN interface, so that latency is
+    /// measured over the route the game will take. Servers are pinged by the queries themselves then.
+    /// Interfaces are picked by address, as binding to one by name needs privileges on Linux and is not
+    /// supported elsewhere. An IPv4 address leaves IPv6 servers unreachable and the other way around. HTTP
+    /// masters, Hedgewars and LAN scans are not affected. Applied on restart.
+    pub bind_address: Option<String>,
+    /// Limits for each game's refresh in seconds. Games without one get a timeout tuned from past refreshes.
+    pub query_timeouts: HashMap<Game, u64>,
+    /// Names games are shown with instead of the built-in ones, e.g. translated or shortened
+    pub game_names: HashMap<Game, String>,
+    /// Versions of the installed games. Servers running another version are flagged, see `games::server_version`.
+    pub local_versions: HashMap<Game, String>,
+    /// Times a server query without a reply is sent again. Applied on restart.
+    pub query_retries: u32,
+    /// Wait for a server's reply before sending the query again, in milliseconds. Applied on restart.
+    pub query_retry_window: u64,
+    /// `query_retries` of games whose servers need more or fewer. The window is the same for all games.
+    /// Applied on restart.
+    pub query_retries_per_game: HashMap<Game, u32>,
+    /// Maximum number of games queried at once, 0 for unlimited
+    pub max_concurrent_refreshes: usize,
+    /// Reuse measured pings for this many seconds instead of pinging on every refresh, 0 to disable
+    pub ping_cache_ttl: u64,
+    /// Give up pinging a server after this many milliseconds and leave it out of the list, 0 to wait for the reply
+    pub ping_limit: u64,
+    /// Reuse the server lists of master servers for this many seconds, querying only the listed servers, 0 to
+    /// always ask the masters. Applied on restart.
+    pub master_cache_ttl: u64,
+    /// Least time between requests to the same master server from any game, in milliseconds. Applied on restart.
+    pub master_request_interval: u64,
+    /// Show servers without players dimmed
+    pub dim_empty_servers: bool,
+    /// Show recent pings of the server in its details
+    pub show_ping_history: bool,
+    /// Strip decorations and clan tags around server names
+    pub strip_name_decorations: bool,
+    /// Applied to server names after game-specific normalization
+    pub name_replacements: Vec<NameReplacement>,
+    /// Flatpak refs to launch games from instead of the stable branch, e.g. `org.xonotic.Xonotic/x86_64/beta`
+    pub flatpak_refs: HashMap<Game, String>,
+    /// How games are launched unless set for the game. Applied on restart.
+    pub launch_method: LaunchMethod,
+    /// Launch methods chosen for single games. Applied on restart.
+    pub game_launch_methods: HashMap<Game, LaunchMethod>,
+    /// Executable run by the native launch method, or the command line of the custom one with `{addr}`,
+    /// `{host}`, `{port}`, `{password}` and `{name}` placeholders. Applied on restart.
+    pub launch_commands: HashMap<Game, String>,
+    /// Offer to relaunch with another password if the game exits within this many seconds, 0 to disable
+    pub password_retry_window: u64,
+    /// Detach the server list from the view while more servers than this arrive per second, 0 to keep it attached
+    pub detach_view_rate: u32,
+    /// Games whose servers are screened with `filters::SpamFilter`
+    pub collapse_spam_games: HashSet<Game>,
+    /// Games whose servers are not pinged, for those that block ICMP. Applied on restart.
+    pub no_ping_games: HashSet<Game>,
+    /// Server list layout last chosen by the user
+    pub list_layout: ListLayout,
+    /// Where servers with unknown ping are sorted
+    pub unknown_ping: UnknownPing,
+    /// Weights of the relevance score the list is sorted by at startup
+    pub smart_sort: ScoreWeights,
+    /// Update the listed servers in place on refresh instead of clearing the list first
+    pub incremental_refresh: bool,
+    /// Refresh only the game selected in the sidebar when a single one is, refreshing all stays in the menu
+    pub refresh_selected_game: bool,
+    /// Keep player counts and maps of the top listed servers up to date between refreshes
+    pub live_update: bool,
+    /// List servers that game filters reject, dimmed, to tell an empty master from an overeager filter.
+    /// Applied on restart.
+    pub bypass_server_filters: bool,
+    pub address_migration: AddressMigration,
+    /// Anti-cheat shown for the secure flag of the game's servers instead of the one from `games::anticheat`,
+    /// empty if the flag means nothing for the game
+    pub anticheat_names: HashMap<Game, String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            share_template: share::DEFAULT_TEMPLATE.into(),
+            favorites: Vec::new(),
+            favorite_details: Vec::new(),
+            pinned: Vec::new(),
+            close_to_tray: false,
+            notify_favorites: true,
+            auto_refresh_interval: 0,
+            joinable_max_ping: 150,
+            disabled_games: HashSet::new(),
+            hidden_games: HashSet::new(),
+            player_name: String::new(),
+            home_country: String::new(),
+            refresh_on_startup: true,
+            rigsofrods_master: None,
+            master_servers: HashMap::new(),
+            http_proxy: None,
+            http_timeout: 30,
+            socks5_proxy: None,
+            bind_address: None,
+            query_timeouts: HashMap::new(),
+            game_names: HashMap::new(),
+            local_versions: HashMap::new(),
+            query_retries: 2,
+            query_retry_window: 1500,
+            query_retries_per_game: HashMap::new(),
+            max_concurrent_refreshes: 0,
+            ping_cache_ttl: 0,
+            ping_limit: 0,
+            master_cache_ttl: 60,
+            master_request_interval: 2000,
+            dim_empty_servers: false,
+            show_ping_history: false,
+            strip_name_decorations: false,
+            name_replacements: Vec::new(),
+            flatpak_refs: HashMap::new(),
+            launch_method: LaunchMethod::Flatpak,
+            game_launch_methods: HashMap::new(),
+            launch_commands: HashMap::new(),
+            password_retry_window: 15,
+            detach_view_rate: 50,
+            collapse_spam_games: HashSet::new(),
+            no_ping_games: HashSet::new(),
+            list_layout: ListLayout::Auto,
+            unknown_ping: UnknownPing::Last,
+            smart_sort: ScoreWeights::default(),
+            incremental_refresh: false,
+            refresh_selected_game: true,
+            live_update: false,
+            bypass_server_filters: false,
+            address_migration: AddressMigration::default(),
+            anticheat_names: HashMap::new(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn path() -> PathBuf {
+        glib::get_user_config_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("obozrenie")
+            .join("settings.json")
+    }
+
+    /// Whether settings were ever saved, i.e. this is not the first run.
+    pub fn exists() -> bool {
+        Self::path().exists()
+    }
+
+    /// Loads settings from disk, falling back to defaults if they are missing or unreadable. Unreadable
+    /// settings are backed up first, as they are overwritten on the next save.
+    pub fn load() -> Self {
+        let path = Self::path();
+
+        match fs::read_to_string(&path) {
+            Ok(data) => match serde_json::from_str::<Self>(&data) {
+                Ok(settings) => {
+                    if settings.is_read_only() {
+                        warn!(
+                            "{} was written by a newer release (version {}, supported up to {}), not saving changes",
+                            path.display(),
+                            settings.version,
+                            SETTINGS_VERSION
+                        );
+                    }
+                    settings
+                }
+                Err(e) => {
+                    let backup = path.with_file_name("settings.json.bak");
+                    match fs::copy(&path, &backup) {
+                        Ok(_) => warn!(
+                            "Failed to parse {}: {}. Using defaults, a copy was kept at {}.",
+                            path.display(),
+                            e,
+                            backup.display()
+                        ),
+                        Err(copy_error) => warn!(
+                            "Failed to parse {}: {}. Using defaults, failed to keep a copy: {}",
+                            path.display(),
+                            e,
+                            copy_error
+                        ),
+                    }
+                    Self::default()
+                }
+            },
+            Err(e) => {
+                debug!("Failed to read {}: {}. Using defaults.", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Name the game is shown with, the user's override or the built-in one
+    pub fn game_name(&self, game: Game) -> String {
+        self.game_names
+            .get(&game)
+            .filter(|name| !name.is_empty())
+            .cloned()
+            .unwrap_or_else(|| game.to_string())
+    }
+
+    /// Resending of queries without a reply to the game's masters and servers
+    pub fn query_retransmit(&self, game: Game) -> Retransmit {
+        Retransmit {
+            retries: self
+                .query_retries_per_game
+                .get(&game)
+                .cloned()
+                .unwrap_or(self.query_retries),
+            window: Duration::from_millis(self.query_retry_window),
+        }
+    }
+
+    /// Configured version of the installed game, `None` if unset or empty
+    pub fn local_version(&self, game: Game) -> Option<&str> {
+        self.local_versions
+            .get(&game)
+            .map(String::as_str)
+            .filter(|version| !version.is_empty())
+    }
+
+    /// Anti-cheat the secure flag of the game's servers stands for, `None` if it means nothing for the game
+    pub fn anticheat_name(&self, game: Game) -> Option<String> {
+        match self.anticheat_names.get(&game) {
+            Some(name) => Some(name.trim().to_string()).filter(|name| !name.is_empty()),
+            None => crate::games::anticheat(game).map(String::from),
+        }
+    }
+
+    pub fn launch_method(&self, game: Game) -> LaunchMethod {
+        self.game_launch_methods
+            .get(&game)
+            .cloned()
+            .unwrap_or(self.launch_method)
+    }
+
+    /// Native executable or custom command line set for the game, `None` if unset or empty
+    pub fn launch_command(&self, game: Game) -> Option<&str> {
+        self.launch_commands
+            .get(&game)
+            .map(|command| command.trim())
+            .filter(|command| !command.is_empty())
+    }
+
+    pub fn is_favorite(&self, game: Game, addr: &str) -> bool {
+        self.favorites
+            .iter()
+            .any(|fav| fav.game == game && fav.addr == addr)
+    }
+
+    /// Adds the server to favorites if absent, removes it with its details otherwise.
+    pub fn toggle_favorite(&mut self, game: Game, addr: String) {
+        let favorite = Favorite { game, addr };
+
+        if self.favorites.contains(&favorite) {
+            self.favorites.retain(|fav| *fav != favorite);
+            self.favorite_details.retain(|v| v.server != favorite);
+        } else {
+            self.favorites.push(favorite);
+        }
+    }
+
+    /// Details of the server if it is a favorite that has any.
+    pub fn favorite_details(&self, game: Game, addr: &str) -> Option<&FavoriteDetails> {
+        self.favorite_details
+            .iter()
+            .find(|v| v.server.game == game && v.server.addr == addr)
+    }
+
+    /// Favorites whose password is in the keyring.
+    pub fn saved_passwords(&self) -> HashSet<Favorite> {
+        self.favorite_details
+            .iter()
+            .filter(|v| v.password_ref.is_some())
+            .map(|v| v.server.clone())
+            .collect()
+    }
+
+    /// Replaces the details of the favorite, dropping them if empty.
+    pub fn set_favorite_details(&mut self, details: FavoriteDetails) {
+        self.favorite_details.retain(|v| v.server != details.server);
+        if !details.is_empty() {
+            self.favorite_details.push(details);
+        }
+    }
+
+    /// Moves the favorite to another address with its details and pin, noting the old address.
+    pub fn move_favorite(&mut self, game: Game, addr: &str, new_addr: String) {
+        let old = Favorite {
+            game,
+            addr: addr.to_string(),
+        };
+        let new = Favorite {
+            game,
+            addr: new_addr,
+        };
+
+        for list in [&mut self.favorites, &mut self.pinned].iter_mut() {
+            if list.contains(&new) {
+                list.retain(|v| *v != old);
+            } else if let Some(v) = list.iter_mut().find(|v| **v == old) {
+                *v = new.clone();
+            }
+        }
+
+        if self.favorite_details.iter().any(|v| v.server == old) {
+            self.favorite_details.retain(|v| v.server != new);
+        }
+        for details in &mut self.favorite_details {
+            if details.server == old {
+                details.server = new.clone();
+                details.past_addrs.retain(|v| *v != new.addr);
+                details.past_addrs.push(old.addr.clone());
+                let excess = details.past_addrs.len().saturating_sub(MAX_PAST_ADDRS);
+                details.past_addrs.drain(..excess);
+            }
+        }
+    }
+
+    pub fn is_pinned(&self, game: Game, addr: &str) -> bool {
+        self.pinned
+            .iter()
+            .any(|fav| fav.game == game && fav.addr == addr)
+    }
+
+    /// Pins the server to the top of the list if unpinned, unpins it otherwise.
+    pub fn toggle_pinned(&mut self, game: Game, addr: String) {
+        let server = Favorite { game, addr };
+
+        if self.pinned.contains(&server) {
+            self.pinned.retain(|v| *v != server);
+        } else {
+            self.pinned.push(server);
+        }
+    }
+
+    /// Whether the settings come from a newer release, which `save` refuses to overwrite as fields unknown to
+    /// this one would be lost.
+    pub fn is_read_only(&self) -> bool {
+        self.version > SETTINGS_VERSION
+    }
+
+    pub fn save(&self) -> Result<(), failure::Error> {
+        let path = Self::path();
+
+        if self.is_read_only() {
+            return Err(failure::err_msg(format!(
+                "{} was written by a newer release, not overwriting it",
+                path.display()
+            )));
+        }
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        fs::write(&path, serde_json::to_string_pretty(self)?)?;
+
+        Ok(())
+    }
+}
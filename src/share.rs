@@ -0,0 +1,249 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use enum_iter::EnumIterator;
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+use crate::games::{self, Game, NameMorpher};
+use crate::settings::Settings;
+
+/// Supported placeholders: `{game}`, `{name}`, `{addr}`, `{map}`, `{players}`, `{max_players}`, `{mod}`, `{ping}`.
+pub const DEFAULT_TEMPLATE: &str = "{game}: {name}
+Address: {addr}
+Map: {map}
+Players: {players}/{max_players}
+Mod: {mod}
+Ping: {ping} ms";
+
+/// Renders server information into a human-readable text block.
+pub fn format_server_info(template: &str, game: Game, srv: &rgs::models::Server) -> String {
+    let unknown = || "?".to_string();
+
+    vec![
+        ("{game}", game.to_string()),
+        ("{name}", srv.name.clone().unwrap_or_else(unknown)),
+        ("{addr}", srv.addr.to_string()),
+        ("{map}", srv.map.clone().unwrap_or_else(unknown)),
+        (
+            "{players}",
+            srv.num_clients
+                .map(|v| v.to_string())
+                .unwrap_or_else(unknown),
+        ),
+        (
+            "{max_players}",
+            srv.max_clients
+                .map(|v| v.to_string())
+                .unwrap_or_else(unknown),
+        ),
+        ("{mod}", srv.mod_name.clone().unwrap_or_else(unknown)),
+        (
+            "{ping}",
+            srv.ping
+                .map(|dur| (dur.as_secs() * 1000 + dur.subsec_nanos() as u64 / 1000000).to_string())
+                .unwrap_or_else(unknown),
+        ),
+    ]
+    .into_iter()
+    .fold(template.to_string(), |out, (placeholder, value)| {
+        out.replace(placeholder, &value)
+    })
+}
+
+/// Detail or rule of two compared servers, `None` where a server does not report it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ComparedField {
+    pub name: String,
+    pub values: [Option<String>; 2],
+}
+
+impl ComparedField {
+    pub fn differs(&self) -> bool {
+        self.values[0] != self.values[1]
+    }
+}
+
+fn compared_details(
+    settings: &Settings,
+    name_morpher: &dyn NameMorpher,
+    game: Game,
+    srv: &rgs::models::Server,
+) -> Vec<(&'static str, Option<String>)> {
+    vec![
+        ("Game", Some(settings.game_name(game))),
+        ("Address", Some(games::server_key(srv))),
+        ("Map", srv.map.clone()),
+        ("Players", srv.num_clients.map(|v| v.to_string())),
+        ("Max players", srv.max_clients.map(|v| v.to_string())),
+        (
+            "Player list",
+            srv.players.as_ref().map(|players| {
+                let mut names = players
+                    .iter()
+                    .map(|player| name_morpher.morph(player.name.clone()))
+                    .collect::<Vec<_>>();
+                names.sort();
+                if names.is_empty() {
+                    "None".into()
+                } else {
+                    names.join(", ")
+                }
+            }),
+        ),
+        ("Mod", srv.mod_name.clone()),
+        (
+            "Password",
+            srv.need_pass
+                .map(|v| if v { "Yes" } else { "No" }.to_string()),
+        ),
+        (
+            "Ping",
+            srv.ping.map(|dur| format!("{} ms", dur.as_millis())),
+        ),
+    ]
+}
+
+fn rule_text(srv: &rgs::models::Server, name: &str) -> Option<String> {
+    match srv.rules.get(name)? {
+        Value::Null => None,
+        Value::String(v) => Some(v.clone()),
+        v => Some(v.to_string()),
+    }
+}
+
+/// Lines up two servers side by side: details first, then the rules of either server by name, leaving out
+/// the ones Obozrenie adds itself. Players are listed where the server list reports them, with their names
+/// as `name_morpher` shows them.
+pub fn compare_servers(
+    settings: &Settings,
+    name_morpher: &dyn NameMorpher,
+    a: (Game, &rgs::models::Server),
+    b: (Game, &rgs::models::Server),
+) -> Vec<ComparedField> {
+    let rules =
+        a.1.rules
+            .keys()
+            .chain(b.1.rules.keys())
+            .filter(|name| !games::is_internal_rule(name))
+            .collect::<BTreeSet<_>>();
+
+    compared_details(settings, name_morpher, a.0, a.1)
+        .into_iter()
+        .zip(compared_details(settings, name_morpher, b.0, b.1))
+        .map(|((name, left), (_, right))| ComparedField {
+            name: name.to_string(),
+            values: [left, right],
+        })
+        .chain(rules.into_iter().map(|name| ComparedField {
+            name: name.clone(),
+            values: [rule_text(a.1, name), rule_text(b.1, name)],
+        }))
+        .collect()
+}
+
+/// Server to join, as read from a link shared in a chat
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JoinLink {
+    /// `None` if the link does not tell
+    pub game: Option<Game>,
+    /// `host:port`
+    pub addr: String,
+    pub password: Option<String>,
+}
+
+/// Checks that the text is a `host:port` address.
+fn parse_addr(text: &str) -> Option<String> {
+    let text = text.trim().trim_end_matches('/');
+    let sep = text.rfind(':')?;
+    let (host, port) = (&text[..sep], &text[sep + 1..]);
+
+    if host.is_empty() || host.contains(char::is_whitespace) || port.parse::<u16>().is_err() {
+        return None;
+    }
+
+    Some(text.to_string())
+}
+
+/// Text after a case insensitive prefix
+fn strip_prefix<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    if text.len() >= prefix.len()
+        && text.is_char_boundary(prefix.len())
+        && text[..prefix.len()].eq_ignore_ascii_case(prefix)
+    {
+        Some(&text[prefix.len()..])
+    } else {
+        None
+    }
+}
+
+/// Reads a join link. Understood are:
+///
+/// - text of the share button, see `DEFAULT_TEMPLATE`, whose first line names the game
+/// - `steam://connect/host:port[/password]`
+/// - `unv://host:port`, and `<game id>://host:port` for every game, see `Game::id`
+/// - `connect host:port` as typed in the console of Quake engine games
+/// - `host:port`
+pub fn parse_join_link(text: &str) -> Option<JoinLink> {
+    let text = text.trim();
+
+    if let Some(addr) = text
+        .lines()
+        .find_map(|line| strip_prefix(line.trim(), "Address:"))
+    {
+        let first_line = text.lines().next().unwrap_or_default();
+        return Some(JoinLink {
+            game: Game::enum_iter().find(|game| first_line.starts_with(&format!("{}: ", game))),
+            addr: parse_addr(addr)?,
+            password: None,
+        });
+    }
+
+    if let Some(rest) = strip_prefix(text, "steam://connect/") {
+        let mut parts = rest.splitn(2, '/');
+        return Some(JoinLink {
+            game: None,
+            addr: parse_addr(parts.next()?)?,
+            password: parts
+                .next()
+                .map(|v| v.trim_end_matches('/').to_string())
+                .filter(|v| !v.is_empty()),
+        });
+    }
+
+    if let Some(sep) = text.find("://") {
+        let scheme = text[..sep].to_lowercase();
+        let game = match scheme.as_str() {
+            "unv" => Some(Game::Unvanquished),
+            _ => Game::from_id(&scheme),
+        };
+        return Some(JoinLink {
+            game: Some(game?),
+            addr: parse_addr(&text[sep + 3..])?,
+            password: None,
+        });
+    }
+
+    // Console commands may start with a slash
+    let command = text.trim_start_matches(|c| c == '/' || c == '\\');
+    let addr = strip_prefix(command, "connect ").unwrap_or(text);
+
+    Some(JoinLink {
+        game: None,
+        addr: parse_addr(addr)?,
+        password: None,
+    })
+}
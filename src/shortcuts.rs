@@ -0,0 +1,74 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use gio::prelude::*;
+use gtk::prelude::*;
+
+pub struct Shortcut {
+    /// Detailed action name
+    pub action: &'static str,
+    pub accels: &'static [&'static str],
+    pub title: &'static str,
+}
+
+/// Keyboard accelerators. Both accelerator registration and the shortcuts window are built from this table.
+pub const SHORTCUTS: &[Shortcut] = &[
+    Shortcut {
+        action: "app.refresh",
+        accels: &["F5", "<Primary>r"],
+        title: "Refresh server list",
+    },
+    Shortcut {
+        action: "app.preferences",
+        accels: &["<Primary>comma"],
+        title: "Preferences",
+    },
+    Shortcut {
+        action: "app.shortcuts",
+        accels: &["<Primary>question", "<Primary>F1"],
+        title: "Keyboard shortcuts",
+    },
+    Shortcut {
+        action: "app.quit",
+        accels: &["<Primary>q"],
+        title: "Quit",
+    },
+];
+
+pub fn register_accels(app: &gtk::Application) {
+    for shortcut in SHORTCUTS {
+        app.set_accels_for_action(shortcut.action, shortcut.accels);
+    }
+}
+
+/// Fills the shortcuts window group with an entry for every accelerator.
+pub fn populate_group(group: &gtk::ShortcutsGroup) {
+    for shortcut in SHORTCUTS {
+        let entry = glib::Object::new(
+            gtk::ShortcutsShortcut::static_type(),
+            &[
+                ("accelerator", &shortcut.accels.join(" ")),
+                ("title", &shortcut.title),
+            ],
+        )
+        .unwrap()
+        .downcast::<gtk::ShortcutsShortcut>()
+        .unwrap();
+
+        entry.show();
+        group.add(&entry);
+    }
+}
@@ -0,0 +1,128 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::activity::{ActivityLog, RecentServers};
+use crate::settings::Settings;
+
+/// Version 2 added recent servers and favorite activity
+pub const BUNDLE_VERSION: u32 = 2;
+
+/// Full application state for moving between machines. Settings carry favorites with their details, filter
+/// presets and added servers.
+///
+/// Unknown keys are ignored and missing sections are defaulted, so bundles from other versions still load.
+/// Recent servers and activity missing from older bundles are left as they are on import.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StateBundle {
+    pub version: u32,
+    pub settings: Settings,
+    pub recent_servers: Option<RecentServers>,
+    pub activity: Option<ActivityLog>,
+}
+
+impl Default for StateBundle {
+    fn default() -> Self {
+        Self {
+            version: BUNDLE_VERSION,
+            settings: Settings::default(),
+            recent_servers: None,
+            activity: None,
+        }
+    }
+}
+
+/// Whether the section of the bundle differs from the current data, `false` if the bundle lacks it.
+fn section_changed<T: Serialize>(new: Option<&T>, current: &T) -> bool {
+    new.map_or(false, |new| {
+        serde_json::to_value(new).ok() != serde_json::to_value(current).ok()
+    })
+}
+
+impl StateBundle {
+    pub fn new(
+        settings: &Settings,
+        recent_servers: &RecentServers,
+        activity: &ActivityLog,
+    ) -> Self {
+        Self {
+            settings: settings.clone(),
+            recent_servers: Some(recent_servers.clone()),
+            activity: Some(activity.clone()),
+            ..Default::default()
+        }
+    }
+
+    pub fn from_json(data: &str) -> Result<Self, failure::Error> {
+        Ok(serde_json::from_str(data)?)
+    }
+
+    pub fn to_json(&self) -> Result<String, failure::Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Names of the settings that differ from `current` and would be overwritten on import.
+    pub fn changed_settings(&self, current: &Settings) -> Vec<String> {
+        match (
+            serde_json::to_value(&self.settings),
+            serde_json::to_value(current),
+        ) {
+            (Ok(Value::Object(new)), Ok(Value::Object(old))) => new
+                .into_iter()
+                .filter(|(k, v)| old.get(k) != Some(v))
+                .map(|(k, _)| k)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// What importing would overwrite: changed settings by name, then the other sections.
+    pub fn overwritten(
+        &self,
+        settings: &Settings,
+        recent_servers: &RecentServers,
+        activity: &ActivityLog,
+    ) -> Vec<String> {
+        let mut out = self.changed_settings(settings);
+        if section_changed(self.recent_servers.as_ref(), recent_servers) {
+            out.push("recent servers".into());
+        }
+        if section_changed(self.activity.as_ref(), activity) {
+            out.push("favorite activity".into());
+        }
+
+        out
+    }
+
+    /// Replaces the current data with the bundle's. Sections the bundle lacks are kept.
+    pub fn apply(
+        self,
+        settings: &mut Settings,
+        recent_servers: &mut RecentServers,
+        activity: &mut ActivityLog,
+    ) {
+        *settings = self.settings;
+        if let Some(v) = self.recent_servers {
+            *recent_servers = v;
+        }
+        if let Some(v) = self.activity {
+            *activity = v;
+        }
+    }
+}
@@ -14,40 +14,206 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use gdk_pixbuf::Pixbuf;
-use gio::{resources_register, Error, Resource};
+use failure::Fail;
+use gdk_pixbuf::{Colorspace, Pixbuf};
+use gio::{resources_get_info, resources_register, Error, Resource, ResourceLookupFlags};
 use glib::Bytes;
 use gtk;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
+use std::sync::{Arc, Mutex};
 
-use crate::games;
 use crate::widgets;
+use log::*;
+use obozrenie::activity::{ActivityLog, DataFile, RecentServers, RefreshStats};
+use obozrenie::games;
+use obozrenie::logging::RingBuffer;
+use obozrenie::session::RunningGames;
+use obozrenie::settings::{Favorite, Settings};
 
 const RES_ROOT_PATH: &str = "/io/obozrenie";
 
-impl games::GameIconSource for Resource {
-    fn get_icon(&self, game: games::Game) -> Pixbuf {
-        for format in ["png", "svg"].into_iter() {
-            if let Ok(pixbuf) = Pixbuf::new_from_resource_at_scale(
-                &format!("{}/game_icons/{}.{}", RES_ROOT_PATH, game.id(), format),
-                24,
-                24,
-                false,
-            ) {
-                return pixbuf;
+#[derive(Clone, Debug, Fail)]
+pub enum IconError {
+    #[fail(display = "no icon for {}", _0)]
+    Missing(games::Game),
+    #[fail(display = "failed to load the icon for {}: {}", _0, _1)]
+    Load(games::Game, String),
+}
+
+/// Game icons bundled into the registered GResource. Loaded icons are kept, so that each is decoded once.
+#[derive(Default)]
+pub struct BundledIcons {
+    cache: RefCell<HashMap<games::Game, Pixbuf>>,
+    fallback: RefCell<Option<Pixbuf>>,
+}
+
+/// Size of game icons in pixels
+const ICON_SIZE: i32 = 24;
+
+impl BundledIcons {
+    /// Icon of the game, or the generic one if it could not be loaded, which is then kept in its place.
+    pub fn icon(&self, game: games::Game) -> Pixbuf {
+        self.get_icon(game).unwrap_or_else(|e| {
+            warn!("Using the generic icon, {}", e);
+            let pixbuf = self.fallback_icon();
+            self.cache.borrow_mut().insert(game, pixbuf.clone());
+            pixbuf
+        })
+    }
+
+    /// Tries each bundled format in turn, as pixbuf loaders vary between systems.
+    fn get_icon(&self, game: games::Game) -> Result<Pixbuf, IconError> {
+        if let Some(pixbuf) = self.cache.borrow().get(&game) {
+            return Ok(pixbuf.clone());
+        }
+
+        let mut error = IconError::Missing(game);
+        for format in ["png", "svg"].iter() {
+            let path = format!("{}/game_icons/{}.{}", RES_ROOT_PATH, game.id(), format);
+            if resources_get_info(&path, ResourceLookupFlags::NONE).is_err() {
+                continue;
+            }
+
+            match Pixbuf::new_from_resource_at_scale(&path, ICON_SIZE, ICON_SIZE, false) {
+                Ok(pixbuf) => {
+                    self.cache.borrow_mut().insert(game, pixbuf.clone());
+                    return Ok(pixbuf);
+                }
+                Err(e) => {
+                    debug!("Failed to load {} icon for {}: {}", format, game, e);
+                    error = IconError::Load(game, e.to_string());
+                }
             }
         }
 
-        panic!("Failed to load icon for {}", game);
+        Err(error)
+    }
+
+    /// Generic icon for games whose own one could not be loaded
+    fn fallback_icon(&self) -> Pixbuf {
+        self.fallback
+            .borrow_mut()
+            .get_or_insert_with(|| {
+                Pixbuf::new_from_resource_at_scale(
+                    &format!("{}/game_icons/image-missing.png", RES_ROOT_PATH),
+                    ICON_SIZE,
+                    ICON_SIZE,
+                    false,
+                )
+                .unwrap_or_else(|e| {
+                    warn!("Failed to load the generic icon: {}", e);
+
+                    // Blank rather than no icon at all, as the list expects one for every game
+                    let pixbuf =
+                        Pixbuf::new(Colorspace::Rgb, true, 8, ICON_SIZE, ICON_SIZE).unwrap();
+                    pixbuf.fill(0);
+                    pixbuf
+                })
+            })
+            .clone()
     }
 }
 
 pub struct Resources {
     pub game_list: games::GameList,
+    /// Icons of the listed games, loaded on startup
+    pub game_icons: BundledIcons,
+    pub settings: Arc<Mutex<Settings>>,
+    pub recent_servers: Arc<Mutex<RecentServers>>,
+    /// Player counts of favorites, see `ActivityLog`
+    pub activity_log: Arc<Mutex<ActivityLog>>,
+    pub refresh_stats: Arc<Mutex<RefreshStats>>,
+    /// Data files that had to be started anew, with the reason
+    pub data_warnings: Vec<String>,
+    /// Recent log records for the debug log window
+    pub log_buffer: Arc<Mutex<RingBuffer>>,
+    /// Games launched in this session that are still running
+    pub running_games: Arc<Mutex<RunningGames>>,
+    /// Games whose launcher is available, `None` until probed in the background
+    pub installed_games: Arc<Mutex<Option<HashSet<games::Game>>>>,
+    /// Set while refresh results are inserted into the server list in one go, which the list filter then
+    /// lets through unchecked
+    pub inserting_servers: Cell<bool>,
+    /// Followed favorites that did not reply while several servers match their name, with the candidates as
+    /// address and name. The choice is left until asked for, see `FollowPrompt`.
+    pub ambiguous_follows: RefCell<Vec<(Favorite, Vec<(String, String)>)>>,
     pub ui: widgets::UIBuilder,
 }
 
-pub(crate) fn init() -> Result<Rc<Resources>, Error> {
+impl Resources {
+    /// Hands the installed games to `f` on the UI thread, once the launcher probes are done.
+    pub fn with_installed_games<F>(&self, f: F)
+    where
+        F: FnOnce(&HashSet<games::Game>) + 'static,
+    {
+        if let Some(installed_games) = &*self.installed_games.lock().unwrap() {
+            f(installed_games);
+            return;
+        }
+
+        let installed_games = self.installed_games.clone();
+        let mut f = Some(f);
+        gtk::timeout_add(100, move || {
+            glib::Continue(match &*installed_games.lock().unwrap() {
+                Some(installed_games) => {
+                    if let Some(f) = f.take() {
+                        f(installed_games);
+                    }
+                    false
+                }
+                None => true,
+            })
+        });
+    }
+}
+
+/// Probes the launchers off the UI thread, as some of them run external programs.
+fn probe_installed_games(game_list: &games::GameList) -> Arc<Mutex<Option<HashSet<games::Game>>>> {
+    let out = Arc::new(Mutex::new(None));
+    let launchers = game_list
+        .0
+        .iter()
+        .map(|(game, entry)| (*game, entry.launcher.clone()))
+        .collect::<Vec<_>>();
+
+    std::thread::spawn({
+        let out = out.clone();
+        move || {
+            let installed_games = launchers
+                .into_iter()
+                .filter(|(_, launcher)| launcher.is_available())
+                .map(|(game, _)| game)
+                .collect();
+            *out.lock().unwrap() = Some(installed_games);
+        }
+    });
+
+    out
+}
+
+/// Loads the data file, starting it anew if it is damaged or of an unsupported schema. The file is backed up
+/// first, as it is overwritten on the next save.
+pub(crate) fn load_data_file<T: DataFile>(warnings: &mut Vec<String>) -> T {
+    T::load().unwrap_or_else(|e| {
+        warn!("Discarding data file. {}", e);
+        warnings.push(match T::back_up() {
+            Ok(backup) => format!(
+                "{}. Started anew, a copy was kept at {}.",
+                e,
+                backup.display()
+            ),
+            Err(backup_error) => {
+                warn!("Failed to back up {}: {}", T::FILE, backup_error);
+                format!("{}. Started anew.", e)
+            }
+        });
+        T::default()
+    })
+}
+
+pub(crate) fn init(log_buffer: Arc<Mutex<RingBuffer>>) -> Result<Rc<Resources>, Error> {
     // load the gresource binary at build time and include/link it into the final binary.
     let res_bytes = include_bytes!(concat!(env!("OUT_DIR"), "/resources.gresource"));
 
@@ -58,8 +224,34 @@ pub(crate) fn init() -> Result<Rc<Resources>, Error> {
     // Register the resource so It wont be dropped and will continue to live in memory.
     resources_register(&resource);
 
+    let settings = Settings::load();
+    let mut data_warnings = Vec::new();
+    if settings.is_read_only() {
+        data_warnings.push(format!(
+            "{}: written by a newer release. Changes to the settings will not be saved.",
+            Settings::path().display()
+        ));
+    }
+    let game_list = games::GameList::new(&settings);
+    let installed_games = probe_installed_games(&game_list);
+    let game_icons = BundledIcons::default();
+    for game in game_list.0.keys() {
+        game_icons.icon(*game);
+    }
+
     let out = Rc::new(Resources {
-        game_list: games::GameList::new(&resource),
+        game_list,
+        game_icons,
+        settings: Arc::new(Mutex::new(settings)),
+        recent_servers: Arc::new(Mutex::new(load_data_file(&mut data_warnings))),
+        activity_log: Arc::new(Mutex::new(load_data_file(&mut data_warnings))),
+        refresh_stats: Arc::new(Mutex::new(load_data_file(&mut data_warnings))),
+        data_warnings,
+        log_buffer,
+        running_games: Arc::new(Mutex::new(RunningGames::default())),
+        installed_games,
+        inserting_servers: Cell::new(false),
+        ambiguous_follows: RefCell::new(Vec::new()),
         ui: widgets::UIBuilder {
             inner: gtk::Builder::new_from_resource(&format!("{}/ui/app.ui", RES_ROOT_PATH)),
         },
@@ -0,0 +1,129 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use ksni::menu::{MenuItem, StandardItem};
+use std::sync::mpsc::Sender;
+
+use obozrenie::settings::Favorite;
+
+/// Number of favorites shown in the tray menu
+pub const MAX_ENTRIES: usize = 5;
+
+#[derive(Clone, Debug)]
+pub enum TrayEvent {
+    ShowWindow,
+    /// Favorite to join, with its password state as of the last refresh
+    Connect(Favorite, Option<bool>),
+    Quit,
+}
+
+#[derive(Clone, Debug)]
+pub struct TrayEntry {
+    pub favorite: Favorite,
+    pub name: Option<String>,
+    pub num_clients: Option<u64>,
+    pub max_clients: Option<u64>,
+    pub need_pass: Option<bool>,
+}
+
+impl TrayEntry {
+    fn label(&self) -> String {
+        let players = match (self.num_clients, self.max_clients) {
+            (Some(num_clients), Some(max_clients)) => format!("{}/{}", num_clients, max_clients),
+            _ => "offline".into(),
+        };
+
+        format!(
+            "{} ({})",
+            self.name.as_ref().unwrap_or(&self.favorite.addr),
+            players
+        )
+    }
+}
+
+/// StatusNotifierItem with quick-connect menu for favorite servers.
+pub struct Tray {
+    pub entries: Vec<TrayEntry>,
+    pub event_sink: Sender<TrayEvent>,
+}
+
+impl ksni::Tray for Tray {
+    fn id(&self) -> String {
+        "io.obozrenie".into()
+    }
+
+    fn title(&self) -> String {
+        "Obozrenie".into()
+    }
+
+    fn icon_name(&self) -> String {
+        "obozrenie-short".into()
+    }
+
+    fn activate(&mut self, _x: i32, _y: i32) {
+        let _ = self.event_sink.send(TrayEvent::ShowWindow);
+    }
+
+    fn menu(&self) -> Vec<MenuItem<Self>> {
+        let mut menu = self
+            .entries
+            .iter()
+            .take(MAX_ENTRIES)
+            .map(|entry| {
+                let favorite = entry.favorite.clone();
+                let need_pass = entry.need_pass;
+
+                StandardItem {
+                    label: entry.label(),
+                    activate: Box::new(move |tray: &mut Self| {
+                        let _ = tray
+                            .event_sink
+                            .send(TrayEvent::Connect(favorite.clone(), need_pass));
+                    }),
+                    ..Default::default()
+                }
+                .into()
+            })
+            .collect::<Vec<_>>();
+
+        if !menu.is_empty() {
+            menu.push(MenuItem::Sepatator);
+        }
+
+        menu.push(
+            StandardItem {
+                label: "Show window".into(),
+                activate: Box::new(|tray: &mut Self| {
+                    let _ = tray.event_sink.send(TrayEvent::ShowWindow);
+                }),
+                ..Default::default()
+            }
+            .into(),
+        );
+        menu.push(
+            StandardItem {
+                label: "Quit".into(),
+                activate: Box::new(|tray: &mut Self| {
+                    let _ = tray.event_sink.send(TrayEvent::Quit);
+                }),
+                ..Default::default()
+            }
+            .into(),
+        );
+
+        menu
+    }
+}
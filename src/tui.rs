@@ -0,0 +1,562 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Terminal frontend, built with the `tui` feature and started with `--tui`.
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use futures01::{future, sync::oneshot};
+use ratatui::{prelude::*, widgets::*};
+use std::collections::HashSet;
+use std::io;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::time::Duration;
+use tokio::runtime::TaskExecutor;
+
+use obozrenie::activity::{self, DataFile};
+use obozrenie::filters::{self, Filters};
+use obozrenie::games::{self, DisplayServer, Game, GameList, LaunchData};
+use obozrenie::refresher::{self, RefreshEvent};
+use obozrenie::settings::Settings;
+
+const HELP: &str =
+    "r refresh  / search  g game  m mod  M map  l max ping  f favorites  e not empty  \
+     F not full  p no password  Enter connect  q quit";
+
+/// Filter edited in the status line
+#[derive(Clone, Copy)]
+enum FilterField {
+    Mod,
+    Map,
+    MaxPing,
+}
+
+impl FilterField {
+    fn prompt(self) -> &'static str {
+        match self {
+            FilterField::Mod => "Mod",
+            FilterField::Map => "Map",
+            FilterField::MaxPing => "Max ping, ms",
+        }
+    }
+}
+
+enum Mode {
+    Browse,
+    Search,
+    Filter(FilterField),
+    Password(Game, LaunchData),
+    /// Launch waiting for the user to accept the launcher's warning
+    Confirm(Game, LaunchData, String),
+}
+
+struct App {
+    settings: Settings,
+    game_list: GameList,
+    servers: Vec<DisplayServer>,
+    /// Keys of received servers, to drop duplicates reported by several masters
+    present_servers: HashSet<(Game, String)>,
+    filters: Filters,
+    /// Indices of servers passing the filters, in display order
+    visible: Vec<usize>,
+    table: TableState,
+    mode: Mode,
+    input: String,
+    status: String,
+    events: Option<Receiver<RefreshEvent>>,
+    /// Dropping the sender cancels the refresh in flight
+    cancel_refresh: Option<oneshot::Sender<()>>,
+}
+
+impl App {
+    fn new(settings: Settings, game_list: GameList) -> Self {
+        Self {
+            settings,
+            game_list,
+            servers: Vec::new(),
+            present_servers: HashSet::new(),
+            filters: Filters::default(),
+            visible: Vec::new(),
+            table: TableState::default(),
+            mode: Mode::Browse,
+            input: String::new(),
+            status: String::new(),
+            events: None,
+            cancel_refresh: None,
+        }
+    }
+
+    fn refresh(&mut self, executor: &TaskExecutor) {
+        let (event_sink, events) = channel();
+        let (cancel_sink, cancelled) = oneshot::channel();
+
+        let queries = self
+            .game_list
+            .0
+            .iter()
+            .filter(|(id, _)| !self.settings.disabled_games.contains(id))
+            .map(|(id, e)| (*id, (e.querier.clone(), e.name_morpher.clone())))
+            .collect();
+        let stats = activity::RefreshStats::load().unwrap_or_default();
+        let timeouts = self
+            .game_list
+            .0
+            .keys()
+            .map(|id| (*id, refresher::query_timeout(*id, &self.settings, &stats)))
+            .collect();
+        let max_concurrent = self.settings.max_concurrent_refreshes;
+
+        executor.spawn(future::lazy(move || {
+            refresher::start(queries, timeouts, max_concurrent, cancelled, event_sink);
+            Ok(())
+        }));
+
+        self.servers.clear();
+        self.present_servers.clear();
+        self.update_visible();
+        self.events = Some(events);
+        self.cancel_refresh = Some(cancel_sink);
+        self.status = "Refreshing...".into();
+    }
+
+    fn poll_events(&mut self) {
+        let mut changed = false;
+
+        while let Some(events) = self.events.as_ref() {
+            match events.try_recv() {
+                Ok(RefreshEvent::AddServer(data)) => {
+                    if self
+                        .present_servers
+                        .insert((data.game_id, games::server_key(&data.srv)))
+                    {
+                        self.servers.push(data);
+                        changed = true;
+                    }
+                }
+                Ok(RefreshEvent::QueryStarted(_)) => {}
+                Ok(RefreshEvent::PingerReady(_)) => {}
+                Ok(RefreshEvent::QueryDuration(_)) => {}
+                Ok(RefreshEvent::Progress(_)) => {}
+                Ok(RefreshEvent::GameRefreshComplete((game_id, Err(e)))) => {
+                    self.status = format!("Failed to query {}: {}", game_id, e);
+                }
+                Ok(RefreshEvent::GameRefreshComplete(_)) => {}
+                Ok(RefreshEvent::RefreshComplete) => {
+                    self.status = format!("{} servers", self.servers.len());
+                    self.events = None;
+                    self.cancel_refresh = None;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => self.events = None,
+            }
+        }
+
+        if changed {
+            self.update_visible();
+        }
+    }
+
+    fn is_favorite(&self, data: &DisplayServer) -> bool {
+        self.settings
+            .is_favorite(data.game_id, &games::server_key(&data.srv))
+    }
+
+    fn update_visible(&mut self) {
+        let selected = self.selected().map(|data| (data.game_id, data.srv.addr));
+
+        let mut visible = (0..self.servers.len())
+            .filter(|i| {
+                let data = &self.servers[*i];
+
+                if !self.filters.matches(data.game_id, &data.srv) {
+                    return false;
+                }
+
+                if self.filters.favorites_only && !self.is_favorite(data) {
+                    return false;
+                }
+
+                if !self.filters.search.is_empty() {
+                    let addr = data.srv.addr.to_string();
+                    let fields = [
+                        data.name.as_str(),
+                        data.srv.map.as_ref().map(|s| s.as_str()).unwrap_or(""),
+                        data.srv.mod_name.as_ref().map(|s| s.as_str()).unwrap_or(""),
+                        addr.as_str(),
+                    ];
+                    if filters::search_score(&self.filters.search, &fields).is_none() {
+                        return false;
+                    }
+                }
+
+                true
+            })
+            .collect::<Vec<_>>();
+        // Most populated first
+        visible.sort_by_key(|i| std::cmp::Reverse(self.servers[*i].srv.num_clients.unwrap_or(0)));
+        self.visible = visible;
+
+        // Keep the selection on the same server while the list changes
+        let position = selected.and_then(|(game_id, addr)| {
+            self.visible.iter().position(|i| {
+                self.servers[*i].game_id == game_id && self.servers[*i].srv.addr == addr
+            })
+        });
+        self.table.select(match position {
+            Some(v) => Some(v),
+            None if self.visible.is_empty() => None,
+            None => Some(0),
+        });
+    }
+
+    fn selected(&self) -> Option<&DisplayServer> {
+        self.table
+            .selected()
+            .and_then(|i| self.visible.get(i))
+            .map(|i| &self.servers[*i])
+    }
+
+    fn select_offset(&mut self, offset: isize) {
+        if self.visible.is_empty() {
+            return;
+        }
+
+        let current = self.table.selected().unwrap_or(0) as isize;
+        let last = self.visible.len() as isize - 1;
+        self.table
+            .select(Some((current + offset).max(0).min(last) as usize));
+    }
+
+    /// Switches the game filter to the next enabled game by name, or back to all games after the last one.
+    fn cycle_game(&mut self) {
+        let mut ids = self
+            .game_list
+            .0
+            .keys()
+            .filter(|id| !self.settings.disabled_games.contains(id))
+            .map(|id| (self.settings.game_name(*id), *id))
+            .collect::<Vec<_>>();
+        ids.sort();
+
+        let current = self.filters.games.iter().next().cloned();
+        let next = match current {
+            None => ids.first(),
+            Some(game) => ids.iter().skip_while(|(_, id)| *id != game).nth(1),
+        };
+
+        self.filters.games.clear();
+        self.status = match next {
+            Some((name, id)) => {
+                self.filters.games.insert(*id);
+                format!("Showing {} servers", name)
+            }
+            None => "Showing all games".into(),
+        };
+        self.update_visible();
+    }
+
+    fn edit_filter(&mut self, field: FilterField) {
+        self.input = match field {
+            FilterField::Mod => self.filters.game_mod.clone(),
+            FilterField::Map => self.filters.map.clone(),
+            FilterField::MaxPing if self.filters.max_ping > Duration::from_millis(0) => {
+                self.filters.max_ping.as_millis().to_string()
+            }
+            FilterField::MaxPing => String::new(),
+        };
+        self.mode = Mode::Filter(field);
+    }
+
+    fn apply_filter(&mut self, field: FilterField) {
+        let value = self.input.trim().to_string();
+        match field {
+            FilterField::Mod => self.filters.game_mod = value,
+            FilterField::Map => self.filters.map = value,
+            FilterField::MaxPing => {
+                if value.is_empty() {
+                    self.filters.max_ping = Duration::from_millis(0);
+                } else {
+                    match value.parse::<u64>() {
+                        Ok(v) => self.filters.max_ping = Duration::from_millis(v),
+                        Err(_) => {
+                            self.status = format!("Invalid ping limit: {}", value);
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+        self.update_visible();
+    }
+
+    fn connect(&mut self) {
+        let (game_id, data, need_pass) = match self.selected() {
+            Some(server) => (
+                server.game_id,
+                LaunchData {
+                    addr: server.srv.addr.to_string(),
+                    password: None,
+                    player_name: Some(self.settings.player_name.clone()).filter(|v| !v.is_empty()),
+                    company: None,
+                    company_password: None,
+                    flatpak_ref: None,
+                    extra_args: Vec::new(),
+                },
+                server.srv.need_pass == Some(true),
+            ),
+            None => {
+                return;
+            }
+        };
+
+        if need_pass && !self.game_list.0[&game_id].launcher.copies_address() {
+            self.input.clear();
+            self.mode = Mode::Password(game_id, data);
+        } else {
+            self.launch(game_id, data, false);
+        }
+    }
+
+    fn launch(&mut self, game_id: Game, data: LaunchData, confirmed: bool) {
+        let launcher = self.game_list.0[&game_id].launcher.clone();
+
+        if !confirmed {
+            if let Some(warning) = launcher.launch_confirmation(&data) {
+                self.mode = Mode::Confirm(game_id, data, warning.replace('\n', " "));
+                return;
+            }
+        }
+
+        self.mode = Mode::Browse;
+        let name = self.settings.game_name(game_id);
+        if launcher.copies_address() {
+            self.status = format!("Join the {} server at {} from the game", name, data.addr);
+            return;
+        }
+        self.status = match launcher.launch_cmd(&data) {
+            Some(mut cmd) => match cmd.spawn() {
+                Ok(_) => format!("Connecting to {} server at {}", name, data.addr),
+                Err(e) => format!("Failed to launch {}: {}", name, e),
+            },
+            None => format!("{} cannot be launched from Obozrenie", name),
+        };
+    }
+
+    /// Returns false when the user quits.
+    fn handle_key(&mut self, key: KeyCode, executor: &TaskExecutor) -> bool {
+        match std::mem::replace(&mut self.mode, Mode::Browse) {
+            Mode::Browse => match key {
+                KeyCode::Char('q') | KeyCode::Esc => {
+                    return false;
+                }
+                KeyCode::Char('r') => self.refresh(executor),
+                KeyCode::Char('/') => {
+                    self.input = self.filters.search.clone();
+                    self.mode = Mode::Search;
+                }
+                KeyCode::Char('g') => self.cycle_game(),
+                KeyCode::Char('m') => self.edit_filter(FilterField::Mod),
+                KeyCode::Char('M') => self.edit_filter(FilterField::Map),
+                KeyCode::Char('l') => self.edit_filter(FilterField::MaxPing),
+                KeyCode::Char('f') => {
+                    self.filters.favorites_only = !self.filters.favorites_only;
+                    self.update_visible();
+                }
+                KeyCode::Char('e') => {
+                    self.filters.not_empty = !self.filters.not_empty;
+                    self.update_visible();
+                }
+                KeyCode::Char('F') => {
+                    self.filters.not_full = !self.filters.not_full;
+                    self.update_visible();
+                }
+                KeyCode::Char('p') => {
+                    self.filters.no_password = !self.filters.no_password;
+                    self.update_visible();
+                }
+                KeyCode::Down | KeyCode::Char('j') => self.select_offset(1),
+                KeyCode::Up | KeyCode::Char('k') => self.select_offset(-1),
+                KeyCode::PageDown => self.select_offset(20),
+                KeyCode::PageUp => self.select_offset(-20),
+                KeyCode::Enter => self.connect(),
+                _ => {}
+            },
+            Mode::Search => match key {
+                KeyCode::Enter | KeyCode::Esc => {}
+                KeyCode::Char(c) => {
+                    self.input.push(c);
+                    self.filters.search = self.input.trim().to_string();
+                    self.update_visible();
+                    self.mode = Mode::Search;
+                }
+                KeyCode::Backspace => {
+                    self.input.pop();
+                    self.filters.search = self.input.trim().to_string();
+                    self.update_visible();
+                    self.mode = Mode::Search;
+                }
+                _ => self.mode = Mode::Search,
+            },
+            Mode::Filter(field) => match key {
+                KeyCode::Esc => {}
+                KeyCode::Enter => self.apply_filter(field),
+                KeyCode::Char(c) => {
+                    self.input.push(c);
+                    self.mode = Mode::Filter(field);
+                }
+                KeyCode::Backspace => {
+                    self.input.pop();
+                    self.mode = Mode::Filter(field);
+                }
+                _ => self.mode = Mode::Filter(field),
+            },
+            Mode::Password(game_id, data) => match key {
+                KeyCode::Esc => {}
+                KeyCode::Enter => self.launch(
+                    game_id,
+                    LaunchData {
+                        password: Some(self.input.clone()),
+                        ..data
+                    },
+                    false,
+                ),
+                KeyCode::Char(c) => {
+                    self.input.push(c);
+                    self.mode = Mode::Password(game_id, data);
+                }
+                KeyCode::Backspace => {
+                    self.input.pop();
+                    self.mode = Mode::Password(game_id, data);
+                }
+                _ => self.mode = Mode::Password(game_id, data),
+            },
+            Mode::Confirm(game_id, data, _) => {
+                if key == KeyCode::Char('y') {
+                    self.launch(game_id, data, true);
+                }
+            }
+        }
+
+        true
+    }
+
+    fn draw(&mut self, f: &mut Frame) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(f.size());
+
+        let rows = self
+            .visible
+            .iter()
+            .map(|i| {
+                let data = &self.servers[*i];
+                Row::new(vec![
+                    if self.is_favorite(data) { "*" } else { "" }.to_string(),
+                    self.settings.game_name(data.game_id),
+                    data.name.clone(),
+                    format!(
+                        "{}/{}",
+                        data.srv.num_clients.unwrap_or(0),
+                        data.srv.max_clients.unwrap_or(0)
+                    ),
+                    data.srv
+                        .ping
+                        .map(|dur| dur.as_millis().to_string())
+                        .unwrap_or_default(),
+                    data.srv.map.clone().unwrap_or_default(),
+                ])
+            })
+            .collect::<Vec<_>>();
+
+        let title = match self.filters.active_count() {
+            0 => format!("Obozrenie - {} servers", self.visible.len()),
+            n => format!("Obozrenie - {} servers, {} filters", self.visible.len(), n),
+        };
+
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(1),
+                Constraint::Length(14),
+                Constraint::Min(20),
+                Constraint::Length(7),
+                Constraint::Length(5),
+                Constraint::Length(16),
+            ],
+        )
+        .header(
+            Row::new(vec!["", "Game", "Name", "Players", "Ping", "Map"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        f.render_stateful_widget(table, chunks[0], &mut self.table);
+
+        let status = match &self.mode {
+            Mode::Browse => format!("{}  |  {}", self.status, HELP),
+            Mode::Search => format!("/{}", self.input),
+            Mode::Filter(field) => format!("{}: {}", field.prompt(), self.input),
+            Mode::Password(_, _) => format!("Password: {}", "*".repeat(self.input.len())),
+            Mode::Confirm(_, _, warning) => format!("{} [y/N]", warning),
+        };
+        f.render_widget(Paragraph::new(status), chunks[1]);
+    }
+
+    fn run<B: Backend>(
+        &mut self,
+        terminal: &mut Terminal<B>,
+        executor: &TaskExecutor,
+    ) -> Result<(), failure::Error> {
+        if self.settings.refresh_on_startup {
+            self.refresh(executor);
+        }
+
+        loop {
+            self.poll_events();
+            terminal.draw(|f| self.draw(f))?;
+
+            if event::poll(Duration::from_millis(100))? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press && !self.handle_key(key.code, executor) {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Runs the terminal frontend until the user quits. Favorites are shown but not editable.
+pub fn run(executor: TaskExecutor) -> Result<(), failure::Error> {
+    let settings = Settings::load();
+    let game_list = GameList::new(&settings);
+    let mut app = App::new(settings, game_list);
+
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+
+    let res = app.run(&mut terminal, &executor);
+
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen)?;
+
+    res
+}
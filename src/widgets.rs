@@ -14,19 +14,49 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use crate::games::*;
+use obozrenie::activity::{self, Sample};
+use obozrenie::filters;
+use obozrenie::games::*;
 
 use derive_more::From;
 use enum_iter::EnumIterator;
+use failure::Fail;
 use gdk_pixbuf::Pixbuf;
 use gtk::{self, prelude::*, TreeIter};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 pub trait Widget<O> {
     fn id() -> &'static str;
     fn inner(self) -> O;
 }
 
+/// Widget expected in the UI definition, collected for the startup validation pass
+pub struct RegisteredWidget {
+    pub id: &'static str,
+    pub static_type: fn() -> glib::Type,
+}
+
+inventory::collect!(RegisteredWidget);
+
+/// Lookup of a widget that is absent from the UI definition or has an unexpected type
+#[derive(Clone, Debug, Fail)]
+#[fail(display = "no {} with id \"{}\" in the UI definition", expected, id)]
+pub struct MissingWidget {
+    pub id: &'static str,
+    pub expected: String,
+}
+
+macro_rules! register_widget {
+    ($inner:ty, $id:expr) => {
+        inventory::submit! {
+            RegisteredWidget {
+                id: $id,
+                static_type: <$inner as glib::StaticType>::static_type,
+            }
+        }
+    };
+}
+
 macro_rules! widget {
     ($name:ident, $inner:ty, $id:expr) => {
         #[derive(Clone, Debug, From)]
@@ -41,42 +71,513 @@ macro_rules! widget {
                 self.0
             }
         }
+
+        register_widget!($inner, $id);
     };
 }
 
 widget!(ServerListFilter, gtk::TreeModelFilter, "ServerListFilter");
+widget!(ServerListSort, gtk::TreeModelSort, "ServerListSort");
 widget!(ServerListView, gtk::TreeView, "ServerListView");
+widget!(ServerSearch, gtk::SearchEntry, "ServerSearch");
+widget!(NameColumn, gtk::TreeViewColumn, "NameColumn");
+widget!(
+    GameIconRenderer,
+    gtk::CellRendererPixbuf,
+    "GameIconRenderer"
+);
+widget!(
+    LockIconRenderer,
+    gtk::CellRendererPixbuf,
+    "LockIconRenderer"
+);
+widget!(
+    StockClientRenderer,
+    gtk::CellRendererPixbuf,
+    "StockClientRenderer"
+);
+widget!(
+    ListenServerRenderer,
+    gtk::CellRendererPixbuf,
+    "ListenServerRenderer"
+);
+widget!(
+    VersionWarningRenderer,
+    gtk::CellRendererPixbuf,
+    "VersionWarningRenderer"
+);
+widget!(
+    SecureIconRenderer,
+    gtk::CellRendererPixbuf,
+    "SecureIconRenderer"
+);
+widget!(ServerListStack, gtk::Stack, "ServerListStack");
+widget!(CompactServerList, gtk::ListBox, "CompactServerList");
+widget!(SidePane, gtk::Box, "SidePane");
+widget!(SidePaneGame, gtk::ComboBoxText, "SidePaneGame");
+widget!(SidePaneFilter, gtk::TreeModelFilter, "SidePaneFilter");
+widget!(SidePaneSort, gtk::TreeModelSort, "SidePaneSort");
+widget!(SidePaneView, gtk::TreeView, "SidePaneView");
+widget!(
+    SidePanePingColumn,
+    gtk::TreeViewColumn,
+    "SidePanePingColumn"
+);
+widget!(
+    SidePanePingRenderer,
+    gtk::CellRendererText,
+    "SidePanePingRenderer"
+);
+widget!(PingColumn, gtk::TreeViewColumn, "PingColumn");
+widget!(PingRenderer, gtk::CellRendererText, "PingRenderer");
+widget!(PlayersIconColumn, gtk::TreeViewColumn, "PlayersIconColumn");
+widget!(
+    PlayersIconRenderer,
+    gtk::CellRendererPixbuf,
+    "PlayersIconRenderer"
+);
+widget!(PlayersColumn, gtk::TreeViewColumn, "PlayersColumn");
+widget!(
+    PlayerCountRenderer,
+    gtk::CellRendererText,
+    "PlayerCountRenderer"
+);
 
 widget!(FilterToggle, gtk::ToggleButton, "FilterToggle");
 widget!(FiltersPopover, gtk::Popover, "FiltersPopover");
 widget!(GameListView, gtk::TreeView, "GameListView");
+widget!(SelectAllGames, gtk::Button, "SelectAllGames");
+widget!(SelectInstalledGames, gtk::Button, "SelectInstalledGames");
 widget!(MainWindow, gtk::ApplicationWindow, "MainWindow");
 widget!(RefreshButton, gtk::Button, "RefreshButton");
+widget!(RefreshProgress, gtk::ProgressBar, "RefreshProgress");
+widget!(NetworkWarning, gtk::InfoBar, "NetworkWarning");
+widget!(NetworkWarningLabel, gtk::Label, "NetworkWarningLabel");
+widget!(DataWarning, gtk::InfoBar, "DataWarning");
+widget!(DataWarningLabel, gtk::Label, "DataWarningLabel");
+widget!(FollowPrompt, gtk::InfoBar, "FollowPrompt");
+widget!(FollowPromptLabel, gtk::Label, "FollowPromptLabel");
+widget!(ShareButton, gtk::Button, "ShareButton");
+widget!(ConnectButton, gtk::Button, "ConnectButton");
+widget!(ImportButton, gtk::Button, "ImportButton");
+widget!(ShortcutsWindow, gtk::ShortcutsWindow, "ShortcutsWindow");
+widget!(ShortcutsGroup, gtk::ShortcutsGroup, "ShortcutsGroup");
 
 widget!(ModFilter, gtk::Entry, "ModFilter");
 widget!(GameTypeFilter, gtk::Entry, "GameTypeFilter");
 widget!(MapFilter, gtk::Entry, "MapFilter");
+widget!(FuzzyMapFilter, gtk::CheckButton, "FuzzyMapFilter");
+widget!(RegexFilterToggle, gtk::CheckButton, "RegexFilterToggle");
 widget!(PingFilter, gtk::SpinButton, "PingFilter");
+widget!(AntiCheatLabel, gtk::Label, "AntiCheatLabel");
 widget!(AntiCheatFilter, gtk::ComboBoxText, "AntiCheatFilter");
 widget!(NotFullFilter, gtk::CheckButton, "NotFullFilter");
 widget!(NotEmptyFilter, gtk::CheckButton, "NotEmptyFilter");
 widget!(NoPasswordFilter, gtk::CheckButton, "NoPasswordFilter");
+widget!(
+    UnknownPasswordFilter,
+    gtk::CheckButton,
+    "UnknownPasswordFilter"
+);
+widget!(JoinableFilter, gtk::CheckButton, "JoinableFilter");
+widget!(DimEmptyToggle, gtk::CheckButton, "DimEmptyToggle");
+widget!(CountryFilter, gtk::Entry, "CountryFilter");
+widget!(StockClientFilter, gtk::CheckButton, "StockClientFilter");
+widget!(DedicatedFilter, gtk::CheckButton, "DedicatedFilter");
+widget!(CompatibleFilter, gtk::CheckButton, "CompatibleFilter");
+widget!(ResetFilters, gtk::Button, "ResetFilters");
+
+widget!(NotEmptyChip, gtk::ToggleButton, "NotEmptyChip");
+widget!(NotFullChip, gtk::ToggleButton, "NotFullChip");
+widget!(NoPasswordChip, gtk::ToggleButton, "NoPasswordChip");
+widget!(InstalledGamesChip, gtk::ToggleButton, "InstalledGamesChip");
+
+widget!(PlayersPopover, gtk::Popover, "PlayersPopover");
+widget!(PlayersSpinner, gtk::Spinner, "PlayersSpinner");
+widget!(PlayersStatus, gtk::Label, "PlayersStatus");
+widget!(PlayersList, gtk::ListBox, "PlayersList");
+
+widget!(RecentButton, gtk::MenuButton, "RecentButton");
+widget!(RecentList, gtk::ListBox, "RecentList");
 
 widget!(PasswordRequest, gtk::Popover, "PasswordRequest");
 widget!(PasswordEntry, gtk::Entry, "PasswordEntry");
 widget!(ConnectWithPassword, gtk::Button, "ConnectWithPassword");
 
+widget!(InfoButton, gtk::Button, "InfoButton");
+widget!(ServerInfoPopover, gtk::Popover, "ServerInfoPopover");
+widget!(ServerInfoName, gtk::Label, "serverinfo-name-data");
+widget!(ServerInfoHost, gtk::Label, "serverinfo-host-data");
+widget!(ServerInfoGame, gtk::Label, "serverinfo-game-data");
+widget!(ServerInfoGameId, gtk::Label, "serverinfo-gameid-data");
+widget!(ServerInfoMap, gtk::Label, "serverinfo-map-data");
+widget!(ServerInfoPlayers, gtk::Label, "serverinfo-players-data");
+widget!(ServerInfoPing, gtk::Label, "serverinfo-ping-data");
+widget!(ServerInfoOSTitle, gtk::Label, "serverinfo-os-title");
+widget!(ServerInfoOS, gtk::Label, "serverinfo-os-data");
+widget!(
+    ServerInfoDescriptionTitle,
+    gtk::Label,
+    "serverinfo-description-title"
+);
+widget!(
+    ServerInfoDescription,
+    gtk::Label,
+    "serverinfo-description-data"
+);
+widget!(
+    ServerInfoPingHistoryTitle,
+    gtk::Label,
+    "serverinfo-pinghistory-title"
+);
+widget!(
+    ServerInfoPingHistory,
+    gtk::DrawingArea,
+    "serverinfo-pinghistory-data"
+);
+widget!(ActivityBox, gtk::Box, "ActivityBox");
+widget!(ActivitySpan, gtk::ComboBoxText, "ActivitySpan");
+widget!(ActivityChartArea, gtk::DrawingArea, "ActivityChartArea");
+
+impl ServerListView {
+    /// Maps the selected row through the sort and filter models back into `ServerStore`.
+    pub fn get_selected_store_iter(&self) -> Option<TreeIter> {
+        let (model, iter) = self.0.get_selection().get_selected()?;
+
+        let sort_model = model.downcast::<gtk::TreeModelSort>().ok()?;
+        let filter_iter = sort_model.convert_iter_to_child_iter(&iter);
+
+        let filter_model = sort_model
+            .get_model()?
+            .downcast::<gtk::TreeModelFilter>()
+            .ok()?;
+
+        Some(filter_model.convert_iter_to_child_iter(&filter_iter))
+    }
+
+    /// Puts the cursor on a `ServerStore` row, selecting it. Returns whether the row is shown and was selected.
+    pub fn select_store_iter(&self, iter: &TreeIter) -> bool {
+        let path = || {
+            let sort_model = self.0.get_model()?.downcast::<gtk::TreeModelSort>().ok()?;
+            let filter_model = sort_model
+                .get_model()?
+                .downcast::<gtk::TreeModelFilter>()
+                .ok()?;
+
+            let filter_iter = filter_model.convert_child_iter_to_iter(iter)?;
+            sort_model.get_path(&sort_model.convert_child_iter_to_iter(&filter_iter)?)
+        };
+
+        match path() {
+            Some(path) => {
+                self.0
+                    .set_cursor(&path, None::<&gtk::TreeViewColumn>, false);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Server list row for narrow windows: the game icon, then the name over map, players and ping.
+#[derive(Clone)]
+pub struct CompactServerRow {
+    pub row: gtk::ListBoxRow,
+    pub game_id: Game,
+    pub addr: String,
+    /// Key of the server among favorites, see `server_key`
+    pub key: String,
+    pub need_pass: Option<bool>,
+    image: gtk::Image,
+    name: gtk::Label,
+    lock: gtk::Image,
+    /// Icon shown by `lock`, `None` while hidden
+    lock_icon: Option<&'static str>,
+    details: gtk::Label,
+}
+
+/// Sets the text unless it is already shown, sparing the label a relayout.
+fn set_label_markup(label: &gtk::Label, markup: &str) {
+    if label.get_label().as_ref().map(|s| s.as_str()) != Some(markup) {
+        label.set_markup(markup);
+    }
+}
+
+impl CompactServerRow {
+    pub fn new(data: &DisplayServer, icon: Option<&Pixbuf>) -> Self {
+        let grid = gtk::Grid::new();
+        grid.set_column_spacing(10);
+        grid.set_margin_start(6);
+        grid.set_margin_end(6);
+        grid.set_margin_top(4);
+        grid.set_margin_bottom(4);
+
+        let image = gtk::Image::new_from_pixbuf(icon);
+        image.set_valign(gtk::Align::Center);
+        grid.attach(&image, 0, 0, 1, 2);
+
+        let name = gtk::Label::new(None);
+        name.set_xalign(0.0);
+        name.set_hexpand(true);
+        name.set_line_wrap(true);
+        grid.attach(&name, 1, 0, 1, 1);
+
+        let lock = gtk::Image::new();
+        lock.set_no_show_all(true);
+        grid.attach(&lock, 2, 0, 1, 1);
+
+        let details = gtk::Label::new(None);
+        details.set_xalign(0.0);
+        details.get_style_context().add_class("dim-label");
+        grid.attach(&details, 1, 1, 2, 1);
+
+        let row = gtk::ListBoxRow::new();
+        row.add(&grid);
+        row.show_all();
+
+        let mut out = Self {
+            row,
+            game_id: data.game_id,
+            addr: String::new(),
+            key: String::new(),
+            need_pass: None,
+            image,
+            name,
+            lock,
+            lock_icon: None,
+            details,
+        };
+        out.update(data, icon);
+        out
+    }
+
+    /// Shows another server, or new values of the same one, in the row. Only what changed is touched, so that
+    /// periodic updates of the whole list stay cheap.
+    pub fn update(&mut self, data: &DisplayServer, icon: Option<&Pixbuf>) {
+        let srv = &data.srv;
+
+        if self.game_id != data.game_id {
+            self.image.set_from_pixbuf(icon);
+        }
+
+        set_label_markup(&self.name, &glib::markup_escape_text(&data.name));
+        set_label_markup(
+            &self.details,
+            &format!(
+                "<small>{}  {}/{}  {}</small>",
+                glib::markup_escape_text(srv.map.as_ref().map(|s| s.as_str()).unwrap_or("")),
+                srv.num_clients
+                    .map(|v| v.to_string())
+                    .unwrap_or_else(|| "—".into()),
+                srv.max_clients.unwrap_or(0),
+                srv.ping
+                    .map(|dur| format!("{} ms", dur.as_millis()))
+                    .unwrap_or_default(),
+            ),
+        );
+
+        // Same as the tree view: unknown is shown dimmed, as it may well be protected
+        let lock_icon = match srv.need_pass {
+            Some(true) => Some("network-wireless-encrypted-symbolic"),
+            Some(false) => None,
+            None => Some("dialog-question-symbolic"),
+        };
+        if self.lock_icon != lock_icon {
+            if let Some(icon) = lock_icon {
+                self.lock
+                    .set_from_icon_name(Some(icon), gtk::IconSize::Menu);
+            }
+            self.lock.set_sensitive(srv.need_pass.is_some());
+            self.lock.set_visible(lock_icon.is_some());
+            self.lock_icon = lock_icon;
+        }
+
+        self.game_id = data.game_id;
+        self.addr = srv.addr.to_string();
+        self.key = server_key(srv);
+        self.need_pass = srv.need_pass;
+    }
+}
+
+/// Player count over time, drawn over a `gtk::DrawingArea`.
+#[derive(Clone)]
+pub struct ActivityChart {
+    pub area: gtk::DrawingArea,
+    /// Samples with the time span they are shown over, in seconds
+    data: Arc<Mutex<(Vec<Sample>, u64)>>,
+}
+
+impl ActivityChart {
+    pub fn new(area: gtk::DrawingArea) -> Self {
+        let data = Arc::new(Mutex::new((Vec::new(), activity::DAY)));
+
+        area.connect_draw({
+            let data = data.clone();
+            move |w, cr| {
+                let (samples, span) = &*data.lock().unwrap();
+
+                let width = f64::from(w.get_allocated_width());
+                let height = f64::from(w.get_allocated_height());
+                let color = w.get_style_context().get_color(gtk::StateFlags::NORMAL);
+
+                // Baseline
+                cr.set_source_rgba(color.red, color.green, color.blue, 0.3);
+                cr.set_line_width(1.0);
+                cr.move_to(0.0, height - 0.5);
+                cr.line_to(width, height - 0.5);
+                cr.stroke();
+
+                if samples.is_empty() {
+                    return Inhibit(false);
+                }
+
+                let until = activity::now();
+                let since = until.saturating_sub(*span);
+                let max_players = samples
+                    .iter()
+                    .map(|Sample(_, v)| *v)
+                    .max()
+                    .unwrap_or(0)
+                    .max(1);
+
+                let x = |time: u64| (time.saturating_sub(since) as f64 / *span as f64) * width;
+                let y =
+                    |players: u64| height - (players as f64 / max_players as f64) * (height - 1.0);
+
+                cr.set_source_rgba(color.red, color.green, color.blue, 0.8);
+                cr.set_line_width(1.5);
+                for (i, Sample(time, players)) in samples.iter().enumerate() {
+                    if i == 0 {
+                        cr.move_to(x(*time), y(*players));
+                    } else {
+                        cr.line_to(x(*time), y(*players));
+                    }
+                }
+                cr.stroke();
+
+                Inhibit(false)
+            }
+        });
+
+        Self { area, data }
+    }
+
+    /// Shows `samples` over the last `span` seconds.
+    pub fn set_samples(&self, samples: &[Sample], span: u64) {
+        *self.data.lock().unwrap() = (samples.to_vec(), span);
+        self.area.queue_draw();
+    }
+}
+
+/// Recent pings drawn as bars over a `gtk::DrawingArea`, newest on the right.
+#[derive(Clone)]
+pub struct PingSparkline {
+    pub area: gtk::DrawingArea,
+    /// Ping of each sample in milliseconds, `None` if lost
+    samples: Arc<Mutex<Vec<Option<u64>>>>,
+}
+
+impl PingSparkline {
+    pub fn new(area: gtk::DrawingArea) -> Self {
+        let samples = Arc::new(Mutex::new(Vec::<Option<u64>>::new()));
+
+        area.connect_draw({
+            let samples = samples.clone();
+            move |w, cr| {
+                let samples = samples.lock().unwrap();
+
+                let width = f64::from(w.get_allocated_width());
+                let height = f64::from(w.get_allocated_height());
+                let color = w.get_style_context().get_color(gtk::StateFlags::NORMAL);
+
+                let max_ping = samples.iter().flatten().copied().max().unwrap_or(0).max(1);
+                let bar_width = width / activity::MAX_PING_SAMPLES as f64;
+                let offset = activity::MAX_PING_SAMPLES.saturating_sub(samples.len());
+
+                cr.set_source_rgba(color.red, color.green, color.blue, 0.8);
+                for (i, ping) in samples.iter().enumerate() {
+                    if let Some(ping) = ping {
+                        let bar_height = (*ping as f64 / max_ping as f64) * (height - 1.0) + 1.0;
+                        cr.rectangle(
+                            (offset + i) as f64 * bar_width,
+                            height - bar_height,
+                            (bar_width - 1.0).max(1.0),
+                            bar_height,
+                        );
+                    }
+                }
+                cr.fill();
+
+                // Lost pings are marked at the top, leaving a gap below
+                cr.set_source_rgba(0.8, 0.0, 0.0, 0.8);
+                for (i, _) in samples
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, ping)| ping.is_none())
+                {
+                    cr.rectangle(
+                        (offset + i) as f64 * bar_width,
+                        0.0,
+                        (bar_width - 1.0).max(1.0),
+                        2.0,
+                    );
+                }
+                cr.fill();
+
+                Inhibit(false)
+            }
+        });
+
+        Self { area, samples }
+    }
+
+    pub fn set_samples(&self, samples: Vec<Option<u64>>) {
+        *self.samples.lock().unwrap() = samples;
+        self.area.queue_draw();
+    }
+}
+
 pub struct UIBuilder {
     pub inner: gtk::Builder,
 }
 
 impl UIBuilder {
-    pub fn get_object<T, O>(&self) -> T
+    pub fn get_object<T, O>(&self) -> Result<T, MissingWidget>
     where
         T: Widget<O> + std::convert::From<O>,
         O: glib::IsA<glib::Object>,
     {
-        T::from(self.inner.get_object::<O>(T::id()).unwrap())
+        self.inner
+            .get_object::<O>(T::id())
+            .map(T::from)
+            .ok_or_else(|| MissingWidget {
+                id: T::id(),
+                expected: O::static_type().name(),
+            })
+    }
+
+    /// Looks up every registered widget, so that a broken UI definition is reported at once instead of
+    /// panicking on first use.
+    pub fn validate(&self) -> Result<(), Vec<MissingWidget>> {
+        let missing = inventory::iter::<RegisteredWidget>
+            .into_iter()
+            .filter_map(|widget| {
+                let expected = (widget.static_type)();
+                match self.inner.get_object::<glib::Object>(widget.id) {
+                    Some(ref obj) if obj.get_type().is_a(&expected) => None,
+                    _ => Some(MissingWidget {
+                        id: widget.id,
+                        expected: expected.name(),
+                    }),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(missing)
+        }
     }
 }
 
@@ -86,6 +587,7 @@ pub enum GameStoreColumn {
     Name,
     Icon,
     StatusIcon,
+    StatusTooltip,
 }
 
 #[derive(Clone, Debug, From)]
@@ -101,14 +603,16 @@ impl Widget<gtk::ListStore> for GameListStore {
     }
 }
 
+register_widget!(gtk::ListStore, "GameListStore");
+
 impl GameListStore {
-    pub fn append_game(&self, game_id: Game, icon: Pixbuf) -> TreeIter {
+    pub fn append_game(&self, game_id: Game, name: &str, icon: Pixbuf) -> TreeIter {
         let mut columns = Vec::<u32>::new();
         let mut values = Vec::<Box<dyn ToValue>>::new();
         for (i, col) in GameStoreColumn::enum_iter().enumerate() {
             let insertable: Option<gtk::Value> = match col {
                 GameStoreColumn::Id => Some(From::from(game_id.id().clone())),
-                GameStoreColumn::Name => Some(From::from(&game_id.to_string())),
+                GameStoreColumn::Name => Some(From::from(name)),
                 GameStoreColumn::Icon => Some(From::from(&icon.clone())),
                 _ => None,
             };
@@ -143,8 +647,45 @@ impl GameListStore {
                 .clone(),
         )
     }
+
+    pub fn find_game(&self, game: Game) -> Option<TreeIter> {
+        let iter = self.0.get_iter_first()?;
+        loop {
+            if self.get_game(&iter).0 == game {
+                return Some(iter);
+            }
+            if !self.0.iter_next(&iter) {
+                return None;
+            }
+        }
+    }
+
+    /// Sets the refresh status icon of the game, `None` to clear it.
+    pub fn set_status(&self, game: Game, icon: Option<&str>, tooltip: Option<&str>) {
+        if let Some(iter) = self.find_game(game) {
+            // Tooltip column is interpreted as markup
+            let tooltip = tooltip.map(glib::markup_escape_text);
+
+            self.0
+                .set_value(&iter, GameStoreColumn::StatusIcon as u32, &icon.to_value());
+            self.0.set_value(
+                &iter,
+                GameStoreColumn::StatusTooltip as u32,
+                &tooltip.as_ref().map(|s| s.as_str()).to_value(),
+            );
+        }
+    }
 }
 
+/// Stored in `ServerStoreColumn::NeedPass` for servers that do not report it, `0` and `1` otherwise
+pub const UNKNOWN_FLAG: i32 = -1;
+
+/// Stored in `ServerStoreColumn::PlayerCount` for servers that do not report it
+pub const UNKNOWN_COUNT: i32 = -1;
+
+/// Stored in `ServerStoreColumn::Ping` for servers that could not be pinged
+pub const UNKNOWN_PING: i32 = 9999;
+
 #[derive(Clone, Copy, Debug, EnumIterator)]
 pub enum ServerStoreColumn {
     Host = 0,
@@ -159,12 +700,32 @@ pub enum ServerStoreColumn {
     GameMod,
     GameType,
     Map,
-    GameIcon,
-    LockIcon,
-    SecureIcon,
-    CountryIcon,
+    /// Joinable without extra downloads
+    StockClient,
+    /// Hosted by a playing client
+    Listen,
+    /// Kept at the top of the list
+    Pinned,
+    /// `RowChange` since the previous refresh
+    Change,
     /// Ugly hack to retain original data
     JSON,
+    /// Game filter the server was kept despite, see `games::FILTERED_OUT_RULE`
+    FilteredOutBy,
+    /// Game version, see `games::server_version`
+    Version,
+    /// Relevance for the smart sort, see `score::score`
+    Score,
+}
+
+/// How a server row differs from the previous refresh
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RowChange {
+    Unchanged = 0,
+    New,
+    Gone,
+    /// Player count or map changed in live mode
+    Updated,
 }
 
 #[derive(Clone, Debug, From)]
@@ -180,6 +741,8 @@ impl Widget<gtk::ListStore> for ServerStore {
     }
 }
 
+register_widget!(gtk::ListStore, "ServerStore");
+
 #[derive(Clone, Debug, From)]
 pub enum ServerListIter {
     Iter(gtk::TreeIter),
@@ -187,51 +750,59 @@ pub enum ServerListIter {
 }
 
 impl ServerStore {
-    pub fn append_server(
-        &self,
-        game_id: Game,
-        icon: Pixbuf,
-        name_morpher: Arc<dyn NameMorpher>,
-        srv: rgs::models::Server,
-    ) -> TreeIter {
+    pub fn append_server(&self, data: DisplayServer) -> TreeIter {
+        self.upsert_server(None, data)
+    }
+
+    /// Overwrites the row at `iter` with the server, or appends a row for it if `None`. Pin and change marks
+    /// of the row are kept.
+    pub fn upsert_server(&self, iter: Option<&TreeIter>, data: DisplayServer) -> TreeIter {
+        let srv = &data.srv;
+
         let mut columns = Vec::<u32>::new();
         let mut values = Vec::<Box<dyn ToValue>>::new();
         for (i, col) in ServerStoreColumn::enum_iter().enumerate() {
             let insertable: Option<gtk::Value> = match col {
                 ServerStoreColumn::Host => Some(From::from(&srv.addr.to_string())),
-                ServerStoreColumn::NeedPass => Some(From::from(&srv.need_pass.unwrap_or(false))),
-                ServerStoreColumn::LockIcon => {
-                    if srv.need_pass.unwrap_or(false) {
-                        Some(From::from("network-wireless-encrypted-symbolic"))
-                    } else {
-                        None
-                    }
-                }
-                ServerStoreColumn::PlayerCount => Some(From::from(&srv.num_clients.unwrap_or(0))),
+                ServerStoreColumn::NeedPass => Some(From::from(
+                    &srv.need_pass.map(i32::from).unwrap_or(UNKNOWN_FLAG),
+                )),
+                ServerStoreColumn::PlayerCount => Some(From::from(
+                    &srv.num_clients.map(|v| v as i32).unwrap_or(UNKNOWN_COUNT),
+                )),
                 ServerStoreColumn::PlayerLimit => Some(From::from(&srv.max_clients.unwrap_or(0))),
                 ServerStoreColumn::Ping => Some(From::from(
                     &srv.ping
-                        .map(|dur| dur.as_secs() * 1000 + dur.subsec_nanos() as u64 / 1000000)
-                        .unwrap_or(9999),
+                        .map(|dur| {
+                            (dur.as_secs() * 1000 + dur.subsec_nanos() as u64 / 1000000) as i32
+                        })
+                        .unwrap_or(UNKNOWN_PING),
                 )),
                 ServerStoreColumn::Secure => Some(From::from(&srv.secure.unwrap_or(false))),
-                ServerStoreColumn::SecureIcon => {
-                    if srv.secure.unwrap_or(false) {
-                        Some(From::from("security-high-symbolic"))
-                    } else {
-                        None
-                    }
-                }
                 ServerStoreColumn::Country => {
-                    Some(From::from(&format!("{:?}", srv.country.clone())))
+                    Some(From::from(&filters::country_code(srv).unwrap_or_default()))
+                }
+                ServerStoreColumn::Name => Some(From::from(&data.name)),
+                ServerStoreColumn::GameId => Some(From::from(&data.game_id.id().clone())),
+                // Set even if absent to clear the value of an updated row
+                ServerStoreColumn::GameMod => {
+                    Some(srv.mod_name.as_ref().map(String::as_str).to_value())
+                }
+                ServerStoreColumn::GameType => {
+                    Some(srv.game_type.as_ref().map(String::as_str).to_value())
+                }
+                ServerStoreColumn::Map => Some(srv.map.as_ref().map(String::as_str).to_value()),
+                ServerStoreColumn::StockClient => {
+                    Some(From::from(&data.stock_client.unwrap_or(false)))
+                }
+                ServerStoreColumn::Listen => Some(From::from(&(data.dedicated == Some(false)))),
+                ServerStoreColumn::JSON => Some(From::from(&data.json)),
+                ServerStoreColumn::FilteredOutBy => {
+                    Some(data.filtered_out_by.as_ref().map(String::as_str).to_value())
+                }
+                ServerStoreColumn::Version => {
+                    Some(data.version.as_ref().map(String::as_str).to_value())
                 }
-                ServerStoreColumn::Name => Some(From::from(
-                    &name_morpher.morph(srv.name.clone().unwrap_or_else(Default::default)),
-                )),
-                ServerStoreColumn::GameId => Some(From::from(&game_id.id().clone())),
-                ServerStoreColumn::GameMod => srv.mod_name.as_ref().map(|v| From::from(v)),
-                ServerStoreColumn::GameIcon => Some(From::from(&icon.clone())),
-                ServerStoreColumn::JSON => Some(From::from(&serde_json::to_string(&srv).unwrap())),
                 _ => None,
             };
 
@@ -241,11 +812,93 @@ impl ServerStore {
             }
         }
 
-        self.0.insert_with_values(
-            None,
-            &columns,
-            &values.iter().map(|v| &**v).collect::<Vec<&dyn ToValue>>(),
-        )
+        let values = values.iter().map(|v| &**v).collect::<Vec<&dyn ToValue>>();
+        match iter {
+            Some(iter) => {
+                self.0.set(iter, &columns, &values);
+                iter.clone()
+            }
+            None => self.0.insert_with_values(None, &columns, &values),
+        }
+    }
+
+    pub fn set_change(&self, iter: &TreeIter, change: RowChange) {
+        self.0.set_value(
+            iter,
+            ServerStoreColumn::Change as u32,
+            &(change as i32).to_value(),
+        );
+    }
+
+    pub fn set_score(&self, iter: &TreeIter, score: i32) {
+        self.0
+            .set_value(iter, ServerStoreColumn::Score as u32, &score.to_value());
+    }
+
+    pub fn set_name(&self, iter: &TreeIter, name: &str) {
+        self.0
+            .set_value(iter, ServerStoreColumn::Name as u32, &name.to_value());
+    }
+
+    /// Applies an info query reply to the row. Returns whether the row changed.
+    pub fn update_info(&self, iter: &TreeIter, info: &ServerInfo) -> bool {
+        let mut data = self.get_display_server(iter);
+        if !info.apply(&mut data.srv) {
+            return false;
+        }
+
+        data.json = serde_json::to_string(&data.srv).unwrap();
+        self.upsert_server(Some(iter), data);
+        true
+    }
+
+    pub fn set_pinned(&self, iter: &TreeIter, pinned: bool) {
+        self.0
+            .set_value(iter, ServerStoreColumn::Pinned as u32, &pinned.to_value());
+    }
+
+    pub fn get_change(&self, iter: &TreeIter) -> RowChange {
+        match self
+            .0
+            .get_value(iter, ServerStoreColumn::Change as i32)
+            .get::<i32>()
+        {
+            Some(1) => RowChange::New,
+            Some(2) => RowChange::Gone,
+            Some(3) => RowChange::Updated,
+            _ => RowChange::Unchanged,
+        }
+    }
+
+    pub fn get_display_server(&self, iter: &TreeIter) -> DisplayServer {
+        let (game_id, srv) = self.get_server(iter);
+
+        DisplayServer {
+            game_id,
+            name: self
+                .0
+                .get_value(iter, ServerStoreColumn::Name as i32)
+                .get::<String>()
+                .unwrap_or_default(),
+            json: self.get_json(iter),
+            stock_client: stock_client_compatible(game_id, &srv),
+            dedicated: server_dedicated(game_id, &srv),
+            server_os: server_os(game_id, &srv),
+            version: server_version(game_id, &srv),
+            filtered_out_by: filtered_out_by(&srv),
+            srv,
+        }
+    }
+
+    pub fn get_servers(&self) -> Vec<(Game, rgs::models::Server)> {
+        let mut out = Vec::new();
+
+        self.0.foreach(|_, _, iter| {
+            out.push(self.get_server(iter));
+            false
+        });
+
+        out
     }
 
     pub fn get_server(&self, iter: &TreeIter) -> (Game, rgs::models::Server) {
@@ -258,14 +911,15 @@ impl ServerStore {
                     .unwrap(),
             )
             .unwrap(),
-            serde_json::from_str(
-                &self
-                    .0
-                    .get_value(iter, ServerStoreColumn::JSON as i32)
-                    .get::<String>()
-                    .unwrap(),
-            )
-            .unwrap(),
+            serde_json::from_str(&self.get_json(iter)).unwrap(),
         )
     }
+
+    /// Original server data as received from the querier
+    pub fn get_json(&self, iter: &TreeIter) -> String {
+        self.0
+            .get_value(iter, ServerStoreColumn::JSON as i32)
+            .get::<String>()
+            .unwrap()
+    }
 }
@@ -0,0 +1,131 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Feeds data files of past schema versions through the loader.
+
+use obozrenie::activity::{
+    self, ActivityLog, DataFile, PingHistory, RecentServers, Sample, MAX_PING_SAMPLES,
+};
+use obozrenie::games::Game;
+use obozrenie::settings::Favorite;
+
+fn xonotic_server() -> Favorite {
+    Favorite {
+        game: Game::Xonotic,
+        addr: "203.0.113.5:26000".into(),
+    }
+}
+
+#[test]
+fn activity_log_from_unversioned_file() {
+    let log: ActivityLog = activity::parse(include_str!("fixtures/activity-v0.json")).unwrap();
+
+    assert_eq!(
+        log.samples(&xonotic_server(), 0),
+        vec![Sample(1560000000, 3), Sample(1560000300, 5)]
+    );
+    assert_eq!(
+        log.samples(
+            &Favorite {
+                game: Game::QuakeIII,
+                addr: "198.51.100.7:27960".into(),
+            },
+            0
+        ),
+        vec![Sample(1560000000, 0)]
+    );
+}
+
+#[test]
+fn recent_servers_from_unversioned_file() {
+    let recent: RecentServers = activity::parse(include_str!("fixtures/recent-v0.json")).unwrap();
+
+    assert_eq!(
+        recent.iter().map(|v| v.server.clone()).collect::<Vec<_>>(),
+        vec![
+            xonotic_server(),
+            Favorite {
+                game: Game::OpenTTD,
+                addr: "ttd.example.org:3979".into(),
+            },
+        ]
+    );
+    // Entries from before the password flag was kept
+    assert!(recent.iter().all(|v| v.need_pass.is_none()));
+}
+
+#[test]
+fn activity_log_from_current_file() {
+    let log: ActivityLog = activity::parse(include_str!("fixtures/activity-v1.json")).unwrap();
+
+    assert_eq!(
+        log.samples(&xonotic_server(), 1560000100),
+        vec![Sample(1560000300, 5)]
+    );
+}
+
+#[test]
+fn round_trip_writes_current_version() {
+    let mut log = ActivityLog::default();
+    log.record(&xonotic_server(), Sample(1560000000, 3));
+
+    let text = activity::serialize(&log).unwrap();
+    assert!(text.starts_with(&format!("{{\"version\":{}", ActivityLog::VERSION)));
+
+    let log: ActivityLog = activity::parse(&text).unwrap();
+    assert_eq!(
+        log.samples(&xonotic_server(), 0),
+        vec![Sample(1560000000, 3)]
+    );
+}
+
+#[test]
+fn newer_schema_is_rejected() {
+    assert!(activity::parse::<ActivityLog>(include_str!("fixtures/activity-future.json")).is_err());
+}
+
+#[test]
+fn damaged_file_is_rejected() {
+    assert!(
+        activity::parse::<ActivityLog>(include_str!("fixtures/activity-damaged.json")).is_err()
+    );
+}
+
+#[test]
+fn ping_history_is_capped() {
+    let mut pings = PingHistory::default();
+    for ping in 0..MAX_PING_SAMPLES as u64 + 5 {
+        pings.record(&xonotic_server(), ping);
+    }
+
+    let samples = pings.samples(&xonotic_server());
+    assert_eq!(samples.len(), MAX_PING_SAMPLES);
+    assert_eq!(samples[0], 5);
+}
+
+#[test]
+fn ping_history_is_pruned_by_age() {
+    let mut pings = PingHistory::default();
+    pings.record(&xonotic_server(), 40);
+
+    pings.prune(activity::now().saturating_sub(activity::PING_HISTORY_MAX_AGE));
+    assert_eq!(pings.len(), 1);
+
+    // Nothing pinged since
+    pings.prune(activity::now() + 1);
+    assert!(pings.is_empty());
+    assert!(pings.samples(&xonotic_server()).is_empty());
+}
@@ -0,0 +1,66 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Configuration descriptions dumped by `--dump-config`
+
+use obozrenie::games::{
+    self, ChainedMorpher, DecorationMorpher, DummyMorpher, Game, NameMorpher, RegexMorpher,
+};
+use obozrenie::settings::{LaunchMethod, NameReplacement, Settings};
+use serde_json::json;
+use std::sync::Arc;
+
+#[test]
+fn nested_morphers_are_flattened() {
+    let user_morpher = ChainedMorpher(vec![
+        Arc::new(DecorationMorpher::default()),
+        Arc::new(RegexMorpher::new(&[])),
+    ]);
+    let morpher = ChainedMorpher(vec![Arc::new(DummyMorpher), Arc::new(user_morpher)]);
+
+    assert_eq!(morpher.describe(), json!(["decorations"]));
+}
+
+#[test]
+fn replacements_are_listed() {
+    let morpher = RegexMorpher::new(&[NameReplacement {
+        pattern: "^EU ".into(),
+        replacement: "".into(),
+    }]);
+
+    assert_eq!(
+        morpher.describe(),
+        json!({ "replacements": [{ "pattern": "^EU ", "replacement": "" }] })
+    );
+}
+
+#[test]
+fn launchers_report_their_backend() {
+    let mut settings = Settings::default();
+    settings.launch_method = LaunchMethod::Steam;
+
+    assert_eq!(
+        games::launcher(Game::QuakeIII, &settings).describe(),
+        json!({ "kind": "steam", "app_id": 2200 })
+    );
+
+    settings.launch_method = LaunchMethod::Flatpak;
+
+    assert_eq!(
+        games::launcher(Game::Xonotic, &settings).describe(),
+        json!({ "kind": "flatpak", "ref": "org.xonotic.Xonotic/x86_64/stable" })
+    );
+}
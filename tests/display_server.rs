@@ -0,0 +1,93 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Per-server work moved off the GTK thread into the query pipeline, see `DisplayServer`.
+
+use obozrenie::games::{ChainedMorpher, DecorationMorpher, DisplayServer, Game, RegexMorpher};
+use obozrenie::settings::NameReplacement;
+use rgs::models::Server;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+fn morpher() -> ChainedMorpher {
+    ChainedMorpher(vec![
+        Arc::new(DecorationMorpher::default()),
+        Arc::new(RegexMorpher::new(&[NameReplacement {
+            pattern: "^EU ".into(),
+            replacement: "".into(),
+        }])),
+    ])
+}
+
+/// Server the size of a busy Xonotic one
+fn server(i: usize) -> Server {
+    Server {
+        name: Some(format!("-=[EU]=- Synthetic server #{} ::", i)),
+        mod_name: Some("data".into()),
+        game_type: Some("dm".into()),
+        map: Some(format!("map{}", i % 20)),
+        num_clients: Some((i % 16) as u64),
+        max_clients: Some(16),
+        need_pass: Some(i % 7 == 0),
+        ping: Some(Duration::from_millis((i % 300) as u64)),
+        rules: (0..30)
+            .map(|n| (format!("rule{}", n), Value::from(format!("value {}", n))))
+            .collect(),
+        ..Server::new(
+            format!("198.51.{}.{}:26000", i / 250, i % 250)
+                .parse()
+                .unwrap(),
+        )
+    }
+}
+
+#[test]
+fn prepares_display_data() {
+    let data = DisplayServer::new(Game::Xonotic, &morpher(), server(1));
+
+    assert_eq!(data.name, "Synthetic server #1");
+    assert_eq!(
+        serde_json::from_str::<Server>(&data.json).unwrap().name,
+        server(1).name
+    );
+}
+
+/// Time the GTK thread used to spend per 1000 incoming servers before the work moved.
+/// Run with `cargo test --release --test display_server -- --ignored --nocapture`.
+#[test]
+#[ignore]
+fn bench_display_server() {
+    const SERVERS: usize = 5000;
+
+    let morpher = morpher();
+    let servers = (0..SERVERS).map(server).collect::<Vec<_>>();
+
+    let start = Instant::now();
+    let out = servers
+        .into_iter()
+        .map(|srv| DisplayServer::new(Game::Xonotic, &morpher, srv))
+        .collect::<Vec<_>>();
+    let elapsed = start.elapsed();
+
+    assert_eq!(out.len(), SERVERS);
+    println!(
+        "{} servers prepared in {:?}, {:?} per 1000",
+        SERVERS,
+        elapsed,
+        elapsed * 1000 / SERVERS as u32
+    );
+}
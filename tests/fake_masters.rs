@@ -0,0 +1,274 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Runs the real queriers against fake masters and game servers on the loopback interface, which replay
+//! captured replies from `fixtures`.
+
+use futures01::Stream;
+use rgs::models::Server;
+use serde_json::Value;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, UdpSocket};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use tokio::timer::Timeout;
+
+use obozrenie::games::{Game, GameList};
+use obozrenie::settings::Settings;
+
+const GETSERVERS_RESPONSE: &[u8] = b"\xff\xff\xff\xffgetserversResponse";
+/// Separator, four address bytes and two port bytes
+const ENTRY_LEN: usize = 7;
+
+/// Answers every datagram with `reply` and hands the requests over.
+fn udp_responder(reply: Vec<u8>) -> (SocketAddr, mpsc::Receiver<Vec<u8>>) {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = socket.local_addr().unwrap();
+
+    let (sink, faucet) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = [0; 2048];
+        while let Ok((n, from)) = socket.recv_from(&mut buf) {
+            socket.send_to(&reply, from).unwrap();
+            let _ = sink.send(buf[..n].to_vec());
+        }
+    });
+
+    (addr, faucet)
+}
+
+/// Serves `body` to one HTTP request.
+fn http_responder(body: &'static str) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+
+        let mut head = Vec::new();
+        let mut buf = [0; 1024];
+        while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+            let n = stream.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            head.extend_from_slice(&buf[..n]);
+        }
+
+        stream
+            .write_all(
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+                .as_bytes(),
+            )
+            .unwrap();
+    });
+
+    addr
+}
+
+/// The captured master reply with its servers replaced by `servers`, in order.
+fn master_reply(captured: &[u8], servers: &[SocketAddr]) -> Vec<u8> {
+    assert!(captured.starts_with(GETSERVERS_RESPONSE));
+
+    let mut end = GETSERVERS_RESPONSE.len();
+    while !captured[end..].starts_with(b"\\EOT") {
+        end += ENTRY_LEN;
+    }
+    assert_eq!(
+        (end - GETSERVERS_RESPONSE.len()) / ENTRY_LEN,
+        servers.len(),
+        "fixture lists another number of servers"
+    );
+
+    let mut out = GETSERVERS_RESPONSE.to_vec();
+    for addr in servers {
+        match addr {
+            SocketAddr::V4(addr) => {
+                out.push(b'\\');
+                out.extend_from_slice(&addr.ip().octets());
+                out.extend_from_slice(&addr.port().to_be_bytes());
+            }
+            SocketAddr::V6(_) => unreachable!(),
+        }
+    }
+    out.extend_from_slice(&captured[end..]);
+
+    out
+}
+
+fn settings() -> Settings {
+    let mut settings = Settings::default();
+    // Latency then comes from the replies, as the fake servers cannot be pinged
+    settings.no_ping_games = vec![Game::QuakeIII, Game::Xonotic, Game::RigsOfRods]
+        .into_iter()
+        .collect();
+    settings.master_cache_ttl = 0;
+    settings
+}
+
+/// First `count` servers the game's querier yields, by address.
+fn query(game: Game, settings: &Settings, count: u64) -> Vec<Server> {
+    let game_list = GameList::new(settings);
+
+    let mut servers = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(Timeout::new(
+            game_list.0[&game].querier.query().take(count).collect(),
+            Duration::from_secs(10),
+        ))
+        .unwrap();
+    servers.sort_by_key(|srv| srv.addr);
+    servers
+}
+
+fn rule<'a>(srv: &'a Server, name: &str) -> Option<&'a str> {
+    srv.rules.get(name).and_then(Value::as_str)
+}
+
+#[test]
+fn xonotic_through_dpmaster() {
+    let (first, first_requests) =
+        udp_responder(include_bytes!("fixtures/q3s-status-xonotic-1").to_vec());
+    let (second, _) = udp_responder(include_bytes!("fixtures/q3s-status-xonotic-2").to_vec());
+    let (master, master_requests) = udp_responder(master_reply(
+        include_bytes!("fixtures/dpmaster-getservers-xonotic"),
+        &[first, second],
+    ));
+
+    let mut settings = settings();
+    settings
+        .master_servers
+        .insert(Game::Xonotic, vec![master.to_string()]);
+    let servers = query(Game::Xonotic, &settings, 2);
+
+    let request = master_requests.try_recv().unwrap();
+    assert!(request.starts_with(b"\xff\xff\xff\xffgetservers Xonotic 3"));
+    assert!(first_requests
+        .try_recv()
+        .unwrap()
+        .starts_with(b"\xff\xff\xff\xffgetstatus"));
+
+    assert_eq!(servers.len(), 2);
+
+    assert_eq!(servers[0].addr, first);
+    assert_eq!(servers[0].name.as_ref().unwrap(), "^1Harness ^7Xonotic");
+    assert_eq!(servers[0].mod_name.as_ref().unwrap(), "data");
+    assert_eq!(servers[0].map.as_ref().unwrap(), "solarium");
+    assert_eq!(servers[0].num_clients, Some(2));
+    assert_eq!(servers[0].max_clients, Some(16));
+    // DarkPlaces does not report passwords
+    assert_eq!(servers[0].need_pass, None);
+    assert_eq!(rule(&servers[0], "gameversion"), Some("802"));
+    assert_eq!(
+        rule(&servers[0], "qcstatus"),
+        Some(":dm:0.8.2:P0:S14:F2:MXonotic::score!!:2:1")
+    );
+
+    assert_eq!(servers[1].addr, second);
+    assert_eq!(servers[1].name.as_ref().unwrap(), "Harness Nexrun");
+    assert_eq!(servers[1].mod_name.as_ref().unwrap(), "nexrun");
+    assert_eq!(servers[1].map.as_ref().unwrap(), "nex_run1");
+    assert_eq!(servers[1].num_clients, Some(0));
+    assert_eq!(servers[1].max_clients, Some(24));
+    assert_eq!(servers[1].need_pass, None);
+    assert_eq!(rule(&servers[1], "gameversion"), Some("802"));
+}
+
+#[test]
+fn quake3_through_id_master() {
+    let (server, _) = udp_responder(include_bytes!("fixtures/q3s-status-quake3").to_vec());
+    let (master, master_requests) = udp_responder(master_reply(
+        include_bytes!("fixtures/q3master-getservers"),
+        &[server],
+    ));
+
+    let mut settings = settings();
+    settings
+        .master_servers
+        .insert(Game::QuakeIII, vec![master.to_string()]);
+    let servers = query(Game::QuakeIII, &settings, 1);
+
+    assert!(master_requests
+        .try_recv()
+        .unwrap()
+        .starts_with(b"\xff\xff\xff\xffgetservers 68"));
+
+    assert_eq!(servers.len(), 1);
+    let srv = &servers[0];
+    assert_eq!(srv.addr, server);
+    assert_eq!(srv.name.as_ref().unwrap(), "^3Harness ^7Q3 FFA");
+    assert_eq!(srv.map.as_ref().unwrap(), "q3dm17");
+    assert_eq!(srv.num_clients, Some(1));
+    assert_eq!(srv.max_clients, Some(12));
+    assert_eq!(srv.need_pass, Some(true));
+    assert_eq!(rule(srv, "sv_pure"), Some("1"));
+    assert_eq!(rule(srv, "sv_allowDownload"), Some("0"));
+    assert_eq!(rule(srv, "g_gametype"), Some("0"));
+}
+
+#[test]
+fn rigsofrods_through_http_master() {
+    let master = http_responder(include_str!("fixtures/rigsofrods-server-list.json"));
+
+    let mut settings = settings();
+    settings.rigsofrods_master = Some(format!("http://{}/server-list", master));
+    settings.http_proxy = None;
+    let servers = query(Game::RigsOfRods, &settings, 2);
+
+    assert_eq!(servers.len(), 2);
+
+    let official = &servers[0];
+    assert_eq!(official.addr, "127.0.0.1:12000".parse().unwrap());
+    assert_eq!(official.name.as_ref().unwrap(), "Official Race Server");
+    assert_eq!(official.map.as_ref().unwrap(), "simple2.terrn2");
+    assert_eq!(official.num_clients, Some(3));
+    assert_eq!(official.max_clients, Some(16));
+    assert_eq!(official.need_pass, Some(false));
+    assert_eq!(official.ping, None);
+    assert_eq!(official.rules.get("is_official"), Some(&Value::from(1)));
+    assert_eq!(official.rules.get("verified"), Some(&Value::from(2)));
+    // Listed by IP address, so no host name is kept
+    assert_eq!(official.rules.get(obozrenie::games::HOST_RULE), None);
+
+    let private = &servers[1];
+    assert_eq!(private.addr, "127.0.0.1:12001".parse().unwrap());
+    assert_eq!(private.name.as_ref().unwrap(), "Private Convoy");
+    assert_eq!(private.map.as_ref().unwrap(), "aspen.terrn2");
+    assert_eq!(private.num_clients, Some(0));
+    assert_eq!(private.max_clients, Some(8));
+    assert_eq!(private.need_pass, Some(true));
+    assert_eq!(private.rules.get("is_official"), Some(&Value::from(0)));
+}
+
+#[test]
+fn invalid_master_addresses_are_skipped() {
+    let mut settings = settings();
+    settings.master_servers.insert(
+        Game::Xonotic,
+        vec!["no port".into(), "127.0.0.1:27950".into()],
+    );
+    let game_list = GameList::new(&settings);
+
+    assert_eq!(
+        game_list.0[&Game::Xonotic].querier.describe()["masters"],
+        serde_json::json!(["127.0.0.1:27950"])
+    );
+}
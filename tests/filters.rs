@@ -0,0 +1,740 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Locks in the semantics of `Filters::matches`.
+
+use rgs::models::Server;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use obozrenie::filters::{anticheat_from_id, Filters, SpamFilter, MAX_SAME_NAME};
+use obozrenie::games::Game;
+use obozrenie::settings::Favorite;
+
+/// Server reporting every filtered field
+fn full_server() -> Server {
+    Server {
+        name: Some("Test server".into()),
+        mod_name: Some("baseq3".into()),
+        game_type: Some("ffa".into()),
+        map: Some("q3dm17".into()),
+        num_clients: Some(4),
+        max_clients: Some(16),
+        need_pass: Some(false),
+        secure: Some(true),
+        ping: Some(Duration::from_millis(50)),
+        ..Server::new("127.0.0.1:27960".parse().unwrap())
+    }
+}
+
+/// Server reporting nothing but its address
+fn bare_server() -> Server {
+    Server::new("127.0.0.1:27960".parse().unwrap())
+}
+
+#[test]
+fn empty_filter_passes_everything() {
+    let filters = Filters::default();
+
+    assert!(filters.matches(Game::QuakeIII, &full_server()));
+    assert!(filters.matches(Game::QuakeIII, &bare_server()));
+    assert!(filters.matches(Game::OpenTTD, &full_server()));
+}
+
+#[test]
+fn game_selection() {
+    let filters = Filters {
+        games: vec![Game::QuakeIII].into_iter().collect(),
+        ..Default::default()
+    };
+
+    assert!(filters.matches(Game::QuakeIII, &full_server()));
+    assert!(!filters.matches(Game::Xonotic, &full_server()));
+}
+
+#[test]
+fn text_fields_match_prefix() {
+    let filters = Filters {
+        game_mod: "base".into(),
+        game_type: "ff".into(),
+        map: "q3dm".into(),
+        ..Default::default()
+    };
+    assert!(filters.matches(Game::QuakeIII, &full_server()));
+
+    for filters in vec![
+        Filters {
+            game_mod: "cpma".into(),
+            ..Default::default()
+        },
+        Filters {
+            game_type: "ctf".into(),
+            ..Default::default()
+        },
+        Filters {
+            map: "dm17".into(),
+            ..Default::default()
+        },
+    ] {
+        assert!(!filters.matches(Game::QuakeIII, &full_server()));
+        // Unreported fields cannot match
+        assert!(!filters.matches(Game::QuakeIII, &bare_server()));
+    }
+}
+
+#[test]
+fn fuzzy_map() {
+    let filters = Filters {
+        map: "dm17".into(),
+        map_fuzzy: true,
+        ..Default::default()
+    };
+    assert!(filters.matches(Game::QuakeIII, &full_server()));
+
+    let filters = Filters {
+        map: "ctf".into(),
+        map_fuzzy: true,
+        ..Default::default()
+    };
+    assert!(!filters.matches(Game::QuakeIII, &full_server()));
+}
+
+#[test]
+fn regex_mode() {
+    let mut filters = Filters {
+        map: "dm1[0-9]$".into(),
+        game_mod: "^base".into(),
+        regex_mode: true,
+        ..Default::default()
+    };
+    filters.compile_patterns().unwrap();
+    assert!(filters.matches(Game::QuakeIII, &full_server()));
+
+    let mut filters = Filters {
+        map: "^ctf".into(),
+        regex_mode: true,
+        ..Default::default()
+    };
+    filters.compile_patterns().unwrap();
+    assert!(!filters.matches(Game::QuakeIII, &full_server()));
+}
+
+#[test]
+fn regex_mode_ignores_fuzzy_map() {
+    let mut filters = Filters {
+        map: "dm17".into(),
+        map_fuzzy: true,
+        regex_mode: true,
+        ..Default::default()
+    };
+    filters.compile_patterns().unwrap();
+    assert!(filters.matches(Game::QuakeIII, &full_server()));
+
+    let mut filters = Filters {
+        map: "q3d.17".into(),
+        map_fuzzy: true,
+        regex_mode: true,
+        ..Default::default()
+    };
+    filters.compile_patterns().unwrap();
+    assert!(filters.matches(Game::QuakeIII, &full_server()));
+}
+
+#[test]
+fn invalid_regex_is_rejected() {
+    let mut filters = Filters {
+        map: "q3dm[".into(),
+        regex_mode: true,
+        ..Default::default()
+    };
+
+    assert!(filters.compile_patterns().is_err());
+}
+
+#[test]
+fn uncompiled_regex_matches_anything() {
+    // Entries with invalid patterns leave the compiled pattern unset
+    let filters = Filters {
+        map: "q3dm[".into(),
+        regex_mode: true,
+        ..Default::default()
+    };
+
+    assert!(filters.matches(Game::QuakeIII, &full_server()));
+}
+
+#[test]
+fn zero_ping_means_no_ping_filter() {
+    let filters = Filters {
+        max_ping: Duration::from_millis(0),
+        ..Default::default()
+    };
+    let srv = Server {
+        ping: Some(Duration::from_millis(9999)),
+        ..full_server()
+    };
+
+    assert!(filters.matches(Game::QuakeIII, &srv));
+}
+
+#[test]
+fn ping_limit() {
+    let filters = Filters {
+        max_ping: Duration::from_millis(100),
+        ..Default::default()
+    };
+
+    assert!(filters.matches(Game::QuakeIII, &full_server()));
+    assert!(filters.matches(
+        Game::QuakeIII,
+        &Server {
+            ping: Some(Duration::from_millis(100)),
+            ..full_server()
+        }
+    ));
+    assert!(!filters.matches(
+        Game::QuakeIII,
+        &Server {
+            ping: Some(Duration::from_millis(101)),
+            ..full_server()
+        }
+    ));
+    assert!(filters.matches(Game::QuakeIII, &bare_server()));
+}
+
+#[test]
+fn anticheat_tri_state() {
+    let insecure = Server {
+        secure: Some(false),
+        ..full_server()
+    };
+
+    let ignore = Filters::default();
+    assert!(ignore.matches(Game::QuakeIII, &full_server()));
+    assert!(ignore.matches(Game::QuakeIII, &insecure));
+
+    let enabled = Filters {
+        anticheat: Some(true),
+        ..Default::default()
+    };
+    assert!(enabled.matches(Game::QuakeIII, &full_server()));
+    assert!(!enabled.matches(Game::QuakeIII, &insecure));
+    assert!(!enabled.matches(Game::QuakeIII, &bare_server()));
+
+    let disabled = Filters {
+        anticheat: Some(false),
+        ..Default::default()
+    };
+    assert!(!disabled.matches(Game::QuakeIII, &full_server()));
+    assert!(disabled.matches(Game::QuakeIII, &insecure));
+    assert!(!disabled.matches(Game::QuakeIII, &bare_server()));
+}
+
+#[test]
+fn anticheat_combo_ids() {
+    assert_eq!(anticheat_from_id(Some("enabled")), Some(true));
+    assert_eq!(anticheat_from_id(Some("disabled")), Some(false));
+    assert_eq!(anticheat_from_id(Some("ignore")), None);
+    // Unknown ids and no selection do not filter anything out
+    assert_eq!(anticheat_from_id(Some("strict")), None);
+    assert_eq!(anticheat_from_id(None), None);
+
+    let insecure = Server {
+        secure: Some(false),
+        ..full_server()
+    };
+    let filters = Filters {
+        anticheat: anticheat_from_id(Some("enabled")),
+        ..Default::default()
+    };
+    assert!(filters.matches(Game::QuakeIII, &full_server()));
+    assert!(!filters.matches(Game::QuakeIII, &insecure));
+}
+
+#[test]
+fn anticheat_only_applies_to_listed_games() {
+    let insecure = Server {
+        secure: Some(false),
+        ..full_server()
+    };
+
+    let filter = Filters {
+        anticheat: Some(true),
+        anticheat_games: Some(vec![Game::Xonotic].into_iter().collect()),
+        ..Default::default()
+    };
+    assert!(!filter.matches(Game::Xonotic, &insecure));
+    assert!(filter.matches(Game::QuakeIII, &insecure));
+    assert!(filter.matches(Game::QuakeIII, &bare_server()));
+}
+
+#[test]
+fn not_full() {
+    let filters = Filters {
+        not_full: true,
+        ..Default::default()
+    };
+    let with_clients = |num_clients, max_clients| Server {
+        num_clients,
+        max_clients,
+        ..full_server()
+    };
+
+    assert!(filters.matches(Game::QuakeIII, &with_clients(Some(15), Some(16))));
+    assert!(!filters.matches(Game::QuakeIII, &with_clients(Some(16), Some(16))));
+    // Over capacity counts as full
+    assert!(!filters.matches(Game::QuakeIII, &with_clients(Some(17), Some(16))));
+    // Zero or missing limit is not reported properly
+    assert!(filters.matches(Game::QuakeIII, &with_clients(Some(16), Some(0))));
+    assert!(filters.matches(Game::QuakeIII, &with_clients(Some(16), None)));
+    assert!(filters.matches(Game::QuakeIII, &with_clients(None, Some(16))));
+}
+
+#[test]
+fn not_empty() {
+    let filters = Filters {
+        not_empty: true,
+        ..Default::default()
+    };
+
+    assert!(filters.matches(Game::QuakeIII, &full_server()));
+    assert!(!filters.matches(
+        Game::QuakeIII,
+        &Server {
+            num_clients: Some(0),
+            ..full_server()
+        }
+    ));
+    assert!(filters.matches(Game::QuakeIII, &bare_server()));
+}
+
+#[test]
+fn no_password() {
+    let filters = Filters {
+        no_password: true,
+        ..Default::default()
+    };
+
+    assert!(filters.matches(Game::QuakeIII, &full_server()));
+    assert!(!filters.matches(
+        Game::QuakeIII,
+        &Server {
+            need_pass: Some(true),
+            ..full_server()
+        }
+    ));
+    assert!(!filters.matches(Game::QuakeIII, &bare_server()));
+}
+
+#[test]
+fn saved_password_passes_no_password() {
+    let filters = Filters {
+        no_password: true,
+        saved_passwords: vec![Favorite {
+            game: Game::QuakeIII,
+            addr: "127.0.0.1:27960".into(),
+        }]
+        .into_iter()
+        .collect(),
+        ..Default::default()
+    };
+    let passworded = Server {
+        need_pass: Some(true),
+        ..full_server()
+    };
+
+    assert!(filters.matches(Game::QuakeIII, &passworded));
+    assert!(!filters.matches(Game::OpenArena, &passworded));
+    assert!(!filters.matches(
+        Game::QuakeIII,
+        &Server {
+            addr: "127.0.0.2:27960".parse().unwrap(),
+            ..passworded
+        }
+    ));
+}
+
+#[test]
+fn countries_keep_unknown_location() {
+    let filters = Filters {
+        countries: obozrenie::filters::parse_countries("de, fr"),
+        ..Default::default()
+    };
+
+    assert!(filters.matches(Game::QuakeIII, &bare_server()));
+}
+
+#[test]
+fn countries_by_name_or_code() {
+    let countries = obozrenie::filters::parse_countries("Germany, united kingdom,nl fr");
+
+    assert_eq!(
+        countries,
+        vec!["de", "gb", "nl", "fr"]
+            .into_iter()
+            .map(String::from)
+            .collect()
+    );
+}
+
+#[test]
+fn country_names() {
+    assert_eq!(obozrenie::countries::name("de"), Some("Germany"));
+    assert_eq!(obozrenie::countries::name("XX"), None);
+    assert_eq!(obozrenie::countries::display_name("nl"), "Netherlands (NL)");
+    assert_eq!(obozrenie::countries::display_name("xx"), "XX");
+}
+
+#[test]
+fn stock_client_only() {
+    let filters = Filters {
+        stock_client_only: true,
+        ..Default::default()
+    };
+    let modded = Server {
+        mod_name: Some("cpma".into()),
+        ..full_server()
+    };
+
+    assert!(filters.matches(Game::QuakeIII, &full_server()));
+    assert!(!filters.matches(Game::QuakeIII, &modded));
+    // Games without the notion of stock clients are kept
+    assert!(filters.matches(Game::OpenTTD, &modded));
+}
+
+#[test]
+fn fields_combine() {
+    let filters = Filters {
+        not_empty: true,
+        no_password: true,
+        max_ping: Duration::from_millis(100),
+        ..Default::default()
+    };
+
+    assert!(filters.matches(Game::QuakeIII, &full_server()));
+    assert!(!filters.matches(
+        Game::QuakeIII,
+        &Server {
+            need_pass: Some(true),
+            ..full_server()
+        }
+    ));
+    assert!(!filters.matches(
+        Game::QuakeIII,
+        &Server {
+            ping: Some(Duration::from_millis(200)),
+            ..full_server()
+        }
+    ));
+}
+
+/// Field state for the missing-field truth table
+#[derive(Clone, Copy, Debug)]
+enum Field {
+    Present,
+    Missing,
+    Empty,
+}
+
+/// `full_server` with one text field replaced
+fn with_text(field: &str, state: Field) -> Server {
+    let value = match state {
+        Field::Present => Some(match field {
+            "mod" => "baseq3",
+            "type" => "ffa",
+            _ => "q3dm17",
+        }),
+        Field::Missing => None,
+        Field::Empty => Some(""),
+    }
+    .map(String::from);
+
+    match field {
+        "mod" => Server {
+            mod_name: value,
+            ..full_server()
+        },
+        "type" => Server {
+            game_type: value,
+            ..full_server()
+        },
+        _ => Server {
+            map: value,
+            ..full_server()
+        },
+    }
+}
+
+/// `full_server` filtered on one text field
+fn text_filter(field: &str, pattern: &str, regex_mode: bool) -> Filters {
+    let mut filters = Filters {
+        regex_mode,
+        ..Default::default()
+    };
+    match field {
+        "mod" => filters.game_mod = pattern.into(),
+        "type" => filters.game_type = pattern.into(),
+        _ => filters.map = pattern.into(),
+    }
+    filters.compile_patterns().unwrap();
+    filters
+}
+
+/// | text filter | field present | field missing | field empty |
+/// |-------------|---------------|---------------|-------------|
+/// | empty       | pass          | pass          | pass        |
+/// | matching    | pass          | reject        | reject      |
+/// | regex `^$`  | reject        | reject        | pass        |
+#[test]
+fn text_fields_truth_table() {
+    for &field in &["mod", "type", "map"] {
+        let prefix = match field {
+            "mod" => "base",
+            "type" => "ff",
+            _ => "q3dm",
+        };
+        for &(state, empty, matching, blank) in &[
+            (Field::Present, true, true, false),
+            (Field::Missing, true, false, false),
+            (Field::Empty, true, false, true),
+        ] {
+            let srv = with_text(field, state);
+            let case = format!("{} {:?}", field, state);
+
+            assert_eq!(
+                text_filter(field, "", false).matches(Game::QuakeIII, &srv),
+                empty,
+                "{}",
+                case
+            );
+            assert_eq!(
+                text_filter(field, "", true).matches(Game::QuakeIII, &srv),
+                empty,
+                "{}",
+                case
+            );
+            assert_eq!(
+                text_filter(field, prefix, false).matches(Game::QuakeIII, &srv),
+                matching,
+                "{}",
+                case
+            );
+            assert_eq!(
+                text_filter(field, &format!("^{}", prefix), true).matches(Game::QuakeIII, &srv),
+                matching,
+                "{}",
+                case
+            );
+            assert_eq!(
+                text_filter(field, "^$", true).matches(Game::QuakeIII, &srv),
+                blank,
+                "{}",
+                case
+            );
+        }
+    }
+}
+
+#[test]
+fn fuzzy_map_truth_table() {
+    let filters = Filters {
+        map: "dm17".into(),
+        map_fuzzy: true,
+        ..Default::default()
+    };
+
+    assert!(filters.matches(Game::QuakeIII, &with_text("map", Field::Present)));
+    assert!(!filters.matches(Game::QuakeIII, &with_text("map", Field::Missing)));
+    assert!(!filters.matches(Game::QuakeIII, &with_text("map", Field::Empty)));
+}
+
+/// | flag filter  | flag reported | unreported, game reports it | unreported, game cannot report it |
+/// |--------------|---------------|-----------------------------|-----------------------------------|
+/// | off          | pass          | pass                        | pass                              |
+/// | on           | by value      | reject                      | pass                              |
+#[test]
+fn flags_truth_table() {
+    let no_password = Filters {
+        no_password: true,
+        ..Default::default()
+    };
+    let unreported = Server {
+        need_pass: None,
+        ..full_server()
+    };
+    assert!(Filters::default().matches(Game::QuakeIII, &unreported));
+    assert!(no_password.matches(Game::QuakeIII, &full_server()));
+    assert!(!no_password.matches(Game::QuakeIII, &unreported));
+    assert!(no_password.matches(Game::Xonotic, &unreported));
+
+    let strict = Filters {
+        unknown_password_protected: true,
+        ..no_password.clone()
+    };
+    assert!(strict.matches(Game::Xonotic, &full_server()));
+    assert!(!strict.matches(Game::Xonotic, &unreported));
+    // Only a modifier of `no_password`
+    assert!(Filters {
+        unknown_password_protected: true,
+        ..Default::default()
+    }
+    .matches(Game::Xonotic, &unreported));
+
+    let unreported = Server {
+        secure: None,
+        ..full_server()
+    };
+    for &filter in &[true, false] {
+        let anticheat = Filters {
+            anticheat: Some(filter),
+            ..Default::default()
+        };
+        assert_eq!(anticheat.matches(Game::QuakeIII, &full_server()), filter);
+        assert!(!anticheat.matches(Game::QuakeIII, &unreported));
+        assert!(anticheat.matches(Game::OpenArena, &unreported));
+        assert!(anticheat.matches(Game::OpenTTD, &unreported));
+    }
+    assert!(Filters::default().matches(Game::QuakeIII, &unreported));
+}
+
+/// Numeric and location filters keep servers that do not report the field, they are too often left out.
+///
+/// | filter       | field reported | field missing |
+/// |--------------|----------------|---------------|
+/// | max ping     | by value       | pass          |
+/// | not full     | by value       | pass          |
+/// | not empty    | by value       | pass          |
+/// | countries    | by value       | pass          |
+/// | stock client | by value       | pass          |
+/// | dedicated    | by value       | pass          |
+#[test]
+fn numeric_fields_truth_table() {
+    for filters in vec![
+        Filters {
+            max_ping: Duration::from_millis(100),
+            ..Default::default()
+        },
+        Filters {
+            not_full: true,
+            ..Default::default()
+        },
+        Filters {
+            not_empty: true,
+            ..Default::default()
+        },
+        Filters {
+            countries: obozrenie::filters::parse_countries("de"),
+            ..Default::default()
+        },
+        Filters {
+            stock_client_only: true,
+            ..Default::default()
+        },
+        Filters {
+            dedicated_only: true,
+            ..Default::default()
+        },
+    ] {
+        let srv = Server {
+            ping: None,
+            num_clients: None,
+            max_clients: None,
+            mod_name: None,
+            ..full_server()
+        };
+        assert!(filters.matches(Game::OpenTTD, &srv), "{:?}", filters);
+        assert!(
+            filters.matches(Game::OpenTTD, &bare_server()),
+            "{:?}",
+            filters
+        );
+    }
+}
+
+#[test]
+fn no_active_filters_by_default() {
+    assert_eq!(Filters::default().active_count(), 0);
+}
+
+#[test]
+fn active_filters_are_counted() {
+    let filters = Filters {
+        games: vec![Game::QuakeIII, Game::Xonotic].into_iter().collect(),
+        map: "q3dm".into(),
+        max_ping: Duration::from_millis(100),
+        anticheat: Some(false),
+        not_full: true,
+        countries: obozrenie::filters::parse_countries("de"),
+        dedicated_only: true,
+        ..Default::default()
+    };
+
+    assert_eq!(filters.active_count(), 7);
+}
+
+#[test]
+fn modifiers_are_not_counted() {
+    let filters = Filters {
+        map_fuzzy: true,
+        regex_mode: true,
+        favorites_only: true,
+        search: "duel".into(),
+        max_ping: Duration::from_millis(0),
+        anticheat: None,
+        ..Default::default()
+    };
+
+    assert_eq!(filters.active_count(), 0);
+}
+
+fn host(i: u8) -> IpAddr {
+    IpAddr::from([198, 51, 100, i])
+}
+
+#[test]
+fn numbered_copies_on_one_host_are_spam() {
+    let mut spam = SpamFilter::default();
+
+    assert!(spam.admit(Game::OpenArena, "Redirect #1", host(1)));
+    assert!(!spam.admit(Game::OpenArena, "Redirect #2", host(1)));
+    assert!(!spam.admit(Game::OpenArena, "Redirect #13 ", host(1)));
+    // Other hosts may use the same numbering
+    assert!(spam.admit(Game::OpenArena, "Redirect #2", host(2)));
+    assert_eq!(spam.hidden[&Game::OpenArena], 2);
+}
+
+#[test]
+fn names_differing_before_the_end_are_not_spam() {
+    let mut spam = SpamFilter::default();
+
+    assert!(spam.admit(Game::OpenArena, "1v1 Duel", host(1)));
+    assert!(spam.admit(Game::OpenArena, "2v2 Duel", host(1)));
+    assert!(spam.admit(Game::OpenArena, "CTF 2 Public", host(1)));
+    assert!(spam.admit(Game::OpenArena, "CTF 3 Public", host(1)));
+    assert!(spam.hidden.is_empty());
+}
+
+#[test]
+fn exact_names_are_capped_across_hosts() {
+    let mut spam = SpamFilter::default();
+
+    for i in 0..MAX_SAME_NAME {
+        assert!(spam.admit(Game::OpenArena, "Free VIP", host(i as u8)));
+    }
+    assert!(!spam.admit(Game::OpenArena, "Free VIP", host(100)));
+    // Numbered names only count on the same host
+    assert!(spam.admit(Game::OpenArena, "Free VIP 2", host(101)));
+}
@@ -0,0 +1,122 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Locks in how favorites followed by name find their server after it moves.
+
+use obozrenie::games::Game;
+use obozrenie::settings::{Favorite, FavoriteDetails, FollowName, FollowResult, Settings};
+
+fn follow(pattern: &str, substring: bool) -> FollowName {
+    FollowName {
+        pattern: pattern.into(),
+        substring,
+    }
+}
+
+fn listed(servers: &[(&str, &str)]) -> Vec<(String, String)> {
+    servers
+        .iter()
+        .map(|(addr, name)| (addr.to_string(), name.to_string()))
+        .collect()
+}
+
+#[test]
+fn name_matching() {
+    assert!(follow("Friday Frag", false).matches("friday frag "));
+    assert!(!follow("Friday Frag", false).matches("Friday Frag #2"));
+    assert!(follow("friday", true).matches("Friday Frag #2"));
+    assert!(!follow("", true).matches("Friday Frag"));
+}
+
+#[test]
+fn stays_while_listed() {
+    let servers = listed(&[
+        ("1.2.3.4:27960", "Renamed"),
+        ("1.2.3.4:27961", "Friday Frag"),
+    ]);
+
+    assert_eq!(
+        follow("Friday Frag", false).locate("1.2.3.4:27960", &servers),
+        FollowResult::Stay
+    );
+    assert_eq!(
+        follow("Nowhere", false).locate("1.2.3.4:27950", &servers),
+        FollowResult::Stay
+    );
+}
+
+#[test]
+fn moves_to_single_match() {
+    let servers = listed(&[("5.6.7.8:27960", "Other"), ("1.2.3.4:27961", "Friday Frag")]);
+
+    assert_eq!(
+        follow("Friday Frag", false).locate("1.2.3.4:27960", &servers),
+        FollowResult::Moved("1.2.3.4:27961".into())
+    );
+}
+
+#[test]
+fn prefers_same_host() {
+    let servers = listed(&[
+        ("5.6.7.8:27960", "Friday Frag"),
+        ("1.2.3.4:27961", "Friday Frag"),
+    ]);
+
+    assert_eq!(
+        follow("Friday Frag", false).locate("1.2.3.4:27960", &servers),
+        FollowResult::Moved("1.2.3.4:27961".into())
+    );
+}
+
+#[test]
+fn ambiguous_matches_are_left_to_the_user() {
+    let servers = listed(&[
+        ("1.2.3.4:27961", "Friday Frag EU"),
+        ("1.2.3.4:27962", "Friday Frag US"),
+    ]);
+
+    assert_eq!(
+        follow("Friday Frag", true).locate("1.2.3.4:27960", &servers),
+        FollowResult::Ambiguous(vec!["1.2.3.4:27961".into(), "1.2.3.4:27962".into()])
+    );
+}
+
+#[test]
+fn moving_keeps_details_and_history() {
+    let old = Favorite {
+        game: Game::OpenArena,
+        addr: "1.2.3.4:27960".into(),
+    };
+    let mut settings = Settings::default();
+    settings.favorites.push(old.clone());
+    settings.pinned.push(old.clone());
+    settings.set_favorite_details(FavoriteDetails {
+        note: "Fridays".into(),
+        follow_name: Some(follow("Friday Frag", false)),
+        ..FavoriteDetails::new(old.clone())
+    });
+
+    settings.move_favorite(old.game, &old.addr, "1.2.3.4:27961".into());
+
+    assert!(!settings.is_favorite(old.game, &old.addr));
+    assert!(settings.is_favorite(old.game, "1.2.3.4:27961"));
+    assert!(settings.is_pinned(old.game, "1.2.3.4:27961"));
+    let details = settings
+        .favorite_details(old.game, "1.2.3.4:27961")
+        .unwrap();
+    assert_eq!(details.note, "Fridays");
+    assert_eq!(details.past_addrs, vec![old.addr.clone()]);
+}
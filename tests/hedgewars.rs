@@ -0,0 +1,202 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Hedgewars room listing, against a local stub of the lobby where the network is involved.
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::thread;
+
+use obozrenie::games::hedgewars::{list_rooms, parse_rooms, room_server, take_messages, Room};
+use obozrenie::games::{self, Game};
+use obozrenie::settings::{LaunchMethod, Settings};
+use serde_json::json;
+
+fn fields(v: &[&str]) -> Vec<String> {
+    v.iter().map(|s| s.to_string()).collect()
+}
+
+const ROOMS: &[&str] = &[
+    "-gp",
+    "Pro league",
+    "4",
+    "3",
+    "unC0Rr",
+    "+rnd+",
+    "Normal",
+    "Pro mode",
+    "Default",
+    "-",
+    "Newbies",
+    "1",
+    "1",
+    "koda",
+    "Bamboo",
+    "Normal",
+    "Default",
+    "Crazy",
+];
+
+/// Lobby that greets the client, reads its handshake and then sends `reply`.
+fn lobby_stub(reply: &'static [u8]) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        let (mut client, _) = listener.accept().unwrap();
+        client
+            .write_all(b"CONNECTED\nHedgewars server https://www.hedgewars.org/\n\n")
+            .unwrap();
+
+        let mut handshake = Vec::new();
+        let mut buf = [0; 256];
+        while !handshake.ends_with(b"PROTO\n60\n\n") {
+            let len = client.read(&mut buf).unwrap();
+            assert!(len > 0);
+            handshake.extend_from_slice(&buf[..len]);
+        }
+        assert!(handshake.starts_with(b"NICK\nObozrenie"));
+
+        client.write_all(reply).unwrap();
+    });
+
+    addr
+}
+
+#[test]
+fn messages_are_split_on_empty_lines() {
+    let mut buf = b"CONNECTED\nHedgewars server\n\nPING\n\nROOMS\n-".to_vec();
+
+    assert_eq!(
+        take_messages(&mut buf),
+        vec![
+            fields(&["CONNECTED", "Hedgewars server"]),
+            fields(&["PING"])
+        ]
+    );
+    assert_eq!(buf, b"ROOMS\n-");
+}
+
+#[test]
+fn rooms_are_parsed() {
+    let rooms = parse_rooms(&fields(ROOMS));
+
+    assert_eq!(rooms.len(), 2);
+    assert_eq!(
+        rooms[0],
+        Room {
+            name: "Pro league".into(),
+            players: 4,
+            teams: 3,
+            owner: "unC0Rr".into(),
+            map: "+rnd+".into(),
+            script: "Normal".into(),
+            scheme: "Pro mode".into(),
+            weapons: "Default".into(),
+            in_progress: true,
+            need_pass: true,
+            restricted_joins: false,
+            registered_only: false,
+        }
+    );
+    assert!(!rooms[1].need_pass);
+    assert!(!rooms[1].in_progress);
+}
+
+#[test]
+fn incomplete_rooms_are_dropped() {
+    assert_eq!(parse_rooms(&fields(&ROOMS[..13])).len(), 1);
+    assert!(parse_rooms(&[]).is_empty());
+}
+
+#[test]
+fn rooms_at_one_address_have_their_own_keys() {
+    let addr = "192.0.2.1:46631".parse().unwrap();
+    let servers = parse_rooms(&fields(ROOMS))
+        .into_iter()
+        .map(|room| room_server(room, addr, Some("netserver.hedgewars.org"), None))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        games::server_key(&servers[0]),
+        "netserver.hedgewars.org:46631/Pro league"
+    );
+    assert_eq!(
+        games::server_key(&servers[1]),
+        "netserver.hedgewars.org:46631/Newbies"
+    );
+    assert_eq!(servers[0].need_pass, Some(true));
+    assert_eq!(servers[0].num_clients, Some(4));
+    assert_eq!(
+        servers[0].map.as_ref().map(String::as_str),
+        Some("Random map")
+    );
+    assert_eq!(servers[1].map.as_ref().map(String::as_str), Some("Bamboo"));
+    assert_eq!(
+        games::server_description(&servers[0])
+            .as_ref()
+            .map(String::as_str),
+        Some("Room: Pro league")
+    );
+}
+
+#[test]
+fn rooms_of_the_lobby() {
+    let lobby = lobby_stub(
+        b"NICK\nObozrenie1\n\nPROTO\n60\n\nLOBBY:JOINED\nObozrenie1\n\nROOMS\n-gp\nPro league\n4\n3\nunC0Rr\n+rnd+\nNormal\nPro mode\nDefault\n\n",
+    );
+
+    let rooms = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(list_rooms(lobby))
+        .unwrap();
+
+    assert_eq!(rooms.len(), 1);
+    assert_eq!(rooms[0].name, "Pro league");
+}
+
+#[test]
+fn early_close_is_an_error() {
+    let lobby = lobby_stub(b"NICK\nObozrenie1\n\n");
+
+    let res = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(list_rooms(lobby));
+
+    assert!(res.is_err());
+}
+
+#[test]
+fn registered_nick_is_an_error() {
+    let lobby = lobby_stub(b"ASKPASSWORD\nsalt\n\n");
+
+    let res = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(list_rooms(lobby));
+
+    assert!(res.is_err());
+}
+
+#[test]
+fn launcher_starts_the_client() {
+    let mut settings = Settings::default();
+    settings.launch_method = LaunchMethod::Native;
+
+    assert_eq!(
+        games::launcher(Game::Hedgewars, &settings).describe(),
+        json!({ "kind": "native", "program": "hedgewars" })
+    );
+}
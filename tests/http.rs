@@ -0,0 +1,93 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Identification and proxying of requests to HTTP master servers.
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use obozrenie::games::http::{client, HTTP_USER_AGENT};
+
+/// Answers one request with an empty server list and hands over the request head.
+fn mock_server() -> (SocketAddr, mpsc::Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let (sink, faucet) = mpsc::channel();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+
+        let mut head = Vec::new();
+        let mut buf = [0; 1024];
+        while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+            let n = stream.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            head.extend_from_slice(&buf[..n]);
+        }
+
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 2\r\nConnection: close\r\n\r\n[]")
+            .unwrap();
+        let _ = sink.send(String::from_utf8_lossy(&head).into_owned());
+    });
+
+    (addr, faucet)
+}
+
+/// Sends a GET through the mock server as the proxy, so that proxy variables of the environment do not matter.
+fn request(url: &str, proxy: SocketAddr) {
+    let client = client(Some(&format!("http://{}", proxy)), Duration::from_secs(5)).unwrap();
+
+    let rsp = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(client.get(url).send())
+        .unwrap();
+    assert!(rsp.status().is_success());
+}
+
+#[test]
+fn requests_identify_obozrenie() {
+    let (addr, head) = mock_server();
+
+    request(&format!("http://{}/server-list", addr), addr);
+
+    let head = head.recv().unwrap().to_lowercase();
+    assert!(
+        head.contains(&format!("user-agent: {}", HTTP_USER_AGENT.to_lowercase())),
+        "{}",
+        head
+    );
+    assert!(HTTP_USER_AGENT.starts_with(concat!("obozrenie/", env!("CARGO_PKG_VERSION"))));
+}
+
+#[test]
+fn configured_proxy_is_used() {
+    let (addr, head) = mock_server();
+
+    request("http://master.invalid/server-list", addr);
+
+    let head = head.recv().unwrap();
+    assert!(
+        head.starts_with("GET http://master.invalid/server-list "),
+        "{}",
+        head
+    );
+}
@@ -0,0 +1,96 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Favorites imported from XQF and qstat server lists.
+
+use obozrenie::games::Game;
+use obozrenie::import::{merge, parse, ImportEntry, ImportSummary};
+use obozrenie::settings::Favorite;
+use std::collections::HashMap;
+
+fn entry(game_id: &str, addr: &str) -> ImportEntry {
+    ImportEntry {
+        game_id: game_id.into(),
+        addr: addr.into(),
+    }
+}
+
+fn favorite(game: Game, addr: &str) -> Favorite {
+    Favorite {
+        game,
+        addr: addr.into(),
+    }
+}
+
+#[test]
+fn xqf_favorites() {
+    let entries = parse(include_str!("fixtures/xqf-favorites"));
+
+    assert_eq!(
+        entries,
+        vec![
+            entry("q3s", "192.0.2.10:27960"),
+            entry("q3s", "192.0.2.11:27961"),
+            entry("xonotics", "198.51.100.4:26000"),
+            entry("nexuizs", "198.51.100.5:26000"),
+            entry("openarenas", "203.0.113.7:27960"),
+            entry("q3s", "192.0.2.10:27960"),
+            entry("woets", "203.0.113.9:27960"),
+        ]
+    );
+
+    let mut favorites = vec![favorite(Game::QuakeIII, "192.0.2.11:27961")];
+    // Chosen by the user in the review step
+    let overrides = vec![("nexuizs".to_string(), Game::Xonotic)]
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+    let summary = merge(&mut favorites, &entries, &overrides);
+
+    assert_eq!(
+        summary,
+        ImportSummary {
+            imported: 4,
+            duplicates: 2,
+            unknown: 1,
+        }
+    );
+    assert_eq!(
+        favorites,
+        vec![
+            favorite(Game::QuakeIII, "192.0.2.11:27961"),
+            favorite(Game::QuakeIII, "192.0.2.10:27960"),
+            favorite(Game::Xonotic, "198.51.100.4:26000"),
+            favorite(Game::Xonotic, "198.51.100.5:26000"),
+            favorite(Game::OpenArena, "203.0.113.7:27960"),
+        ]
+    );
+    assert_eq!(
+        summary.to_string(),
+        "Imported 4, skipped 2 duplicates, 1 unknown games"
+    );
+}
+
+#[test]
+fn qstat_list() {
+    assert_eq!(
+        parse(include_str!("fixtures/qstat-servers")),
+        vec![
+            entry("q3s", "192.0.2.10:27960"),
+            entry("xonotics", "198.51.100.4:26000"),
+            entry("openttds", "203.0.113.20:3979"),
+        ]
+    );
+}
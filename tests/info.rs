@@ -0,0 +1,183 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Reads the cheap info replies of live mode.
+
+use futures01::{future, stream, Future, Stream};
+use rgs::models::Server;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+use obozrenie::games::info::{parse_q3_info, query_q3, ServerInfo};
+use obozrenie::games::{detect_game, DummyQuerier, Game, Querier, Retransmit};
+
+/// Answers info queries with a fixed protocol version and recognizes another one
+struct VersionQuerier {
+    reported: u32,
+    expected: u32,
+}
+
+impl Querier for VersionQuerier {
+    fn query(&self) -> Box<dyn Stream<Item = Server, Error = failure::Error> + Send> {
+        Box::new(stream::empty())
+    }
+
+    fn can_query_info(&self) -> bool {
+        true
+    }
+
+    fn query_info(
+        &self,
+        _addr: SocketAddr,
+    ) -> Box<dyn Future<Item = ServerInfo, Error = failure::Error> + Send> {
+        Box::new(future::ok(ServerInfo {
+            protocol: Some(self.reported),
+            ..Default::default()
+        }))
+    }
+
+    fn recognizes(&self, info: &ServerInfo) -> bool {
+        info.protocol == Some(self.expected)
+    }
+}
+
+#[test]
+fn q3_info_response() {
+    let reply = b"\xff\xff\xff\xffinfoResponse\n\\protocol\\68\\hostname\\Test server\\mapname\\q3dm17\\clients\\4\\sv_maxclients\\16\\gametype\\0";
+
+    assert_eq!(
+        parse_q3_info(reply),
+        Some(ServerInfo {
+            map: Some("q3dm17".into()),
+            num_clients: Some(4),
+            max_clients: Some(16),
+            protocol: Some(68),
+        })
+    );
+}
+
+#[test]
+fn q3_info_missing_fields() {
+    let reply = b"\xff\xff\xff\xffinfoResponse\n\\mapname\\dm4\\clients\\many\n";
+
+    assert_eq!(
+        parse_q3_info(reply),
+        Some(ServerInfo {
+            map: Some("dm4".into()),
+            num_clients: None,
+            max_clients: None,
+            protocol: None,
+        })
+    );
+}
+
+#[test]
+fn other_replies_are_rejected() {
+    assert_eq!(
+        parse_q3_info(b"\xff\xff\xff\xffstatusResponse\n\\mapname\\q3dm17\n"),
+        None
+    );
+    assert_eq!(parse_q3_info(b""), None);
+}
+
+#[test]
+fn apply_reports_changes() {
+    let mut srv = Server {
+        map: Some("q3dm17".into()),
+        num_clients: Some(4),
+        max_clients: Some(16),
+        ..Server::new("127.0.0.1:27960".parse().unwrap())
+    };
+
+    let same = ServerInfo::from_server(&srv);
+    assert!(!same.apply(&mut srv));
+
+    let changed = ServerInfo {
+        num_clients: Some(5),
+        ..same.clone()
+    };
+    assert!(changed.apply(&mut srv));
+    assert_eq!(srv.num_clients, Some(5));
+
+    // Unreported fields keep their value
+    let partial = ServerInfo {
+        map: None,
+        ..changed
+    };
+    assert!(!partial.apply(&mut srv));
+    assert_eq!(srv.map.as_ref().map(String::as_str), Some("q3dm17"));
+}
+
+#[test]
+fn detects_game_by_protocol_version() {
+    let queriers = vec![
+        (
+            Game::OpenArena,
+            Arc::new(VersionQuerier {
+                reported: 68,
+                expected: 71,
+            }) as Arc<dyn Querier>,
+        ),
+        (Game::OpenTTD, Arc::new(DummyQuerier) as Arc<dyn Querier>),
+        (
+            Game::QuakeIII,
+            Arc::new(VersionQuerier {
+                reported: 68,
+                expected: 68,
+            }) as Arc<dyn Querier>,
+        ),
+    ];
+
+    assert_eq!(
+        detect_game(queriers, "127.0.0.1:27960".parse().unwrap())
+            .wait()
+            .unwrap(),
+        vec![Game::QuakeIII]
+    );
+}
+
+#[test]
+fn query_goes_out_from_bind_address() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server = socket.local_addr().unwrap();
+    let (from_sink, from) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = [0; 1500];
+        let (_, client) = socket.recv_from(&mut buf).unwrap();
+        from_sink.send(client.ip()).unwrap();
+        socket
+            .send_to(
+                b"\xff\xff\xff\xffinfoResponse\n\\mapname\\q3dm17\\protocol\\68",
+                client,
+            )
+            .unwrap();
+    });
+
+    let bind_addr = "127.0.0.1".parse::<IpAddr>().unwrap();
+    let policy = Retransmit {
+        retries: 0,
+        window: Duration::from_secs(5),
+    };
+    let info = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(query_q3(server, policy, None, Some(bind_addr)))
+        .unwrap();
+
+    assert_eq!(info.map.as_ref().map(String::as_str), Some("q3dm17"));
+    assert_eq!(from.recv().unwrap(), bind_addr);
+}
@@ -0,0 +1,68 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Custom launch command templates
+
+use obozrenie::games::{expand_template, LaunchData};
+
+fn data(password: Option<&str>) -> LaunchData {
+    LaunchData {
+        addr: "203.0.113.5:27960".into(),
+        password: password.map(String::from),
+        player_name: Some("Some Player".into()),
+        company: None,
+        company_password: None,
+        flatpak_ref: None,
+        extra_args: Vec::new(),
+    }
+}
+
+#[test]
+fn fills_in_placeholders() {
+    assert_eq!(
+        expand_template("ioquake3 +connect {addr} +name {name}", &data(None)),
+        vec![
+            "ioquake3",
+            "+connect",
+            "203.0.113.5:27960",
+            "+name",
+            "Some Player"
+        ]
+    );
+    assert_eq!(
+        expand_template("game --host={host} --port={port}", &data(None)),
+        vec!["game", "--host=203.0.113.5", "--port=27960"]
+    );
+}
+
+#[test]
+fn missing_password_is_empty() {
+    assert_eq!(
+        expand_template("game {password}", &data(None)),
+        vec!["game", ""]
+    );
+}
+
+#[test]
+fn values_are_not_expanded_again() {
+    assert_eq!(
+        expand_template(
+            "game +password {password} {unknown}",
+            &data(Some("{name} x"))
+        ),
+        vec!["game", "+password", "{name} x", "{unknown}"]
+    );
+}
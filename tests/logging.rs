@@ -0,0 +1,114 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Command line logging switches and the in-memory record buffer
+
+use log::{Level, LevelFilter};
+use std::path::PathBuf;
+
+use obozrenie::logging::{Options, Record, RingBuffer};
+
+fn args(v: &[&str]) -> Vec<String> {
+    v.iter().map(|s| s.to_string()).collect()
+}
+
+fn record(message: &str) -> Record {
+    Record {
+        time: 0,
+        level: Level::Info,
+        target: "obozrenie".into(),
+        message: message.into(),
+    }
+}
+
+#[test]
+fn switches_are_taken_out() {
+    let (options, rest) = Options::parse(args(&[
+        "obozrenie-gtk",
+        "-vv",
+        "--refresh",
+        "--log-file",
+        "/tmp/obozrenie.log",
+    ]));
+
+    assert_eq!(options.verbosity, 2);
+    assert_eq!(options.log_file, Some(PathBuf::from("/tmp/obozrenie.log")));
+    assert_eq!(rest, args(&["obozrenie-gtk", "--refresh"]));
+
+    let (options, rest) = Options::parse(args(&[
+        "obozrenie-gtk",
+        "-v",
+        "--verbose",
+        "--log-file=a.log",
+    ]));
+    assert_eq!(options.verbosity, 2);
+    assert_eq!(options.log_file, Some(PathBuf::from("a.log")));
+    assert_eq!(rest, args(&["obozrenie-gtk"]));
+}
+
+#[test]
+fn verbosity_maps_to_levels() {
+    let level = |verbosity| {
+        Options {
+            verbosity,
+            log_file: None,
+        }
+        .level()
+    };
+
+    assert_eq!(level(0), None);
+    assert_eq!(level(1), Some(LevelFilter::Info));
+    assert_eq!(level(2), Some(LevelFilter::Debug));
+    assert_eq!(level(5), Some(LevelFilter::Trace));
+}
+
+#[test]
+fn info_is_always_captured() {
+    assert_eq!(Options::default().capture_level(), LevelFilter::Info);
+    assert_eq!(
+        Options {
+            verbosity: 3,
+            log_file: None,
+        }
+        .capture_level(),
+        LevelFilter::Trace
+    );
+}
+
+#[test]
+fn buffer_drops_oldest() {
+    let mut buffer = RingBuffer::new(2);
+    buffer.push(record("first"));
+    buffer.push(record("second"));
+    buffer.push(record("third"));
+
+    assert_eq!(buffer.len(), 2);
+    assert_eq!(
+        buffer
+            .records()
+            .map(|r| r.message.as_str())
+            .collect::<Vec<_>>(),
+        vec!["second", "third"]
+    );
+    assert_eq!(
+        buffer.to_text(),
+        "0 INFO  obozrenie: second\n0 INFO  obozrenie: third\n"
+    );
+
+    let mut disabled = RingBuffer::new(0);
+    disabled.push(record("dropped"));
+    assert!(disabled.is_empty());
+}
@@ -0,0 +1,167 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Locks in how master server lists are reused and requests to a master are spaced out.
+
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use obozrenie::games::master_cache::MasterCache;
+
+const DPMASTER: &str = "dpmaster.deathmask.net:27950";
+
+fn addr(s: &str) -> SocketAddr {
+    s.parse().unwrap()
+}
+
+fn masters(v: &[&str]) -> Vec<String> {
+    v.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn lists_are_reused_within_ttl() {
+    let cache = MasterCache::new(Duration::from_secs(60), Duration::from_secs(0));
+    let masters = masters(&[DPMASTER]);
+    let start = Instant::now();
+
+    assert_eq!(cache.get(&masters, "xonotic", start), None);
+
+    cache.record(&masters, "xonotic", start, addr("192.0.2.1:26000"));
+    cache.record(&masters, "xonotic", start, addr("192.0.2.2:26000"));
+    cache.record(&masters, "xonotic", start, addr("192.0.2.1:26000"));
+
+    assert_eq!(
+        cache.get(&masters, "xonotic", start + Duration::from_secs(30)),
+        Some(vec![addr("192.0.2.1:26000"), addr("192.0.2.2:26000")])
+    );
+    assert_eq!(
+        cache.get(&masters, "xonotic", start + Duration::from_secs(60)),
+        None
+    );
+}
+
+#[test]
+fn games_sharing_a_master_keep_their_own_lists() {
+    let cache = MasterCache::new(Duration::from_secs(60), Duration::from_secs(0));
+    let masters = masters(&[DPMASTER]);
+    let start = Instant::now();
+
+    cache.record(&masters, "xonotic", start, addr("192.0.2.1:26000"));
+
+    assert_eq!(cache.get(&masters, "openarena", start), None);
+    assert!(cache.get(&masters, "xonotic", start).is_some());
+}
+
+#[test]
+fn lists_are_reused_only_while_every_master_is_fresh() {
+    let cache = MasterCache::new(Duration::from_secs(60), Duration::from_secs(0));
+    let start = Instant::now();
+
+    cache.record(
+        &masters(&[DPMASTER]),
+        "openarena",
+        start,
+        addr("192.0.2.1:27960"),
+    );
+
+    let both = masters(&["master.ioquake3.org:27950", DPMASTER]);
+    assert_eq!(cache.get(&both, "openarena", start), None);
+
+    cache.record(&both, "openarena", start, addr("192.0.2.2:27960"));
+    assert_eq!(
+        cache.get(&both, "openarena", start),
+        Some(vec![addr("192.0.2.2:27960")])
+    );
+}
+
+#[test]
+fn later_requests_replace_lists() {
+    let cache = MasterCache::new(Duration::from_secs(60), Duration::from_secs(0));
+    let masters = masters(&[DPMASTER]);
+    let start = Instant::now();
+    let later = start + Duration::from_secs(90);
+
+    cache.record(&masters, "xonotic", start, addr("192.0.2.1:26000"));
+    cache.record(&masters, "xonotic", later, addr("192.0.2.2:26000"));
+    // A straggling reply to the earlier request
+    cache.record(&masters, "xonotic", start, addr("192.0.2.3:26000"));
+
+    assert_eq!(
+        cache.get(&masters, "xonotic", later),
+        Some(vec![addr("192.0.2.2:26000")])
+    );
+}
+
+#[test]
+fn zero_ttl_always_asks() {
+    let cache = MasterCache::new(Duration::from_secs(0), Duration::from_secs(0));
+    let masters = masters(&[DPMASTER]);
+    let start = Instant::now();
+
+    cache.record(&masters, "xonotic", start, addr("192.0.2.1:26000"));
+    assert_eq!(cache.get(&masters, "xonotic", start), None);
+}
+
+#[test]
+fn requests_to_a_master_are_spaced_out() {
+    let interval = Duration::from_secs(2);
+    let cache = MasterCache::new(Duration::from_secs(60), interval);
+    let start = Instant::now();
+
+    // OpenArena and Xonotic refreshing together
+    assert_eq!(
+        cache.reserve(&masters(&["master.ioquake3.org:27950", DPMASTER]), start),
+        start
+    );
+    assert_eq!(
+        cache.reserve(&masters(&[DPMASTER]), start),
+        start + interval
+    );
+    assert_eq!(
+        cache.reserve(&masters(&[DPMASTER]), start),
+        start + interval * 2
+    );
+
+    // Other masters are not held back
+    assert_eq!(
+        cache.reserve(&masters(&["master.unvanquished.net:27950"]), start),
+        start
+    );
+    // Nor are requests once the interval has passed
+    let later = start + Duration::from_secs(10);
+    assert_eq!(cache.reserve(&masters(&[DPMASTER]), later), later);
+}
+
+#[test]
+fn status_reports_reuse() {
+    let cache = MasterCache::new(Duration::from_secs(60), Duration::from_secs(2));
+    let masters = masters(&[DPMASTER]);
+    let start = Instant::now();
+
+    let requested = cache.reserve(&masters, start);
+    cache.record(&masters, "xonotic", requested, addr("192.0.2.1:26000"));
+    cache.get(&masters, "xonotic", start + Duration::from_secs(1));
+
+    let status = cache.status(start + Duration::from_secs(1));
+    assert_eq!(status.len(), 1);
+    assert_eq!(status[0].master, DPMASTER);
+    assert_eq!(status[0].tag, "xonotic");
+    assert_eq!(status[0].servers, 1);
+    assert_eq!(status[0].age, Duration::from_secs(1));
+    assert!(status[0].fresh);
+    assert_eq!(status[0].reused, 1);
+    assert_eq!(status[0].next_request, Duration::from_secs(1));
+}
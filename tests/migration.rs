@@ -0,0 +1,159 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Locks in when a server that changed address is taken for the same one.
+
+use obozrenie::activity::{PingHistory, RecentServers};
+use obozrenie::games::{self, Game};
+use obozrenie::settings::{AddressMigration, Favorite, ServerIdentity, Settings};
+use serde_json::Value;
+use std::collections::HashMap;
+
+fn server(
+    migration: &AddressMigration,
+    addr: &str,
+    name: &str,
+    rules: &[(&str, &str)],
+) -> (String, ServerIdentity) {
+    let rules = rules
+        .iter()
+        .map(|(k, v)| (k.to_string(), Value::String(v.to_string())))
+        .collect::<HashMap<_, _>>();
+
+    (
+        addr.to_string(),
+        migration
+            .identity(Game::Xonotic, addr, name, &rules)
+            .unwrap(),
+    )
+}
+
+#[test]
+fn moves_server_with_same_name_and_port() {
+    let migration = AddressMigration::default();
+    let gone = vec![server(&migration, "198.51.100.1:26000", "Friday Frag", &[])];
+    let new = vec![
+        server(&migration, "198.51.100.7:26000", "friday frag ", &[]),
+        server(&migration, "198.51.100.8:26001", "Friday Frag", &[]),
+    ];
+
+    assert_eq!(
+        migration.find_moves(&gone, &new),
+        vec![(
+            "198.51.100.1:26000".to_string(),
+            "198.51.100.7:26000".to_string()
+        )]
+    );
+}
+
+#[test]
+fn ambiguous_identities_stay() {
+    let migration = AddressMigration::default();
+    let gone = vec![server(&migration, "198.51.100.1:26000", "Frag", &[])];
+    let new = vec![
+        server(&migration, "198.51.100.7:26000", "Frag", &[]),
+        server(&migration, "198.51.100.8:26000", "Frag", &[]),
+    ];
+
+    assert!(migration.find_moves(&gone, &new).is_empty());
+}
+
+#[test]
+fn host_ids_must_agree() {
+    let migration = AddressMigration::default();
+    let gone = vec![server(
+        &migration,
+        "198.51.100.1:26000",
+        "Frag",
+        &[("hostid", "a")],
+    )];
+
+    for rules in &[&[("hostid", "b")][..], &[][..]] {
+        let new = vec![server(&migration, "198.51.100.7:26000", "Frag", rules)];
+        assert!(migration.find_moves(&gone, &new).is_empty());
+    }
+}
+
+#[test]
+fn port_is_optional_and_migration_can_be_off() {
+    let mut migration = AddressMigration {
+        same_port: false,
+        ..AddressMigration::default()
+    };
+    let gone = vec![server(&migration, "198.51.100.1:26000", "Frag", &[])];
+    let new = vec![server(&migration, "198.51.100.7:26010", "Frag", &[])];
+    assert_eq!(migration.find_moves(&gone, &new).len(), 1);
+
+    migration.enabled = false;
+    assert!(migration.find_moves(&gone, &new).is_empty());
+}
+
+#[test]
+fn history_follows_the_server() {
+    let from = Favorite {
+        game: Game::Xonotic,
+        addr: "198.51.100.1:26000".into(),
+    };
+    let to = Favorite {
+        game: Game::Xonotic,
+        addr: "198.51.100.7:26000".into(),
+    };
+
+    let mut pings = PingHistory::default();
+    pings.record(&from, 40);
+    pings.rename(&from, &to);
+    assert!(pings.samples(&from).is_empty());
+    assert_eq!(pings.samples(&to), vec![40]);
+
+    let mut recent = RecentServers::default();
+    recent.record(from.clone(), Some(true));
+    recent.rename(&from, &to);
+    assert_eq!(
+        recent.iter().map(|v| v.server.clone()).collect::<Vec<_>>(),
+        vec![to]
+    );
+    assert_eq!(recent.iter().next().unwrap().need_pass, Some(true));
+}
+
+#[test]
+fn host_keys_replace_addresses() {
+    let mut srv = rgs::models::Server::new("198.51.100.1:12000".parse().unwrap());
+    assert_eq!(games::legacy_server_key(&srv), None);
+
+    srv.rules
+        .insert(games::HOST_RULE.into(), Value::from("ror.example.org"));
+    assert_eq!(games::server_key(&srv), "ror.example.org:12000");
+    assert_eq!(
+        games::legacy_server_key(&srv),
+        Some("198.51.100.1:12000".into())
+    );
+
+    let mut settings = Settings::default();
+    let old = Favorite {
+        game: Game::RigsOfRods,
+        addr: games::legacy_server_key(&srv).unwrap(),
+    };
+    settings.favorites.push(old.clone());
+    settings.move_favorite(old.game, &old.addr, games::server_key(&srv));
+    assert!(settings.is_favorite(Game::RigsOfRods, "ror.example.org:12000"));
+    assert!(!settings.is_favorite(Game::RigsOfRods, &old.addr));
+}
+
+#[test]
+fn internal_rules_are_hidden() {
+    assert!(games::is_internal_rule(games::HOST_RULE));
+    assert!(!games::is_internal_rule("sv_hostname"));
+}
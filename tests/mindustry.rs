@@ -0,0 +1,186 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Mindustry community server list and discovery replies, pinged against a local stub.
+
+use std::net::UdpSocket;
+use std::thread;
+use std::time::Duration;
+
+use obozrenie::games::mindustry::{
+    parse_host, parse_server_data, parse_server_list, ping, to_server, ServerData, DEFAULT_PORT,
+    PING_REQUEST,
+};
+use obozrenie::games::{self, Game, Retransmit};
+use obozrenie::settings::{LaunchMethod, Settings};
+use serde_json::{json, Value};
+
+fn write_string(out: &mut Vec<u8>, v: &str) {
+    out.push(v.len() as u8);
+    out.extend_from_slice(v.as_bytes());
+}
+
+/// Discovery reply of a current server
+fn reply() -> Vec<u8> {
+    let mut out = Vec::new();
+    write_string(&mut out, "Nydus Network");
+    write_string(&mut out, "Ancient Caldera");
+    out.extend_from_slice(&7i32.to_be_bytes());
+    out.extend_from_slice(&42i32.to_be_bytes());
+    out.extend_from_slice(&146i32.to_be_bytes());
+    write_string(&mut out, "official");
+    out.push(3);
+    out.extend_from_slice(&16i32.to_be_bytes());
+    write_string(&mut out, "PvP all day");
+    write_string(&mut out, "");
+    out
+}
+
+#[test]
+fn hosts_default_to_the_game_port() {
+    assert_eq!(
+        parse_host("md.surrealment.com"),
+        Some(("md.surrealment.com".into(), DEFAULT_PORT))
+    );
+    assert_eq!(
+        parse_host("mindustry.pl:6000"),
+        Some(("mindustry.pl".into(), 6000))
+    );
+    assert_eq!(
+        parse_host("[2001:db8::1]:6568"),
+        Some(("2001:db8::1".into(), 6568))
+    );
+    assert_eq!(
+        parse_host("2001:db8::1"),
+        Some(("2001:db8::1".into(), DEFAULT_PORT))
+    );
+    assert_eq!(parse_host("host:port"), None);
+    assert_eq!(parse_host(" "), None);
+}
+
+#[test]
+fn server_list_is_flattened() {
+    let hosts = parse_server_list(
+        br#"[
+            {"name": "Nydus", "address": ["nydus.example.org", "nydus.example.org:6568"]},
+            {"address": ["192.0.2.1"]}
+        ]"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        hosts,
+        vec![
+            ("nydus.example.org".into(), 6567, Some("Nydus".into())),
+            ("nydus.example.org".into(), 6568, Some("Nydus".into())),
+            ("192.0.2.1".into(), 6567, None),
+        ]
+    );
+    assert!(parse_server_list(b"{}").is_err());
+}
+
+#[test]
+fn discovery_reply() {
+    assert_eq!(
+        parse_server_data(&reply()),
+        Some(ServerData {
+            name: "Nydus Network".into(),
+            map: "Ancient Caldera".into(),
+            players: 7,
+            wave: 42,
+            version: 146,
+            version_type: "official".into(),
+            gamemode: Some("pvp"),
+            player_limit: 16,
+            description: "PvP all day".into(),
+            mode_name: None,
+        })
+    );
+}
+
+#[test]
+fn truncated_replies() {
+    let reply = reply();
+
+    // Older servers stop after the version
+    let data = parse_server_data(&reply[..51]).unwrap();
+    assert_eq!(data.version_type, "official");
+    assert_eq!(data.gamemode, None);
+    assert_eq!(data.player_limit, 0);
+
+    assert_eq!(parse_server_data(&reply[..20]), None);
+    assert_eq!(parse_server_data(&[]), None);
+}
+
+#[test]
+fn server_fields() {
+    let data = parse_server_data(&reply()).unwrap();
+    let srv = to_server(
+        data,
+        "192.0.2.1:6567".parse().unwrap(),
+        Some("nydus.example.org"),
+        Some("Nydus"),
+        Duration::from_millis(40),
+    );
+
+    assert_eq!(srv.name.as_ref().map(String::as_str), Some("Nydus Network"));
+    assert_eq!(srv.num_clients, Some(7));
+    assert_eq!(srv.max_clients, Some(16));
+    assert_eq!(srv.mod_name.as_ref().map(String::as_str), Some("pvp"));
+    assert_eq!(srv.rules.get("wave"), Some(&Value::from(42)));
+    assert_eq!(srv.rules.get("gamemode"), Some(&Value::from("pvp")));
+    assert_eq!(games::server_key(&srv), "nydus.example.org:6567");
+    assert_eq!(
+        games::server_version(Game::Mindustry, &srv)
+            .as_ref()
+            .map(String::as_str),
+        Some("146")
+    );
+}
+
+#[test]
+fn ping_stub() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server = socket.local_addr().unwrap();
+    thread::spawn(move || {
+        let mut buf = [0; 1500];
+        let (len, from) = socket.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..len], PING_REQUEST);
+        socket.send_to(&reply(), from).unwrap();
+    });
+
+    let policy = Retransmit {
+        retries: 0,
+        window: Duration::from_secs(5),
+    };
+    let (data, _) = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(ping(server, policy, None, None))
+        .unwrap();
+
+    assert_eq!(data.name, "Nydus Network");
+}
+
+#[test]
+fn launched_from_flatpak() {
+    let mut settings = Settings::default();
+    settings.launch_method = LaunchMethod::Flatpak;
+
+    assert_eq!(
+        games::launcher(Game::Mindustry, &settings).describe(),
+        json!({ "kind": "flatpak", "ref": "com.github.Anuken.Mindustry/x86_64/stable" })
+    );
+}
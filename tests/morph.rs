@@ -0,0 +1,68 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! User name replacements stacked on the per-game morphers.
+
+use obozrenie::games::{
+    ChainedMorpher, DecorationMorpher, DummyMorpher, NameMorpher, RegexMorpher,
+};
+use obozrenie::settings::NameReplacement;
+use std::sync::Arc;
+
+fn replacement(pattern: &str, replacement: &str) -> NameReplacement {
+    NameReplacement {
+        pattern: pattern.into(),
+        replacement: replacement.into(),
+    }
+}
+
+#[test]
+fn invalid_patterns_are_reported() {
+    let (morpher, errors) = RegexMorpher::compile(&[
+        replacement("(unclosed", ""),
+        replacement("^EU ", ""),
+        replacement("[z-a]", "x"),
+    ]);
+
+    assert_eq!(errors.len(), 2);
+    assert!(errors[0].starts_with("\"(unclosed\""));
+    assert!(errors[1].starts_with("\"[z-a]\""));
+    // The valid pattern is kept
+    assert_eq!(morpher.0.len(), 1);
+}
+
+#[test]
+fn invalid_patterns_leave_names_intact() {
+    let morpher = RegexMorpher::new(&[replacement("(unclosed", ""), replacement("^EU ", "")]);
+
+    assert_eq!(morpher.morph("EU Frag Fest".into()), "Frag Fest");
+    assert_eq!(morpher.morph("(unclosed".into()), "(unclosed");
+    assert_eq!(morpher.morph(String::new()), "");
+
+    let all_invalid = RegexMorpher::new(&[replacement("*", "")]);
+    assert_eq!(all_invalid.morph("Frag Fest".into()), "Frag Fest");
+}
+
+#[test]
+fn user_morphs_stack_on_game_morphs() {
+    let morpher = ChainedMorpher(vec![
+        Arc::new(DummyMorpher),
+        Arc::new(DecorationMorpher::default()),
+        Arc::new(RegexMorpher::new(&[replacement("^EU ", "")])),
+    ]);
+
+    assert_eq!(morpher.morph("-=~ EU Frag Fest ~=-".into()), "Frag Fest");
+}
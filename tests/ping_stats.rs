@@ -0,0 +1,51 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Locks in the summary shown when the latency monitor stops.
+
+use obozrenie::activity::PingStats;
+
+#[test]
+fn summary_of_replies() {
+    let stats = PingStats::new(&[Some(30), None, Some(50), Some(40), None]);
+
+    assert_eq!(
+        stats,
+        PingStats {
+            sent: 5,
+            lost: 2,
+            min: Some(30),
+            avg: Some(40),
+            max: Some(50),
+            jitter: Some(15),
+        }
+    );
+    assert_eq!(stats.loss_percent(), 40);
+    assert_eq!(
+        stats.to_string(),
+        "5 sent, 2 lost (40%), min 30 ms, avg 40 ms, max 50 ms, jitter 15 ms"
+    );
+}
+
+#[test]
+fn nothing_replied() {
+    let stats = PingStats::new(&[None, None]);
+
+    assert_eq!(stats.loss_percent(), 100);
+    assert_eq!(stats.avg, None);
+    assert_eq!(stats.to_string(), "2 sent, 2 lost (100%)");
+    assert_eq!(PingStats::new(&[]).loss_percent(), 0);
+}
@@ -0,0 +1,327 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Tunes query timeouts from synthetic refresh histories and runs refreshes against mock queriers.
+
+use futures01::{future, stream, sync::oneshot, Stream};
+use rgs::models::Server;
+use std::collections::HashMap;
+use std::sync::{mpsc::channel, Arc};
+use std::time::Duration;
+
+use obozrenie::activity::{RefreshStats, MAX_DURATION_SAMPLES};
+use obozrenie::games::{DummyMorpher, Game, MasterAnswered, NameMorpher, Querier};
+use obozrenie::refresher::{
+    self, Progress, RefreshEvent, DEFAULT_TIMEOUT, MAX_TIMEOUT, MIN_TIMEOUT,
+};
+use obozrenie::settings::Settings;
+
+fn secs(v: &[u64]) -> Vec<Duration> {
+    v.iter().copied().map(Duration::from_secs).collect()
+}
+
+#[test]
+fn cold_start_uses_default() {
+    assert_eq!(refresher::auto_timeout(&[]), DEFAULT_TIMEOUT);
+}
+
+#[test]
+fn timeout_is_multiple_of_average() {
+    assert_eq!(
+        refresher::auto_timeout(&secs(&[8])),
+        Duration::from_secs(24)
+    );
+    assert_eq!(
+        refresher::auto_timeout(&secs(&[6, 8, 10])),
+        Duration::from_secs(24)
+    );
+}
+
+#[test]
+fn fast_master_is_clamped_to_minimum() {
+    assert_eq!(refresher::auto_timeout(&secs(&[1, 1, 1])), MIN_TIMEOUT);
+    assert_eq!(
+        refresher::auto_timeout(&[Duration::from_millis(50)]),
+        MIN_TIMEOUT
+    );
+}
+
+#[test]
+fn slow_master_is_clamped_to_maximum() {
+    assert_eq!(refresher::auto_timeout(&secs(&[30, 45])), MAX_TIMEOUT);
+}
+
+#[test]
+fn stats_keep_recent_durations() {
+    let mut stats = RefreshStats::default();
+    assert_eq!(stats.average(Game::OpenTTD), None);
+
+    // One slow refresh long ago no longer counts
+    stats.record(Game::OpenTTD, Duration::from_secs(50));
+    for _ in 0..MAX_DURATION_SAMPLES {
+        stats.record(Game::OpenTTD, Duration::from_secs(1));
+    }
+
+    assert_eq!(stats.durations(Game::OpenTTD).len(), MAX_DURATION_SAMPLES);
+    assert_eq!(stats.average(Game::OpenTTD), Some(Duration::from_secs(1)));
+    assert_eq!(stats.average(Game::RigsOfRods), None);
+}
+
+#[test]
+fn explicit_timeout_wins() {
+    let mut stats = RefreshStats::default();
+    stats.record(Game::RigsOfRods, Duration::from_secs(8));
+
+    let mut settings = Settings::default();
+    assert_eq!(
+        refresher::query_timeout(Game::RigsOfRods, &settings, &stats),
+        Duration::from_secs(24)
+    );
+    assert_eq!(
+        refresher::query_timeout(Game::OpenTTD, &settings, &stats),
+        DEFAULT_TIMEOUT
+    );
+
+    settings.query_timeouts.insert(Game::RigsOfRods, 4);
+    assert_eq!(
+        refresher::query_timeout(Game::RigsOfRods, &settings, &stats),
+        Duration::from_secs(4)
+    );
+}
+
+/// Lists servers on consecutive ports, then panics if `panics` as a malformed reply would
+struct MockQuerier {
+    servers: u16,
+    panics: bool,
+}
+
+impl Querier for MockQuerier {
+    fn query(&self) -> Box<dyn Stream<Item = Server, Error = failure::Error> + Send> {
+        let servers = stream::iter_ok(
+            (0..self.servers)
+                .map(|i| Server::new(format!("127.0.0.1:{}", 27960 + i).parse().unwrap())),
+        );
+
+        if self.panics {
+            Box::new(servers.chain(stream::poll_fn(
+                || -> futures01::Poll<Option<Server>, failure::Error> { panic!("malformed reply") },
+            )))
+        } else {
+            Box::new(servers)
+        }
+    }
+}
+
+#[test]
+fn panicking_query_fails_only_its_game() {
+    let mut games = HashMap::new();
+    for (game, panics) in &[
+        (Game::QuakeIII, true),
+        (Game::Xonotic, false),
+        (Game::OpenArena, false),
+    ] {
+        games.insert(
+            *game,
+            (
+                Arc::new(MockQuerier {
+                    servers: 2,
+                    panics: *panics,
+                }) as Arc<dyn Querier>,
+                Arc::new(DummyMorpher) as Arc<dyn NameMorpher>,
+            ),
+        );
+    }
+
+    let (event_sink, event_faucet) = channel();
+    // Dropping the sender would cancel the refresh
+    let (_cancel, cancelled) = oneshot::channel::<()>();
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime
+        .block_on(future::lazy(move || {
+            refresher::start(games, HashMap::new(), 0, cancelled, event_sink);
+            Ok::<_, ()>(())
+        }))
+        .unwrap();
+
+    let mut added = HashMap::<Game, usize>::new();
+    let mut results = HashMap::new();
+    loop {
+        match event_faucet.recv_timeout(Duration::from_secs(10)).unwrap() {
+            RefreshEvent::AddServer(data) => *added.entry(data.game_id).or_default() += 1,
+            RefreshEvent::GameRefreshComplete((game, res)) => {
+                results.insert(game, res);
+            }
+            RefreshEvent::RefreshComplete => break,
+            _ => {}
+        }
+    }
+
+    assert_eq!(results[&Game::Xonotic], Ok(2));
+    assert_eq!(results[&Game::OpenArena], Ok(2));
+    assert!(results[&Game::QuakeIII]
+        .as_ref()
+        .unwrap_err()
+        .contains("malformed reply"));
+    // Servers listed before the panic are kept
+    assert_eq!(added[&Game::QuakeIII], 2);
+    assert_eq!(added[&Game::Xonotic], 2);
+}
+
+/// Asks masters that never reply
+struct StalledQuerier;
+
+impl Querier for StalledQuerier {
+    fn query(&self) -> Box<dyn Stream<Item = Server, Error = failure::Error> + Send> {
+        Box::new(stream::poll_fn(
+            || -> futures01::Poll<Option<Server>, failure::Error> {
+                Ok(futures01::Async::NotReady)
+            },
+        ))
+    }
+
+    fn master_count(&self) -> usize {
+        3
+    }
+}
+
+#[test]
+fn silent_masters_are_done_on_timeout() {
+    let mut games = HashMap::new();
+    games.insert(
+        Game::Xonotic,
+        (
+            Arc::new(MockQuerier {
+                servers: 1,
+                panics: false,
+            }) as Arc<dyn Querier>,
+            Arc::new(DummyMorpher) as Arc<dyn NameMorpher>,
+        ),
+    );
+    games.insert(
+        Game::OpenArena,
+        (
+            Arc::new(StalledQuerier) as Arc<dyn Querier>,
+            Arc::new(DummyMorpher) as Arc<dyn NameMorpher>,
+        ),
+    );
+    let mut timeouts = HashMap::new();
+    timeouts.insert(Game::OpenArena, Duration::from_millis(200));
+
+    let (event_sink, event_faucet) = channel();
+    let (_cancel, cancelled) = oneshot::channel::<()>();
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime
+        .block_on(future::lazy(move || {
+            refresher::start(games, timeouts, 0, cancelled, event_sink);
+            Ok::<_, ()>(())
+        }))
+        .unwrap();
+
+    let mut progress = Vec::new();
+    loop {
+        match event_faucet.recv_timeout(Duration::from_secs(10)).unwrap() {
+            RefreshEvent::Progress(v) => progress.push(v),
+            RefreshEvent::RefreshComplete => break,
+            _ => {}
+        }
+    }
+
+    assert_eq!(
+        progress.first(),
+        Some(&Progress {
+            games_done: 0,
+            games_total: 2,
+            masters_done: 0,
+            masters_total: 4,
+        })
+    );
+    // The stalled game finishes last
+    assert_eq!(progress[1].masters_done, 1);
+    let last = progress.last().unwrap();
+    assert_eq!(last.to_string(), "2/2 games done, 4/4 masters");
+    assert!((last.fraction() - 1.0).abs() < std::f64::EPSILON);
+}
+
+/// Lists a server from each of its two masters, reporting one master too many with the last
+struct ReportingQuerier;
+
+impl Querier for ReportingQuerier {
+    fn query(&self) -> Box<dyn Stream<Item = Server, Error = failure::Error> + Send> {
+        self.query_reporting(Arc::new(|| {}))
+    }
+
+    fn master_count(&self) -> usize {
+        2
+    }
+
+    fn query_reporting(
+        &self,
+        master_answered: MasterAnswered,
+    ) -> Box<dyn Stream<Item = Server, Error = failure::Error> + Send> {
+        Box::new(stream::iter_ok((0..2).map(move |i| {
+            master_answered();
+            if i == 1 {
+                master_answered();
+            }
+            Server::new(format!("127.0.0.1:{}", 27960 + i).parse().unwrap())
+        })))
+    }
+}
+
+#[test]
+fn masters_are_done_as_they_answer() {
+    let mut games = HashMap::new();
+    games.insert(
+        Game::Xonotic,
+        (
+            Arc::new(ReportingQuerier) as Arc<dyn Querier>,
+            Arc::new(DummyMorpher) as Arc<dyn NameMorpher>,
+        ),
+    );
+
+    let (event_sink, event_faucet) = channel();
+    let (_cancel, cancelled) = oneshot::channel::<()>();
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime
+        .block_on(future::lazy(move || {
+            refresher::start(games, HashMap::new(), 0, cancelled, event_sink);
+            Ok::<_, ()>(())
+        }))
+        .unwrap();
+
+    let mut progress = Vec::new();
+    loop {
+        match event_faucet.recv_timeout(Duration::from_secs(10)).unwrap() {
+            RefreshEvent::Progress(v) => progress.push((v.games_done, v.masters_done)),
+            RefreshEvent::RefreshComplete => break,
+            _ => {}
+        }
+    }
+
+    assert_eq!(progress, vec![(0, 0), (0, 1), (0, 2), (1, 2)]);
+}
+
+#[test]
+fn progress_without_masters_counts_games() {
+    let progress = Progress {
+        games_done: 1,
+        games_total: 4,
+        masters_done: 0,
+        masters_total: 0,
+    };
+    assert!((progress.fraction() - 0.25).abs() < std::f64::EPSILON);
+    assert_eq!(progress.to_string(), "1/4 games done");
+}
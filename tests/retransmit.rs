@@ -0,0 +1,152 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Resends queries whose replies get lost.
+
+use futures01::{future, stream, Future, Stream};
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+use std::time::Duration;
+
+use obozrenie::games::retransmit::{retransmit, retransmit_stream, Retransmit};
+
+const POLICY: Retransmit = Retransmit {
+    retries: 2,
+    window: Duration::from_millis(20),
+};
+
+/// Query that gets no reply for the first `lost` sends. Returns the query and the number of sends made.
+fn lossy_query(
+    lost: u32,
+) -> (
+    impl Future<Item = u32, Error = failure::Error> + Send,
+    Arc<AtomicU32>,
+) {
+    let sent = Arc::new(AtomicU32::new(0));
+    let query = retransmit(POLICY, {
+        let sent = sent.clone();
+        move || {
+            let n = sent.fetch_add(1, Ordering::SeqCst) + 1;
+            if n > lost {
+                Box::new(future::ok(n))
+                    as Box<dyn Future<Item = u32, Error = failure::Error> + Send>
+            } else {
+                Box::new(future::empty())
+            }
+        }
+    });
+
+    (query, sent)
+}
+
+fn run<F: Future<Item = u32, Error = failure::Error> + Send + 'static>(
+    f: F,
+) -> Result<u32, failure::Error> {
+    tokio::runtime::Runtime::new().unwrap().block_on(f)
+}
+
+#[test]
+fn reply_to_first_send() {
+    let (query, sent) = lossy_query(0);
+
+    assert_eq!(run(query).unwrap(), 1);
+    assert_eq!(sent.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn lost_sends_are_repeated() {
+    let (query, sent) = lossy_query(2);
+
+    assert_eq!(run(query).unwrap(), 3);
+    assert_eq!(sent.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn gives_up_after_retries() {
+    let (query, sent) = lossy_query(10);
+
+    assert!(run(query).is_err());
+    assert_eq!(sent.load(Ordering::SeqCst), 1 + POLICY.retries);
+}
+
+#[test]
+fn errors_are_not_retried() {
+    let sent = Arc::new(AtomicU32::new(0));
+    let query = retransmit(POLICY, {
+        let sent = sent.clone();
+        move || {
+            sent.fetch_add(1, Ordering::SeqCst);
+            future::err::<u32, _>(failure::err_msg("Unreachable"))
+        }
+    });
+
+    assert!(run(query).is_err());
+    assert_eq!(sent.load(Ordering::SeqCst), 1);
+}
+
+/// Master query whose masters do not answer the first `lost` sends. Returns the query and the number of sends
+/// made.
+fn lossy_master_query(
+    lost: u32,
+) -> (
+    impl Stream<Item = u32, Error = failure::Error> + Send,
+    Arc<AtomicU32>,
+) {
+    let sent = Arc::new(AtomicU32::new(0));
+    let query = retransmit_stream(POLICY, {
+        let sent = sent.clone();
+        move || {
+            let n = sent.fetch_add(1, Ordering::SeqCst) + 1;
+            if n > lost {
+                Box::new(stream::iter_ok(vec![n, n + 100]))
+                    as Box<dyn Stream<Item = u32, Error = failure::Error> + Send>
+            } else {
+                Box::new(future::empty().into_stream())
+            }
+        }
+    });
+
+    (query, sent)
+}
+
+#[test]
+fn master_queries_are_repeated_until_servers_arrive() {
+    let (query, sent) = lossy_master_query(2);
+
+    assert_eq!(
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(query.collect())
+            .unwrap(),
+        vec![3, 103]
+    );
+    assert_eq!(sent.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn last_master_query_is_not_cut_short() {
+    let (query, sent) = lossy_master_query(10);
+
+    // Only the caller's timeout ends it
+    let res = tokio::runtime::Runtime::new().unwrap().block_on(
+        tokio::timer::Timeout::new(query.collect(), Duration::from_millis(200))
+            .map_err(|e| e.is_elapsed()),
+    );
+    assert_eq!(res, Err(true));
+    assert_eq!(sent.load(Ordering::SeqCst), 1 + POLICY.retries);
+}
@@ -0,0 +1,89 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Locks in the ordering produced by the smart sort score.
+
+use std::time::Duration;
+
+use obozrenie::score::{score, ScoreInput, ScoreWeights};
+
+fn server(num_clients: u64, max_clients: u64, ping: u64) -> ScoreInput {
+    ScoreInput {
+        favorite: false,
+        num_clients: Some(num_clients),
+        max_clients: Some(max_clients),
+        ping: Some(Duration::from_millis(ping)),
+    }
+}
+
+fn scored(input: &ScoreInput) -> i32 {
+    score(&ScoreWeights::default(), input)
+}
+
+#[test]
+fn sweet_spot_beats_empty_and_full() {
+    let sweet = scored(&server(10, 16, 50));
+
+    assert!(sweet > scored(&server(2, 16, 50)));
+    assert!(sweet > scored(&server(16, 16, 50)));
+    assert!(scored(&server(2, 16, 50)) > scored(&server(0, 16, 50)));
+    assert_eq!(scored(&server(9, 16, 50)), scored(&server(12, 16, 50)));
+}
+
+#[test]
+fn ping_penalty() {
+    assert!(scored(&server(10, 16, 30)) > scored(&server(10, 16, 150)));
+    assert!(
+        scored(&server(10, 16, 150))
+            > scored(&ScoreInput {
+                ping: None,
+                ..server(10, 16, 150)
+            })
+    );
+}
+
+#[test]
+fn favorites_first() {
+    let favorite = ScoreInput {
+        favorite: true,
+        ..server(0, 16, 300)
+    };
+
+    assert!(scored(&favorite) > scored(&server(10, 16, 10)));
+}
+
+#[test]
+fn weights_are_tunable() {
+    let weights = ScoreWeights {
+        ping: 0.0,
+        ..Default::default()
+    };
+
+    assert_eq!(
+        score(&weights, &server(10, 16, 30)),
+        score(&weights, &server(10, 16, 300))
+    );
+}
+
+#[test]
+fn missing_fields() {
+    let unknown = ScoreInput::default();
+
+    assert_eq!(
+        scored(&unknown),
+        -ScoreWeights::default().unknown_ping as i32
+    );
+}
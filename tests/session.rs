@@ -0,0 +1,58 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Tracking of games launched in this session
+
+use obozrenie::games::Game;
+use obozrenie::session::{windows_of, AlreadyRunning, RunningGames};
+
+#[test]
+fn latest_instance_is_tracked_until_exit() {
+    let mut running = RunningGames::default();
+    running.started(Game::Xonotic, 100);
+    running.started(Game::Xonotic, 200);
+
+    assert_eq!(running.latest(Game::Xonotic), Some(200));
+    assert_eq!(running.latest(Game::OpenArena), None);
+
+    running.exited(Game::Xonotic, 200);
+    assert_eq!(running.latest(Game::Xonotic), Some(100));
+
+    running.exited(Game::Xonotic, 100);
+    assert_eq!(running.latest(Game::Xonotic), None);
+}
+
+#[test]
+fn decisions_are_per_game() {
+    let mut running = RunningGames::default();
+    running.remember(Game::Xonotic, AlreadyRunning::LaunchAnyway);
+
+    assert_eq!(
+        running.decision(Game::Xonotic),
+        Some(AlreadyRunning::LaunchAnyway)
+    );
+    assert_eq!(running.decision(Game::OpenTTD), None);
+}
+
+#[test]
+fn finds_windows_by_owner() {
+    let output = "0x02a00003  0 4242   host Xonotic\n\
+                  0x03c00001 -1 1337   host Desktop\n\
+                  0x02a00007  0 4242   host Xonotic console\n";
+
+    assert_eq!(windows_of(output, 4242), vec!["0x02a00003", "0x02a00007"]);
+    assert!(windows_of(output, 1).is_empty());
+}
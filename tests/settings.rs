@@ -0,0 +1,78 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Locks in how settings files of other releases are treated, and how favorites are looked up.
+
+use obozrenie::games::{Game, HOST_RULE};
+use obozrenie::settings::{Favorite, FavoriteDetails, Settings, SETTINGS_VERSION};
+use serde_json::Value;
+
+#[test]
+fn unversioned_settings_are_current() {
+    let settings: Settings = serde_json::from_str(r#"{"player_name":"UnnamedPlayer"}"#).unwrap();
+
+    assert_eq!(settings.version, SETTINGS_VERSION);
+    assert_eq!(settings.player_name, "UnnamedPlayer");
+    assert!(!settings.is_read_only());
+}
+
+#[test]
+fn newer_settings_are_not_overwritten() {
+    let settings: Settings = serde_json::from_str(&format!(
+        r#"{{"version":{},"player_name":"UnnamedPlayer","setting_of_the_future":true}}"#,
+        SETTINGS_VERSION + 1
+    ))
+    .unwrap();
+
+    assert_eq!(settings.player_name, "UnnamedPlayer");
+    assert!(settings.is_read_only());
+    assert!(settings.save().is_err());
+}
+
+#[test]
+fn saved_settings_record_their_version() {
+    let value = serde_json::to_value(Settings::default()).unwrap();
+
+    assert_eq!(value["version"], SETTINGS_VERSION);
+}
+
+#[test]
+fn host_keyed_favorite_is_found_from_its_server() {
+    let mut srv = rgs::models::Server::new("1.2.3.4:12000".parse().unwrap());
+    srv.rules
+        .insert(HOST_RULE.into(), Value::from("rigs.example.org"));
+
+    let mut settings = Settings::default();
+    let mut details = FavoriteDetails::new(Favorite {
+        game: Game::RigsOfRods,
+        addr: "rigs.example.org:12000".into(),
+    });
+    details.password_ref = Some("password".into());
+    settings.favorite_details.push(details);
+
+    let favorite = Favorite::of_server(Game::RigsOfRods, &srv);
+    assert_eq!(favorite.addr, "rigs.example.org:12000");
+    assert_eq!(
+        settings
+            .favorite_details(favorite.game, &favorite.addr)
+            .and_then(|details| details.password_ref.clone()),
+        Some("password".into())
+    );
+    // Not under the address the server replied from
+    assert!(settings
+        .favorite_details(Game::RigsOfRods, &srv.addr.to_string())
+        .is_none());
+}
@@ -0,0 +1,200 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Reads join links shared in chats.
+
+use obozrenie::games::{DummyMorpher, Game};
+use obozrenie::settings::Settings;
+use obozrenie::share::{
+    compare_servers, format_server_info, parse_join_link, ComparedField, JoinLink, DEFAULT_TEMPLATE,
+};
+use serde_json::Value;
+use std::time::Duration;
+
+fn link(game: Option<Game>, addr: &str, password: Option<&str>) -> Option<JoinLink> {
+    Some(JoinLink {
+        game,
+        addr: addr.into(),
+        password: password.map(String::from),
+    })
+}
+
+#[test]
+fn share_button_text() {
+    let srv = rgs::models::Server {
+        name: Some("Test server".into()),
+        ..rgs::models::Server::new("1.2.3.4:27960".parse().unwrap())
+    };
+    let text = format_server_info(DEFAULT_TEMPLATE, Game::QuakeIII, &srv);
+
+    assert_eq!(
+        parse_join_link(&text),
+        link(Some(Game::QuakeIII), "1.2.3.4:27960", None)
+    );
+}
+
+#[test]
+fn custom_template_round_trip() {
+    let mut settings = Settings::default();
+    settings.share_template =
+        "{name} ({game})\n{addr} on {map}, {players}/{max_players} {mod}, {ping} ms".into();
+    let settings =
+        serde_json::from_str::<Settings>(&serde_json::to_string(&settings).unwrap()).unwrap();
+
+    let srv = rgs::models::Server {
+        name: Some("Test server".into()),
+        map: Some("stormkeep".into()),
+        mod_name: Some("data".into()),
+        num_clients: Some(3),
+        max_clients: Some(16),
+        ping: Some(Duration::from_millis(42)),
+        ..rgs::models::Server::new("1.2.3.4:26000".parse().unwrap())
+    };
+
+    assert_eq!(
+        format_server_info(&settings.share_template, Game::Xonotic, &srv),
+        "Test server (Xonotic)\n1.2.3.4:26000 on stormkeep, 3/16 data, 42 ms"
+    );
+}
+
+#[test]
+fn steam_links() {
+    assert_eq!(
+        parse_join_link("steam://connect/1.2.3.4:27015"),
+        link(None, "1.2.3.4:27015", None)
+    );
+    assert_eq!(
+        parse_join_link("steam://connect/1.2.3.4:27015/secret"),
+        link(None, "1.2.3.4:27015", Some("secret"))
+    );
+    assert_eq!(parse_join_link("steam://connect/1.2.3.4"), None);
+}
+
+#[test]
+fn game_schemes() {
+    assert_eq!(
+        parse_join_link("unv://unv.example.org:27960"),
+        link(Some(Game::Unvanquished), "unv.example.org:27960", None)
+    );
+    assert_eq!(
+        parse_join_link("xonotic://[2001:db8::1]:26000/"),
+        link(Some(Game::Xonotic), "[2001:db8::1]:26000", None)
+    );
+    assert_eq!(parse_join_link("https://example.org:443"), None);
+}
+
+#[test]
+fn console_commands() {
+    for text in &[
+        "connect 1.2.3.4:27960",
+        "/connect 1.2.3.4:27960",
+        "  \\CONNECT 1.2.3.4:27960\n",
+    ] {
+        assert_eq!(
+            parse_join_link(text),
+            link(None, "1.2.3.4:27960", None),
+            "{}",
+            text
+        );
+    }
+}
+
+#[test]
+fn bare_addresses() {
+    assert_eq!(
+        parse_join_link("host.example.org:3979"),
+        link(None, "host.example.org:3979", None)
+    );
+    assert_eq!(parse_join_link("1.2.3.4"), None);
+    assert_eq!(parse_join_link("1.2.3.4:port"), None);
+    assert_eq!(parse_join_link("join me at 1.2.3.4:27960"), None);
+    assert_eq!(parse_join_link(""), None);
+}
+
+#[test]
+fn compared_servers() {
+    let mut a = rgs::models::Server {
+        map: Some("q3dm17".into()),
+        num_clients: Some(4),
+        ping: Some(Duration::from_millis(30)),
+        ..rgs::models::Server::new("1.2.3.4:27960".parse().unwrap())
+    };
+    a.rules.insert("g_gametype".into(), Value::from(0));
+    a.rules.insert("sv_fps".into(), Value::from("20"));
+    a.rules
+        .insert("_host".into(), Value::from("q3.example.org"));
+    let mut b = rgs::models::Server {
+        map: Some("q3dm17".into()),
+        num_clients: Some(2),
+        ..rgs::models::Server::new("5.6.7.8:27960".parse().unwrap())
+    };
+    b.rules.insert("g_gametype".into(), Value::from(0));
+    b.rules.insert("g_needpass".into(), Value::from(1));
+
+    let mut settings = Settings::default();
+    settings.game_names.insert(Game::QuakeIII, "Q3".into());
+
+    let fields = compare_servers(
+        &settings,
+        &DummyMorpher,
+        (Game::QuakeIII, &a),
+        (Game::QuakeIII, &b),
+    );
+    let field = |name: &str| fields.iter().find(|f| f.name == name).unwrap();
+
+    assert_eq!(field("Game").values, [Some("Q3".into()), Some("Q3".into())]);
+    // Neither list reported players
+    assert!(!field("Player list").differs());
+    assert_eq!(field("Player list").values, [None, None]);
+
+    assert!(!field("Map").differs());
+    assert_eq!(
+        field("Address").values,
+        [
+            Some("q3.example.org:27960".into()),
+            Some("5.6.7.8:27960".into())
+        ]
+    );
+    assert_eq!(
+        field("Players").values,
+        [Some("4".into()), Some("2".into())]
+    );
+    assert_eq!(field("Ping").values, [Some("30 ms".into()), None]);
+
+    // Rules follow the details, sorted by name
+    let rules = fields
+        .iter()
+        .skip_while(|f| f.name != "g_gametype")
+        .cloned()
+        .collect::<Vec<_>>();
+    assert_eq!(
+        rules,
+        vec![
+            ComparedField {
+                name: "g_gametype".into(),
+                values: [Some("0".into()), Some("0".into())],
+            },
+            ComparedField {
+                name: "g_needpass".into(),
+                values: [None, Some("1".into())],
+            },
+            ComparedField {
+                name: "sv_fps".into(),
+                values: [Some("20".into()), None],
+            },
+        ]
+    );
+}
@@ -0,0 +1,194 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Relays info queries through a local SOCKS5 stub.
+
+use futures01::{Future, Sink, Stream};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, UdpSocket};
+use std::thread;
+use std::time::Duration;
+
+use obozrenie::games::info::{query_q3, ServerInfo};
+use obozrenie::games::socks5::{associate, decapsulate, encapsulate};
+use obozrenie::games::Retransmit;
+
+const POLICY: Retransmit = Retransmit {
+    retries: 0,
+    window: Duration::from_secs(5),
+};
+
+/// SOCKS5 proxy serving one client. Refuses to relay UDP with `reply` unless it is 0.
+fn socks5_stub(reply: u8) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        let (mut control, _) = listener.accept().unwrap();
+
+        let mut greeting = [0; 3];
+        control.read_exact(&mut greeting).unwrap();
+        assert_eq!(greeting, [5, 1, 0]);
+        control.write_all(&[5, 0]).unwrap();
+
+        let mut request = [0; 10];
+        control.read_exact(&mut request).unwrap();
+        assert_eq!(request[..4], [5, 3, 0, 1]);
+
+        // Unspecified address of the relay, meaning the proxy's own
+        let relay = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = relay.local_addr().unwrap().port().to_be_bytes();
+        control
+            .write_all(&[5, reply, 0, 1, 0, 0, 0, 0, port[0], port[1]])
+            .unwrap();
+        if reply != 0 {
+            return;
+        }
+
+        relay
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut client = None;
+        let mut buf = [0; 1500];
+        while let Ok((len, from)) = relay.recv_from(&mut buf) {
+            if client.is_none() || client == Some(from) {
+                client = Some(from);
+                let (dest, data) = decapsulate(&buf[..len]).unwrap();
+                relay.send_to(data, dest).unwrap();
+            } else {
+                relay
+                    .send_to(&encapsulate(from, &buf[..len]), client.unwrap())
+                    .unwrap();
+            }
+        }
+    });
+
+    addr
+}
+
+/// Quake III server answering one info request
+fn server_stub() -> SocketAddr {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = socket.local_addr().unwrap();
+
+    thread::spawn(move || {
+        let mut buf = [0; 1500];
+        let (len, from) = socket.recv_from(&mut buf).unwrap();
+        assert!(buf[..len].starts_with(b"\xff\xff\xff\xffgetinfo"));
+        socket
+            .send_to(
+                b"\xff\xff\xff\xffinfoResponse\n\\mapname\\q3dm17\\clients\\4\\sv_maxclients\\16\\protocol\\68",
+                from,
+            )
+            .unwrap();
+    });
+
+    addr
+}
+
+/// Sends back the first datagram it gets
+fn echo_stub() -> SocketAddr {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = socket.local_addr().unwrap();
+
+    thread::spawn(move || {
+        let mut buf = [0; 1500];
+        let (len, from) = socket.recv_from(&mut buf).unwrap();
+        socket.send_to(&buf[..len], from).unwrap();
+    });
+
+    addr
+}
+
+#[test]
+fn datagrams_round_trip() {
+    for dest in &["1.2.3.4:27960", "[2001:db8::1]:26000"] {
+        let dest = dest.parse().unwrap();
+        let datagram = encapsulate(dest, b"payload");
+
+        assert_eq!(decapsulate(&datagram), Some((dest, &b"payload"[..])));
+    }
+}
+
+#[test]
+fn fragments_are_dropped() {
+    let mut datagram = encapsulate("1.2.3.4:27960".parse().unwrap(), b"payload");
+    datagram[2] = 1;
+
+    assert_eq!(decapsulate(&datagram), None);
+    assert_eq!(decapsulate(&[0, 0, 0, 1, 127]), None);
+}
+
+#[test]
+fn info_query_through_proxy() {
+    let proxy = socks5_stub(0);
+    let server = server_stub();
+
+    let info = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(query_q3(server, POLICY, Some(proxy.to_string()), None))
+        .unwrap();
+
+    assert_eq!(
+        info,
+        ServerInfo {
+            map: Some("q3dm17".into()),
+            num_clients: Some(4),
+            max_clients: Some(16),
+            protocol: Some(68),
+        }
+    );
+}
+
+#[test]
+fn proxy_without_udp_is_reported() {
+    let proxy = socks5_stub(7);
+
+    let e = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(query_q3(
+            "127.0.0.1:27960".parse().unwrap(),
+            POLICY,
+            Some(proxy.to_string()),
+            None,
+        ))
+        .unwrap_err();
+
+    assert!(e.to_string().contains("does not support UDP"), "{}", e);
+}
+
+#[test]
+fn relayed_socket_round_trip() {
+    let proxy = socks5_stub(0);
+    let echo = echo_stub();
+
+    let reply = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(
+            associate(&proxy.to_string())
+                .and_then(move |relay| {
+                    relay
+                        .into_socket()
+                        .send((b"payload".to_vec(), echo))
+                        .map_err(failure::Error::from)
+                })
+                .and_then(|socket| socket.into_future().map_err(|(e, _)| e.into())),
+        )
+        .unwrap()
+        .0;
+
+    assert_eq!(reply, Some((b"payload".to_vec(), echo)));
+}
@@ -0,0 +1,105 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Application state bundles moved between machines.
+
+use obozrenie::activity::{ActivityLog, RecentServers, Sample};
+use obozrenie::games::Game;
+use obozrenie::settings::{Favorite, Settings};
+use obozrenie::state::{StateBundle, BUNDLE_VERSION};
+
+fn favorite() -> Favorite {
+    Favorite {
+        game: Game::Xonotic,
+        addr: "203.0.113.5:26000".into(),
+    }
+}
+
+fn state() -> (Settings, RecentServers, ActivityLog) {
+    let mut settings = Settings::default();
+    settings.player_name = "Player".into();
+    settings.favorites.push(favorite());
+    let mut recent_servers = RecentServers::default();
+    recent_servers.record(favorite(), None);
+    let mut activity = ActivityLog::default();
+    activity.record(&favorite(), Sample(1_500_000_000, 4));
+
+    (settings, recent_servers, activity)
+}
+
+#[test]
+fn round_trip() {
+    let (settings, recent_servers, activity) = state();
+    let json = StateBundle::new(&settings, &recent_servers, &activity)
+        .to_json()
+        .unwrap();
+
+    let bundle = StateBundle::from_json(&json).unwrap();
+    assert_eq!(bundle.version, BUNDLE_VERSION);
+
+    let (mut new_settings, mut new_recent_servers, mut new_activity) = Default::default();
+    assert_eq!(
+        bundle.overwritten(&new_settings, &new_recent_servers, &new_activity),
+        vec![
+            "favorites",
+            "player_name",
+            "recent servers",
+            "favorite activity"
+        ]
+    );
+    bundle.apply(
+        &mut new_settings,
+        &mut new_recent_servers,
+        &mut new_activity,
+    );
+
+    assert_eq!(new_settings.player_name, "Player");
+    assert!(new_settings.is_favorite(Game::Xonotic, &favorite().addr));
+    assert_eq!(
+        new_recent_servers
+            .iter()
+            .map(|recent| recent.server.clone())
+            .collect::<Vec<_>>(),
+        vec![favorite()]
+    );
+    assert_eq!(
+        new_activity.samples(&favorite(), 0),
+        vec![Sample(1_500_000_000, 4)]
+    );
+
+    // Nothing left to overwrite
+    assert!(StateBundle::from_json(&json)
+        .unwrap()
+        .overwritten(&new_settings, &new_recent_servers, &new_activity)
+        .is_empty());
+}
+
+#[test]
+fn older_bundles_keep_missing_sections() {
+    let (mut settings, mut recent_servers, mut activity) = state();
+    let bundle =
+        StateBundle::from_json(r#"{"version": 1, "settings": {"player_name": "Other"}}"#).unwrap();
+
+    assert_eq!(
+        bundle.overwritten(&settings, &recent_servers, &activity),
+        vec!["favorites", "player_name"]
+    );
+    bundle.apply(&mut settings, &mut recent_servers, &mut activity);
+
+    assert_eq!(settings.player_name, "Other");
+    assert_eq!(recent_servers.iter().count(), 1);
+    assert_eq!(activity.samples(&favorite(), 0).len(), 1);
+}
@@ -0,0 +1,164 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Per-game derivation of `games::stock_client_compatible` from rule sets of real servers.
+
+use obozrenie::games::{self, Game};
+use rgs::models::Server;
+use serde_json::Value;
+
+fn server(mod_name: Option<&str>, rules: &[(&str, &str)]) -> Server {
+    Server {
+        mod_name: mod_name.map(String::from),
+        rules: rules
+            .iter()
+            .map(|(k, v)| (k.to_string(), Value::String(v.to_string())))
+            .collect(),
+        ..Server::new("127.0.0.1:27960".parse().unwrap())
+    }
+}
+
+/// Plain ioquake3 FFA server
+const Q3_STOCK: &[(&str, &str)] = &[
+    ("sv_hostname", "^1Stock ^7FFA"),
+    ("version", "ioq3 1.36_GIT_f3ae2e2 linux-x86_64 Apr 10 2019"),
+    ("protocol", "68"),
+    ("g_gametype", "0"),
+    ("mapname", "q3dm17"),
+    ("sv_maxclients", "16"),
+    ("sv_pure", "1"),
+    ("sv_allowDownload", "0"),
+    ("dedicated", "2"),
+];
+
+/// CPMA server serving its paks
+const Q3_CPMA_DOWNLOADS: &[(&str, &str)] = &[
+    ("sv_hostname", "CPMA Duel"),
+    ("gamename", "cpma"),
+    ("version", "ioq3 1.36 linux-x86_64"),
+    ("mapname", "cpm22"),
+    ("sv_pure", "1"),
+    ("sv_allowDownload", "1"),
+    ("dedicated", "2"),
+];
+
+/// Defrag server allowing downloads but impure, so clients are not told what to fetch
+const Q3_DEFRAG_IMPURE: &[(&str, &str)] = &[
+    ("sv_hostname", "Defrag"),
+    ("gamename", "defrag"),
+    ("mapname", "st1"),
+    ("sv_pure", "0"),
+    ("sv_allowDownload", "1"),
+    ("dedicated", "2"),
+];
+
+/// OpenArena 0.8.8 server
+const OA_STOCK: &[(&str, &str)] = &[
+    ("sv_hostname", "OA CTF"),
+    ("version", "ioq3+oa 1.35 linux-x86_64"),
+    ("g_gametype", "4"),
+    ("mapname", "oa_ctf4ish"),
+    ("sv_pure", "0"),
+    ("sv_allowDownload", "0"),
+    ("dedicated", "2"),
+];
+
+/// OpenArena mod server that reports neither `sv_pure` nor the download setting
+const OA_MOD_SILENT: &[(&str, &str)] = &[
+    ("sv_hostname", "Excessive Plus"),
+    ("gamename", "excessiveplus"),
+    ("mapname", "oasago2"),
+];
+
+/// OpenArena mod server serving its paks, not reporting `sv_pure`
+const OA_MOD_DOWNLOADS: &[(&str, &str)] = &[
+    ("sv_hostname", "Instagib"),
+    ("gamename", "oax"),
+    ("mapname", "wrackdm17"),
+    ("sv_allowDownload", "1"),
+];
+
+/// Xonotic 0.8.2 server. DarkPlaces reports neither `sv_pure` nor the download setting
+const XONOTIC_STOCK: &[(&str, &str)] = &[
+    ("gamename", "Xonotic"),
+    ("modname", "data"),
+    ("gameversion", "802"),
+    ("sv_maxclients", "24"),
+    ("clients", "3"),
+    ("bots", "0"),
+    ("mapname", "stormkeep"),
+    ("hostname", "Xonotic Server"),
+    ("protocol", "3"),
+    ("qcstatus", ":dm:0.8.2:P0:M:"),
+];
+
+/// Xonotic mod server with downloads, `sv_pure` meaningless to the engine
+const XONOTIC_MOD_DOWNLOADS: &[(&str, &str)] = &[
+    ("gamename", "Xonotic"),
+    ("modname", "nexrun"),
+    ("gameversion", "802"),
+    ("mapname", "nex_run1"),
+    ("sv_pure", "0"),
+    ("sv_allowDownload", "1"),
+];
+
+#[test]
+fn quake3() {
+    let check = |mod_name, rules| {
+        games::stock_client_compatible(Game::QuakeIII, &server(mod_name, rules)).unwrap()
+    };
+
+    assert!(check(None, Q3_STOCK));
+    assert!(check(Some("baseq3"), Q3_STOCK));
+    assert!(check(Some("cpma"), Q3_CPMA_DOWNLOADS));
+    assert!(!check(Some("defrag"), Q3_DEFRAG_IMPURE));
+    // The mod is needed and nothing gets downloaded
+    assert!(!check(Some("osp"), Q3_STOCK));
+}
+
+#[test]
+fn openarena() {
+    let check = |mod_name, rules| {
+        games::stock_client_compatible(Game::OpenArena, &server(mod_name, rules)).unwrap()
+    };
+
+    // Impure servers of the base game need nothing extra
+    assert!(check(Some("baseoa"), OA_STOCK));
+    assert!(!check(Some("excessiveplus"), OA_MOD_SILENT));
+    assert!(check(Some("oax"), OA_MOD_DOWNLOADS));
+}
+
+#[test]
+fn xonotic() {
+    let check = |mod_name, rules| {
+        games::stock_client_compatible(Game::Xonotic, &server(mod_name, rules)).unwrap()
+    };
+
+    assert!(check(Some("data"), XONOTIC_STOCK));
+    assert!(check(Some("Xonotic"), XONOTIC_STOCK));
+    assert!(check(Some("nexrun"), XONOTIC_MOD_DOWNLOADS));
+    assert!(!check(Some("nexrun"), XONOTIC_STOCK));
+}
+
+#[test]
+fn other_games_are_unknown() {
+    for game in &[Game::OpenTTD, Game::RigsOfRods, Game::Hedgewars] {
+        assert_eq!(
+            games::stock_client_compatible(*game, &server(Some("mod"), Q3_STOCK)),
+            None
+        );
+    }
+}
@@ -0,0 +1,80 @@
+// Obozrenie Game Server Browser
+// Copyright (C) 2018-2019  Artem Vorotnikov
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Locks in the version checks behind the incompatible server warning.
+
+use rgs::models::Server;
+use serde_json::Value;
+
+use obozrenie::games::{self, Game};
+
+fn server_with_rule(name: &str, value: Value) -> Server {
+    let mut srv = Server::new("127.0.0.1:3979".parse().unwrap());
+    srv.rules.insert(name.into(), value);
+    srv
+}
+
+#[test]
+fn server_version_from_rules() {
+    assert_eq!(
+        games::server_version(
+            Game::OpenTTD,
+            &server_with_rule("server_revision", Value::String("1.9.3".into()))
+        ),
+        Some("1.9.3".into())
+    );
+    assert_eq!(
+        games::server_version(
+            Game::Xonotic,
+            &server_with_rule("gameversion", Value::from(803))
+        ),
+        Some("803".into())
+    );
+
+    // Not checked for the game, or not reported
+    assert_eq!(
+        games::server_version(
+            Game::QuakeIII,
+            &server_with_rule("version", Value::String("ioq3 1.36".into()))
+        ),
+        None
+    );
+    assert_eq!(
+        games::server_version(
+            Game::OpenTTD,
+            &server_with_rule("server_revision", Value::String(" ".into()))
+        ),
+        None
+    );
+}
+
+#[test]
+fn versions_compared_by_numbers() {
+    assert!(games::versions_compatible("1.9.3", "1.9.3"));
+    assert!(games::versions_compatible("1.9.3", "1.9"));
+    assert!(games::versions_compatible("Xonotic 0.8.2", "0.8.2"));
+    assert!(games::versions_compatible("803", "0803"));
+
+    assert!(!games::versions_compatible("1.9.3", "1.10"));
+    assert!(!games::versions_compatible("1.9", "1.9.3"));
+    assert!(!games::versions_compatible("803", "802"));
+}
+
+#[test]
+fn versions_without_numbers_match_exactly() {
+    assert!(games::versions_compatible("Nightly", "nightly"));
+    assert!(!games::versions_compatible("1.9.3", "nightly"));
+}